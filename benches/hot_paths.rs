@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use flyhoney::*;
+
+fn sample_rom() -> Rom {
+    /* a deterministic, non-uniform 1MB image so neither search nor checksum can short-circuit */
+    let data = (0..0x100000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect::<Vec<u8>>();
+
+    Rom::new(data)
+}
+
+fn bench_tile_decode(c: &mut Criterion) {
+    let data = sample_rom().as_slice()[..0x8000].to_vec();
+    let mut group = c.benchmark_group("tile_decode");
+
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("2bpp", |b| b.iter(|| TileSheet::<SNESTile2BPPIntertwined>::from_data(black_box(&data), 16).unwrap()));
+    group.bench_function("4bpp", |b| b.iter(|| TileSheet::<SNESTile4BPPIntertwined>::from_data(black_box(&data), 16).unwrap()));
+    group.bench_function("8bpp", |b| b.iter(|| TileSheet::<SNESTile8BPPIntertwined>::from_data(black_box(&data), 16).unwrap()));
+    group.finish();
+}
+
+fn bench_tile_render(c: &mut Criterion) {
+    let data = sample_rom().as_slice()[..0x8000].to_vec();
+    let sheet = TileSheet::<SNESTile4BPPIntertwined>::from_data(&data, 16).unwrap();
+    let palette = SNESPalette16::from_data(&data[..32]).unwrap();
+
+    c.bench_function("tile_render_4bpp", |b| b.iter(|| sheet.render(black_box(&palette)).unwrap()));
+}
+
+fn bench_checksum(c: &mut Criterion) {
+    let rom = sample_rom();
+    let mut group = c.benchmark_group("checksum");
+
+    group.throughput(Throughput::Bytes(rom.len() as u64));
+    group.bench_function("snes", |b| b.iter(|| black_box(&rom).checksum()));
+    group.bench_function("crc32", |b| b.iter(|| black_box(&rom).crc32()));
+    group.finish();
+}
+
+fn bench_search(c: &mut Criterion) {
+    let rom = sample_rom();
+    let cancel = CancelToken::new();
+    let pattern = BytePattern::parse("A9 ?? 8D ?? 21").unwrap();
+    let mut group = c.benchmark_group("search");
+
+    group.throughput(Throughput::Bytes(rom.len() as u64));
+    group.bench_function("bytes", |b| b.iter(|| rom.find_bytes(black_box(b"EARTH BOUND"), &cancel).unwrap()));
+    group.bench_function("pattern", |b| b.iter(|| rom.find_pattern(black_box(&pattern), &cancel).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_tile_decode, bench_tile_render, bench_checksum, bench_search);
+criterion_main!(benches);
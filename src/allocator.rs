@@ -0,0 +1,500 @@
+use crate::{parse_number, quote, unquote, Addr24, Error, Mapper, MemoryMap, RegionKind, Rom, UsageLog};
+use std::ops::{Range, RangeInclusive};
+
+/* the "STAR" tag, then the block size minus one and its complement */
+pub const RATS_TAG_SIZE: usize = 8;
+
+pub fn rats_tag(length: usize) -> Result<[u8; RATS_TAG_SIZE], Error> {
+    if length == 0 || length > 0x10000 { return Err(Error::DataLengthMismatch(length,0x10000)); }
+
+    let size = (length - 1) as u16;
+    let (size, complement) = (size.to_le_bytes(), (!size).to_le_bytes());
+
+    Ok([b'S', b'T', b'A', b'R', size[0], size[1], complement[0], complement[1]])
+}
+
+/* how alloc picks among the places a block fits; Rats places like FirstFit but puts a tag in front of the block */
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum AllocationStrategy {
+    #[default]
+    FirstFit,
+    BestFit,
+    BankAffinity(u8),
+    Rats,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BankConstraints {
+    pub banks: Option<RangeInclusive<u8>>,
+    pub alignment: usize,
+    pub cross_banks: bool,
+    pub strategy: AllocationStrategy,
+}
+impl BankConstraints {
+    pub fn new() -> Self {
+        Self { banks: None, alignment: 1, cross_banks: false, strategy: AllocationStrategy::default() }
+    }
+    pub fn banks(mut self, banks: RangeInclusive<u8>) -> Self {
+        self.banks = Some(banks);
+        self
+    }
+    pub fn alignment(mut self, alignment: usize) -> Self {
+        self.alignment = alignment.max(1);
+        self
+    }
+    pub fn cross_banks(mut self, cross_banks: bool) -> Self {
+        self.cross_banks = cross_banks;
+        self
+    }
+    pub fn strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+}
+impl Default for BankConstraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/* where ExHiROM's second 4MB starts; on other boards expanded_from takes the size before expansion */
+pub const EXPANDED_AREA_START: usize = 0x400000;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FreeSpaceFilter {
+    pub min_length: usize,
+    pub slow_banks: bool,
+    pub expanded_from: Option<usize>,
+    pub banks: Option<RangeInclusive<u8>>,
+    pub exclude_code: Option<UsageLog>,
+}
+impl FreeSpaceFilter {
+    pub fn new() -> Self {
+        Self { min_length: 1, slow_banks: false, expanded_from: None, banks: None, exclude_code: None }
+    }
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length.max(1);
+        self
+    }
+    pub fn slow_banks(mut self, slow_banks: bool) -> Self {
+        /* only space the CPU can reach through banks $00-$7F, so code that never switched to FastROM can use it */
+        self.slow_banks = slow_banks;
+        self
+    }
+    pub fn expanded_area(self) -> Self {
+        self.expanded_from(EXPANDED_AREA_START)
+    }
+    pub fn expanded_from(mut self, offset: usize) -> Self {
+        self.expanded_from = Some(offset);
+        self
+    }
+    pub fn banks(mut self, banks: RangeInclusive<u8>) -> Self {
+        self.banks = Some(banks);
+        self
+    }
+    pub fn exclude_code(mut self, usage: UsageLog) -> Self {
+        /* a bank with any executed byte in it is off limits, not just the executed bytes themselves */
+        self.exclude_code = Some(usage);
+        self
+    }
+}
+impl Default for FreeSpaceFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bank_size(mapper: &Mapper) -> usize {
+    match mapper {
+        Mapper::LoRom(_) | Mapper::SuperFx(_) | Mapper::Sdd1(_) => 0x8000,
+        _ => 0x10000,
+    }
+}
+
+fn slow_accessible(mapper: &Mapper, offset: usize) -> bool {
+    /* ExHiROM's first 4MB only appears at $80 and up; everything else has a mirror below $80 */
+    match mapper.pc_to_address(offset) {
+        Ok(a) if a.bank < 0x80 => true,
+        Ok(a) => matches!(mapper.address_to_pc(Addr24::new(a.bank & 0x7F, a.address)), Ok(p) if p == offset),
+        Err(_) => false,
+    }
+}
+
+/* offset and length cover the RATS tag when there is one; address is always where the data itself starts */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Allocation {
+    pub offset: usize,
+    pub length: usize,
+    pub address: Addr24,
+    pub rats: bool,
+    pub name: Option<String>,
+}
+impl Allocation {
+    pub fn data_offset(&self) -> usize {
+        if self.rats { self.offset + RATS_TAG_SIZE } else { self.offset }
+    }
+    pub fn data_length(&self) -> usize {
+        if self.rats { self.length - RATS_TAG_SIZE } else { self.length }
+    }
+}
+
+/* offsets are ROM offsets without the copier header, the same as region maps; addresses are CPU addresses
+   under the ROM's mapper. state survives a session through to_toml and from_toml */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FreeSpaceAllocator {
+    mapper: Mapper,
+    size: usize,
+    free: Vec<Range<usize>>,
+    allocations: Vec<Allocation>,
+}
+impl FreeSpaceAllocator {
+    pub fn new(rom: &Rom) -> Result<Self, Error> {
+        /* starts with no free space at all; add_free says where it is */
+        match rom.memory_map() {
+            Ok(mapper) => Ok(Self { mapper, size: rom.rom_size(), free: Vec::new(), allocations: Vec::new() }),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn from_rom(rom: &Rom) -> Result<Self, Error> {
+        let mut result = match Self::new(rom) {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+
+        for run in rom.fill_regions().runs().iter().filter(|r| r.kind == RegionKind::Free) {
+            if let Err(e) = result.add_free(run.offset..run.offset + run.length) { return Err(e); }
+        }
+
+        Ok(result)
+    }
+    pub fn free_ranges(&self) -> &[Range<usize>] {
+        &self.free
+    }
+    pub fn free_bytes(&self) -> usize {
+        self.free.iter().map(|r| r.end - r.start).sum()
+    }
+    pub fn allocations(&self) -> &[Allocation] {
+        &self.allocations
+    }
+    fn overlapping(&self, range: &Range<usize>) -> Option<&Allocation> {
+        self.allocations.iter().find(|a| a.offset < range.end && a.offset + a.length > range.start)
+    }
+    fn release(&mut self, range: Range<usize>) {
+        /* keeps the free list sorted and merged so neighbouring frees become one block again */
+        self.free.push(range);
+        self.free.sort_by_key(|r| r.start);
+
+        let mut merged = Vec::<Range<usize>>::with_capacity(self.free.len());
+
+        for range in self.free.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        self.free = merged;
+    }
+    fn claim(&mut self, range: &Range<usize>) {
+        let mut remaining = Vec::<Range<usize>>::with_capacity(self.free.len() + 1);
+
+        for free in self.free.drain(..) {
+            if free.end <= range.start || free.start >= range.end { remaining.push(free); continue; }
+
+            if free.start < range.start { remaining.push(free.start..range.start); }
+            if free.end > range.end { remaining.push(range.end..free.end); }
+        }
+
+        self.free = remaining;
+    }
+    fn record(&mut self, offset: usize, length: usize, rats: bool, name: Option<&str>) -> Result<Addr24, Error> {
+        let address = match self.mapper.pc_to_address(if rats { offset + RATS_TAG_SIZE } else { offset }) {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+
+        self.claim(&(offset..offset + length));
+        self.allocations.push(Allocation { offset, length, address, rats, name: name.map(|n| n.to_string()) });
+        self.allocations.sort_by_key(|a| a.offset);
+
+        Ok(address)
+    }
+    pub fn named(&self, name: &str) -> Option<&Allocation> {
+        self.allocations.iter().find(|a| a.name.as_deref() == Some(name))
+    }
+    pub fn add_free(&mut self, range: Range<usize>) -> Result<(), Error> {
+        if range.end > self.size { return Err(Error::OutOfBounds(range.end,self.size)); }
+        if let Some(a) = self.overlapping(&range) { return Err(Error::AllocationOverlap(range.start,a.offset)); }
+        if range.start >= range.end { return Ok(()); }
+
+        self.release(range);
+        Ok(())
+    }
+    pub fn reserve(&mut self, offset: usize, length: usize) -> Result<Addr24, Error> {
+        /* for data placed by hand: it needn't be in free space, but it can't land on another allocation */
+        self.reserve_record(offset, length, false, None)
+    }
+    fn reserve_record(&mut self, offset: usize, length: usize, rats: bool, name: Option<&str>) -> Result<Addr24, Error> {
+        /* both numbers may come from a hand-edited allocations file */
+        let range = match offset.checked_add(length.max(1)) {
+            Some(end) => offset..end,
+            None => return Err(Error::OutOfBounds(usize::MAX,self.size)),
+        };
+
+        if rats && length <= RATS_TAG_SIZE { return Err(Error::DataLengthMismatch(length,RATS_TAG_SIZE + 1)); }
+        if range.end > self.size { return Err(Error::OutOfBounds(range.end,self.size)); }
+        if let Some(a) = self.overlapping(&range) { return Err(Error::AllocationOverlap(offset,a.offset)); }
+
+        self.record(offset, length.max(1), rats, name)
+    }
+    fn candidates(&self, length: usize, constraints: &BankConstraints) -> Vec<(usize, usize)> {
+        /* the first place the block fits in each bank of each free range, with the size of the range it came from */
+        let bank_size = bank_size(&self.mapper);
+        let align = |offset: usize| offset.div_ceil(constraints.alignment) * constraints.alignment;
+        let bank_allowed = |offset: usize| match &constraints.banks {
+            Some(banks) => matches!(self.mapper.pc_to_address(offset), Ok(a) if banks.contains(&a.bank)),
+            None => true,
+        };
+        let mut result = Vec::<(usize, usize)>::new();
+
+        for range in &self.free {
+            let mut start = align(range.start);
+
+            while matches!(start.checked_add(length), Some(end) if end <= range.end) {
+                let bank_end = (start / bank_size + 1) * bank_size;
+
+                if (constraints.cross_banks || start + length <= bank_end) && bank_allowed(start) && bank_allowed(start + length - 1) {
+                    result.push((start, range.end - range.start));
+                }
+
+                start = align(bank_end);
+            }
+        }
+
+        result
+    }
+    fn place(&mut self, length: usize, constraints: &BankConstraints, name: Option<&str>) -> Result<Addr24, Error> {
+        /* every strategy breaks ties on the lowest offset, so the same requests in the same order always land in the same place */
+        let rats = constraints.strategy == AllocationStrategy::Rats;
+        let length = length.max(1);
+
+        if rats {
+            if let Err(e) = rats_tag(length) { return Err(e); }
+        }
+
+        let total = if rats { length + RATS_TAG_SIZE } else { length };
+
+        let candidates = self.candidates(total, constraints);
+        let found = match constraints.strategy {
+            AllocationStrategy::FirstFit | AllocationStrategy::Rats => candidates.first().map(|c| c.0),
+            AllocationStrategy::BestFit => candidates.iter().min_by_key(|(offset, size)| (*size, *offset)).map(|c| c.0),
+            AllocationStrategy::BankAffinity(bank) => {
+                let distance = |offset: usize| match self.mapper.pc_to_address(offset) {
+                    Ok(a) => (a.bank as i32 - bank as i32).abs(),
+                    Err(_) => i32::MAX,
+                };
+
+                candidates.iter().min_by_key(|(offset, _)| (distance(*offset), *offset)).map(|c| c.0)
+            },
+        };
+
+        match found {
+            Some(offset) => self.record(offset, total, rats, name),
+            None => Err(Error::NoFreeSpace(total)),
+        }
+    }
+    pub fn alloc(&mut self, length: usize, constraints: &BankConstraints) -> Result<Addr24, Error> {
+        self.place(length, constraints, None)
+    }
+    pub fn alloc_named(&mut self, name: &str, length: usize, constraints: &BankConstraints) -> Result<Addr24, Error> {
+        /* a name already on record keeps its address as long as the block still fits there, so rebuilds don't shuffle */
+        if let Some(index) = self.allocations.iter().position(|a| a.name.as_deref() == Some(name)) {
+            let existing = &self.allocations[index];
+            let rats = constraints.strategy == AllocationStrategy::Rats;
+            let bank_allowed = match &constraints.banks {
+                Some(banks) => banks.contains(&existing.address.bank),
+                None => true,
+            };
+
+            if existing.data_length() >= length.max(1) && existing.rats == rats && bank_allowed { return Ok(existing.address); }
+
+            /* the old block is only given up for good once the new one has somewhere to go */
+            let existing = self.allocations.remove(index);
+            let range = existing.offset..existing.offset + existing.length;
+
+            self.release(range.clone());
+
+            return match self.place(length, constraints, Some(name)) {
+                Ok(a) => Ok(a),
+                Err(e) => {
+                    self.claim(&range);
+                    self.allocations.push(existing);
+                    self.allocations.sort_by_key(|a| a.offset);
+                    Err(e)
+                },
+            };
+        }
+
+        self.place(length, constraints, Some(name))
+    }
+    pub fn free(&mut self, address: Addr24) -> Result<usize, Error> {
+        /* any mirror of the allocated address will do */
+        let offset = match self.mapper.address_to_pc(address) {
+            Ok(o) => o,
+            Err(e) => return Err(e),
+        };
+        let index = match self.allocations.iter().position(|a| a.data_offset() == offset) {
+            Some(i) => i,
+            None => return Err(Error::NotAllocated(address)),
+        };
+        let allocation = self.allocations.remove(index);
+
+        self.release(allocation.offset..allocation.offset + allocation.length);
+        Ok(allocation.data_length())
+    }
+    pub fn to_toml(&self) -> String {
+        let mut result = String::from("# flyhoney free space\n");
+        result.push_str(&format!("size = 0x{:X}\n", self.size));
+
+        for range in &self.free {
+            result.push_str("\n[[free]]\n");
+            result.push_str(&format!("start = 0x{:06X}\n", range.start));
+            result.push_str(&format!("end = 0x{:06X}\n", range.end));
+        }
+        for allocation in &self.allocations {
+            result.push_str("\n[[allocation]]\n");
+            result.push_str(&format!("offset = 0x{:06X}\n", allocation.offset));
+            result.push_str(&format!("length = 0x{:X}\n", allocation.length));
+
+            if allocation.rats { result.push_str("rats = true\n"); }
+            if let Some(name) = &allocation.name { result.push_str(&format!("name = {}\n", quote(name))); }
+        }
+
+        result
+    }
+    pub fn from_toml(text: &str, rom: &Rom) -> Result<Self, Error> {
+        /* the mapper isn't stored; it comes from the ROM the state is being reattached to */
+        let mut result = match Self::new(rom) {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+        let mut free = Vec::<Range<usize>>::new();
+        let mut allocations = Vec::<(usize, usize, bool, Option<String>)>::new();
+        let mut section = "";
+
+        for (number_index, raw_line) in text.lines().enumerate() {
+            /* a name can hold a '#', so comments only come off once it's clear the value isn't a string */
+            let line = raw_line.trim();
+            let bare = line.split('#').next().unwrap_or("").trim();
+            let bad_line = Error::InvalidMetadataLine(number_index + 1);
+
+            if bare.is_empty() { continue; }
+
+            match bare {
+                "[[free]]" => { free.push(0..0); section = "free"; continue; },
+                "[[allocation]]" => { allocations.push((0, 0, false, None)); section = "allocation"; continue; },
+                _ => (),
+            }
+
+            let (key, quoted) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(bad_line),
+            };
+            let value = quoted.split('#').next().unwrap_or("").trim();
+
+            match (section, key, value) {
+                ("allocation", "rats", "true") => { allocations.last_mut().unwrap().2 = true; continue; },
+                ("allocation", "rats", "false") => continue,
+                ("allocation", "name", _) => match unquote(quoted) {
+                    Some(name) => { allocations.last_mut().unwrap().3 = Some(name); continue; },
+                    None => return Err(bad_line),
+                },
+                _ => (),
+            }
+
+            let n = match parse_number(value) {
+                Some(n) => n,
+                None => return Err(bad_line),
+            };
+
+            match (section, key) {
+                ("", "size") if n != result.size => return Err(Error::ROMSizeMismatch(n,result.size)),
+                ("", "size") => (),
+                ("free", "start") => free.last_mut().unwrap().start = n,
+                ("free", "end") => free.last_mut().unwrap().end = n,
+                ("allocation", "offset") => allocations.last_mut().unwrap().0 = n,
+                ("allocation", "length") => allocations.last_mut().unwrap().1 = n,
+                _ => return Err(Error::UnknownField(key.to_string())),
+            }
+        }
+
+        /* allocations go in first so a hand-edited file can't hand out space that is already taken */
+        for (offset, length, rats, name) in allocations {
+            if let Err(e) = result.reserve_record(offset, length, rats, name.as_deref()) { return Err(e); }
+        }
+        for range in free {
+            if let Err(e) = result.add_free(range) { return Err(e); }
+        }
+
+        Ok(result)
+    }
+}
+
+impl Rom {
+    pub fn free_space_allocator(&self) -> Result<FreeSpaceAllocator, Error> {
+        FreeSpaceAllocator::from_rom(self)
+    }
+    pub fn write_rats_tags(&mut self, allocator: &FreeSpaceAllocator) -> Result<usize, Error> {
+        /* allocator offsets skip the copier header; the tag goes right in front of each tagged block */
+        let mut count = 0usize;
+
+        for allocation in allocator.allocations().iter().filter(|a| a.rats) {
+            let tag = match rats_tag(allocation.data_length()) {
+                Ok(t) => t,
+                Err(e) => return Err(e),
+            };
+
+            if let Err(e) = self.write(self.header_size() + allocation.offset, tag) { return Err(e); }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+    pub fn find_free_space(&self, filter: &FreeSpaceFilter) -> Result<Vec<Range<usize>>, Error> {
+        /* fill runs cut at bank boundaries, since a payload spanning two banks is rarely usable, then filtered
+           a bank at a time; offsets are without the copier header, like fill_regions */
+        let mapper = match self.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        let bank_size = bank_size(&mapper);
+        let mut result = Vec::<Range<usize>>::new();
+
+        for run in self.fill_regions().runs().iter().filter(|r| r.kind == RegionKind::Free) {
+            let mut start = run.offset;
+            let end = run.offset + run.length;
+
+            if let Some(from) = filter.expanded_from { start = start.max(from); }
+
+            while start < end {
+                let bank_start = start / bank_size * bank_size;
+                let piece = start..end.min(bank_start + bank_size);
+                start = piece.end;
+
+                if piece.end - piece.start < filter.min_length { continue; }
+                if filter.slow_banks && !slow_accessible(&mapper, piece.start) { continue; }
+
+                if let Some(banks) = &filter.banks {
+                    if !matches!(mapper.pc_to_address(piece.start), Ok(a) if banks.contains(&a.bank)) { continue; }
+                }
+                if let Some(usage) = &filter.exclude_code {
+                    if (bank_start..bank_start + bank_size).any(|o| usage.is_code(o)) { continue; }
+                }
+
+                result.push(piece);
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{hits_to_region_map, CancelToken, Error, RegionMap, Rom, SurveyHit, SurveyPass};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct AnalysisProgress {
+    pub pass: SurveyPass,
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnalysisReport {
+    pub passes: Vec<SurveyPass>,
+    pub hits: Vec<SurveyHit>,
+}
+impl AnalysisReport {
+    pub fn hits_for(&self, pass: SurveyPass) -> Vec<SurveyHit> {
+        self.hits.iter().filter(|h| h.kind == pass.kind()).copied().collect()
+    }
+    pub fn to_region_map(&self, rom: &Rom) -> Result<RegionMap, Error> {
+        /* later passes win where passes overlap, so code is marked last and takes priority over data guesses */
+        hits_to_region_map(rom, &self.hits)
+    }
+}
+
+pub struct AnalysisSession<'a> {
+    rom: &'a Rom,
+    passes: Vec<SurveyPass>,
+    cancel: CancelToken,
+    progress: Option<Box<dyn Fn(AnalysisProgress) + Send + Sync + 'a>>,
+}
+impl<'a> AnalysisSession<'a> {
+    pub fn new(rom: &'a Rom) -> Self {
+        Self { rom, passes: SurveyPass::ALL.to_vec(), cancel: CancelToken::new(), progress: None }
+    }
+    pub fn passes(mut self, passes: &[SurveyPass]) -> Self {
+        self.passes = passes.to_vec();
+        self
+    }
+    pub fn on_progress<F: Fn(AnalysisProgress) + Send + Sync + 'a>(mut self, f: F) -> Self {
+        self.progress = Some(Box::new(f));
+        self
+    }
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+    pub fn cancel_handle(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+    pub fn run(&self) -> Result<AnalysisReport, Error> {
+        /* one failing pass makes the combined report meaningless, so it stops the others early;
+           that goes through a token of our own, since the caller's may be shared with work beyond this session */
+        let cancel = self.cancel.child();
+
+        let results = std::thread::scope(|scope| {
+            let handles = self.passes.iter().map(|&pass| {
+                let cancel = &cancel;
+
+                (pass, scope.spawn(move || {
+                    let report = |done: usize, total: usize| {
+                        if let Some(f) = &self.progress { f(AnalysisProgress { pass, done, total }); }
+                    };
+                    /* a pass that panics is caught here rather than at the join, so the others hear about it straight away */
+                    let result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| pass.run(self.rom, &report, cancel))) {
+                        Ok(r) => r,
+                        Err(_) => Err(Error::AnalysisPassPanicked(pass)),
+                    };
+
+                    if result.is_err() { cancel.cancel(); }
+
+                    result
+                }))
+            }).collect::<Vec<_>>();
+
+            handles.into_iter().map(|(pass, h)| match h.join() {
+                Ok(r) => r,
+                Err(_) => Err(Error::AnalysisPassPanicked(pass)),
+            }).collect::<Vec<Result<Vec<SurveyHit>, Error>>>()
+        });
+
+        let mut hits = Vec::<SurveyHit>::new();
+        let mut first_error: Option<Error> = None;
+
+        for result in results {
+            match result {
+                Ok(h) => hits.extend(h),
+                /* prefer the error that caused the cancellation over the cancellations it triggered */
+                Err(e) => match first_error {
+                    None | Some(Error::Cancelled) => first_error = Some(e),
+                    _ => (),
+                },
+            }
+        }
+
+        if let Some(e) = first_error { return Err(e); }
+
+        let mut ordered = Vec::<SurveyHit>::new();
+
+        for pass in &self.passes {
+            ordered.extend(hits.iter().filter(|h| h.kind == pass.kind()));
+        }
+
+        Ok(AnalysisReport { passes: self.passes.clone(), hits: ordered })
+    }
+}
@@ -0,0 +1,812 @@
+use crate::{compress_checked, decode_png, encode_indexed_png, metrics, quote, unquote, Addr24, AddrNotation, AnalysisSession, Bgr555, CancelToken, Codec, Error, ErrorContext, MetricCounter, PaletteRemap, ResultContext, RoundTripCheck, RegionKind, Rgb888, Rom,
+            SNESTile, SNESTile2BPPIntertwined, SNESTile4BPPIntertwined, SNESTile8BPPIntertwined, SurveyPass, TextTable};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AssetKind {
+    Graphics,
+    Palette,
+    Text,
+    Map,
+    Sample,
+    Other,
+}
+impl AssetKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AssetKind::Graphics => "graphics",
+            AssetKind::Palette => "palette",
+            AssetKind::Text => "text",
+            AssetKind::Map => "map",
+            AssetKind::Sample => "sample",
+            AssetKind::Other => "other",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "graphics" => Some(AssetKind::Graphics),
+            "palette" => Some(AssetKind::Palette),
+            "text" => Some(AssetKind::Text),
+            "map" => Some(AssetKind::Map),
+            "sample" => Some(AssetKind::Sample),
+            "other" => Some(AssetKind::Other),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AssetEntry {
+    pub kind: AssetKind,
+    pub path: String,
+    pub offset: usize,
+    pub length: usize,
+    pub bpp: Option<usize>,
+    pub columns: Option<usize>,
+    pub codec: Option<String>,
+    pub pointers: Vec<usize>,
+}
+impl AssetEntry {
+    pub fn new(kind: AssetKind, path: &str, offset: usize, length: usize) -> Self {
+        Self { kind, path: path.to_string(), offset, length, bpp: None, columns: None, codec: None, pointers: Vec::new() }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct AssetBundle {
+    pub entries: Vec<AssetEntry>,
+    pub files: Vec<(String, Vec<u8>)>,
+}
+impl AssetBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, entry: AssetEntry, data: Vec<u8>) {
+        self.files.push((entry.path.clone(), data));
+        self.entries.push(entry);
+    }
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.files.iter().find(|(p, _)| p == path).map(|(_, d)| d.as_slice())
+    }
+    pub fn add_map(&mut self, rom: &Rom, offset: usize, width: usize, height: usize) -> Result<(), Error> {
+        /* a BG tilemap of width x height entries, two bytes each */
+        if width == 0 || height == 0 { return Err(Error::InvalidImage(format!("empty {}x{} map", width, height))); }
+
+        let length = match width.checked_mul(height).and_then(|n| n.checked_mul(2)) {
+            Some(l) => l,
+            None => return Err(Error::OutOfBounds(usize::MAX,rom.len())),
+        };
+        let data = match rom.read(offset, length) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+        let entries = data.chunks_exact(2).map(|w| u16::from_le_bytes([w[0], w[1]])).collect::<Vec<u16>>();
+        let mut entry = AssetEntry::new(AssetKind::Map, &format!("maps/{:06X}.tmx", offset), offset, length);
+
+        entry.columns = Some(width);
+        self.add(entry, encode_tmx(&entries, width));
+        Ok(())
+    }
+    pub fn add_sample(&mut self, rom: &Rom, offset: usize) -> Result<usize, Error> {
+        /* a BRR sample runs to the first block with its end flag set */
+        let data = match rom.read(offset, rom.len().saturating_sub(offset)) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+        let length = match brr_length(data) {
+            Some(l) => l,
+            None => return Err(Error::TruncatedData(offset)),
+        };
+
+        self.add(AssetEntry::new(AssetKind::Sample, &format!("samples/{:06X}.brr", offset), offset, length), data[..length].to_vec());
+        Ok(length)
+    }
+    pub fn manifest(&self) -> String {
+        /* a small TOML subset: one [[asset]] table per entry, offsets as buffer offsets in hex */
+        let mut result = String::from("# extracted by flyhoney\n");
+
+        for entry in &self.entries {
+            result.push_str("\n[[asset]]\n");
+            result.push_str(&format!("kind = {}\n", quote(entry.kind.name())));
+            result.push_str(&format!("path = {}\n", quote(&entry.path)));
+            result.push_str(&format!("offset = 0x{:06X}\n", entry.offset));
+            result.push_str(&format!("length = 0x{:X}\n", entry.length));
+            if let Some(bpp) = entry.bpp { result.push_str(&format!("bpp = {}\n", bpp)); }
+            if let Some(columns) = entry.columns { result.push_str(&format!("columns = {}\n", columns)); }
+            if let Some(codec) = &entry.codec { result.push_str(&format!("codec = {}\n", quote(codec))); }
+
+            if !entry.pointers.is_empty() {
+                let pointers = entry.pointers.iter().map(|p| format!("0x{:06X}", p)).collect::<Vec<String>>();
+                result.push_str(&format!("pointers = [{}]\n", pointers.join(", ")));
+            }
+        }
+
+        result
+    }
+    pub fn write_to<P: AsRef<Path>>(&self, directory: P) -> Result<usize, Error> {
+        let root = directory.as_ref();
+
+        for (path, data) in &self.files {
+            let target = root.join(path);
+
+            if let Some(parent) = target.parent() {
+                if let Err(e) = std::fs::create_dir_all(parent) { return Err(Error::IoError(e)); }
+            }
+
+            if let Err(e) = std::fs::write(&target, data) { return Err(Error::IoError(e)); }
+        }
+
+        match std::fs::write(root.join(ASSET_MANIFEST), self.manifest()) {
+            Ok(()) => Ok(self.files.len()),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+}
+
+pub const ASSET_MANIFEST: &str = "manifest.toml";
+pub const BRR_BLOCK_SIZE: usize = 9;
+
+pub trait AssetModule {
+    fn name(&self) -> &str;
+    fn extract(&self, rom: &Rom, bundle: &mut AssetBundle) -> Result<(), Error>;
+}
+
+pub struct AssetConfig {
+    pub passes: Vec<SurveyPass>,
+    pub bpp: usize,
+    pub columns: usize,
+    pub palette: Option<Vec<Bgr555>>,
+    pub auto_palette: bool,
+    pub text_table: Option<TextTable>,
+    pub maps: Vec<(usize, usize, usize)>,
+    pub samples: Vec<usize>,
+    pub modules: Vec<Box<dyn AssetModule>>,
+    pub cancel: CancelToken,
+}
+impl AssetConfig {
+    pub fn new() -> Self {
+        /* code is left out by default; there is nothing to extract from it as an asset */
+        Self {
+            passes: vec![SurveyPass::Graphics, SurveyPass::Palette, SurveyPass::Text],
+            bpp: 4,
+            columns: 16,
+            palette: None,
+            auto_palette: true,
+            text_table: None,
+            maps: Vec::new(),
+            samples: Vec::new(),
+            modules: Vec::new(),
+            cancel: CancelToken::new(),
+        }
+    }
+    pub fn passes(mut self, passes: &[SurveyPass]) -> Self {
+        self.passes = passes.to_vec();
+        self
+    }
+    pub fn bpp(mut self, bpp: usize) -> Self {
+        self.bpp = bpp;
+        self
+    }
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = columns.max(1);
+        self
+    }
+    pub fn palette(mut self, palette: Vec<Bgr555>) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+    pub fn auto_palette(mut self, auto_palette: bool) -> Self {
+        self.auto_palette = auto_palette;
+        self
+    }
+    pub fn text_table(mut self, table: TextTable) -> Self {
+        self.text_table = Some(table);
+        self
+    }
+    pub fn map(mut self, offset: usize, width: usize, height: usize) -> Self {
+        /* no survey can tell a tilemap or a sample from other data, so they're listed by hand (or found by a module) */
+        self.maps.push((offset, width, height));
+        self
+    }
+    pub fn sample(mut self, offset: usize) -> Self {
+        self.samples.push(offset);
+        self
+    }
+    pub fn module<M: AssetModule + 'static>(mut self, module: M) -> Self {
+        self.modules.push(Box::new(module));
+        self
+    }
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn decode_tile_colormaps(data: &[u8], bpp: usize) -> Result<Vec<Vec<u8>>, Error> {
+    /* SNES-native (intertwined) layouts, the ones VRAM actually expects */
+    fn decode<T: SNESTile>(data: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        let mut result = Vec::<Vec<u8>>::new();
+
+        for chunk in data.chunks_exact(T::BPP * 8) {
+            match T::from_data(chunk).and_then(|t| t.to_colormap()) {
+                Ok(c) => result.push(c),
+                Err(e) => return Err(e),
+            }
+        }
+
+        metrics::record(MetricCounter::BytesDecoded, result.len() * T::BPP * 8);
+        metrics::record(MetricCounter::TilesDecoded, result.len());
+        Ok(result)
+    }
+
+    match bpp {
+        2 => decode::<SNESTile2BPPIntertwined>(data),
+        4 => decode::<SNESTile4BPPIntertwined>(data),
+        8 => decode::<SNESTile8BPPIntertwined>(data),
+        _ => Err(Error::InvalidBpp(bpp)),
+    }
+}
+
+pub fn palette_fitness(colormaps: &[Vec<u8>], palette: &[Bgr555]) -> f32 {
+    /* how well a palette shows off some graphics: luminance contrast plus colorfulness (Hasler & Suesstrunk),
+       scaled down when indices the tiles use collapse onto the same color and detail is lost, to nothing if all of them do */
+    let mut histogram = vec![0usize; palette.len()];
+
+    for &value in colormaps.iter().flatten() {
+        match histogram.get_mut(value as usize) {
+            Some(h) => *h += 1,
+            None => return 0.0,
+        }
+    }
+
+    let used = (0..palette.len()).filter(|&i| histogram[i] > 0).collect::<Vec<usize>>();
+    if used.len() < 2 { return 0.0; }
+
+    let total = used.iter().map(|&i| histogram[i]).sum::<usize>() as f32;
+    let channels = |c: Bgr555| (c.get_red() as f32 * 8.0, c.get_green() as f32 * 8.0, c.get_blue() as f32 * 8.0);
+    let (mut luma, mut rg, mut yb) = ((0.0f32, 0.0f32), (0.0f32, 0.0f32), (0.0f32, 0.0f32));
+
+    for &i in &used {
+        let weight = histogram[i] as f32 / total;
+        let (r, g, b) = channels(palette[i]);
+        let accumulate = |sums: &mut (f32, f32), value: f32| {
+            sums.0 += weight * value;
+            sums.1 += weight * value * value;
+        };
+
+        accumulate(&mut luma, 0.299 * r + 0.587 * g + 0.114 * b);
+        accumulate(&mut rg, r - g);
+        accumulate(&mut yb, 0.5 * (r + g) - b);
+    }
+
+    let deviation = |(mean, square): (f32, f32)| (square - mean * mean).max(0.0).sqrt();
+    let contrast = deviation(luma);
+    let colorfulness = (deviation(rg).powi(2) + deviation(yb).powi(2)).sqrt() + 0.3 * (rg.0.powi(2) + yb.0.powi(2)).sqrt();
+    let distinct = used.iter().map(|&i| palette[i].0 & 0x7FFF).collect::<std::collections::BTreeSet<u16>>().len();
+    let distinct = (distinct - 1) as f32 / (used.len() - 1) as f32;
+
+    (contrast + colorfulness) / 255.0 * distinct
+}
+
+pub fn best_palette(colormaps: &[Vec<u8>], candidates: &[Vec<Bgr555>]) -> Option<(usize, f32)> {
+    let mut best: Option<(usize, f32)> = None;
+
+    for (index, palette) in candidates.iter().enumerate() {
+        let score = palette_fitness(colormaps, palette);
+
+        if score > 0.0 && best.is_none_or(|(_, s)| score > s) { best = Some((index, score)); }
+    }
+
+    best
+}
+
+pub fn tiles_to_indexed_image(colormaps: &[Vec<u8>], columns: usize) -> (usize, usize, Vec<u8>) {
+    let columns = columns.max(1);
+    let rows = colormaps.len().div_ceil(columns);
+    let (width, height) = (columns * 8, rows * 8);
+    let mut pixels = vec![0u8; width * height];
+
+    for (i, colormap) in colormaps.iter().enumerate() {
+        let (tx, ty) = ((i % columns) * 8, (i / columns) * 8);
+
+        for (p, value) in colormap.iter().enumerate().take(64) {
+            pixels[(ty + p / 8) * width + tx + p % 8] = *value;
+        }
+    }
+
+    (width, height, pixels)
+}
+
+pub fn brr_length(data: &[u8]) -> Option<usize> {
+    /* nine-byte blocks, bit 0 of each header marking the last one */
+    data.chunks_exact(BRR_BLOCK_SIZE).position(|b| b[0] & 0x01 != 0).map(|i| (i + 1) * BRR_BLOCK_SIZE)
+}
+
+pub fn encode_tmx(entries: &[u16], width: usize) -> Vec<u8> {
+    /* one CSV layer over a 1024-tile sheet per palette and priority, so the low 14 bits become the gid as they are
+       and the flip bits become Tiled's own: nothing in an entry is lost on the way out */
+    let width = width.max(1);
+    let height = entries.len().div_ceil(width);
+    let mut result = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+    result.push_str(&format!("<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" tilewidth=\"8\" tileheight=\"8\" infinite=\"0\">\n", width, height));
+    result.push_str(" <tileset firstgid=\"1\" name=\"tiles\" tilewidth=\"8\" tileheight=\"8\" tilecount=\"16384\" columns=\"32\"/>\n");
+    result.push_str(&format!(" <layer id=\"1\" name=\"map\" width=\"{}\" height=\"{}\">\n  <data encoding=\"csv\">\n", width, height));
+
+    for (row, chunk) in entries.chunks(width).enumerate() {
+        let gids = chunk.iter().map(|&e| {
+            let gid = (e as u32 & 0x3FFF) + 1;
+            let flips = ((e as u32 & 0x4000) << 17) | ((e as u32 & 0x8000) << 15);
+            (gid | flips).to_string()
+        }).collect::<Vec<String>>();
+
+        result.push_str(&gids.join(","));
+        result.push_str(if (row + 1) * width < entries.len() { ",\n" } else { "\n" });
+    }
+
+    result.push_str("</data>\n </layer>\n</map>\n");
+    result.into_bytes()
+}
+
+pub fn decode_tmx(data: &[u8]) -> Result<Vec<u16>, Error> {
+    /* the first CSV layer; an empty cell (gid 0) goes back as entry 0 */
+    let text = String::from_utf8_lossy(data);
+    let start = match text.find("<data encoding=\"csv\">") {
+        Some(s) => s + "<data encoding=\"csv\">".len(),
+        None => return Err(Error::InvalidImage("no CSV layer".to_string())),
+    };
+    let end = match text[start..].find("</data>") {
+        Some(e) => start + e,
+        None => return Err(Error::InvalidImage("unterminated layer".to_string())),
+    };
+    let mut result = Vec::<u16>::new();
+
+    for cell in text[start..end].split(',').map(|c| c.trim()) {
+        let gid = match cell.parse::<u32>() {
+            Ok(g) => g,
+            Err(_) => return Err(Error::InvalidImage(format!("bad tile \"{}\"", cell))),
+        };
+        let tile = gid & 0x0FFFFFFF;
+        if tile > 0x4000 { return Err(Error::InvalidImage(format!("tile {} past the sheet", tile))); }
+
+        let flips = ((gid >> 17) & 0x4000) | ((gid >> 15) & 0x8000);
+        result.push((tile.saturating_sub(1) | flips) as u16);
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn parse_number(value: &str) -> Option<usize> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).or_else(|| value.strip_prefix('$')) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse::<usize>().ok(),
+    }
+}
+
+pub fn parse_asset_manifest(text: &str) -> Result<Vec<AssetEntry>, Error> {
+    /* reads back what AssetBundle::manifest writes, plus hand-added pointers lists */
+    let mut result = Vec::<AssetEntry>::new();
+
+    for (number_index, raw_line) in text.lines().enumerate() {
+        /* paths can hold a '#', so a comment only comes off a value that isn't a string */
+        let line = raw_line.trim();
+        let bare = line.split('#').next().unwrap_or("").trim();
+        let bad_line = Error::InvalidManifestLine(number_index + 1);
+
+        if bare.is_empty() { continue; }
+        if bare == "[[asset]]" { result.push(AssetEntry::new(AssetKind::Other, "", 0, 0)); continue; }
+
+        let (key, quoted) = match (line.split_once('='), result.last_mut()) {
+            (Some((k, v)), Some(_)) => (k.trim(), v.trim()),
+            _ => return Err(bad_line),
+        };
+        let value = quoted.split('#').next().unwrap_or("").trim();
+        let entry = result.last_mut().unwrap();
+        let string = unquote(quoted);
+
+        match (key, string) {
+            ("kind", Some(v)) => entry.kind = match AssetKind::from_name(&v) {
+                Some(k) => k,
+                None => return Err(bad_line),
+            },
+            ("path", Some(v)) => entry.path = v,
+            ("codec", Some(v)) => entry.codec = Some(v),
+            ("pointers", None) => {
+                let list = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                    Some(l) => l,
+                    None => return Err(bad_line),
+                };
+
+                for item in list.split(',').map(|i| i.trim()).filter(|i| !i.is_empty()) {
+                    match parse_number(item) {
+                        Some(n) => entry.pointers.push(n),
+                        None => return Err(bad_line),
+                    }
+                }
+            },
+            (_, None) => {
+                let n = match parse_number(value) {
+                    Some(n) => n,
+                    None => return Err(bad_line),
+                };
+
+                match key {
+                    "offset" => entry.offset = n,
+                    "length" => entry.length = n,
+                    "bpp" => entry.bpp = Some(n),
+                    "columns" => entry.columns = Some(n),
+                    _ => return Err(Error::UnknownField(key.to_string())),
+                }
+            },
+            _ => return Err(Error::UnknownField(key.to_string())),
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn encode_tile_colormaps(colormaps: &[Vec<u8>], bpp: usize) -> Result<Vec<u8>, Error> {
+    fn encode<T: SNESTile>(colormaps: &[Vec<u8>]) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::<u8>::new();
+
+        for colormap in colormaps {
+            if let Some(&bad) = colormap.iter().find(|&&v| (v as usize) >= 1 << T::BPP) { return Err(Error::InvalidColorIndex(bad)); }
+
+            match T::from_colormap(colormap) {
+                Ok(t) => result.extend_from_slice(t.as_data()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+
+    match bpp {
+        2 => encode::<SNESTile2BPPIntertwined>(colormaps),
+        4 => encode::<SNESTile4BPPIntertwined>(colormaps),
+        8 => encode::<SNESTile8BPPIntertwined>(colormaps),
+        _ => Err(Error::InvalidBpp(bpp)),
+    }
+}
+
+pub fn indexed_image_to_tiles(width: usize, height: usize, pixels: &[u8], columns: usize, count: usize) -> Result<Vec<Vec<u8>>, Error> {
+    let columns = columns.max(1);
+    let mut result = Vec::<Vec<u8>>::new();
+
+    for i in 0..count {
+        let (tx, ty) = ((i % columns) * 8, (i / columns) * 8);
+        if tx + 8 > width || ty + 8 > height { return Err(Error::OutOfBounds(i,(width / 8) * (height / 8))); }
+
+        result.push((0..64).map(|p| pixels[(ty + p / 8) * width + tx + p % 8]).collect());
+    }
+
+    Ok(result)
+}
+
+pub fn dedup_tile_colormaps(colormaps: &[Vec<u8>]) -> (Vec<Vec<u8>>, Vec<usize>) {
+    /* unique tiles keep the order they first turn up in and are looked up by content, so the same tiles always come out
+       numbered the same way; nothing here depends on hashing, deterministic build or not */
+    let mut seen = BTreeMap::<&[u8], usize>::new();
+    let mut unique = Vec::<Vec<u8>>::new();
+    let mut indices = Vec::<usize>::with_capacity(colormaps.len());
+
+    for colormap in colormaps {
+        let next = unique.len();
+        let index = *seen.entry(colormap.as_slice()).or_insert(next);
+
+        if index == next { unique.push(colormap.clone()); }
+        indices.push(index);
+    }
+
+    (unique, indices)
+}
+
+#[derive(Clone, Default)]
+pub struct PackOptions {
+    pub text_table: Option<TextTable>,
+    pub free_space: Vec<Range<usize>>,
+    pub fill: u8,
+    pub palette: Option<Vec<Bgr555>>,
+    pub palette_remap: PaletteRemap,
+    pub codecs: Vec<(String, Arc<dyn Codec + Send + Sync>)>,
+}
+impl PackOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn text_table(mut self, table: TextTable) -> Self {
+        self.text_table = Some(table);
+        self
+    }
+    pub fn free_space(mut self, range: Range<usize>) -> Self {
+        self.free_space.push(range);
+        self
+    }
+    pub fn fill(mut self, fill: u8) -> Self {
+        self.fill = fill;
+        self
+    }
+    pub fn palette(mut self, palette: Vec<Bgr555>, remap: PaletteRemap) -> Self {
+        /* graphics are matched against this palette on the way in instead of trusting the file's index order */
+        self.palette = Some(palette);
+        self.palette_remap = remap;
+        self
+    }
+    pub fn codec<C: Codec + Send + Sync + 'static>(mut self, name: &str, codec: C) -> Self {
+        /* entries whose manifest names this codec are compressed with it again before they go back in */
+        self.codecs.push((name.to_string(), Arc::new(codec)));
+        self
+    }
+}
+impl std::fmt::Debug for PackOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let codecs = self.codecs.iter().map(|(n, _)| n.as_str()).collect::<Vec<&str>>();
+
+        f.debug_struct("PackOptions").field("text_table", &self.text_table).field("free_space", &self.free_space).field("fill", &self.fill)
+            .field("palette", &self.palette).field("palette_remap", &self.palette_remap).field("codecs", &codecs).finish()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PackReport {
+    pub written: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub relocated: Vec<(String, usize, usize)>,
+}
+
+fn greyscale_ramp(bpp: usize) -> Vec<Rgb888> {
+    let count = 1usize << bpp.min(8);
+
+    (0..count).map(|i| {
+        let level = (i * 255 / (count - 1).max(1)) as u32;
+        Rgb888((level << 16) | (level << 8) | level)
+    }).collect()
+}
+
+impl Rom {
+    pub fn extract_assets(&self, config: &AssetConfig) -> Result<AssetBundle, Error> {
+        if !matches!(config.bpp, 2 | 4 | 8) { return Err(Error::InvalidBpp(config.bpp)); }
+
+        let report = match AnalysisSession::new(self).passes(&config.passes).cancel_token(config.cancel.clone()).run() {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+        let mut bundle = AssetBundle::new();
+        let data = self.as_slice();
+
+        /* surveyed palettes, split into rows as wide as the tile depth needs, become candidates for coloring graphics */
+        let row = 2 << config.bpp;
+        let candidates = match config.auto_palette && config.palette.is_none() {
+            true => report.hits.iter()
+                .filter(|h| h.kind == RegionKind::Palette)
+                .flat_map(|h| data[h.offset..h.offset + h.length].chunks_exact(row))
+                .map(|c| c.chunks_exact(2).map(|w| Bgr555(u16::from_le_bytes([w[0], w[1]]))).collect::<Vec<Bgr555>>())
+                .collect::<Vec<Vec<Bgr555>>>(),
+            false => Vec::new(),
+        };
+
+        for hit in &report.hits {
+            if let Err(e) = config.cancel.check() { return Err(e); }
+
+            let region = &data[hit.offset..hit.offset + hit.length];
+            if region.is_empty() { continue; }
+
+            match hit.kind {
+                RegionKind::Graphics => {
+                    let colormaps = match decode_tile_colormaps(region, config.bpp) {
+                        Ok(c) => c,
+                        Err(e) => return Err(e),
+                    };
+
+                    /* shorter than one tile there's nothing to draw, and a zero-height PNG is no image at all */
+                    if colormaps.is_empty() { continue; }
+
+                    let (width, height, pixels) = tiles_to_indexed_image(&colormaps, config.columns);
+                    let palette = match (&config.palette, best_palette(&colormaps, &candidates)) {
+                        (Some(p), _) => p.iter().map(|&c| Rgb888::from(c)).collect(),
+                        (None, Some((index, _))) => candidates[index].iter().map(|&c| Rgb888::from(c)).collect(),
+                        (None, None) => greyscale_ramp(config.bpp),
+                    };
+                    let mut entry = AssetEntry::new(AssetKind::Graphics, &format!("graphics/{:06X}.png", hit.offset), hit.offset, colormaps.len() * config.bpp * 8);
+
+                    entry.bpp = Some(config.bpp);
+                    entry.columns = Some(config.columns);
+                    bundle.add(entry, encode_indexed_png(width, height, &pixels, &palette));
+                },
+                RegionKind::Palette => {
+                    /* JASC-PAL, which most paint programs read and write */
+                    let colors = region.chunks_exact(2).map(|c| Rgb888::from(Bgr555(u16::from_le_bytes([c[0], c[1]])))).collect::<Vec<Rgb888>>();
+                    let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+
+                    for c in &colors { text.push_str(&format!("{} {} {}\r\n", (c.0 >> 16) & 0xFF, (c.0 >> 8) & 0xFF, c.0 & 0xFF)); }
+
+                    bundle.add(AssetEntry::new(AssetKind::Palette, &format!("palettes/{:06X}.pal", hit.offset), hit.offset, colors.len() * 2), text.into_bytes());
+                },
+                RegionKind::Text => {
+                    let text = match &config.text_table {
+                        Some(t) => t.decode(region),
+                        None => String::from_utf8_lossy(region).to_string(),
+                    };
+
+                    bundle.add(AssetEntry::new(AssetKind::Text, &format!("text/{:06X}.txt", hit.offset), hit.offset, hit.length), text.into_bytes());
+                },
+                _ => (),
+            }
+        }
+
+        for &(offset, width, height) in &config.maps {
+            if let Err(e) = bundle.add_map(self, offset, width, height) { return Err(e); }
+        }
+        for &offset in &config.samples {
+            if let Err(e) = bundle.add_sample(self, offset) { return Err(e); }
+        }
+
+        /* game-specific modules cover what generic surveys cannot find: maps and samples without a known address,
+           compressed archives. whole songs as SPC need the game's sound driver running, so they're left to modules too */
+        for module in &config.modules {
+            if let Err(e) = module.extract(self, &mut bundle) { return Err(e); }
+        }
+
+        Ok(bundle)
+    }
+    fn encode_asset(&self, entry: &AssetEntry, data: &[u8], options: &PackOptions) -> Result<Vec<u8>, Error> {
+        match entry.kind {
+            AssetKind::Graphics => {
+                let bpp = entry.bpp.unwrap_or(4);
+                if !matches!(bpp, 2 | 4 | 8) { return Err(Error::InvalidBpp(bpp)); }
+
+                let image = match decode_png(data) {
+                    Ok(i) => i,
+                    Err(e) => return Err(e),
+                };
+                let indices = match (&options.palette, image.indices()) {
+                    (Some(palette), _) if options.palette_remap != PaletteRemap::Keep => match image.remap_indices(palette, options.palette_remap) {
+                        Ok(i) => i,
+                        Err(e) => return Err(e),
+                    },
+                    /* a greyscale export read back as levels has to be scaled down to palette indices again */
+                    (_, Ok(i)) if image.color_type == 0 && image.bit_depth == 8 && bpp < 8 => i.iter().map(|&v| ((v as usize * ((1 << bpp) - 1) + 127) / 255) as u8).collect(),
+                    (_, Ok(i)) => i,
+                    (_, Err(e)) => return Err(e),
+                };
+                let columns = entry.columns.unwrap_or(image.width / 8);
+
+                match indexed_image_to_tiles(image.width, image.height, &indices, columns, entry.length / (bpp * 8)) {
+                    Ok(tiles) => encode_tile_colormaps(&tiles, bpp),
+                    Err(e) => Err(e),
+                }
+            },
+            AssetKind::Palette => {
+                let text = String::from_utf8_lossy(data);
+                let mut lines = text.lines().map(|l| l.trim());
+
+                if lines.next() != Some("JASC-PAL") { return Err(Error::BadMagic); }
+
+                let mut result = Vec::<u8>::new();
+
+                for line in lines.skip(2) {
+                    let parts = line.split_whitespace().filter_map(|p| p.parse::<u32>().ok()).collect::<Vec<u32>>();
+                    if parts.len() < 3 { continue; }
+
+                    let color = Bgr555::from(Rgb888((parts[0].min(255) << 16) | (parts[1].min(255) << 8) | parts[2].min(255)));
+                    result.extend_from_slice(&color.0.to_le_bytes());
+                }
+
+                Ok(result)
+            },
+            AssetKind::Text => {
+                let text = String::from_utf8_lossy(data);
+
+                match &options.text_table {
+                    Some(t) => t.encode(&text),
+                    None => Ok(text.into_owned().into_bytes()),
+                }
+            },
+            AssetKind::Map => match decode_tmx(data) {
+                Ok(entries) => Ok(entries.iter().flat_map(|e| e.to_le_bytes()).collect()),
+                Err(e) => Err(e),
+            },
+            AssetKind::Sample => match brr_length(data) {
+                /* a sample that doesn't end where the file does would play into whatever follows it */
+                Some(l) if l == data.len() => Ok(data.to_vec()),
+                _ => Err(Error::TruncatedData(data.len())),
+            },
+            AssetKind::Other => Ok(data.to_vec()),
+        }
+    }
+    fn pointer_to(&self, offset: usize) -> Result<Addr24, Error> {
+        match self.notation() {
+            AddrNotation::LoRom | AddrNotation::HiRom => self.offset_to_notation(offset, self.notation()),
+            _ => match self.offset_to_address(offset) {
+                Ok(a) => Ok(a),
+                Err(_) => Addr24::from_offset(self, offset).to_rom_address(),
+            },
+        }
+    }
+    pub fn pack_assets<P: AsRef<Path>>(&mut self, directory: P, entries: &[AssetEntry], options: &PackOptions) -> Result<PackReport, Error> {
+        let root = directory.as_ref();
+        let mut free = options.free_space.clone();
+
+        /* free_space is an open field, so the ranges are checked here rather than where they were added */
+        if let Some(r) = free.iter().find(|r| r.start > r.end) { return Err(Error::ReversedRange(r.start,r.end)); }
+
+        /* deterministic builds allocate lowest-address first no matter how the caller listed the free space;
+           that's the only ordering left to the caller, since tile dedup and DTE dictionaries are ordered either way */
+        if self.is_deterministic() { free.sort_by_key(|r| (r.start, r.end)); }
+        let mut report = PackReport::default();
+
+        for entry in entries {
+            let context = || ErrorContext::new(&format!("packing {}", entry.path)).offset(entry.offset).length(entry.length);
+            let data = match std::fs::read(root.join(&entry.path)) {
+                Ok(d) => d,
+                Err(e) => return Err(Error::IoError(e).context(context())),
+            };
+            let mut encoded = match self.encode_asset(entry, &data, options).with_context(context) {
+                Ok(e) => e,
+                Err(e) => return Err(e),
+            };
+
+            /* the ROM holds the packed form, so that's what gets measured against the slot; every block is decoded
+               again before it's trusted */
+            if let Some(name) = &entry.codec {
+                let codec = match options.codecs.iter().find(|(n, _)| n == name) {
+                    Some((_, c)) => c,
+                    None => return Err(Error::UnknownField(name.clone()).context(context())),
+                };
+
+                encoded = match compress_checked(codec.as_ref(), &encoded, &RoundTripCheck::new()).with_context(context) {
+                    Ok(c) => c,
+                    Err(e) => return Err(e),
+                };
+            }
+
+            if encoded.len() <= entry.length {
+                encoded.resize(entry.length, options.fill);
+
+                match self.read(entry.offset, entry.length) {
+                    Ok(current) if current == encoded.as_slice() => { report.unchanged.push(entry.path.clone()); continue; },
+                    Ok(_) => (),
+                    Err(e) => return Err(e),
+                }
+
+                if let Err(e) = self.write(entry.offset, &encoded) { return Err(e); }
+
+                report.written.push(entry.path.clone());
+                continue;
+            }
+
+            /* grown assets only move when the manifest says where they are referenced from */
+            if entry.pointers.is_empty() { return Err(Error::DataLengthMismatch(encoded.len(),entry.length).context(context())); }
+
+            let slot = match free.iter().position(|r| r.end - r.start >= encoded.len()) {
+                Some(i) => i,
+                None => return Err(Error::NoFreeSpace(encoded.len())),
+            };
+            let target = free[slot].start;
+            free[slot].start += encoded.len();
+
+            let address = match self.pointer_to(target) {
+                Ok(a) => a,
+                Err(e) => return Err(e),
+            };
+
+            if let Err(e) = self.write(target, &encoded) { return Err(e); }
+
+            for &pointer in &entry.pointers {
+                if let Err(e) = self.write(pointer, &address.as_u32().to_le_bytes()[..3]) { return Err(e); }
+            }
+
+            report.relocated.push((entry.path.clone(), entry.offset, target));
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{Error, Rom};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+impl BitOrder {
+    fn shift(&self, bit: usize) -> usize {
+        match self {
+            BitOrder::MsbFirst => 7 - (bit % 8),
+            BitOrder::LsbFirst => bit % 8,
+        }
+    }
+}
+
+/* fields come out in stream order: MSB-first streams put the first bit read at the top of the value,
+   LSB-first streams (deflate and most SNES packers) put it at the bottom */
+#[derive(Clone, Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+    order: BitOrder,
+}
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, bit: 0, order: BitOrder::MsbFirst }
+    }
+    pub fn order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+    pub fn position(&self) -> usize {
+        self.bit
+    }
+    pub fn seek(&mut self, bit: usize) {
+        self.bit = bit;
+    }
+    pub fn remaining(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.bit)
+    }
+    pub fn align(&mut self) {
+        self.bit = self.bit.div_ceil(8) * 8;
+    }
+    pub fn skip(&mut self, count: usize) -> Result<(), Error> {
+        if count > self.remaining() { return Err(Error::TruncatedData(self.data.len())); }
+
+        self.bit += count;
+        Ok(())
+    }
+    pub fn read_bit(&mut self) -> Result<bool, Error> {
+        let byte = match self.data.get(self.bit / 8) {
+            Some(b) => *b,
+            None => return Err(Error::TruncatedData(self.data.len())),
+        };
+        let set = (byte >> self.order.shift(self.bit)) & 1 == 1;
+
+        self.bit += 1;
+        Ok(set)
+    }
+    pub fn read(&mut self, count: usize) -> Result<u32, Error> {
+        if count > 32 { return Err(Error::InvalidBitWidth(count)); }
+        if count > self.remaining() { return Err(Error::TruncatedData(self.data.len())); }
+
+        let mut result = 0u32;
+
+        for i in 0..count {
+            let set = match self.read_bit() {
+                Ok(b) => b as u32,
+                Err(e) => return Err(e),
+            };
+
+            match self.order {
+                BitOrder::MsbFirst => result = (result << 1) | set,
+                BitOrder::LsbFirst => result |= set << i,
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn read_signed(&mut self, count: usize) -> Result<i32, Error> {
+        /* two's complement at the field's own width */
+        let value = match self.read(count) {
+            Ok(v) => v,
+            Err(e) => return Err(e),
+        };
+
+        if count == 0 || count == 32 { return Ok(value as i32); }
+
+        let shift = 32 - count;
+        Ok(((value << shift) as i32) >> shift)
+    }
+    pub fn peek(&self, count: usize) -> Result<u32, Error> {
+        self.clone().read(count)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BitWriter {
+    data: Vec<u8>,
+    bit: usize,
+    order: BitOrder,
+}
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::from_data(Vec::new())
+    }
+    pub fn from_data(data: Vec<u8>) -> Self {
+        /* writing over existing bytes only touches the bits written; the buffer grows if a field runs past the end */
+        Self { data, bit: 0, order: BitOrder::MsbFirst }
+    }
+    pub fn order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+    pub fn position(&self) -> usize {
+        self.bit
+    }
+    pub fn seek(&mut self, bit: usize) {
+        self.bit = bit;
+    }
+    pub fn align(&mut self) {
+        self.bit = self.bit.div_ceil(8) * 8;
+        if self.data.len() < self.bit / 8 { self.data.resize(self.bit / 8, 0); }
+    }
+    pub fn write_bit(&mut self, set: bool) {
+        let index = self.bit / 8;
+        let mask = 1u8 << self.order.shift(self.bit);
+
+        if index >= self.data.len() { self.data.resize(index + 1, 0); }
+
+        if set { self.data[index] |= mask; } else { self.data[index] &= !mask; }
+        self.bit += 1;
+    }
+    pub fn write(&mut self, value: u32, count: usize) -> Result<(), Error> {
+        if count > 32 { return Err(Error::InvalidBitWidth(count)); }
+
+        for i in 0..count {
+            let bit = match self.order {
+                BitOrder::MsbFirst => count - 1 - i,
+                BitOrder::LsbFirst => i,
+            };
+
+            self.write_bit((value >> bit) & 1 == 1);
+        }
+
+        Ok(())
+    }
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+impl Default for BitWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rom {
+    pub fn bit_reader(&self, offset: usize, size: usize, order: BitOrder) -> Result<BitReader<'_>, Error> {
+        match self.read(offset, size) {
+            Ok(d) => Ok(BitReader::new(d).order(order)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_bits(&self, offset: usize, bit: usize, count: usize, order: BitOrder) -> Result<u32, Error> {
+        /* bit counts from offset in stream order, so it may run past the first byte */
+        let end = (bit + count).div_ceil(8);
+        let mut reader = match self.bit_reader(offset, end, order) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+
+        reader.seek(bit);
+        reader.read(count)
+    }
+    pub fn write_bits(&mut self, offset: usize, bit: usize, count: usize, value: u32, order: BitOrder) -> Result<(), Error> {
+        let end = (bit + count).div_ceil(8);
+        let original = match self.read(offset, end) {
+            Ok(d) => d.to_vec(),
+            Err(e) => return Err(e),
+        };
+        let mut writer = BitWriter::from_data(original).order(order);
+
+        writer.seek(bit);
+        if let Err(e) = writer.write(value, count) { return Err(e); }
+
+        self.write(offset, writer.as_slice())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{Addr24, ExHiRom, HiRom, LoRom, Mapper, MemoryMap, Sa1, Sdd1, Spc7110, SuperFx};
+
+/* one rom mapping line of a board layout: banks and addresses it covers, where in the image it
+   starts and how far apart consecutive banks are in the image */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct MappingRange {
+    pub banks: (u8, u8),
+    pub addresses: (u16, u16),
+    pub base: usize,
+    pub stride: usize,
+}
+impl MappingRange {
+    pub const fn new(banks: (u8, u8), addresses: (u16, u16), base: usize, stride: usize) -> Self {
+        Self { banks, addresses, base, stride }
+    }
+    pub fn expected(&self, address: Addr24) -> usize {
+        self.base + (address.bank - self.banks.0) as usize * self.stride + (address.address - self.addresses.0) as usize
+    }
+    fn samples(&self) -> Vec<Addr24> {
+        let middle = self.addresses.0 + (self.addresses.1 - self.addresses.0) / 2;
+        let mut result = Vec::<Addr24>::new();
+
+        for &bank in &[self.banks.0, self.banks.1] {
+            for &address in &[self.addresses.0, middle, self.addresses.1] {
+                result.push(Addr24::new(bank, address));
+            }
+        }
+
+        result
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BoardTable {
+    pub board: &'static str,
+    pub mapper: Mapper,
+    pub ranges: &'static [MappingRange],
+}
+
+/* written out by hand from the common board layouts, not taken from any emulator's database, so they're a reference
+   to check a mapper against rather than an independent source of truth; coprocessor boards use their power-on bank registers */
+pub const BOARD_TABLES: [BoardTable; 7] = [
+    BoardTable { board: "SHVC-1A3M (lorom)", mapper: Mapper::LoRom(LoRom), ranges: &[
+        MappingRange::new((0x00, 0x3F), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0x40, 0x6F), (0x0000, 0x7FFF), 0x200000, 0x8000),
+        MappingRange::new((0x40, 0x7D), (0x8000, 0xFFFF), 0x200000, 0x8000),
+        MappingRange::new((0x80, 0xBF), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0xC0, 0xEF), (0x0000, 0x7FFF), 0x200000, 0x8000),
+        MappingRange::new((0xC0, 0xFF), (0x8000, 0xFFFF), 0x200000, 0x8000),
+    ] },
+    BoardTable { board: "SHVC-1J3M (hirom)", mapper: Mapper::HiRom(HiRom), ranges: &[
+        MappingRange::new((0x00, 0x3F), (0x8000, 0xFFFF), 0x008000, 0x10000),
+        MappingRange::new((0x40, 0x7D), (0x0000, 0xFFFF), 0x000000, 0x10000),
+        MappingRange::new((0x80, 0xBF), (0x8000, 0xFFFF), 0x008000, 0x10000),
+        MappingRange::new((0xC0, 0xFF), (0x0000, 0xFFFF), 0x000000, 0x10000),
+    ] },
+    BoardTable { board: "SHVC-LJ3M (exhirom)", mapper: Mapper::ExHiRom(ExHiRom), ranges: &[
+        MappingRange::new((0x00, 0x3F), (0x8000, 0xFFFF), 0x408000, 0x10000),
+        MappingRange::new((0x40, 0x7D), (0x0000, 0xFFFF), 0x400000, 0x10000),
+        MappingRange::new((0x80, 0xBF), (0x8000, 0xFFFF), 0x008000, 0x10000),
+        MappingRange::new((0xC0, 0xFF), (0x0000, 0xFFFF), 0x000000, 0x10000),
+    ] },
+    BoardTable { board: "SHVC-1L5B (sa1)", mapper: Mapper::Sa1(Sa1 { banks: [0, 1, 2, 3] }), ranges: &[
+        MappingRange::new((0x00, 0x1F), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0x20, 0x3F), (0x8000, 0xFFFF), 0x100000, 0x8000),
+        MappingRange::new((0x80, 0x9F), (0x8000, 0xFFFF), 0x200000, 0x8000),
+        MappingRange::new((0xA0, 0xBF), (0x8000, 0xFFFF), 0x300000, 0x8000),
+        MappingRange::new((0xC0, 0xFF), (0x0000, 0xFFFF), 0x000000, 0x10000),
+    ] },
+    BoardTable { board: "SHVC-1C0N (superfx)", mapper: Mapper::SuperFx(SuperFx), ranges: &[
+        MappingRange::new((0x00, 0x3F), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0x40, 0x5F), (0x0000, 0xFFFF), 0x000000, 0x10000),
+        MappingRange::new((0x80, 0xBF), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0xC0, 0xDF), (0x0000, 0xFFFF), 0x000000, 0x10000),
+    ] },
+    BoardTable { board: "SHVC-1N0N (sdd1)", mapper: Mapper::Sdd1(Sdd1 { banks: [0, 1, 2, 3] }), ranges: &[
+        MappingRange::new((0x00, 0x3F), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0x80, 0xBF), (0x8000, 0xFFFF), 0x000000, 0x8000),
+        MappingRange::new((0xC0, 0xFF), (0x0000, 0xFFFF), 0x000000, 0x10000),
+    ] },
+    BoardTable { board: "SHVC-LDH3C (spc7110)", mapper: Mapper::Spc7110(Spc7110 { banks: [0, 1, 2] }), ranges: &[
+        MappingRange::new((0x00, 0x0F), (0x8000, 0xFFFF), 0x008000, 0x10000),
+        MappingRange::new((0x80, 0x8F), (0x8000, 0xFFFF), 0x008000, 0x10000),
+        MappingRange::new((0xC0, 0xCF), (0x0000, 0xFFFF), 0x000000, 0x10000),
+        MappingRange::new((0xD0, 0xFF), (0x0000, 0xFFFF), 0x100000, 0x10000),
+    ] },
+];
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MappingMismatch {
+    pub board: &'static str,
+    pub address: Addr24,
+    pub expected: usize,
+    pub actual: Option<usize>,
+}
+impl std::fmt::Display for MappingMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.actual {
+            Some(a) => write!(f, "{}: ${:02X}:{:04X} should be 0x{:06X}, mapped to 0x{:06X}", self.board, { self.address.bank }, { self.address.address }, self.expected, a),
+            None => write!(f, "{}: ${:02X}:{:04X} should be 0x{:06X}, did not map", self.board, { self.address.bank }, { self.address.address }, self.expected),
+        }
+    }
+}
+
+impl BoardTable {
+    pub fn verify(&self) -> Vec<MappingMismatch> {
+        /* each sample is checked both ways: the address must land on the expected byte, and that byte's
+           canonical address must lead back to it */
+        let mut result = Vec::<MappingMismatch>::new();
+
+        for range in self.ranges {
+            for address in range.samples() {
+                let expected = range.expected(address);
+                let actual = self.mapper.address_to_pc(address).ok();
+
+                if actual != Some(expected) {
+                    result.push(MappingMismatch { board: self.board, address, expected, actual });
+                    continue;
+                }
+
+                let round_trip = self.mapper.pc_to_address(expected).and_then(|a| self.mapper.address_to_pc(a)).ok();
+
+                if round_trip != Some(expected) {
+                    result.push(MappingMismatch { board: self.board, address, expected, actual: round_trip });
+                }
+            }
+        }
+
+        result
+    }
+}
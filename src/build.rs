@@ -0,0 +1,63 @@
+use crate::{crc32, Rom};
+
+pub const FNV_OFFSET: u64 = 0xCBF29CE484222325;
+pub const FNV_PRIME: u64 = 0x100000001B3;
+
+pub fn fnv1a64(state: u64, data: &[u8]) -> u64 {
+    /* spelled out rather than std's Hasher, whose output may change between Rust releases */
+    data.iter().fold(state, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BuildOperation {
+    pub name: String,
+    pub offset: usize,
+    pub length: usize,
+    pub crc: u32,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BuildLog {
+    pub deterministic: bool,
+    pub base_crc: u32,
+    pub operations: Vec<BuildOperation>,
+}
+impl BuildLog {
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = fnv1a64(FNV_OFFSET, &self.base_crc.to_le_bytes());
+
+        for op in &self.operations {
+            hash = fnv1a64(hash, op.name.as_bytes());
+            hash = fnv1a64(hash, &[0]);
+            hash = fnv1a64(hash, &(op.offset as u64).to_le_bytes());
+            hash = fnv1a64(hash, &(op.length as u64).to_le_bytes());
+            hash = fnv1a64(hash, &op.crc.to_le_bytes());
+        }
+
+        hash
+    }
+}
+
+impl Rom {
+    pub fn start_build_log(&mut self, deterministic: bool) {
+        /* the fingerprint starts from the input image so the same steps on a different base never collide */
+        self.build_log = Some(BuildLog { deterministic, base_crc: self.crc32(), operations: Vec::new() });
+    }
+    pub fn stop_build_log(&mut self) -> Option<BuildLog> {
+        self.build_log.take()
+    }
+    pub fn build_log(&self) -> Option<&BuildLog> {
+        self.build_log.as_ref()
+    }
+    pub fn is_deterministic(&self) -> bool {
+        self.build_log.as_ref().is_some_and(|l| l.deterministic)
+    }
+    pub fn record_operation(&mut self, name: &str, offset: usize, data: &[u8]) {
+        if let Some(log) = &mut self.build_log {
+            log.operations.push(BuildOperation { name: name.to_string(), offset, length: data.len(), crc: crc32(data) });
+        }
+    }
+    pub fn build_fingerprint(&self) -> Option<u64> {
+        self.build_log.as_ref().map(|l| l.fingerprint())
+    }
+}
@@ -0,0 +1,33 @@
+use crate::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    parent: Option<Box<CancelToken>>,
+}
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn child(&self) -> Self {
+        /* sees its parent's cancellation, but cancelling it leaves the parent alone */
+        Self { flag: Arc::new(AtomicBool::new(false)), parent: Some(Box::new(self.clone())) }
+    }
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed) || self.parent.as_ref().is_some_and(|p| p.is_cancelled())
+    }
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() { Err(Error::Cancelled) }
+        else { Ok(()) }
+    }
+}
+impl From<Arc<AtomicBool>> for CancelToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self { flag, parent: None }
+    }
+}
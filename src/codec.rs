@@ -0,0 +1,57 @@
+use crate::Error;
+
+/* anything that packs a block of bytes and can unpack it again given the original size */
+pub trait Codec {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error>;
+    fn decompress(&self, data: &[u8], size: usize) -> Result<Vec<u8>, Error>;
+}
+
+/* off by default: the extra decode roughly doubles the cost of an encode */
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct RoundTripCheck {
+    pub verify: bool,
+    pub max_size: Option<usize>,
+}
+impl RoundTripCheck {
+    pub fn new() -> Self {
+        Self { verify: true, max_size: None }
+    }
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.verify || self.max_size.is_some()
+    }
+    pub fn check<F: FnOnce(&[u8], usize) -> Result<Vec<u8>, Error>>(&self, original: &[u8], encoded: &[u8], decompress: F) -> Result<(), Error> {
+        /* the size limit is the allocation the block has to go back into */
+        if let Some(max) = self.max_size {
+            if encoded.len() > max { return Err(Error::DataLengthMismatch(encoded.len(),max)); }
+        }
+
+        if !self.verify { return Ok(()); }
+
+        let decoded = match decompress(encoded, original.len()) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+
+        match decoded.iter().zip(original).position(|(a, b)| a != b) {
+            Some(p) => Err(Error::RoundTripMismatch(p)),
+            None if decoded.len() != original.len() => Err(Error::RoundTripMismatch(decoded.len().min(original.len()))),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn compress_checked<C: Codec + ?Sized>(codec: &C, data: &[u8], check: &RoundTripCheck) -> Result<Vec<u8>, Error> {
+    let encoded = match codec.compress(data) {
+        Ok(e) => e,
+        Err(e) => return Err(e),
+    };
+
+    match check.check(data, &encoded, |d, size| codec.decompress(d, size)) {
+        Ok(()) => Ok(encoded),
+        Err(e) => Err(e),
+    }
+}
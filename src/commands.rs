@@ -0,0 +1,192 @@
+use crate::{Error, Rom, SNESTile};
+
+pub trait Command {
+    fn name(&self) -> &'static str;
+    fn apply(&mut self, rom: &mut Rom) -> Result<(), Error>;
+    fn revert(&mut self, rom: &mut Rom) -> Result<(), Error>;
+}
+
+/* every command boils down to overwriting one span, so they share the bookkeeping for what was there before */
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct Overwrite {
+    offset: usize,
+    previous: Option<Vec<u8>>,
+}
+impl Overwrite {
+    fn new(offset: usize) -> Self {
+        Self { offset, previous: None }
+    }
+    fn apply(&mut self, rom: &mut Rom, data: &[u8]) -> Result<(), Error> {
+        let previous = match rom.read(self.offset, data.len()) {
+            Ok(d) => d.to_vec(),
+            Err(e) => return Err(e),
+        };
+
+        if let Err(e) = rom.write(self.offset, data) { return Err(e); }
+
+        self.previous = Some(previous);
+        Ok(())
+    }
+    fn revert(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        /* reverting something never applied leaves the rom alone */
+        let previous = match self.previous.take() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        rom.write(self.offset, previous)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WriteBytes {
+    pub data: Vec<u8>,
+    span: Overwrite,
+}
+impl WriteBytes {
+    pub fn new<B: AsRef<[u8]>>(offset: usize, data: B) -> Self {
+        Self { data: data.as_ref().to_vec(), span: Overwrite::new(offset) }
+    }
+}
+impl Command for WriteBytes {
+    fn name(&self) -> &'static str {
+        "write bytes"
+    }
+    fn apply(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.apply(rom, &self.data)
+    }
+    fn revert(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.revert(rom)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FillRegion {
+    pub length: usize,
+    pub value: u8,
+    span: Overwrite,
+}
+impl FillRegion {
+    pub fn new(offset: usize, length: usize, value: u8) -> Self {
+        Self { length, value, span: Overwrite::new(offset) }
+    }
+}
+impl Command for FillRegion {
+    fn name(&self) -> &'static str {
+        "fill region"
+    }
+    fn apply(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.apply(rom, &vec![self.value; self.length])
+    }
+    fn revert(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.revert(rom)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CopyRegion {
+    pub source: usize,
+    pub length: usize,
+    span: Overwrite,
+}
+impl CopyRegion {
+    pub fn new(source: usize, destination: usize, length: usize) -> Self {
+        Self { source, length, span: Overwrite::new(destination) }
+    }
+}
+impl Command for CopyRegion {
+    fn name(&self) -> &'static str {
+        "copy region"
+    }
+    fn apply(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        /* read the whole source first so overlapping copies behave like memmove */
+        let data = match rom.read(self.source, self.length) {
+            Ok(d) => d.to_vec(),
+            Err(e) => return Err(e),
+        };
+
+        self.span.apply(rom, &data)
+    }
+    fn revert(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.revert(rom)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InsertTiles {
+    pub data: Vec<u8>,
+    span: Overwrite,
+}
+impl InsertTiles {
+    pub fn new<T: SNESTile>(offset: usize, tiles: &[T]) -> Self {
+        Self { data: tiles.iter().flat_map(|t| t.as_data().to_vec()).collect(), span: Overwrite::new(offset) }
+    }
+}
+impl Command for InsertTiles {
+    fn name(&self) -> &'static str {
+        "insert tiles"
+    }
+    fn apply(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.apply(rom, &self.data)
+    }
+    fn revert(&mut self, rom: &mut Rom) -> Result<(), Error> {
+        self.span.revert(rom)
+    }
+}
+
+#[derive(Default)]
+pub struct CommandHistory {
+    undo: Vec<Box<dyn Command>>,
+    redo: Vec<Box<dyn Command>>,
+}
+impl CommandHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn execute(&mut self, rom: &mut Rom, mut command: Box<dyn Command>) -> Result<(), Error> {
+        if let Err(e) = command.apply(rom) { return Err(e); }
+
+        /* a fresh edit forks history, so whatever was undone can no longer be redone */
+        self.undo.push(command);
+        self.redo.clear();
+        Ok(())
+    }
+    pub fn undo(&mut self, rom: &mut Rom) -> Result<bool, Error> {
+        let mut command = match self.undo.pop() {
+            Some(c) => c,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = command.revert(rom) { self.undo.push(command); return Err(e); }
+
+        self.redo.push(command);
+        Ok(true)
+    }
+    pub fn redo(&mut self, rom: &mut Rom) -> Result<bool, Error> {
+        let mut command = match self.redo.pop() {
+            Some(c) => c,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = command.apply(rom) { self.redo.push(command); return Err(e); }
+
+        self.undo.push(command);
+        Ok(true)
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    pub fn undo_name(&self) -> Option<&'static str> {
+        self.undo.last().map(|c| c.name())
+    }
+    pub fn redo_name(&self) -> Option<&'static str> {
+        self.redo.last().map(|c| c.name())
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{Addr24, Error};
+
+/* where a failure happened: the high-level step, and the ROM location it was working on if it had one */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ErrorContext {
+    pub operation: String,
+    pub address: Option<Addr24>,
+    pub offset: Option<usize>,
+    pub length: Option<usize>,
+}
+impl ErrorContext {
+    pub fn new(operation: &str) -> Self {
+        Self { operation: operation.to_string(), address: None, offset: None, length: None }
+    }
+    pub fn address(mut self, address: Addr24) -> Self {
+        self.address = Some(address);
+        self
+    }
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = Some(length);
+        self
+    }
+}
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Err(e) = write!(f, "{}", self.operation) { return Err(e); }
+        if let Some(address) = self.address { if let Err(e) = write!(f, " at ${:02X}:{:04X}", address.bank, { address.address }) { return Err(e); } }
+        if let Some(offset) = self.offset { if let Err(e) = write!(f, " (offset 0x{:06X})", offset) { return Err(e); } }
+        if let Some(length) = self.length { if let Err(e) = write!(f, ", {} bytes", length) { return Err(e); } }
+
+        Ok(())
+    }
+}
+
+impl Error {
+    pub fn context(self, context: ErrorContext) -> Self {
+        Error::Context(context, Box::new(self))
+    }
+    pub fn root(&self) -> &Error {
+        /* the error that actually happened, under however many layers of context */
+        match self {
+            Error::Context(_, inner) => inner.root(),
+            _ => self,
+        }
+    }
+    pub fn contexts(&self) -> Vec<&ErrorContext> {
+        /* outermost first, the order a pipeline would describe itself in */
+        let mut result = Vec::<&ErrorContext>::new();
+        let mut current = self;
+
+        while let Error::Context(context, inner) = current {
+            result.push(context);
+            current = inner;
+        }
+
+        result
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Context(context, inner) => write!(f, "{}: {}", context, inner),
+            Error::IoError(e) => write!(f, "IoError({})", e),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+pub trait ResultContext<T> {
+    fn context(self, context: ErrorContext) -> Result<T, Error>;
+    fn with_context<F: FnOnce() -> ErrorContext>(self, context: F) -> Result<T, Error>;
+}
+impl<T> ResultContext<T> for Result<T, Error> {
+    fn context(self, context: ErrorContext) -> Result<T, Error> {
+        self.map_err(|e| e.context(context))
+    }
+    fn with_context<F: FnOnce() -> ErrorContext>(self, context: F) -> Result<T, Error> {
+        /* for hot loops: the context is only built if something actually failed */
+        self.map_err(|e| e.context(context()))
+    }
+}
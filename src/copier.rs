@@ -0,0 +1,361 @@
+use crate::{Error, Mapper, Rom, COPIER_SIGNATURE};
+use std::path::Path;
+
+pub const COPIER_HEADER_SIZE: usize = 0x200;
+
+/* Pro Fighter keeps the board type in bytes 4-5: (byte 4, byte 5, has sram, has dsp) */
+pub const FIG_BOARD_CODES: [(u8, u8, bool, bool); 6] = [
+    (0x77, 0x83, false, false),
+    (0x00, 0x80, true, false),
+    (0xDD, 0x82, false, false),
+    (0xDD, 0x02, true, false),
+    (0x47, 0x83, false, true),
+    (0xFD, 0x82, false, true),
+];
+
+pub trait CopierHeader: Sized {
+    fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error>;
+    fn as_data(&self) -> &[u8];
+    fn hirom(&self) -> bool;
+    fn split(&self) -> bool;
+    fn has_sram(&self) -> bool;
+    fn blocks(&self) -> u16 {
+        /* every format starts with the image size in 8KB blocks */
+        u16::from_le_bytes([self.as_data()[0], self.as_data()[1]])
+    }
+    fn rom_size(&self) -> usize {
+        self.blocks() as usize * 0x2000
+    }
+}
+
+fn header_bytes(data: &[u8]) -> Result<[u8; COPIER_HEADER_SIZE], Error> {
+    if data.len() < COPIER_HEADER_SIZE { return Err(Error::TruncatedData(data.len())); }
+
+    let mut result = [0u8; COPIER_HEADER_SIZE];
+    result.copy_from_slice(&data[..COPIER_HEADER_SIZE]);
+    Ok(result)
+}
+
+/* Super Wild Card: byte 2 holds the mode flags, bits 3-2 the SRAM size */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SwcHeader(pub [u8; COPIER_HEADER_SIZE]);
+impl SwcHeader {
+    pub const SPLIT: u8 = 0x40;
+    pub const SRAM_HIROM: u8 = 0x20;
+    pub const DRAM_HIROM: u8 = 0x10;
+
+    pub fn new(rom_size: usize) -> Self {
+        let mut data = [0u8; COPIER_HEADER_SIZE];
+
+        data[0..2].copy_from_slice(&((rom_size / 0x2000) as u16).to_le_bytes());
+        data[8..11].copy_from_slice(&COPIER_SIGNATURE);
+        /* no SRAM until told otherwise */
+        data[2] = 0x0C;
+
+        Self(data)
+    }
+    pub fn set_split(&mut self, split: bool) {
+        if split { self.0[2] |= Self::SPLIT; } else { self.0[2] &= !Self::SPLIT; }
+    }
+    pub fn set_hirom(&mut self, hirom: bool) {
+        if hirom { self.0[2] |= Self::SRAM_HIROM | Self::DRAM_HIROM; } else { self.0[2] &= !(Self::SRAM_HIROM | Self::DRAM_HIROM); }
+    }
+    pub fn set_sram_size(&mut self, size: Option<usize>) {
+        let bits = match size {
+            Some(s) if s > 0x2000 => 0x00,
+            Some(s) if s > 0x800 => 0x04,
+            Some(s) if s > 0 => 0x08,
+            _ => 0x0C,
+        };
+
+        self.0[2] = (self.0[2] & !0x0C) | bits;
+    }
+    pub fn sram_size(&self) -> Option<usize> {
+        match (self.0[2] >> 2) & 0x03 {
+            0 => Some(0x8000),
+            1 => Some(0x2000),
+            2 => Some(0x800),
+            _ => None,
+        }
+    }
+}
+impl CopierHeader for SwcHeader {
+    fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        match header_bytes(data.as_ref()) {
+            Ok(d) => Ok(Self(d)),
+            Err(e) => Err(e),
+        }
+    }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
+    fn hirom(&self) -> bool {
+        self.0[2] & Self::DRAM_HIROM != 0
+    }
+    fn split(&self) -> bool {
+        self.0[2] & Self::SPLIT != 0
+    }
+    fn has_sram(&self) -> bool {
+        self.sram_size().is_some()
+    }
+}
+
+/* Super Magicom: same size word and split bit as SWC but no signature, and no SRAM setting */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SmcHeader(pub [u8; COPIER_HEADER_SIZE]);
+impl SmcHeader {
+    pub const SPLIT: u8 = 0x40;
+    pub const HIROM: u8 = 0x30;
+
+    pub fn new(rom_size: usize) -> Self {
+        let mut data = [0u8; COPIER_HEADER_SIZE];
+        data[0..2].copy_from_slice(&((rom_size / 0x2000) as u16).to_le_bytes());
+
+        Self(data)
+    }
+    pub fn set_split(&mut self, split: bool) {
+        if split { self.0[2] |= Self::SPLIT; } else { self.0[2] &= !Self::SPLIT; }
+    }
+    pub fn set_hirom(&mut self, hirom: bool) {
+        if hirom { self.0[2] |= Self::HIROM; } else { self.0[2] &= !Self::HIROM; }
+    }
+}
+impl CopierHeader for SmcHeader {
+    fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        match header_bytes(data.as_ref()) {
+            Ok(d) => Ok(Self(d)),
+            Err(e) => Err(e),
+        }
+    }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
+    fn hirom(&self) -> bool {
+        self.0[2] & Self::HIROM != 0
+    }
+    fn split(&self) -> bool {
+        self.0[2] & Self::SPLIT != 0
+    }
+    fn has_sram(&self) -> bool {
+        false
+    }
+}
+
+/* Pro Fighter: byte 2 is the split flag, byte 3 the HiROM flag and bytes 4-5 a board code */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct FigHeader(pub [u8; COPIER_HEADER_SIZE]);
+impl FigHeader {
+    pub const SPLIT: u8 = 0x40;
+    pub const HIROM: u8 = 0x80;
+
+    pub fn new(rom_size: usize, hirom: bool) -> Self {
+        let mut data = [0u8; COPIER_HEADER_SIZE];
+
+        data[0..2].copy_from_slice(&((rom_size / 0x2000) as u16).to_le_bytes());
+        if hirom { data[3] = Self::HIROM; data[4] = 0xDD; data[5] = 0x82; }
+        else { data[4] = 0x77; data[5] = 0x83; }
+
+        Self(data)
+    }
+    pub fn set_split(&mut self, split: bool) {
+        self.0[2] = if split { Self::SPLIT } else { 0 };
+    }
+    pub fn board_code(&self) -> Option<(bool, bool)> {
+        FIG_BOARD_CODES.iter().find(|(a, b, _, _)| *a == self.0[4] && *b == self.0[5]).map(|(_, _, sram, dsp)| (*sram, *dsp))
+    }
+    pub fn dsp(&self) -> bool {
+        self.board_code().is_some_and(|(_, dsp)| dsp)
+    }
+}
+impl CopierHeader for FigHeader {
+    fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        match header_bytes(data.as_ref()) {
+            Ok(d) => Ok(Self(d)),
+            Err(e) => Err(e),
+        }
+    }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
+    fn hirom(&self) -> bool {
+        self.0[3] & Self::HIROM != 0
+    }
+    fn split(&self) -> bool {
+        self.0[2] & Self::SPLIT != 0
+    }
+    fn has_sram(&self) -> bool {
+        /* the board code only says whether there is SRAM, not how much; the internal header has the size */
+        self.board_code().is_some_and(|(sram, _)| sram)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CopierHeaderFormat {
+    Smc(SmcHeader),
+    Swc(SwcHeader),
+    Fig(FigHeader),
+}
+impl CopierHeaderFormat {
+    pub fn detect<B: AsRef<[u8]>>(data: B) -> Option<Self> {
+        let data = match header_bytes(data.as_ref()) {
+            Ok(d) => d,
+            Err(_) => return None,
+        };
+
+        /* the SWC signature is unambiguous; a known FIG board code comes next; anything else that leaves
+           the rest of the header blank is taken for a Magicom */
+        if data[8..11] == COPIER_SIGNATURE { return Some(CopierHeaderFormat::Swc(SwcHeader(data))); }
+
+        let fig = FigHeader(data);
+        if fig.board_code().is_some() && (data[3] & !FigHeader::HIROM) == 0 && data[6..].iter().all(|&b| b == 0) { return Some(CopierHeaderFormat::Fig(fig)); }
+
+        if data[3..].iter().all(|&b| b == 0) { return Some(CopierHeaderFormat::Smc(SmcHeader(data))); }
+
+        None
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            CopierHeaderFormat::Smc(_) => "smc",
+            CopierHeaderFormat::Swc(_) => "swc",
+            CopierHeaderFormat::Fig(_) => "fig",
+        }
+    }
+    fn header(&self) -> &dyn CopierFields {
+        match self {
+            CopierHeaderFormat::Smc(h) => h,
+            CopierHeaderFormat::Swc(h) => h,
+            CopierHeaderFormat::Fig(h) => h,
+        }
+    }
+    pub fn rom_size(&self) -> usize {
+        self.header().fields().0
+    }
+    pub fn hirom(&self) -> bool {
+        self.header().fields().1
+    }
+    pub fn split(&self) -> bool {
+        self.header().fields().2
+    }
+    pub fn has_sram(&self) -> bool {
+        self.header().fields().3
+    }
+    pub fn as_data(&self) -> &[u8] {
+        match self {
+            CopierHeaderFormat::Smc(h) => h.as_data(),
+            CopierHeaderFormat::Swc(h) => h.as_data(),
+            CopierHeaderFormat::Fig(h) => h.as_data(),
+        }
+    }
+    pub fn set_rom_size(&mut self, size: usize) {
+        let data = match self {
+            CopierHeaderFormat::Smc(h) => &mut h.0,
+            CopierHeaderFormat::Swc(h) => &mut h.0,
+            CopierHeaderFormat::Fig(h) => &mut h.0,
+        };
+
+        data[0..2].copy_from_slice(&((size / 0x2000) as u16).to_le_bytes());
+    }
+    pub fn set_split(&mut self, split: bool) {
+        match self {
+            CopierHeaderFormat::Smc(h) => h.set_split(split),
+            CopierHeaderFormat::Swc(h) => h.set_split(split),
+            CopierHeaderFormat::Fig(h) => h.set_split(split),
+        }
+    }
+}
+
+/* CopierHeader has a generic constructor, so it can't be a trait object; this is the object-safe slice of it */
+trait CopierFields {
+    fn fields(&self) -> (usize, bool, bool, bool);
+}
+impl<T: CopierHeader> CopierFields for T {
+    fn fields(&self) -> (usize, bool, bool, bool) {
+        (self.rom_size(), self.hirom(), self.split(), self.has_sram())
+    }
+}
+
+impl Rom {
+    pub fn copier_header(&self) -> Option<CopierHeaderFormat> {
+        if self.header_size() != COPIER_HEADER_SIZE { return None; }
+
+        CopierHeaderFormat::detect(&self.as_slice()[..COPIER_HEADER_SIZE])
+    }
+    pub fn from_split_data<B: AsRef<[u8]>>(parts: &[B]) -> Result<Self, Error> {
+        /* every part may carry its own copier header; only the first survives, rewritten to cover the whole image */
+        if parts.is_empty() { return Err(Error::InvalidPartCount(0)); }
+
+        let mut header: Option<Vec<u8>> = None;
+        let mut data = Vec::<u8>::new();
+
+        for (index, part) in parts.iter().enumerate() {
+            let part = part.as_ref();
+            let skip = if part.len() % 1024 == COPIER_HEADER_SIZE { COPIER_HEADER_SIZE } else { 0 };
+
+            if index == 0 && skip != 0 { header = Some(part[..skip].to_vec()); }
+            data.extend_from_slice(&part[skip..]);
+        }
+
+        let mut result = match header {
+            Some(h) => match CopierHeaderFormat::detect(&h) {
+                Some(mut format) => {
+                    format.set_rom_size(data.len());
+                    format.set_split(false);
+                    format.as_data().to_vec()
+                },
+                None => h,
+            },
+            None => Vec::new(),
+        };
+
+        result.extend_from_slice(&data);
+        Ok(Self::new(result))
+    }
+    pub fn from_split_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self, Error> {
+        let mut parts = Vec::<Vec<u8>>::with_capacity(paths.len());
+
+        for path in paths {
+            match std::fs::read(path) {
+                Ok(d) => parts.push(d),
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+
+        Self::from_split_data(&parts)
+    }
+    pub fn split(&self, parts: usize) -> Result<Vec<Vec<u8>>, Error> {
+        /* parts are whole 8KB blocks; any remainder goes to the earlier parts so only the last ones come up short */
+        let data = &self.as_slice()[self.header_size()..];
+        let blocks = data.len() / 0x2000;
+
+        if parts == 0 || parts > blocks { return Err(Error::InvalidPartCount(parts)); }
+        if !data.len().is_multiple_of(0x2000) { return Err(Error::DataLengthMismatch(data.len(),blocks * 0x2000)); }
+
+        let mut header = match self.copier_header() {
+            Some(h) => h,
+            None => {
+                let mut swc = SwcHeader::new(0);
+                swc.set_hirom(matches!(self.memory_map(), Ok(Mapper::HiRom(_)) | Ok(Mapper::ExHiRom(_))));
+                CopierHeaderFormat::Swc(swc)
+            },
+        };
+
+        let mut result = Vec::<Vec<u8>>::with_capacity(parts);
+        let mut offset = 0;
+
+        for index in 0..parts {
+            let size = (blocks / parts + if index < blocks % parts { 1 } else { 0 }) * 0x2000;
+
+            /* the split flag marks every part that has another after it */
+            header.set_rom_size(size);
+            header.set_split(index + 1 < parts);
+
+            let mut part = header.as_data().to_vec();
+            part.extend_from_slice(&data[offset..offset+size]);
+            result.push(part);
+
+            offset += size;
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,881 @@
+use crate::{Addr24, DmaParams, Error, Mapper, MemoryMap, Rom};
+use std::collections::HashMap;
+
+pub const FLAG_C: u8 = 0x01;
+pub const FLAG_Z: u8 = 0x02;
+pub const FLAG_I: u8 = 0x04;
+pub const FLAG_D: u8 = 0x08;
+pub const FLAG_X: u8 = 0x10;
+pub const FLAG_M: u8 = 0x20;
+pub const FLAG_V: u8 = 0x40;
+pub const FLAG_N: u8 = 0x80;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Mnemonic {
+    ADC, AND, ASL, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRA, BRK, BRL, BVC, BVS, CLC,
+    CLD, CLI, CLV, CMP, COP, CPX, CPY, DEC, DEX, DEY, EOR, INC, INX, INY, JML, JMP,
+    JSL, JSR, LDA, LDX, LDY, LSR, MVN, MVP, NOP, ORA, PEA, PEI, PER, PHA, PHB, PHD,
+    PHK, PHP, PHX, PHY, PLA, PLB, PLD, PLP, PLX, PLY, REP, ROL, ROR, RTI, RTL, RTS,
+    SBC, SEC, SED, SEI, SEP, STA, STP, STX, STY, STZ, TAX, TAY, TCD, TCS, TDC, TRB,
+    TSB, TSC, TSX, TXA, TXS, TXY, TYA, TYX, WAI, WDM, XBA, XCE,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    ImmediateM,
+    ImmediateX,
+    Immediate8,
+    Immediate16,
+    Relative,
+    RelativeLong,
+    Direct,
+    DirectX,
+    DirectY,
+    DirectIndirect,
+    DirectXIndirect,
+    DirectIndirectY,
+    DirectIndirectLong,
+    DirectIndirectLongY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    AbsoluteLong,
+    AbsoluteLongX,
+    StackRelative,
+    StackRelativeIndirectY,
+    AbsoluteIndirect,
+    AbsoluteXIndirect,
+    AbsoluteIndirectLong,
+    BlockMove,
+}
+impl AddressingMode {
+    pub fn operand_size(&self, m8: bool, x8: bool) -> usize {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::ImmediateM if m8 => 1,
+            AddressingMode::ImmediateX if x8 => 1,
+            AddressingMode::ImmediateM | AddressingMode::ImmediateX => 2,
+            AddressingMode::Immediate8 | AddressingMode::Relative => 1,
+            AddressingMode::Direct | AddressingMode::DirectX | AddressingMode::DirectY => 1,
+            AddressingMode::DirectIndirect | AddressingMode::DirectXIndirect | AddressingMode::DirectIndirectY => 1,
+            AddressingMode::DirectIndirectLong | AddressingMode::DirectIndirectLongY => 1,
+            AddressingMode::StackRelative | AddressingMode::StackRelativeIndirectY => 1,
+            AddressingMode::AbsoluteLong | AddressingMode::AbsoluteLongX => 3,
+            _ => 2,
+        }
+    }
+}
+
+pub fn decode_opcode(opcode: u8) -> (Mnemonic, AddressingMode) {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    static OPCODES: [(Mnemonic, AddressingMode); 256] = [
+        (BRK,Immediate8),(ORA,DirectXIndirect),(COP,Immediate8),(ORA,StackRelative),(TSB,Direct),(ORA,Direct),(ASL,Direct),(ORA,DirectIndirectLong),
+        (PHP,Implied),(ORA,ImmediateM),(ASL,Accumulator),(PHD,Implied),(TSB,Absolute),(ORA,Absolute),(ASL,Absolute),(ORA,AbsoluteLong),
+        (BPL,Relative),(ORA,DirectIndirectY),(ORA,DirectIndirect),(ORA,StackRelativeIndirectY),(TRB,Direct),(ORA,DirectX),(ASL,DirectX),(ORA,DirectIndirectLongY),
+        (CLC,Implied),(ORA,AbsoluteY),(INC,Accumulator),(TCS,Implied),(TRB,Absolute),(ORA,AbsoluteX),(ASL,AbsoluteX),(ORA,AbsoluteLongX),
+        (JSR,Absolute),(AND,DirectXIndirect),(JSL,AbsoluteLong),(AND,StackRelative),(BIT,Direct),(AND,Direct),(ROL,Direct),(AND,DirectIndirectLong),
+        (PLP,Implied),(AND,ImmediateM),(ROL,Accumulator),(PLD,Implied),(BIT,Absolute),(AND,Absolute),(ROL,Absolute),(AND,AbsoluteLong),
+        (BMI,Relative),(AND,DirectIndirectY),(AND,DirectIndirect),(AND,StackRelativeIndirectY),(BIT,DirectX),(AND,DirectX),(ROL,DirectX),(AND,DirectIndirectLongY),
+        (SEC,Implied),(AND,AbsoluteY),(DEC,Accumulator),(TSC,Implied),(BIT,AbsoluteX),(AND,AbsoluteX),(ROL,AbsoluteX),(AND,AbsoluteLongX),
+        (RTI,Implied),(EOR,DirectXIndirect),(WDM,Immediate8),(EOR,StackRelative),(MVP,BlockMove),(EOR,Direct),(LSR,Direct),(EOR,DirectIndirectLong),
+        (PHA,Implied),(EOR,ImmediateM),(LSR,Accumulator),(PHK,Implied),(JMP,Absolute),(EOR,Absolute),(LSR,Absolute),(EOR,AbsoluteLong),
+        (BVC,Relative),(EOR,DirectIndirectY),(EOR,DirectIndirect),(EOR,StackRelativeIndirectY),(MVN,BlockMove),(EOR,DirectX),(LSR,DirectX),(EOR,DirectIndirectLongY),
+        (CLI,Implied),(EOR,AbsoluteY),(PHY,Implied),(TCD,Implied),(JML,AbsoluteLong),(EOR,AbsoluteX),(LSR,AbsoluteX),(EOR,AbsoluteLongX),
+        (RTS,Implied),(ADC,DirectXIndirect),(PER,RelativeLong),(ADC,StackRelative),(STZ,Direct),(ADC,Direct),(ROR,Direct),(ADC,DirectIndirectLong),
+        (PLA,Implied),(ADC,ImmediateM),(ROR,Accumulator),(RTL,Implied),(JMP,AbsoluteIndirect),(ADC,Absolute),(ROR,Absolute),(ADC,AbsoluteLong),
+        (BVS,Relative),(ADC,DirectIndirectY),(ADC,DirectIndirect),(ADC,StackRelativeIndirectY),(STZ,DirectX),(ADC,DirectX),(ROR,DirectX),(ADC,DirectIndirectLongY),
+        (SEI,Implied),(ADC,AbsoluteY),(PLY,Implied),(TDC,Implied),(JMP,AbsoluteXIndirect),(ADC,AbsoluteX),(ROR,AbsoluteX),(ADC,AbsoluteLongX),
+        (BRA,Relative),(STA,DirectXIndirect),(BRL,RelativeLong),(STA,StackRelative),(STY,Direct),(STA,Direct),(STX,Direct),(STA,DirectIndirectLong),
+        (DEY,Implied),(BIT,ImmediateM),(TXA,Implied),(PHB,Implied),(STY,Absolute),(STA,Absolute),(STX,Absolute),(STA,AbsoluteLong),
+        (BCC,Relative),(STA,DirectIndirectY),(STA,DirectIndirect),(STA,StackRelativeIndirectY),(STY,DirectX),(STA,DirectX),(STX,DirectY),(STA,DirectIndirectLongY),
+        (TYA,Implied),(STA,AbsoluteY),(TXS,Implied),(TXY,Implied),(STZ,Absolute),(STA,AbsoluteX),(STZ,AbsoluteX),(STA,AbsoluteLongX),
+        (LDY,ImmediateX),(LDA,DirectXIndirect),(LDX,ImmediateX),(LDA,StackRelative),(LDY,Direct),(LDA,Direct),(LDX,Direct),(LDA,DirectIndirectLong),
+        (TAY,Implied),(LDA,ImmediateM),(TAX,Implied),(PLB,Implied),(LDY,Absolute),(LDA,Absolute),(LDX,Absolute),(LDA,AbsoluteLong),
+        (BCS,Relative),(LDA,DirectIndirectY),(LDA,DirectIndirect),(LDA,StackRelativeIndirectY),(LDY,DirectX),(LDA,DirectX),(LDX,DirectY),(LDA,DirectIndirectLongY),
+        (CLV,Implied),(LDA,AbsoluteY),(TSX,Implied),(TYX,Implied),(LDY,AbsoluteX),(LDA,AbsoluteX),(LDX,AbsoluteY),(LDA,AbsoluteLongX),
+        (CPY,ImmediateX),(CMP,DirectXIndirect),(REP,Immediate8),(CMP,StackRelative),(CPY,Direct),(CMP,Direct),(DEC,Direct),(CMP,DirectIndirectLong),
+        (INY,Implied),(CMP,ImmediateM),(DEX,Implied),(WAI,Implied),(CPY,Absolute),(CMP,Absolute),(DEC,Absolute),(CMP,AbsoluteLong),
+        (BNE,Relative),(CMP,DirectIndirectY),(CMP,DirectIndirect),(CMP,StackRelativeIndirectY),(PEI,Direct),(CMP,DirectX),(DEC,DirectX),(CMP,DirectIndirectLongY),
+        (CLD,Implied),(CMP,AbsoluteY),(PHX,Implied),(STP,Implied),(JML,AbsoluteIndirectLong),(CMP,AbsoluteX),(DEC,AbsoluteX),(CMP,AbsoluteLongX),
+        (CPX,ImmediateX),(SBC,DirectXIndirect),(SEP,Immediate8),(SBC,StackRelative),(CPX,Direct),(SBC,Direct),(INC,Direct),(SBC,DirectIndirectLong),
+        (INX,Implied),(SBC,ImmediateM),(NOP,Implied),(XBA,Implied),(CPX,Absolute),(SBC,Absolute),(INC,Absolute),(SBC,AbsoluteLong),
+        (BEQ,Relative),(SBC,DirectIndirectY),(SBC,DirectIndirect),(SBC,StackRelativeIndirectY),(PEA,Immediate16),(SBC,DirectX),(INC,DirectX),(SBC,DirectIndirectLongY),
+        (SED,Implied),(SBC,AbsoluteY),(PLX,Implied),(XCE,Implied),(JSR,AbsoluteXIndirect),(SBC,AbsoluteX),(INC,AbsoluteX),(SBC,AbsoluteLongX),
+    ];
+
+    OPCODES[opcode as usize]
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Instruction {
+    pub address: Addr24,
+    pub opcode: u8,
+    pub mnemonic: Mnemonic,
+    pub mode: AddressingMode,
+    pub operand: u32,
+    pub length: usize,
+}
+impl Instruction {
+    pub fn branch_target(&self) -> Option<Addr24> {
+        let next = self.address.address.wrapping_add(self.length as u16);
+
+        match self.mode {
+            AddressingMode::Relative => Some(Addr24::new(self.address.bank, next.wrapping_add(self.operand as u8 as i8 as u16))),
+            AddressingMode::RelativeLong if self.mnemonic == Mnemonic::BRL => Some(Addr24::new(self.address.bank, next.wrapping_add(self.operand as u16))),
+            _ => None,
+        }
+    }
+}
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mnemonic = format!("{:?}", self.mnemonic);
+        let operand = match self.mode {
+            AddressingMode::Implied => String::new(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::ImmediateM | AddressingMode::ImmediateX | AddressingMode::Immediate8 | AddressingMode::Immediate16 => {
+                if self.length == 2 { format!("#${:02X}", self.operand) } else { format!("#${:04X}", self.operand) }
+            },
+            AddressingMode::Relative | AddressingMode::RelativeLong => match self.branch_target() {
+                Some(t) => format!("${:04X}", { t.address }),
+                None => format!("${:04X}", self.operand),
+            },
+            AddressingMode::Direct => format!("${:02X}", self.operand),
+            AddressingMode::DirectX => format!("${:02X},X", self.operand),
+            AddressingMode::DirectY => format!("${:02X},Y", self.operand),
+            AddressingMode::DirectIndirect => format!("(${:02X})", self.operand),
+            AddressingMode::DirectXIndirect => format!("(${:02X},X)", self.operand),
+            AddressingMode::DirectIndirectY => format!("(${:02X}),Y", self.operand),
+            AddressingMode::DirectIndirectLong => format!("[${:02X}]", self.operand),
+            AddressingMode::DirectIndirectLongY => format!("[${:02X}],Y", self.operand),
+            AddressingMode::Absolute => format!("${:04X}", self.operand),
+            AddressingMode::AbsoluteX => format!("${:04X},X", self.operand),
+            AddressingMode::AbsoluteY => format!("${:04X},Y", self.operand),
+            AddressingMode::AbsoluteLong => format!("${:06X}", self.operand),
+            AddressingMode::AbsoluteLongX => format!("${:06X},X", self.operand),
+            AddressingMode::StackRelative => format!("${:02X},S", self.operand),
+            AddressingMode::StackRelativeIndirectY => format!("(${:02X},S),Y", self.operand),
+            AddressingMode::AbsoluteIndirect => format!("(${:04X})", self.operand),
+            AddressingMode::AbsoluteXIndirect => format!("(${:04X},X)", self.operand),
+            AddressingMode::AbsoluteIndirectLong => format!("[${:04X}]", self.operand),
+            AddressingMode::BlockMove => format!("${:02X},${:02X}", self.operand & 0xFF, self.operand >> 8),
+        };
+
+        if operand.is_empty() { write!(f, "{}", mnemonic) }
+        else { write!(f, "{} {}", mnemonic, operand) }
+    }
+}
+
+pub fn disassemble(rom: &Rom, address: Addr24, m8: bool, x8: bool) -> Result<Instruction, Error> {
+    let offset = match address.to_mapped_offset(rom) {
+        Ok(o) => o,
+        Err(e) => return Err(e),
+    };
+    let opcode = match rom.read(offset, 1) {
+        Ok(d) => d[0],
+        Err(e) => return Err(e),
+    };
+    let (mnemonic, mode) = decode_opcode(opcode);
+    let size = mode.operand_size(m8, x8);
+    let bytes = match rom.read(offset + 1, size) {
+        Ok(d) => d,
+        Err(e) => return Err(e),
+    };
+    let mut operand = 0u32;
+
+    for (i, byte) in bytes.iter().enumerate() {
+        operand |= (*byte as u32) << (i * 8);
+    }
+
+    Ok(Instruction { address, opcode, mnemonic, mode, operand, length: size + 1 })
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Registers {
+    pub a: u16,
+    pub x: u16,
+    pub y: u16,
+    pub s: u16,
+    pub d: u16,
+    pub db: u8,
+    pub pb: u8,
+    pub pc: u16,
+    pub p: u8,
+    pub e: bool,
+}
+impl Registers {
+    pub fn new() -> Self {
+        Self { a: 0, x: 0, y: 0, s: 0x1FF, d: 0, db: 0, pb: 0, pc: 0, p: FLAG_M | FLAG_X | FLAG_I, e: true }
+    }
+    pub fn m8(&self) -> bool {
+        self.e || self.p & FLAG_M != 0
+    }
+    pub fn x8(&self) -> bool {
+        self.e || self.p & FLAG_X != 0
+    }
+    pub fn pc_address(&self) -> Addr24 {
+        Addr24::new(self.pb, self.pc)
+    }
+}
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CpuEvent {
+    RomRead { pc: Addr24, address: Addr24, offset: usize },
+    RegisterWrite { pc: Addr24, register: u16, value: u8 },
+    Dma { pc: Addr24, channel: u8, params: DmaParams, vram_address: Option<u16> },
+    HdmaEnable { pc: Addr24, channels: u8 },
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StopReason {
+    StepLimit,
+    Returned,
+    Break(Addr24),
+    Stopped(Addr24),
+}
+
+enum Mapped {
+    Wram(usize),
+    Io(u16),
+    Rom(usize),
+    Other,
+}
+
+pub struct Cpu<'a> {
+    rom: &'a Rom,
+    mapper: Option<Mapper>,
+    pub registers: Registers,
+    wram: Vec<u8>,
+    io: Vec<u8>,
+    extra: HashMap<u32, u8>,
+    events: Vec<CpuEvent>,
+    instruction_pc: Addr24,
+    depth: usize,
+    steps: usize,
+}
+impl<'a> Cpu<'a> {
+    pub fn new(rom: &'a Rom) -> Self {
+        Self {
+            rom,
+            mapper: rom.memory_map().ok(),
+            registers: Registers::new(),
+            wram: vec![0u8; 0x20000],
+            io: vec![0u8; 0x4000],
+            extra: HashMap::new(),
+            events: Vec::new(),
+            instruction_pc: Addr24::new(0, 0),
+            depth: 0,
+            steps: 0,
+        }
+    }
+    pub fn reset(&mut self) -> Result<(), Error> {
+        let vector = match Addr24::new(0, 0xFFFC).to_mapped_offset(self.rom).and_then(|o| self.rom.read(o, 2)) {
+            Ok(d) => u16::from_le_bytes([d[0], d[1]]),
+            Err(e) => return Err(e),
+        };
+
+        self.registers = Registers::new();
+        self.registers.pc = vector;
+        self.depth = 0;
+
+        Ok(())
+    }
+    pub fn events(&self) -> &[CpuEvent] {
+        &self.events
+    }
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+    pub fn rom_reads(&self) -> Vec<Addr24> {
+        self.events.iter().filter_map(|e| match e {
+            CpuEvent::RomRead { address, .. } => Some(*address),
+            _ => None,
+        }).collect()
+    }
+    pub fn dma_transfers(&self) -> Vec<(DmaParams, Option<u16>)> {
+        self.events.iter().filter_map(|e| match e {
+            CpuEvent::Dma { params, vram_address, .. } => Some((*params, *vram_address)),
+            _ => None,
+        }).collect()
+    }
+    pub fn peek(&self, address: Addr24) -> u8 {
+        match self.map(address.as_u32()) {
+            Mapped::Wram(o) => self.wram[o],
+            Mapped::Io(r) => self.io[(r - 0x2000) as usize],
+            Mapped::Rom(o) => self.rom.as_slice()[o],
+            Mapped::Other => *self.extra.get(&address.as_u32()).unwrap_or(&0),
+        }
+    }
+    pub fn poke(&mut self, address: Addr24, value: u8) {
+        match self.map(address.as_u32()) {
+            Mapped::Wram(o) => self.wram[o] = value,
+            Mapped::Io(r) => self.io[(r - 0x2000) as usize] = value,
+            Mapped::Rom(_) => (),
+            Mapped::Other => { self.extra.insert(address.as_u32(), value); },
+        }
+    }
+    pub fn call(&mut self, address: Addr24, max_steps: usize) -> Result<StopReason, Error> {
+        self.registers.pb = address.bank;
+        self.registers.pc = address.address;
+        self.depth = 0;
+        self.run(max_steps)
+    }
+    pub fn run(&mut self, max_steps: usize) -> Result<StopReason, Error> {
+        for _ in 0..max_steps {
+            match self.step() {
+                Ok(Some(r)) => return Ok(r),
+                Ok(None) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(StopReason::StepLimit)
+    }
+
+    fn map(&self, address: u32) -> Mapped {
+        let bank = ((address >> 16) & 0xFF) as u8;
+        let addr = (address & 0xFFFF) as u16;
+
+        if bank == 0x7E || bank == 0x7F { return Mapped::Wram((address - 0x7E0000) as usize); }
+
+        if bank & 0x7F < 0x40 {
+            if addr < 0x2000 { return Mapped::Wram(addr as usize); }
+            if addr < 0x6000 { return Mapped::Io(addr); }
+            if addr < 0x8000 { return Mapped::Other; }
+        }
+
+        /* looked up once in new(), asking the header on every fetch would be far too slow */
+        let offset = match self.mapper.map(|m| m.address_to_pc(Addr24::new(bank, addr))) {
+            Some(Ok(pc)) => pc + self.rom.header_size(),
+            Some(Err(_)) => return Mapped::Other,
+            /* no map was found up front, so this can only come back as an error unless one has been set since */
+            None => match Addr24::new(bank, addr).to_mapped_offset(self.rom) {
+                Ok(o) => o,
+                Err(_) => return Mapped::Other,
+            },
+        };
+
+        if offset < self.rom.len() { Mapped::Rom(offset) }
+        else { Mapped::Other }
+    }
+    fn fetch8(&mut self) -> u8 {
+        let value = self.peek(self.registers.pc_address());
+        self.registers.pc = self.registers.pc.wrapping_add(1);
+        value
+    }
+    fn read8(&mut self, address: u32) -> u8 {
+        let address = address & 0xFFFFFF;
+
+        if let Mapped::Rom(offset) = self.map(address) {
+            self.events.push(CpuEvent::RomRead { pc: self.instruction_pc, address: Addr24::from_u32(address), offset });
+        }
+
+        self.peek(Addr24::from_u32(address))
+    }
+    fn read16(&mut self, address: u32) -> u16 {
+        (self.read8(address) as u16) | ((self.read8(address + 1) as u16) << 8)
+    }
+    fn read24(&mut self, address: u32) -> u32 {
+        (self.read16(address) as u32) | ((self.read8(address + 2) as u32) << 16)
+    }
+    fn write8(&mut self, address: u32, value: u8) {
+        let address = address & 0xFFFFFF;
+
+        match self.map(address) {
+            Mapped::Io(register) => self.write_register(register, value),
+            _ => self.poke(Addr24::from_u32(address), value),
+        }
+    }
+    fn write16(&mut self, address: u32, value: u16) {
+        self.write8(address, (value & 0xFF) as u8);
+        self.write8(address + 1, (value >> 8) as u8);
+    }
+    fn io_value(&self, register: u16) -> u8 {
+        self.io[(register - 0x2000) as usize]
+    }
+    fn set_io_value(&mut self, register: u16, value: u8) {
+        self.io[(register - 0x2000) as usize] = value;
+    }
+    fn write_register(&mut self, register: u16, value: u8) {
+        let pc = self.instruction_pc;
+
+        self.set_io_value(register, value);
+
+        if (0x2100..0x2200).contains(&register) || (0x4200..0x4400).contains(&register) {
+            self.events.push(CpuEvent::RegisterWrite { pc, register, value });
+        }
+
+        match register {
+            0x4203 => {
+                let product = self.io_value(0x4202) as u16 * value as u16;
+                self.set_io_value(0x4216, (product & 0xFF) as u8);
+                self.set_io_value(0x4217, (product >> 8) as u8);
+            },
+            0x4206 => {
+                let dividend = u16::from_le_bytes([self.io_value(0x4204), self.io_value(0x4205)]);
+                let (quotient, remainder) = if value == 0 { (0xFFFF, dividend) } else { (dividend / value as u16, dividend % value as u16) };
+
+                self.set_io_value(0x4214, (quotient & 0xFF) as u8);
+                self.set_io_value(0x4215, (quotient >> 8) as u8);
+                self.set_io_value(0x4216, (remainder & 0xFF) as u8);
+                self.set_io_value(0x4217, (remainder >> 8) as u8);
+            },
+            0x420B => {
+                for channel in 0..8u8 {
+                    if value & (1 << channel) == 0 { continue; }
+
+                    let base = 0x4300 + ((channel as u16) << 4);
+                    let block = (0..8).map(|i| self.io_value(base + i)).collect::<Vec<u8>>();
+                    let params = match DmaParams::from_data(&block) {
+                        Ok(p) => p,
+                        Err(_) => continue,
+                    };
+                    let vram_address = match params.b_bus_address {
+                        0x18 | 0x19 => Some(u16::from_le_bytes([self.io_value(0x2116), self.io_value(0x2117)])),
+                        _ => None,
+                    };
+
+                    self.events.push(CpuEvent::Dma { pc, channel, params, vram_address });
+                }
+            },
+            0x420C => self.events.push(CpuEvent::HdmaEnable { pc, channels: value }),
+            _ => (),
+        }
+    }
+
+    fn push8(&mut self, value: u8) {
+        self.write8(self.registers.s as u32, value);
+        self.registers.s = self.registers.s.wrapping_sub(1);
+
+        if self.registers.e { self.registers.s = 0x100 | (self.registers.s & 0xFF); }
+    }
+    fn push16(&mut self, value: u16) {
+        self.push8((value >> 8) as u8);
+        self.push8((value & 0xFF) as u8);
+    }
+    fn pull8(&mut self) -> u8 {
+        self.registers.s = self.registers.s.wrapping_add(1);
+
+        if self.registers.e { self.registers.s = 0x100 | (self.registers.s & 0xFF); }
+
+        self.read8(self.registers.s as u32)
+    }
+    fn pull16(&mut self) -> u16 {
+        let low = self.pull8() as u16;
+        let high = self.pull8() as u16;
+
+        low | (high << 8)
+    }
+
+    fn set_nz(&mut self, value: u16, wide: bool) {
+        let (zero, negative) = if wide { (value == 0, value & 0x8000 != 0) } else { (value & 0xFF == 0, value & 0x80 != 0) };
+
+        self.registers.p &= !(FLAG_Z | FLAG_N);
+        if zero { self.registers.p |= FLAG_Z; }
+        if negative { self.registers.p |= FLAG_N; }
+    }
+    fn set_flag(&mut self, flag: u8, value: bool) {
+        if value { self.registers.p |= flag; }
+        else { self.registers.p &= !flag; }
+    }
+    fn set_p(&mut self, p: u8) {
+        self.registers.p = p;
+
+        if self.registers.e { self.registers.p |= FLAG_M | FLAG_X; }
+
+        if self.registers.p & FLAG_X != 0 {
+            self.registers.x &= 0xFF;
+            self.registers.y &= 0xFF;
+        }
+    }
+    fn set_a(&mut self, value: u16) {
+        if self.registers.m8() { self.registers.a = (self.registers.a & 0xFF00) | (value & 0xFF); }
+        else { self.registers.a = value; }
+    }
+    fn index_value(&self, value: u16) -> u16 {
+        if self.registers.x8() { value & 0xFF } else { value }
+    }
+
+    fn effective_address(&mut self, mode: AddressingMode, operand: u32) -> u32 {
+        let r = self.registers;
+        let data_bank = (r.db as u32) << 16;
+        let direct = |offset: u32| (r.d as u32 + offset) & 0xFFFF;
+
+        match mode {
+            AddressingMode::Direct => direct(operand),
+            AddressingMode::DirectX => direct(operand + r.x as u32),
+            AddressingMode::DirectY => direct(operand + r.y as u32),
+            AddressingMode::DirectIndirect => data_bank | self.read16(direct(operand)) as u32,
+            AddressingMode::DirectXIndirect => data_bank | self.read16(direct(operand + r.x as u32)) as u32,
+            AddressingMode::DirectIndirectY => ((data_bank | self.read16(direct(operand)) as u32) + r.y as u32) & 0xFFFFFF,
+            AddressingMode::DirectIndirectLong => self.read24(direct(operand)),
+            AddressingMode::DirectIndirectLongY => (self.read24(direct(operand)) + r.y as u32) & 0xFFFFFF,
+            AddressingMode::Absolute => data_bank | operand,
+            AddressingMode::AbsoluteX => ((data_bank | operand) + r.x as u32) & 0xFFFFFF,
+            AddressingMode::AbsoluteY => ((data_bank | operand) + r.y as u32) & 0xFFFFFF,
+            AddressingMode::AbsoluteLong => operand,
+            AddressingMode::AbsoluteLongX => (operand + r.x as u32) & 0xFFFFFF,
+            AddressingMode::StackRelative => (r.s as u32 + operand) & 0xFFFF,
+            AddressingMode::StackRelativeIndirectY => {
+                let pointer = self.read16((r.s as u32 + operand) & 0xFFFF) as u32;
+                ((data_bank | pointer) + r.y as u32) & 0xFFFFFF
+            },
+            AddressingMode::AbsoluteIndirect => ((r.pb as u32) << 16) | self.read16(operand) as u32,
+            AddressingMode::AbsoluteXIndirect => {
+                let pointer_address = ((r.pb as u32) << 16) | ((operand + r.x as u32) & 0xFFFF);
+                ((r.pb as u32) << 16) | self.read16(pointer_address) as u32
+            },
+            AddressingMode::AbsoluteIndirectLong => self.read24(operand),
+            _ => 0,
+        }
+    }
+    fn load(&mut self, mode: AddressingMode, operand: u32, wide: bool) -> u16 {
+        match mode {
+            AddressingMode::ImmediateM | AddressingMode::ImmediateX | AddressingMode::Immediate8 | AddressingMode::Immediate16 => operand as u16,
+            AddressingMode::Accumulator => self.registers.a,
+            _ => {
+                let address = self.effective_address(mode, operand);
+
+                if wide { self.read16(address) } else { self.read8(address) as u16 }
+            },
+        }
+    }
+    fn store(&mut self, mode: AddressingMode, operand: u32, value: u16, wide: bool) {
+        let address = self.effective_address(mode, operand);
+
+        if wide { self.write16(address, value); }
+        else { self.write8(address, (value & 0xFF) as u8); }
+    }
+    fn modify<F: Fn(&mut Self, u16, bool) -> u16>(&mut self, mode: AddressingMode, operand: u32, f: F) {
+        let wide = !self.registers.m8();
+
+        if mode == AddressingMode::Accumulator {
+            let value = f(self, self.registers.a, wide);
+            self.set_a(value);
+        }
+        else {
+            let address = self.effective_address(mode, operand);
+            let value = if wide { self.read16(address) } else { self.read8(address) as u16 };
+            let result = f(self, value, wide);
+
+            if wide { self.write16(address, result); }
+            else { self.write8(address, (result & 0xFF) as u8); }
+        }
+    }
+    fn add_with_carry(&mut self, value: u16) {
+        let wide = !self.registers.m8();
+        let mask: u32 = if wide { 0xFFFF } else { 0xFF };
+        let sign: u32 = if wide { 0x8000 } else { 0x80 };
+        let a = self.registers.a as u32 & mask;
+        let v = value as u32 & mask;
+        let carry = (self.registers.p & FLAG_C) as u32;
+        let mut result;
+
+        if self.registers.p & FLAG_D != 0 {
+            let digits = if wide { 4 } else { 2 };
+            let mut digit_carry = carry;
+            result = 0;
+
+            for i in 0..digits {
+                let mut digit = ((a >> (i * 4)) & 0xF) + ((v >> (i * 4)) & 0xF) + digit_carry;
+                if digit > 9 { digit += 6; }
+                digit_carry = (digit > 0xF) as u32;
+                result |= (digit & 0xF) << (i * 4);
+            }
+
+            if digit_carry != 0 { result |= mask + 1; }
+        }
+        else {
+            result = a + v + carry;
+        }
+
+        let overflow = (!(a ^ v) & (a ^ result) & sign) != 0;
+
+        self.set_flag(FLAG_C, result > mask);
+        self.set_flag(FLAG_V, overflow);
+        self.set_nz((result & mask) as u16, wide);
+        self.set_a((result & mask) as u16);
+    }
+    fn subtract_with_carry(&mut self, value: u16) {
+        if self.registers.p & FLAG_D == 0 {
+            self.add_with_carry(!value);
+            return;
+        }
+
+        let wide = !self.registers.m8();
+        let mask: u32 = if wide { 0xFFFF } else { 0xFF };
+        let sign: u32 = if wide { 0x8000 } else { 0x80 };
+        let a = self.registers.a as u32 & mask;
+        let v = value as u32 & mask;
+        let digits = if wide { 4 } else { 2 };
+        let mut borrow = 1 - (self.registers.p & FLAG_C) as i32;
+        let mut result = 0u32;
+
+        for i in 0..digits {
+            let mut digit = ((a >> (i * 4)) & 0xF) as i32 - ((v >> (i * 4)) & 0xF) as i32 - borrow;
+            borrow = (digit < 0) as i32;
+            if digit < 0 { digit += 10; }
+            result |= (digit as u32 & 0xF) << (i * 4);
+        }
+
+        let binary = a.wrapping_sub(v).wrapping_sub(1 - (self.registers.p & FLAG_C) as u32);
+        let overflow = ((a ^ v) & (a ^ binary) & sign) != 0;
+
+        self.set_flag(FLAG_C, borrow == 0);
+        self.set_flag(FLAG_V, overflow);
+        self.set_nz(result as u16, wide);
+        self.set_a(result as u16);
+    }
+    fn compare(&mut self, register: u16, value: u16, wide: bool) {
+        let mask = if wide { 0xFFFF } else { 0xFF };
+        let register = register & mask;
+        let value = value & mask;
+
+        self.set_flag(FLAG_C, register >= value);
+        self.set_nz(register.wrapping_sub(value) & mask, wide);
+    }
+    fn branch(&mut self, condition: bool, operand: u32) {
+        if condition { self.registers.pc = self.registers.pc.wrapping_add(operand as u8 as i8 as u16); }
+    }
+    fn stop_on_return(&mut self) -> Option<StopReason> {
+        if self.depth == 0 { return Some(StopReason::Returned); }
+
+        self.depth -= 1;
+        None
+    }
+
+    pub fn step(&mut self) -> Result<Option<StopReason>, Error> {
+        self.instruction_pc = self.registers.pc_address();
+        self.steps += 1;
+
+        let opcode = self.fetch8();
+        let (mnemonic, mode) = decode_opcode(opcode);
+        let mut operand = 0u32;
+
+        for i in 0..mode.operand_size(self.registers.m8(), self.registers.x8()) {
+            operand |= (self.fetch8() as u32) << (i * 8);
+        }
+
+        let m16 = !self.registers.m8();
+        let x16 = !self.registers.x8();
+        let p = self.registers.p;
+
+        match mnemonic {
+            Mnemonic::LDA => { let v = self.load(mode, operand, m16); self.set_a(v); self.set_nz(v, m16); },
+            Mnemonic::LDX => { let v = self.load(mode, operand, x16); self.registers.x = self.index_value(v); self.set_nz(v, x16); },
+            Mnemonic::LDY => { let v = self.load(mode, operand, x16); self.registers.y = self.index_value(v); self.set_nz(v, x16); },
+            Mnemonic::STA => self.store(mode, operand, self.registers.a, m16),
+            Mnemonic::STX => self.store(mode, operand, self.registers.x, x16),
+            Mnemonic::STY => self.store(mode, operand, self.registers.y, x16),
+            Mnemonic::STZ => self.store(mode, operand, 0, m16),
+            Mnemonic::ORA => { let v = self.load(mode, operand, m16); let r = self.registers.a | v; self.set_a(r); self.set_nz(r, m16); },
+            Mnemonic::AND => { let v = self.load(mode, operand, m16); let r = self.registers.a & v; self.set_a(r); self.set_nz(r, m16); },
+            Mnemonic::EOR => { let v = self.load(mode, operand, m16); let r = self.registers.a ^ v; self.set_a(r); self.set_nz(r, m16); },
+            Mnemonic::ADC => { let v = self.load(mode, operand, m16); self.add_with_carry(v); },
+            Mnemonic::SBC => { let v = self.load(mode, operand, m16); self.subtract_with_carry(v); },
+            Mnemonic::CMP => { let v = self.load(mode, operand, m16); self.compare(self.registers.a, v, m16); },
+            Mnemonic::CPX => { let v = self.load(mode, operand, x16); self.compare(self.registers.x, v, x16); },
+            Mnemonic::CPY => { let v = self.load(mode, operand, x16); self.compare(self.registers.y, v, x16); },
+            Mnemonic::BIT => {
+                let v = self.load(mode, operand, m16);
+                let mask = if m16 { 0xFFFF } else { 0xFF };
+
+                self.set_flag(FLAG_Z, self.registers.a & v & mask == 0);
+
+                if mode != AddressingMode::ImmediateM {
+                    let (n, v_bit) = if m16 { (0x8000, 0x4000) } else { (0x80, 0x40) };
+
+                    self.set_flag(FLAG_N, v & n != 0);
+                    self.set_flag(FLAG_V, v & v_bit != 0);
+                }
+            },
+            Mnemonic::TSB | Mnemonic::TRB => {
+                let set = mnemonic == Mnemonic::TSB;
+
+                self.modify(mode, operand, |cpu, v, wide| {
+                    let mask = if wide { 0xFFFF } else { 0xFF };
+                    cpu.set_flag(FLAG_Z, cpu.registers.a & v & mask == 0);
+
+                    if set { v | cpu.registers.a } else { v & !cpu.registers.a }
+                });
+            },
+            Mnemonic::ASL => self.modify(mode, operand, |cpu, v, wide| {
+                let sign = if wide { 0x8000 } else { 0x80 };
+                let r = v << 1;
+                cpu.set_flag(FLAG_C, v & sign != 0);
+                cpu.set_nz(r, wide);
+                r
+            }),
+            Mnemonic::LSR => self.modify(mode, operand, |cpu, v, wide| {
+                let v = if wide { v } else { v & 0xFF };
+                let r = v >> 1;
+                cpu.set_flag(FLAG_C, v & 1 != 0);
+                cpu.set_nz(r, wide);
+                r
+            }),
+            Mnemonic::ROL => self.modify(mode, operand, |cpu, v, wide| {
+                let sign = if wide { 0x8000 } else { 0x80 };
+                let r = (v << 1) | (cpu.registers.p & FLAG_C) as u16;
+                cpu.set_flag(FLAG_C, v & sign != 0);
+                cpu.set_nz(r, wide);
+                r
+            }),
+            Mnemonic::ROR => self.modify(mode, operand, |cpu, v, wide| {
+                let v = if wide { v } else { v & 0xFF };
+                let carry_in = if cpu.registers.p & FLAG_C != 0 { if wide { 0x8000 } else { 0x80 } } else { 0 };
+                let r = (v >> 1) | carry_in;
+                cpu.set_flag(FLAG_C, v & 1 != 0);
+                cpu.set_nz(r, wide);
+                r
+            }),
+            Mnemonic::INC => self.modify(mode, operand, |cpu, v, wide| { let r = v.wrapping_add(1); cpu.set_nz(r, wide); r }),
+            Mnemonic::DEC => self.modify(mode, operand, |cpu, v, wide| { let r = v.wrapping_sub(1); cpu.set_nz(r, wide); r }),
+            Mnemonic::INX => { let v = self.index_value(self.registers.x.wrapping_add(1)); self.registers.x = v; self.set_nz(v, x16); },
+            Mnemonic::INY => { let v = self.index_value(self.registers.y.wrapping_add(1)); self.registers.y = v; self.set_nz(v, x16); },
+            Mnemonic::DEX => { let v = self.index_value(self.registers.x.wrapping_sub(1)); self.registers.x = v; self.set_nz(v, x16); },
+            Mnemonic::DEY => { let v = self.index_value(self.registers.y.wrapping_sub(1)); self.registers.y = v; self.set_nz(v, x16); },
+            Mnemonic::TAX => { let v = self.index_value(self.registers.a); self.registers.x = v; self.set_nz(v, x16); },
+            Mnemonic::TAY => { let v = self.index_value(self.registers.a); self.registers.y = v; self.set_nz(v, x16); },
+            Mnemonic::TXA => { let v = self.registers.x; self.set_a(v); self.set_nz(v, m16); },
+            Mnemonic::TYA => { let v = self.registers.y; self.set_a(v); self.set_nz(v, m16); },
+            Mnemonic::TXY => { let v = self.registers.x; self.registers.y = v; self.set_nz(v, x16); },
+            Mnemonic::TYX => { let v = self.registers.y; self.registers.x = v; self.set_nz(v, x16); },
+            Mnemonic::TSX => { let v = self.index_value(self.registers.s); self.registers.x = v; self.set_nz(v, x16); },
+            Mnemonic::TXS => {
+                self.registers.s = if self.registers.e { 0x100 | (self.registers.x & 0xFF) } else { self.registers.x };
+            },
+            Mnemonic::TCD => { self.registers.d = self.registers.a; self.set_nz(self.registers.a, true); },
+            Mnemonic::TDC => { self.registers.a = self.registers.d; self.set_nz(self.registers.a, true); },
+            Mnemonic::TCS => {
+                self.registers.s = if self.registers.e { 0x100 | (self.registers.a & 0xFF) } else { self.registers.a };
+            },
+            Mnemonic::TSC => { self.registers.a = self.registers.s; self.set_nz(self.registers.a, true); },
+            Mnemonic::XBA => {
+                self.registers.a = self.registers.a.rotate_left(8);
+                self.set_nz(self.registers.a & 0xFF, false);
+            },
+            Mnemonic::XCE => {
+                let carry = self.registers.p & FLAG_C != 0;
+
+                self.set_flag(FLAG_C, self.registers.e);
+                self.registers.e = carry;
+
+                if carry {
+                    self.registers.s = 0x100 | (self.registers.s & 0xFF);
+                    self.set_p(self.registers.p | FLAG_M | FLAG_X);
+                }
+            },
+            Mnemonic::REP => self.set_p(p & !(operand as u8)),
+            Mnemonic::SEP => self.set_p(p | operand as u8),
+            Mnemonic::CLC => self.set_flag(FLAG_C, false),
+            Mnemonic::SEC => self.set_flag(FLAG_C, true),
+            Mnemonic::CLI => self.set_flag(FLAG_I, false),
+            Mnemonic::SEI => self.set_flag(FLAG_I, true),
+            Mnemonic::CLD => self.set_flag(FLAG_D, false),
+            Mnemonic::SED => self.set_flag(FLAG_D, true),
+            Mnemonic::CLV => self.set_flag(FLAG_V, false),
+            Mnemonic::PHA => if m16 { self.push16(self.registers.a) } else { self.push8((self.registers.a & 0xFF) as u8) },
+            Mnemonic::PHX => if x16 { self.push16(self.registers.x) } else { self.push8(self.registers.x as u8) },
+            Mnemonic::PHY => if x16 { self.push16(self.registers.y) } else { self.push8(self.registers.y as u8) },
+            Mnemonic::PHB => self.push8(self.registers.db),
+            Mnemonic::PHK => self.push8(self.registers.pb),
+            Mnemonic::PHP => self.push8(p),
+            Mnemonic::PHD => self.push16(self.registers.d),
+            Mnemonic::PEA => self.push16(operand as u16),
+            Mnemonic::PEI => { let v = self.read16((self.registers.d as u32 + operand) & 0xFFFF); self.push16(v); },
+            Mnemonic::PER => { let v = self.registers.pc.wrapping_add(operand as u16); self.push16(v); },
+            Mnemonic::PLA => { let v = if m16 { self.pull16() } else { self.pull8() as u16 }; self.set_a(v); self.set_nz(v, m16); },
+            Mnemonic::PLX => { let v = if x16 { self.pull16() } else { self.pull8() as u16 }; self.registers.x = v; self.set_nz(v, x16); },
+            Mnemonic::PLY => { let v = if x16 { self.pull16() } else { self.pull8() as u16 }; self.registers.y = v; self.set_nz(v, x16); },
+            Mnemonic::PLB => { let v = self.pull8(); self.registers.db = v; self.set_nz(v as u16, false); },
+            Mnemonic::PLD => { let v = self.pull16(); self.registers.d = v; self.set_nz(v, true); },
+            Mnemonic::PLP => { let v = self.pull8(); self.set_p(v); },
+            Mnemonic::BPL => self.branch(p & FLAG_N == 0, operand),
+            Mnemonic::BMI => self.branch(p & FLAG_N != 0, operand),
+            Mnemonic::BVC => self.branch(p & FLAG_V == 0, operand),
+            Mnemonic::BVS => self.branch(p & FLAG_V != 0, operand),
+            Mnemonic::BCC => self.branch(p & FLAG_C == 0, operand),
+            Mnemonic::BCS => self.branch(p & FLAG_C != 0, operand),
+            Mnemonic::BNE => self.branch(p & FLAG_Z == 0, operand),
+            Mnemonic::BEQ => self.branch(p & FLAG_Z != 0, operand),
+            Mnemonic::BRA => self.branch(true, operand),
+            Mnemonic::BRL => self.registers.pc = self.registers.pc.wrapping_add(operand as u16),
+            Mnemonic::JMP => {
+                let target = if mode == AddressingMode::Absolute { operand } else { self.effective_address(mode, operand) };
+                self.registers.pc = (target & 0xFFFF) as u16;
+            },
+            Mnemonic::JML => {
+                let target = if mode == AddressingMode::AbsoluteLong { operand } else { self.effective_address(mode, operand) };
+                self.registers.pb = (target >> 16) as u8;
+                self.registers.pc = (target & 0xFFFF) as u16;
+            },
+            Mnemonic::JSR => {
+                let target = if mode == AddressingMode::Absolute { operand } else { self.effective_address(mode, operand) };
+                self.push16(self.registers.pc.wrapping_sub(1));
+                self.registers.pc = (target & 0xFFFF) as u16;
+                self.depth += 1;
+            },
+            Mnemonic::JSL => {
+                self.push8(self.registers.pb);
+                self.push16(self.registers.pc.wrapping_sub(1));
+                self.registers.pb = (operand >> 16) as u8;
+                self.registers.pc = (operand & 0xFFFF) as u16;
+                self.depth += 1;
+            },
+            Mnemonic::RTS => {
+                if let Some(r) = self.stop_on_return() { return Ok(Some(r)); }
+
+                self.registers.pc = self.pull16().wrapping_add(1);
+            },
+            Mnemonic::RTL => {
+                if let Some(r) = self.stop_on_return() { return Ok(Some(r)); }
+
+                self.registers.pc = self.pull16().wrapping_add(1);
+                self.registers.pb = self.pull8();
+            },
+            Mnemonic::RTI => {
+                if let Some(r) = self.stop_on_return() { return Ok(Some(r)); }
+
+                let v = self.pull8();
+                self.set_p(v);
+                self.registers.pc = self.pull16();
+
+                if !self.registers.e { self.registers.pb = self.pull8(); }
+            },
+            Mnemonic::MVN | Mnemonic::MVP => {
+                let destination = (operand & 0xFF) as u8;
+                let source = (operand >> 8) as u8;
+                let step: u16 = if mnemonic == Mnemonic::MVN { 1 } else { 0xFFFF };
+
+                self.registers.db = destination;
+
+                loop {
+                    let v = self.read8(((source as u32) << 16) | self.registers.x as u32);
+                    self.write8(((destination as u32) << 16) | self.registers.y as u32, v);
+
+                    self.registers.x = self.index_value(self.registers.x.wrapping_add(step));
+                    self.registers.y = self.index_value(self.registers.y.wrapping_add(step));
+                    self.registers.a = self.registers.a.wrapping_sub(1);
+
+                    if self.registers.a == 0xFFFF { break; }
+                }
+            },
+            Mnemonic::NOP | Mnemonic::WDM => (),
+            Mnemonic::BRK | Mnemonic::COP => return Ok(Some(StopReason::Break(self.instruction_pc))),
+            Mnemonic::STP | Mnemonic::WAI => return Ok(Some(StopReason::Stopped(self.instruction_pc))),
+        }
+
+        Ok(None)
+    }
+}
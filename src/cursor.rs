@@ -0,0 +1,117 @@
+use crate::{Addr24, BankCrossPolicy, Error, Rom};
+
+/* sequential reads at a SNES address; the position moves past whatever was read, following the bank policy */
+#[derive(Clone, Debug)]
+pub struct RomCursor<'a> {
+    rom: &'a Rom,
+    position: Addr24,
+    policy: BankCrossPolicy,
+    exhausted: bool,
+}
+impl<'a> RomCursor<'a> {
+    pub fn new(rom: &'a Rom, position: Addr24) -> Self {
+        Self { rom, position, policy: rom.bank_policy(), exhausted: false }
+    }
+    pub fn from_offset(rom: &'a Rom, offset: usize) -> Result<Self, Error> {
+        match Addr24::from_mapped_offset(rom, offset) {
+            Ok(a) => Ok(Self::new(rom, a)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn wrap(mut self, policy: BankCrossPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    pub fn position(&self) -> Addr24 {
+        self.position
+    }
+    pub fn offset(&self) -> Result<usize, Error> {
+        self.rom.mapped_offset(self.position)
+    }
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+    pub fn seek(&mut self, position: Addr24) {
+        self.position = position;
+        self.exhausted = false;
+    }
+    pub fn skip(&mut self, count: usize) -> Result<(), Error> {
+        if self.exhausted && count > 0 { return Err(Error::BankBoundaryCrossed(self.position)); }
+
+        match self.policy.offset(self.position, count as u32) {
+            Ok(a) => { self.position = a; Ok(()) },
+            Err(e) => Err(e),
+        }
+    }
+    pub fn peek_bytes(&self, count: usize) -> Result<Vec<u8>, Error> {
+        if self.exhausted && count > 0 { return Err(Error::BankBoundaryCrossed(self.position)); }
+
+        self.rom.read_mapped_with(self.position, count, self.policy)
+    }
+    pub fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, Error> {
+        /* nothing moves if the read fails, so a caller can back off and try a shorter one */
+        let data = match self.peek_bytes(count) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+
+        /* a Forbid read can end exactly on the last byte of a bank; there's no address after it, so park on that byte */
+        if self.skip(count).is_err() {
+            if let Err(e) = self.skip(count - 1) { return Err(e); }
+            self.exhausted = true;
+        }
+
+        Ok(data)
+    }
+    pub fn peek_u8(&self) -> Result<u8, Error> {
+        match self.peek_bytes(1) {
+            Ok(d) => Ok(d[0]),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        match self.read_bytes(1) {
+            Ok(d) => Ok(d[0]),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_u16(&mut self) -> Result<u16, Error> {
+        match self.read_bytes(2) {
+            Ok(d) => Ok(u16::from_le_bytes([d[0], d[1]])),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_u24(&mut self) -> Result<u32, Error> {
+        match self.read_bytes(3) {
+            Ok(d) => Ok(u32::from_le_bytes([d[0], d[1], d[2], 0])),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_u32(&mut self) -> Result<u32, Error> {
+        match self.read_bytes(4) {
+            Ok(d) => Ok(u32::from_le_bytes([d[0], d[1], d[2], d[3]])),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_addr24(&mut self) -> Result<Addr24, Error> {
+        match self.read_u24() {
+            Ok(v) => Ok(Addr24::from_u32(v)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_pointer(&mut self) -> Result<Addr24, Error> {
+        /* a two-byte pointer into the bank the cursor is reading from */
+        let bank = self.position.bank;
+
+        match self.read_u16() {
+            Ok(v) => Ok(Addr24::new(bank, v)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Rom {
+    pub fn cursor(&self, position: Addr24) -> RomCursor<'_> {
+        RomCursor::new(self, position)
+    }
+}
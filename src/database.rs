@@ -0,0 +1,230 @@
+use crate::{hex_digest, Error, Rom};
+use std::path::Path;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DatEntry {
+    pub name: String,
+    pub title: String,
+    pub region: Option<String>,
+    pub revision: Option<String>,
+    pub rom_name: String,
+    pub size: Option<usize>,
+    pub crc32: Option<u32>,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+}
+impl DatEntry {
+    pub fn new(name: &str) -> Self {
+        /* No-Intro names read "Title (Region) (Rev N) (other flags)", so the parenthesized groups carry the rest */
+        let (title, groups) = split_name(name);
+        let region = groups.first().cloned();
+        let revision = groups.iter().skip(1).find(|g| is_revision(g)).cloned();
+
+        Self { name: name.to_string(), title, region, revision, rom_name: String::new(), size: None, crc32: None, md5: None, sha1: None }
+    }
+    pub fn matches(&self, rom: &Rom) -> bool {
+        self.matches_digests(&mut RomDigests::new(rom))
+    }
+    pub fn matches_digests(&self, digests: &mut RomDigests) -> bool {
+        /* trust the strongest hash the DAT gives; CRC32 alone also has to agree on size */
+        if let Some(sha1) = &self.sha1 { return sha1 == digests.sha1(); }
+        if let Some(md5) = &self.md5 { return md5 == digests.md5(); }
+
+        match (self.crc32, self.size) {
+            (Some(crc), Some(size)) => crc == digests.crc32() && size == digests.size(),
+            (Some(crc), None) => crc == digests.crc32(),
+            _ => false,
+        }
+    }
+}
+
+/* a ROM's hashes, each worked out the first time an entry asks and reused for every entry after */
+#[derive(Clone, Debug)]
+pub struct RomDigests<'a> {
+    rom: &'a Rom,
+    crc32: Option<u32>,
+    md5: Option<String>,
+    sha1: Option<String>,
+}
+impl<'a> RomDigests<'a> {
+    pub fn new(rom: &'a Rom) -> Self {
+        Self { rom, crc32: None, md5: None, sha1: None }
+    }
+    pub fn size(&self) -> usize {
+        self.rom.rom_size()
+    }
+    pub fn crc32(&mut self) -> u32 {
+        let rom = self.rom;
+        *self.crc32.get_or_insert_with(|| rom.crc32())
+    }
+    pub fn md5(&mut self) -> &str {
+        let rom = self.rom;
+        self.md5.get_or_insert_with(|| hex_digest(&rom.md5()))
+    }
+    pub fn sha1(&mut self) -> &str {
+        let rom = self.rom;
+        self.sha1.get_or_insert_with(|| hex_digest(&rom.sha1()))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct RomDatabase {
+    pub name: String,
+    pub entries: Vec<DatEntry>,
+}
+impl RomDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn from_dat(text: &str) -> Result<Self, Error> {
+        /* the Logiqx XML No-Intro publishes: a header name, then one <game> per title holding its <rom> */
+        let mut result = Self::new();
+        let mut current: Option<DatEntry> = None;
+        let mut in_header = false;
+        let mut position = 0;
+        let mut line = 1;
+        let mut counted = 0;
+
+        while let Some(start) = text[position..].find('<') {
+            let start = position + start;
+
+            /* counted as the parser moves forward, so each newline is only ever looked at once */
+            line += text[counted..start].matches('\n').count();
+            counted = start;
+
+            if text[start..].starts_with("<!--") {
+                position = match text[start..].find("-->") {
+                    Some(e) => start + e + 3,
+                    None => return Err(Error::InvalidDatLine(line)),
+                };
+                continue;
+            }
+
+            let end = match text[start..].find('>') {
+                Some(e) => start + e,
+                None => return Err(Error::InvalidDatLine(line)),
+            };
+            let tag = &text[start + 1..end];
+            let tag_name = tag.split_whitespace().next().unwrap_or("").trim_end_matches('/');
+
+            position = end + 1;
+
+            match tag_name {
+                "header" => in_header = true,
+                "/header" => in_header = false,
+                "name" if in_header => {
+                    let close = match text[position..].find("</name>") {
+                        Some(c) => position + c,
+                        None => return Err(Error::InvalidDatLine(line)),
+                    };
+
+                    result.name = unescape(text[position..close].trim());
+                    position = close;
+                },
+                "game" | "machine" => {
+                    let name = match attribute(tag, "name") {
+                        Some(n) => n,
+                        None => return Err(Error::InvalidDatLine(line)),
+                    };
+
+                    current = Some(DatEntry::new(&name));
+                },
+                "/game" | "/machine" => match current.take() {
+                    Some(e) => result.entries.push(e),
+                    None => return Err(Error::InvalidDatLine(line)),
+                },
+                "rom" => {
+                    let entry = match current.as_mut() {
+                        Some(e) => e,
+                        None => return Err(Error::InvalidDatLine(line)),
+                    };
+
+                    entry.rom_name = attribute(tag, "name").unwrap_or_default();
+                    entry.size = attribute(tag, "size").and_then(|s| s.parse::<usize>().ok());
+                    entry.crc32 = attribute(tag, "crc").and_then(|s| u32::from_str_radix(&s, 16).ok());
+                    entry.md5 = attribute(tag, "md5").map(|s| s.to_lowercase());
+                    entry.sha1 = attribute(tag, "sha1").map(|s| s.to_lowercase());
+                },
+                _ => (),
+            }
+        }
+
+        if current.is_some() { return Err(Error::InvalidDatLine(text.lines().count())); }
+
+        Ok(result)
+    }
+    pub fn from_file<P: AsRef<Path>>(filename: P) -> Result<Self, Error> {
+        match std::fs::read_to_string(filename) {
+            Ok(t) => Self::from_dat(&t),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+    pub fn identify(&self, rom: &Rom) -> Option<&DatEntry> {
+        let mut digests = RomDigests::new(rom);
+
+        self.entries.iter().find(|e| e.matches_digests(&mut digests))
+    }
+}
+
+fn split_name(name: &str) -> (String, Vec<String>) {
+    let title = match name.find(" (") {
+        Some(i) => &name[..i],
+        None => name,
+    };
+    let mut groups = Vec::<String>::new();
+    let mut rest = &name[title.len()..];
+
+    while let Some(open) = rest.find('(') {
+        let close = match rest[open..].find(')') {
+            Some(c) => open + c,
+            None => break,
+        };
+
+        groups.push(rest[open + 1..close].to_string());
+        rest = &rest[close + 1..];
+    }
+
+    (title.trim().to_string(), groups)
+}
+
+fn is_revision(group: &str) -> bool {
+    group.starts_with("Rev ") || (group.starts_with('v') && group[1..].starts_with(|c: char| c.is_ascii_digit()))
+}
+
+fn attribute(tag: &str, key: &str) -> Option<String> {
+    /* values are always quoted in DATs; either quote style is legal XML */
+    let mut rest = tag;
+
+    while let Some(index) = rest.find(key) {
+        let before = rest[..index].chars().last();
+        let after = rest[index + key.len()..].trim_start();
+
+        rest = &rest[index + key.len()..];
+
+        if !matches!(before, Some(c) if c.is_whitespace()) { continue; }
+
+        let value = match after.strip_prefix('=') {
+            Some(v) => v.trim_start(),
+            None => continue,
+        };
+        let quote = match value.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => return None,
+        };
+        let close = value[1..].find(quote)?;
+
+        return Some(unescape(&value[1..1 + close]));
+    }
+
+    None
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+impl Rom {
+    pub fn identify<'a>(&self, database: &'a RomDatabase) -> Option<&'a DatEntry> {
+        database.identify(self)
+    }
+}
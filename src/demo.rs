@@ -0,0 +1,200 @@
+use crate::{Error, Rom};
+
+/* bit layout of the auto-joypad registers read as one word ($4218 low, $4219 high) */
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Buttons(pub u16);
+impl Buttons {
+    pub const B: u16 = 0x8000;
+    pub const Y: u16 = 0x4000;
+    pub const SELECT: u16 = 0x2000;
+    pub const START: u16 = 0x1000;
+    pub const UP: u16 = 0x0800;
+    pub const DOWN: u16 = 0x0400;
+    pub const LEFT: u16 = 0x0200;
+    pub const RIGHT: u16 = 0x0100;
+    pub const A: u16 = 0x0080;
+    pub const X: u16 = 0x0040;
+    pub const L: u16 = 0x0020;
+    pub const R: u16 = 0x0010;
+
+    pub const NAMES: [(u16, &'static str); 12] = [
+        (Self::B, "B"), (Self::Y, "Y"), (Self::SELECT, "Select"), (Self::START, "Start"),
+        (Self::UP, "Up"), (Self::DOWN, "Down"), (Self::LEFT, "Left"), (Self::RIGHT, "Right"),
+        (Self::A, "A"), (Self::X, "X"), (Self::L, "L"), (Self::R, "R"),
+    ];
+
+    pub fn has(&self, button: u16) -> bool {
+        self.0 & button == button
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0 & 0xFFF0 == 0
+    }
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        /* names joined with +, as Display writes them; "none" or an empty string is no buttons */
+        let mut result = 0u16;
+
+        for name in text.split('+').map(|n| n.trim()).filter(|n| !n.is_empty() && !n.eq_ignore_ascii_case("none")) {
+            match Self::NAMES.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)) {
+                Some((bit, _)) => result |= bit,
+                None => return Err(Error::InvalidButton(name.to_string())),
+            }
+        }
+
+        Ok(Self(result))
+    }
+}
+impl std::fmt::Display for Buttons {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let held = Self::NAMES.iter().filter(|(bit, _)| self.has(*bit)).map(|(_, name)| *name).collect::<Vec<&str>>();
+
+        if held.is_empty() { write!(f, "none") }
+        else { write!(f, "{}", held.join("+")) }
+    }
+}
+
+/* narrow states keep only the high register byte (B, Y, Select, Start and the d-pad), which is all most
+   attract modes bother recording. run tables are (state, frame count) pairs ended by a zero count */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DemoFormat {
+    Frames { wide: bool },
+    Runs { wide: bool, duration_first: bool },
+}
+impl DemoFormat {
+    fn wide(&self) -> bool {
+        match self {
+            DemoFormat::Frames { wide } | DemoFormat::Runs { wide, .. } => *wide,
+        }
+    }
+    fn read_state(&self, data: &[u8]) -> Buttons {
+        if self.wide() { Buttons(u16::from_le_bytes([data[0], data[1]])) } else { Buttons((data[0] as u16) << 8) }
+    }
+    fn push_state(&self, result: &mut Vec<u8>, buttons: Buttons) {
+        if self.wide() { result.extend_from_slice(&buttons.0.to_le_bytes()); } else { result.push((buttons.0 >> 8) as u8); }
+    }
+    pub fn entry_size(&self) -> usize {
+        let state = if self.wide() { 2 } else { 1 };
+
+        match self {
+            DemoFormat::Frames { .. } => state,
+            DemoFormat::Runs { .. } => state + 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ButtonEvent {
+    pub frame: usize,
+    pub buttons: Buttons,
+    pub duration: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct DemoInput {
+    pub events: Vec<ButtonEvent>,
+}
+impl DemoInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn hold(mut self, buttons: Buttons, frames: usize) -> Self {
+        /* holding the same state again just lengthens the current event */
+        if frames == 0 { return self; }
+
+        let frame = self.frames();
+
+        match self.events.last_mut() {
+            Some(last) if last.buttons == buttons => last.duration += frames,
+            _ => self.events.push(ButtonEvent { frame, buttons, duration: frames }),
+        }
+
+        self
+    }
+    pub fn frames(&self) -> usize {
+        self.events.last().map_or(0, |e| e.frame + e.duration)
+    }
+    pub fn buttons_at(&self, frame: usize) -> Option<Buttons> {
+        self.events.iter().find(|e| frame >= e.frame && frame < e.frame + e.duration).map(|e| e.buttons)
+    }
+    pub fn from_frames(frames: &[Buttons]) -> Self {
+        frames.iter().fold(Self::new(), |demo, &buttons| demo.hold(buttons, 1))
+    }
+    pub fn to_frames(&self) -> Vec<Buttons> {
+        self.events.iter().flat_map(|e| std::iter::repeat_n(e.buttons, e.duration)).collect()
+    }
+    pub fn parse(data: &[u8], format: DemoFormat, limit: usize) -> Result<Self, Error> {
+        /* limit is the frame count for per-frame tables and the most runs to read for run tables */
+        let size = format.entry_size();
+        let mut result = Self::new();
+
+        for index in 0..limit {
+            let entry = match data.get(index * size..(index + 1) * size) {
+                Some(e) => e,
+                None => return Err(Error::TruncatedData(data.len())),
+            };
+
+            result = match format {
+                DemoFormat::Frames { .. } => result.hold(format.read_state(entry), 1),
+                DemoFormat::Runs { duration_first, .. } => {
+                    let (duration, state) = if duration_first { (entry[0], &entry[1..]) } else { (entry[size-1], &entry[..size-1]) };
+                    if duration == 0 { break; }
+
+                    result.hold(format.read_state(state), duration as usize)
+                },
+            };
+        }
+
+        Ok(result)
+    }
+    pub fn encode(&self, format: DemoFormat) -> Vec<u8> {
+        /* runs longer than a count byte holds are split; run tables get their zero terminator */
+        let mut result = Vec::<u8>::new();
+
+        match format {
+            DemoFormat::Frames { .. } => {
+                for buttons in self.to_frames() { format.push_state(&mut result, buttons); }
+            },
+            DemoFormat::Runs { duration_first, .. } => {
+                for event in &self.events {
+                    let mut remaining = event.duration;
+
+                    while remaining > 0 {
+                        let duration = remaining.min(0xFF);
+
+                        if duration_first { result.push(duration as u8); }
+                        format.push_state(&mut result, event.buttons);
+                        if !duration_first { result.push(duration as u8); }
+
+                        remaining -= duration;
+                    }
+                }
+
+                result.resize(result.len() + format.entry_size(), 0);
+            },
+        }
+
+        result
+    }
+}
+
+impl Rom {
+    pub fn read_demo(&self, offset: usize, format: DemoFormat, limit: usize) -> Result<DemoInput, Error> {
+        let available = self.len().saturating_sub(offset).min(limit * format.entry_size());
+
+        match self.read(offset, available) {
+            Ok(d) => DemoInput::parse(d, format, limit),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn write_demo(&mut self, offset: usize, demo: &DemoInput, format: DemoFormat, capacity: usize) -> Result<usize, Error> {
+        /* capacity is the room the original table had; a longer demo has to be relocated by the caller */
+        let data = demo.encode(format);
+        if data.len() > capacity { return Err(Error::DataLengthMismatch(data.len(),capacity)); }
+
+        let length = data.len();
+
+        match self.write(offset, data) {
+            Ok(()) => Ok(length),
+            Err(e) => Err(e),
+        }
+    }
+}
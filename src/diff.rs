@@ -0,0 +1,101 @@
+use crate::{Error, IpsPatch, IpsRecord, Rom, IPS_EOF_OFFSET};
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DiffRange {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+impl DiffRange {
+    pub fn len(&self) -> usize {
+        self.old.len().max(self.new.len())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn end(&self) -> usize {
+        self.offset + self.len()
+    }
+    pub fn changed_bytes(&self) -> usize {
+        /* bytes only one side has count as changed too */
+        let common = self.old.len().min(self.new.len());
+        self.old.iter().zip(&self.new).filter(|(a, b)| a != b).count() + self.len() - common
+    }
+}
+
+pub fn diff_bytes(old: &[u8], new: &[u8], merge_gap: usize) -> Vec<DiffRange> {
+    /* runs of up to merge_gap unchanged bytes are folded into the ranges around them */
+    let longest = old.len().max(new.len());
+    let same = |i: usize| i < old.len() && i < new.len() && old[i] == new[i];
+    let mut spans = Vec::<(usize, usize)>::new();
+    let mut cursor = 0usize;
+
+    while cursor < longest {
+        if same(cursor) { cursor += 1; continue; }
+
+        let start = cursor;
+        while cursor < longest && !same(cursor) { cursor += 1; }
+
+        match spans.last_mut() {
+            Some(last) if start - last.1 <= merge_gap => last.1 = cursor,
+            _ => spans.push((start, cursor)),
+        }
+    }
+
+    spans.iter().map(|&(start, end)| DiffRange {
+        offset: start,
+        old: old[start.min(old.len())..end.min(old.len())].to_vec(),
+        new: new[start.min(new.len())..end.min(new.len())].to_vec(),
+    }).collect()
+}
+
+impl Rom {
+    pub fn diff(&self, other: &Rom) -> Vec<DiffRange> {
+        self.diff_merged(other, 0)
+    }
+    pub fn diff_merged(&self, other: &Rom, merge_gap: usize) -> Vec<DiffRange> {
+        /* copier headers are skipped on both sides; offsets land in this ROM's buffer, header included */
+        let old = &self.as_slice()[self.header_size()..];
+        let new = &other.as_slice()[other.header_size()..];
+        let mut result = diff_bytes(old, new, merge_gap);
+
+        for range in &mut result { range.offset += self.header_size(); }
+
+        result
+    }
+}
+
+impl IpsPatch {
+    pub fn from_ranges(ranges: &[DiffRange], modified: &[u8]) -> Result<Self, Error> {
+        /* ranges that only shrink the ROM carry no new data; the truncation is left for the caller.
+           modified is the image the patch produces, at the ranges' offsets: a record that would start at
+           IPS_EOF_OFFSET starts a byte early instead, and that byte may lie outside every range */
+        let mut result = Self::new();
+
+        for range in ranges {
+            let end = range.offset + range.new.len();
+            if end > 0x1000000 { return Err(Error::OutOfBounds(end,0x1000000)); }
+
+            let mut start = range.offset;
+
+            while start < end {
+                let record_start = if start == IPS_EOF_OFFSET { start - 1 } else { start };
+                let record_end = (record_start + 0xFFFF).min(end);
+                let mut data = Vec::<u8>::with_capacity(record_end - record_start);
+
+                if record_start < range.offset {
+                    match modified.get(record_start) {
+                        Some(&b) => data.push(b),
+                        None => return Err(Error::OutOfBounds(record_start,modified.len())),
+                    }
+                }
+
+                data.extend_from_slice(&range.new[record_start.max(range.offset) - range.offset..record_end - range.offset]);
+                result.records.push(IpsRecord::Data { offset: record_start, data });
+                start = record_end;
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{Addr24, Error, Rom};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TransferMode {
+    Mode0,
+    Mode1,
+    Mode2,
+    Mode3,
+    Mode4,
+    Mode5,
+    Mode6,
+    Mode7,
+}
+impl TransferMode {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 7 {
+            0 => TransferMode::Mode0,
+            1 => TransferMode::Mode1,
+            2 => TransferMode::Mode2,
+            3 => TransferMode::Mode3,
+            4 => TransferMode::Mode4,
+            5 => TransferMode::Mode5,
+            6 => TransferMode::Mode6,
+            _ => TransferMode::Mode7,
+        }
+    }
+    pub fn as_bits(&self) -> u8 {
+        *self as u8
+    }
+    pub fn b_bus_pattern(&self) -> &'static [u8] {
+        match self {
+            TransferMode::Mode0 => &[0],
+            TransferMode::Mode1 => &[0,1],
+            TransferMode::Mode2 | TransferMode::Mode6 => &[0,0],
+            TransferMode::Mode3 | TransferMode::Mode7 => &[0,0,1,1],
+            TransferMode::Mode4 => &[0,1,2,3],
+            TransferMode::Mode5 => &[0,1,0,1],
+        }
+    }
+    pub fn unit_size(&self) -> usize {
+        self.b_bus_pattern().len()
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AddressStep {
+    Increment,
+    Decrement,
+    Fixed,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct DmaParams {
+    pub control: u8,
+    pub b_bus_address: u8,
+    pub a_bus_address: Addr24,
+    pub byte_count: u16,
+    pub indirect_bank: u8,
+}
+impl DmaParams {
+    pub fn new(mode: TransferMode, b_bus_address: u8, a_bus_address: Addr24, byte_count: u16) -> Self {
+        Self { control: mode.as_bits(), b_bus_address, a_bus_address, byte_count, indirect_bank: 0 }
+    }
+    pub fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        /* parameter blocks mirror the $43n0-$43n7 register layout; the indirect bank byte is optional */
+        let buf = data.as_ref();
+        if buf.len() != 7 && buf.len() != 8 { return Err(Error::DataLengthMismatch(buf.len(),7)); }
+
+        Ok(Self {
+            control: buf[0],
+            b_bus_address: buf[1],
+            a_bus_address: Addr24::new(buf[4], u16::from_le_bytes([buf[2], buf[3]])),
+            byte_count: u16::from_le_bytes([buf[5], buf[6]]),
+            indirect_bank: if buf.len() == 8 { buf[7] } else { 0 },
+        })
+    }
+    pub fn from_rom(rom: &Rom, address: Addr24) -> Result<Self, Error> {
+        match address.to_mapped_offset(rom).and_then(|o| rom.read(o, 7)) {
+            Ok(d) => Self::from_data(d),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let a_bus = self.a_bus_address.address.to_le_bytes();
+        let count = self.byte_count.to_le_bytes();
+
+        vec![self.control, self.b_bus_address, a_bus[0], a_bus[1], self.a_bus_address.bank, count[0], count[1], self.indirect_bank]
+    }
+    pub fn mode(&self) -> TransferMode {
+        TransferMode::from_bits(self.control)
+    }
+    pub fn to_cpu(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+    pub fn hdma_indirect(&self) -> bool {
+        self.control & 0x40 != 0
+    }
+    pub fn step(&self) -> AddressStep {
+        match (self.control >> 3) & 3 {
+            0 => AddressStep::Increment,
+            2 => AddressStep::Decrement,
+            _ => AddressStep::Fixed,
+        }
+    }
+    pub fn b_bus_register(&self) -> u16 {
+        0x2100 | self.b_bus_address as u16
+    }
+    pub fn transfer_size(&self) -> usize {
+        if self.byte_count == 0 { 0x10000 } else { self.byte_count as usize }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum HdmaEntryData {
+    Direct(Vec<u8>),
+    Indirect(u16),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct HdmaEntry {
+    pub line_count: u8,
+    pub repeat: bool,
+    pub data: HdmaEntryData,
+}
+impl HdmaEntry {
+    pub fn line_count_byte(&self) -> Result<u8, Error> {
+        /* a repeat entry of 128 lines encodes as $80, but a plain 128 would be the terminator */
+        match (self.line_count, self.repeat) {
+            (1..=127, repeat) => Ok(self.line_count | ((repeat as u8) << 7)),
+            (128, true) => Ok(0x80),
+            _ => Err(Error::InvalidLineCount(self.line_count as usize)),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct HdmaTable {
+    pub mode: TransferMode,
+    pub indirect: bool,
+    pub entries: Vec<HdmaEntry>,
+}
+impl HdmaTable {
+    pub fn new(mode: TransferMode, indirect: bool) -> Self {
+        Self { mode, indirect, entries: Vec::new() }
+    }
+    pub fn parse<B: AsRef<[u8]>>(data: B, mode: TransferMode, indirect: bool) -> Result<Self, Error> {
+        let buf = data.as_ref();
+        let unit = mode.unit_size();
+        let mut entries = Vec::<HdmaEntry>::new();
+        let mut cursor = 0usize;
+
+        loop {
+            if cursor >= buf.len() { return Err(Error::TruncatedData(cursor)); }
+
+            let header = buf[cursor];
+            cursor += 1;
+
+            if header == 0 { break; }
+
+            let repeat = header & 0x80 != 0;
+            let line_count = if header & 0x7F == 0 { 128 } else { header & 0x7F };
+
+            let data = if indirect {
+                if cursor + 2 > buf.len() { return Err(Error::TruncatedData(cursor)); }
+
+                let pointer = u16::from_le_bytes([buf[cursor], buf[cursor+1]]);
+                cursor += 2;
+
+                HdmaEntryData::Indirect(pointer)
+            }
+            else {
+                let size = if repeat { unit * line_count as usize } else { unit };
+                if cursor + size > buf.len() { return Err(Error::TruncatedData(cursor)); }
+
+                let bytes = buf[cursor..cursor+size].to_vec();
+                cursor += size;
+
+                HdmaEntryData::Direct(bytes)
+            };
+
+            entries.push(HdmaEntry { line_count, repeat, data });
+        }
+
+        Ok(Self { mode, indirect, entries })
+    }
+    pub fn from_rom(rom: &Rom, address: Addr24, mode: TransferMode, indirect: bool) -> Result<Self, Error> {
+        /* HDMA table addresses wrap within their bank, so never read past it */
+        let offset = match address.to_mapped_offset(rom) {
+            Ok(o) => o,
+            Err(e) => return Err(e),
+        };
+        let available = (0x10000 - address.address as usize).min(rom.len().saturating_sub(offset));
+
+        match rom.read(offset, available) {
+            Ok(d) => Self::parse(d, mode, indirect),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let unit = self.mode.unit_size();
+        let mut result = Vec::<u8>::new();
+
+        for entry in &self.entries {
+            match entry.line_count_byte() {
+                Ok(b) => result.push(b),
+                Err(e) => return Err(e),
+            }
+
+            match &entry.data {
+                HdmaEntryData::Indirect(pointer) => {
+                    if !self.indirect { return Err(Error::InvalidHdmaEntry); }
+
+                    result.extend_from_slice(&pointer.to_le_bytes());
+                },
+                HdmaEntryData::Direct(bytes) => {
+                    let expected = if entry.repeat { unit * entry.line_count as usize } else { unit };
+
+                    if self.indirect { return Err(Error::InvalidHdmaEntry); }
+                    if bytes.len() != expected { return Err(Error::DataLengthMismatch(bytes.len(),expected)); }
+
+                    result.extend_from_slice(bytes);
+                },
+            }
+        }
+
+        result.push(0);
+        Ok(result)
+    }
+    pub fn total_lines(&self) -> usize {
+        self.entries.iter().map(|e| e.line_count as usize).sum()
+    }
+    pub fn resolve_indirect(&self, rom: &Rom, bank: u8) -> Result<Vec<Vec<u8>>, Error> {
+        let unit = self.mode.unit_size();
+        let mut result = Vec::<Vec<u8>>::new();
+
+        for entry in &self.entries {
+            let pointer = match entry.data {
+                HdmaEntryData::Indirect(p) => p,
+                HdmaEntryData::Direct(_) => return Err(Error::InvalidHdmaEntry),
+            };
+            let size = if entry.repeat { unit * entry.line_count as usize } else { unit };
+
+            match Addr24::new(bank, pointer).to_mapped_offset(rom).and_then(|o| rom.read(o, size)) {
+                Ok(d) => result.push(d.to_vec()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{Error, TextTable};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DictionaryEntry {
+    pub code: Vec<u8>,
+    pub text: String,
+    pub uses: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DictionaryProposal {
+    pub entries: Vec<DictionaryEntry>,
+    pub original_size: usize,
+    pub optimized_size: usize,
+}
+impl DictionaryProposal {
+    pub fn savings(&self) -> usize {
+        self.original_size.saturating_sub(self.optimized_size)
+    }
+    pub fn apply(&self, table: &mut TextTable) {
+        for entry in &self.entries {
+            table.insert(entry.code.clone(), &entry.text);
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct Token {
+    text: String,
+    cost: usize,
+    mergeable: bool,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DictionaryOptimizer {
+    codes: Vec<Vec<u8>>,
+    max_length: usize,
+}
+impl DictionaryOptimizer {
+    pub fn new(codes: Vec<Vec<u8>>) -> Self {
+        Self { codes, max_length: 2 }
+    }
+    pub fn dte(codes: Vec<u8>) -> Self {
+        Self::new(codes.into_iter().map(|c| vec![c]).collect())
+    }
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length.max(2);
+        self
+    }
+    pub fn optimize<S: AsRef<str>>(&self, table: &TextTable, script: &[S]) -> Result<DictionaryProposal, Error> {
+        /* greedy pair merging: each free code goes to the adjacent token pair that saves the most bytes right now.
+           control codes and existing multi-character entries are never merged, only plain characters and our own entries */
+        let mut sequences = Vec::<Vec<Token>>::new();
+
+        for text in script {
+            let tokens = match table.tokenize(text.as_ref()) {
+                Ok(t) => t,
+                Err(e) => return Err(e),
+            };
+
+            sequences.push(tokens.into_iter().map(|(text, bytes)| {
+                let mergeable = text.chars().count() == 1 && text != "\n";
+                Token { text, cost: bytes.len(), mergeable }
+            }).collect());
+        }
+
+        let original_size = sequences.iter().flatten().map(|t| t.cost).sum::<usize>();
+        let mut entries = Vec::<DictionaryEntry>::new();
+
+        for code in &self.codes {
+            let mut counts = BTreeMap::<(String, String), (usize, usize)>::new();
+
+            for sequence in &sequences {
+                let mut i = 0usize;
+
+                while i + 1 < sequence.len() {
+                    let (a, b) = (&sequence[i], &sequence[i+1]);
+
+                    if !a.mergeable || !b.mergeable || a.text.chars().count() + b.text.chars().count() > self.max_length {
+                        i += 1;
+                        continue;
+                    }
+
+                    let entry = counts.entry((a.text.clone(), b.text.clone())).or_insert((0, a.cost + b.cost));
+                    entry.0 += 1;
+
+                    /* a run like "aaa" only holds one non-overlapping "aa" pair per two characters */
+                    i += if i + 2 < sequence.len() && sequence[i+2].text == b.text && a.text == b.text { 2 } else { 1 };
+                }
+            }
+
+            let mut best: Option<((String, String), usize)> = None;
+
+            for (pair, (count, cost)) in &counts {
+                if *cost <= code.len() { continue; }
+
+                let saved = count * (cost - code.len());
+
+                if best.as_ref().is_none_or(|(_, s)| saved > *s) { best = Some((pair.clone(), saved)); }
+            }
+
+            let (pair, _) = match best {
+                Some(b) => b,
+                None => break,
+            };
+            let merged = format!("{}{}", pair.0, pair.1);
+
+            for sequence in &mut sequences {
+                let mut result = Vec::<Token>::new();
+                let mut i = 0usize;
+
+                while i < sequence.len() {
+                    if i + 1 < sequence.len() && sequence[i].mergeable && sequence[i+1].mergeable && sequence[i].text == pair.0 && sequence[i+1].text == pair.1 {
+                        result.push(Token { text: merged.clone(), cost: code.len(), mergeable: true });
+                        i += 2;
+                    }
+                    else {
+                        result.push(sequence[i].clone());
+                        i += 1;
+                    }
+                }
+
+                *sequence = result;
+            }
+
+            entries.push(DictionaryEntry { code: code.clone(), text: merged, uses: 0 });
+        }
+
+        for entry in &mut entries {
+            entry.uses = sequences.iter().flatten().filter(|t| t.text == entry.text && t.cost == entry.code.len()).count();
+        }
+
+        let optimized_size = sequences.iter().flatten().map(|t| t.cost).sum::<usize>();
+
+        Ok(DictionaryProposal { entries, original_size, optimized_size })
+    }
+}
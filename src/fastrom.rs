@@ -0,0 +1,89 @@
+use crate::{decode_run, survey_code, AddressingMode, CancelToken, Error, InterruptVector, Mnemonic, Rom};
+
+pub const MEMSEL: u16 = 0x420D;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FastRomEdit {
+    pub offset: usize,
+    pub original: Vec<u8>,
+    pub replacement: Vec<u8>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FastRomReport {
+    pub map_mode: (u8, u8),
+    pub memsel_write: Option<usize>,
+    pub edits: Vec<FastRomEdit>,
+    pub review: Vec<(usize, String)>,
+}
+
+impl Rom {
+    pub fn convert_to_fastrom(&mut self, rewrite_code: bool) -> Result<FastRomReport, Error> {
+        /* the speed bit alone does nothing: code has to run from banks $80+ and write 1 to MEMSEL.
+           we fix what is unambiguous and list what needs a human */
+        let old_mode = match self.find_valid_snes_header() {
+            Ok(h) => h.get_mapping_mode(),
+            Err(e) => return Err(e),
+        };
+        let hits = match survey_code(self, &|_, _| (), &CancelToken::new()) {
+            Ok(h) => h,
+            Err(e) => return Err(e),
+        };
+        let mut memsel_write = None;
+        let mut edits = Vec::<FastRomEdit>::new();
+        let mut review = Vec::<(usize, String)>::new();
+
+        for hit in &hits {
+            let run = decode_run(self.as_slice(), hit.offset, hit.offset + hit.length);
+
+            for (index, ins) in run.iter().enumerate() {
+                let bytes = &self.as_slice()[ins.offset..ins.offset + ins.length];
+
+                match (ins.mnemonic, ins.mode) {
+                    (Mnemonic::STA, AddressingMode::Absolute) | (Mnemonic::STZ, AddressingMode::Absolute) if ins.operand as u16 == MEMSEL => {
+                        memsel_write = Some(ins.offset);
+                    },
+                    (Mnemonic::JSL, _) | (Mnemonic::JML, AddressingMode::AbsoluteLong) if (ins.operand >> 16) < 0x40 => {
+                        let mut replacement = bytes.to_vec();
+                        replacement[3] |= 0x80;
+
+                        edits.push(FastRomEdit { offset: ins.offset, original: bytes.to_vec(), replacement });
+                    },
+                    /* LDA #bank : PHA : PLB is the usual way to point the data bank at ROM */
+                    (Mnemonic::LDA, AddressingMode::ImmediateM) if ins.length == 2 && ins.operand < 0x40 && ins.operand != 0 => {
+                        let next = run.get(index+1).map(|i| i.mnemonic);
+                        let after = run.get(index+2).map(|i| i.mnemonic);
+
+                        if next == Some(Mnemonic::PHA) && after == Some(Mnemonic::PLB) {
+                            edits.push(FastRomEdit { offset: ins.offset, original: bytes.to_vec(), replacement: vec![bytes[0], bytes[1] | 0x80] });
+                        }
+                    },
+                    (Mnemonic::JML, AddressingMode::AbsoluteIndirectLong) | (Mnemonic::JMP, AddressingMode::AbsoluteXIndirect) | (Mnemonic::JSR, AddressingMode::AbsoluteXIndirect) => {
+                        review.push((ins.offset, format!("{:?} through a pointer table; long pointers in it may need bank $80+", ins.mnemonic)));
+                    },
+                    (Mnemonic::PLB, _) if index > 0 && run[index-1].mnemonic != Mnemonic::PHA && run[index-1].mnemonic != Mnemonic::PHK => {
+                        review.push((ins.offset, "data bank pulled from a computed value".to_string()));
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        if memsel_write.is_none() { review.push((0, "no write to MEMSEL ($420D) found; the reset code must set it to 1".to_string())); }
+
+        /* execution starts in bank 0 from the reset vector, which cannot be redirected without free space */
+        if let Ok(reset) = self.get_vector(InterruptVector::Reset) {
+            review.push((0, format!("reset handler at $00:{:04X} needs a JML into bank $80 before any fast code runs", reset)));
+        }
+
+        if rewrite_code {
+            for edit in &edits {
+                if let Err(e) = self.write(edit.offset, edit.replacement.clone()) { return Err(e); }
+            }
+        }
+
+        if let Err(e) = self.update_header(|h| h.set_mapping_mode(old_mode | 0x10)) { return Err(e); }
+
+        Ok(FastRomReport { map_mode: (old_mode, old_mode | 0x10), memsel_write, edits, review })
+    }
+}
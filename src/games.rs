@@ -0,0 +1,4 @@
+pub mod common;
+pub use common::*;
+pub mod tables;
+pub use tables::*;
@@ -0,0 +1,95 @@
+use crate::{decode_tile_colormaps, Addr24, Bgr555, Error, PixelBuffer, Rgb888, Rom};
+
+/* sprite character data sits in a 16 tile wide grid in VRAM, so a 16x16 sprite at tile N uses N, N+1, N+16, N+17 */
+pub const OAM_GRID_COLUMNS: usize = 16;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SpriteLayout {
+    Linear,
+    Oam,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SpriteDescriptor {
+    pub tiles: Addr24,
+    pub width: usize,
+    pub height: usize,
+    pub palette: Addr24,
+    pub bpp: usize,
+    pub layout: SpriteLayout,
+}
+impl SpriteDescriptor {
+    pub fn new(tiles: Addr24, width: usize, height: usize, palette: Addr24) -> Self {
+        /* width and height are in 8x8 tiles; 4bpp is the only depth sprites can actually use */
+        Self { tiles, width: width.max(1), height: height.max(1), palette, bpp: 4, layout: SpriteLayout::Linear }
+    }
+    pub fn bpp(mut self, bpp: usize) -> Self {
+        self.bpp = bpp;
+        self
+    }
+    pub fn layout(mut self, layout: SpriteLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+    fn tile_index(&self, x: usize, y: usize) -> usize {
+        match self.layout {
+            SpriteLayout::Linear => y * self.width + x,
+            SpriteLayout::Oam => y * OAM_GRID_COLUMNS + x,
+        }
+    }
+    fn tiles_spanned(&self) -> usize {
+        self.tile_index(self.width - 1, self.height - 1) + 1
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RippedSprite {
+    pub image: PixelBuffer,
+    pub tiles: Vec<u8>,
+    pub palette: Vec<Bgr555>,
+    pub colormaps: Vec<Vec<u8>>,
+}
+
+pub fn rip_sprite(rom: &Rom, descriptor: &SpriteDescriptor) -> Result<RippedSprite, Error> {
+    /* the depth sizes the palette read as a shift, so it's checked before anything is read */
+    if !matches!(descriptor.bpp, 2 | 4 | 8) { return Err(Error::InvalidBpp(descriptor.bpp)); }
+
+    let tile_size = descriptor.bpp * 8;
+
+    if descriptor.layout == SpriteLayout::Oam && descriptor.width > OAM_GRID_COLUMNS { return Err(Error::OutOfBounds(descriptor.width,OAM_GRID_COLUMNS)); }
+
+    let data = match rom.read_mapped(descriptor.tiles, descriptor.tiles_spanned() * tile_size) {
+        Ok(d) => d,
+        Err(e) => return Err(e),
+    };
+    let palette_data = match rom.read_mapped(descriptor.palette, 2 << descriptor.bpp) {
+        Ok(d) => d,
+        Err(e) => return Err(e),
+    };
+    let palette = palette_data.chunks_exact(2).map(|c| Bgr555(u16::from_le_bytes([c[0], c[1]]))).collect::<Vec<Bgr555>>();
+
+    /* gather the tiles in on-screen order so the raw data comes back as a plain linear sheet */
+    let mut tiles = Vec::<u8>::new();
+
+    for y in 0..descriptor.height {
+        for x in 0..descriptor.width {
+            let index = descriptor.tile_index(x, y);
+            tiles.extend_from_slice(&data[index*tile_size..(index+1)*tile_size]);
+        }
+    }
+
+    let colormaps = match decode_tile_colormaps(&tiles, descriptor.bpp) {
+        Ok(c) => c,
+        Err(e) => return Err(e),
+    };
+    let colors = palette.iter().map(|&c| Rgb888::from(c)).collect::<Vec<Rgb888>>();
+    let mut image = PixelBuffer::new(descriptor.width * 8, descriptor.height * 8);
+
+    for (i, colormap) in colormaps.iter().enumerate() {
+        let pixels = colormap.iter().map(|&v| colors[v as usize]).collect::<Vec<Rgb888>>();
+
+        image.blit((i % descriptor.width) * 8, (i / descriptor.width) * 8, 8, &pixels);
+    }
+
+    Ok(RippedSprite { image, tiles, palette, colormaps })
+}
@@ -0,0 +1,184 @@
+use crate::{Addr24, Error, IndexedAccess, Rom};
+use std::marker::PhantomData;
+
+/* a value a game table can hold: fixed size, little-endian like everything else the 65816 reads */
+pub trait TableValue: Sized {
+    const SIZE: usize;
+
+    fn decode(data: &[u8]) -> Self;
+    fn encode(&self) -> Vec<u8>;
+}
+impl TableValue for u8 {
+    const SIZE: usize = 1;
+
+    fn decode(data: &[u8]) -> Self {
+        data[0]
+    }
+    fn encode(&self) -> Vec<u8> {
+        vec![*self]
+    }
+}
+impl TableValue for i8 {
+    const SIZE: usize = 1;
+
+    fn decode(data: &[u8]) -> Self {
+        data[0] as i8
+    }
+    fn encode(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+impl TableValue for u16 {
+    const SIZE: usize = 2;
+
+    fn decode(data: &[u8]) -> Self {
+        u16::from_le_bytes([data[0], data[1]])
+    }
+    fn encode(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+impl TableValue for i16 {
+    const SIZE: usize = 2;
+
+    fn decode(data: &[u8]) -> Self {
+        i16::from_le_bytes([data[0], data[1]])
+    }
+    fn encode(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+impl TableValue for u32 {
+    const SIZE: usize = 4;
+
+    fn decode(data: &[u8]) -> Self {
+        u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+    }
+    fn encode(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+impl TableValue for Addr24 {
+    /* long pointers: address low, address high, bank */
+    const SIZE: usize = 3;
+
+    fn decode(data: &[u8]) -> Self {
+        Addr24::new(data[2], u16::from_le_bytes([data[0], data[1]]))
+    }
+    fn encode(&self) -> Vec<u8> {
+        let address = self.address;
+        vec![(address & 0xFF) as u8, (address >> 8) as u8, self.bank]
+    }
+}
+impl<const N: usize> TableValue for [u8; N] {
+    /* raw records: names, stat blocks, anything a game module wants to pick apart itself */
+    const SIZE: usize = N;
+
+    fn decode(data: &[u8]) -> Self {
+        let mut result = [0u8; N];
+        result.copy_from_slice(&data[..N]);
+        result
+    }
+    fn encode(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+/* a typed run of count values at a fixed SNES address; declare them with game_tables! */
+pub struct GameTable<T> {
+    pub name: &'static str,
+    pub base: Addr24,
+    pub count: usize,
+    value: PhantomData<T>,
+}
+impl<T> GameTable<T> {
+    pub const fn new(name: &'static str, base: Addr24, count: usize) -> Self {
+        Self { name, base, count, value: PhantomData }
+    }
+}
+impl<T: TableValue> GameTable<T> {
+    pub fn access(&self) -> IndexedAccess {
+        IndexedAccess::new(self.base, T::SIZE as u32)
+    }
+    pub fn size(&self) -> usize {
+        self.count * T::SIZE
+    }
+    pub fn address(&self, index: usize) -> Result<Addr24, Error> {
+        if index >= self.count { return Err(Error::OutOfBounds(index,self.count)); }
+
+        self.access().address(index)
+    }
+    pub fn get(&self, rom: &Rom, index: usize) -> Result<T, Error> {
+        let address = match self.address(index) {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+
+        match rom.read_mapped(address, T::SIZE) {
+            Ok(d) => Ok(T::decode(&d)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read(&self, rom: &Rom) -> Result<T, Error> {
+        self.get(rom, 0)
+    }
+    pub fn all(&self, rom: &Rom) -> Result<Vec<T>, Error> {
+        let data = match rom.read_mapped(self.base, self.size()) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+
+        Ok(data.chunks(T::SIZE).map(T::decode).collect())
+    }
+    pub fn set(&self, rom: &mut Rom, index: usize, value: &T) -> Result<(), Error> {
+        /* values are small enough that a single element never straddles a bank in any table a game actually ships */
+        let address = match self.address(index) {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+        let offset = match rom.mapped_offset(address) {
+            Ok(o) => o,
+            Err(e) => return Err(e),
+        };
+
+        rom.write(offset, value.encode())
+    }
+    pub fn write(&self, rom: &mut Rom, value: &T) -> Result<(), Error> {
+        self.set(rom, 0, value)
+    }
+}
+impl<T> Copy for GameTable<T> {}
+impl<T> Clone for GameTable<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> std::fmt::Debug for GameTable<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} @ {:?} x {}", self.name, self.base, self.count)
+    }
+}
+
+/* declares one GameTable constant per line:
+
+       game_tables! {
+           pub ITEM_PRICES: u16 = (0xD5, 0x5000) * 254;
+           pub MAP_POINTERS: Addr24 = (0xCF, 0x0000) * 32;
+           pub START_LEVEL: u8 = (0xC0, 0x1234);
+       }
+
+   the count defaults to 1, for single values */
+#[macro_export]
+macro_rules! game_tables {
+    ($($vis:vis $name:ident: $ty:ty = ($bank:expr, $address:expr) $(* $count:expr)?;)*) => {
+        $(
+            $vis const $name: $crate::games::GameTable<$ty> = $crate::games::GameTable::new(
+                stringify!($name),
+                $crate::Addr24 { address: $address, bank: $bank },
+                $crate::game_tables!(@count $($count)?),
+            );
+        )*
+    };
+    (@count) => { 1 };
+    (@count $count:expr) => { $count };
+}
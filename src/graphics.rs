@@ -1,4 +1,5 @@
-use crate::Error;
+use crate::{metrics, Addr24, Error, MetricCounter, Rom};
+use std::borrow::Cow;
 use std::convert::{TryFrom, TryInto};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -80,6 +81,29 @@ impl Bgr555 {
     pub fn as_rgb888(&self) -> Rgb888 {
         (*self).into()
     }
+    pub fn cmath(&self, other: Bgr555, subtract: bool, half: bool) -> Bgr555 {
+        /* per channel like the PPU: a sum clamps at 31, a difference at 0, and half mode shifts after the
+           operation, so a halved subtraction that went negative is still black */
+        let mut result = 0u16;
+
+        for shift in &[0u16, 5, 10] {
+            let a = (self.0 >> shift) & 0x1F;
+            let b = (other.0 >> shift) & 0x1F;
+            let mut c = if subtract { a.saturating_sub(b) } else { a + b };
+
+            if half { c >>= 1; }
+
+            result |= c.min(0x1F) << shift;
+        }
+
+        Bgr555(result)
+    }
+    pub fn cmath_add(&self, other: Bgr555, half: bool) -> Bgr555 {
+        self.cmath(other, false, half)
+    }
+    pub fn cmath_sub(&self, other: Bgr555, half: bool) -> Bgr555 {
+        self.cmath(other, true, half)
+    }
 }
 impl From<u16> for Bgr555 {
     fn from(data: u16) -> Self {
@@ -96,14 +120,166 @@ impl From<Rgb888> for Bgr555 {
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FadeDirection {
+    ToBlack,
+    FromBlack,
+    ToWhite,
+    FromWhite,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FadeTableOrder {
+    ByStep,
+    ByColor,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct FadeTableLayout {
+    pub order: FadeTableOrder,
+    pub stride: usize,
+    pub fill: u8,
+}
+impl FadeTableLayout {
+    pub fn new(order: FadeTableOrder) -> Self {
+        Self { order, stride: 0, fill: 0 }
+    }
+}
+
+pub fn apply_brightness(color: Bgr555, brightness: u8) -> Bgr555 {
+    /* INIDISP brightness scales each channel by (b+1)/16, with 0 being black */
+    let brightness = brightness.min(15) as u16;
+    let scale = |c: u8| if brightness == 0 { 0 } else { ((c as u16 * (brightness + 1)) >> 4) as u8 };
+
+    Bgr555::new(scale(color.get_red()), scale(color.get_green()), scale(color.get_blue()))
+}
+
+pub fn apply_whiteness(color: Bgr555, brightness: u8) -> Bgr555 {
+    let brightness = brightness.min(15) as u16;
+    let scale = |c: u8| if brightness == 0 { 31 } else { 31 - (((31 - c as u16) * (brightness + 1)) >> 4) as u8 };
+
+    Bgr555::new(scale(color.get_red()), scale(color.get_green()), scale(color.get_blue()))
+}
+
+pub fn generate_fade_tables<B: AsRef<[Bgr555]>>(palette: B, steps: usize, direction: FadeDirection) -> Vec<Vec<Bgr555>> {
+    let colors = palette.as_ref();
+    let mut result = Vec::<Vec<Bgr555>>::new();
+
+    for step in 0..steps {
+        let progress = if steps <= 1 { 15 } else { (step * 15 / (steps - 1)) as u8 };
+        let table = colors.iter().map(|&c| match direction {
+            FadeDirection::ToBlack => apply_brightness(c, 15 - progress),
+            FadeDirection::FromBlack => apply_brightness(c, progress),
+            FadeDirection::ToWhite => apply_whiteness(c, 15 - progress),
+            FadeDirection::FromWhite => apply_whiteness(c, progress),
+        }).collect();
+
+        result.push(table);
+    }
+
+    result
+}
+
+pub fn serialize_fade_tables(tables: &[Vec<Bgr555>], layout: &FadeTableLayout) -> Vec<u8> {
+    let mut result = Vec::<u8>::new();
+    let colors = tables.iter().map(|t| t.len()).max().unwrap_or(0);
+    let groups = match layout.order {
+        FadeTableOrder::ByStep => tables.len(),
+        FadeTableOrder::ByColor => colors,
+    };
+
+    for group in 0..groups {
+        let start = result.len();
+
+        match layout.order {
+            FadeTableOrder::ByStep => {
+                for color in &tables[group] { result.extend_from_slice(&color.0.to_le_bytes()); }
+            },
+            FadeTableOrder::ByColor => {
+                for table in tables {
+                    let color = table.get(group).copied().unwrap_or(Bgr555(0));
+                    result.extend_from_slice(&color.0.to_le_bytes());
+                }
+            },
+        }
+
+        if layout.stride > result.len() - start { result.resize(start + layout.stride, layout.fill); }
+    }
+
+    result
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PaletteDelta {
+    pub index: u8,
+    pub before: Bgr555,
+    pub after: Bgr555,
+}
+impl PaletteDelta {
+    pub fn red(&self) -> i8 {
+        self.after.get_red() as i8 - self.before.get_red() as i8
+    }
+    pub fn green(&self) -> i8 {
+        self.after.get_green() as i8 - self.before.get_green() as i8
+    }
+    pub fn blue(&self) -> i8 {
+        self.after.get_blue() as i8 - self.before.get_blue() as i8
+    }
+    pub fn distance(&self) -> u32 {
+        let (r, g, b) = (self.red() as i32, self.green() as i32, self.blue() as i32);
+        (r*r + g*g + b*b) as u32
+    }
+}
+
 pub trait SNESPalette: Sized {
     fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error>;
     fn set_index(&mut self, index: u8, color: Bgr555) -> Result<(), Error>;
     fn get_index(&self, index: u8) -> Result<Bgr555, Error>;
+    fn colors(&self) -> &[Bgr555];
+    fn diff<T: SNESPalette>(&self, other: &T) -> Vec<PaletteDelta> {
+        self.colors().iter().zip(other.colors().iter()).enumerate()
+            .filter(|(_, (before, after))| before.0 & 0x7FFF != after.0 & 0x7FFF)
+            .map(|(index, (before, after))| PaletteDelta { index: index as u8, before: *before, after: *after })
+            .collect()
+    }
+    fn suggest_remap<T: SNESPalette>(&self, other: &T) -> Option<Vec<u8>> {
+        /* maps each of our indexes to an index in other holding the same color, preferring ones not yet claimed so duplicates spread out */
+        let target = other.colors();
+        let mut claimed = vec![false; target.len()];
+        let mut result = Vec::<u8>::new();
+
+        for color in self.colors() {
+            let matches = target.iter().enumerate()
+                .filter(|(_, c)| c.0 & 0x7FFF == color.0 & 0x7FFF)
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>();
+
+            let index = match matches.iter().find(|&&i| !claimed[i]).or(matches.first()) {
+                Some(&i) => i,
+                None => return None,
+            };
+
+            claimed[index] = true;
+            result.push(index as u8);
+        }
+
+        Some(result)
+    }
+}
+
+pub fn apply_index_remap(colormap: &mut [u8], remap: &[u8]) -> Result<(), Error> {
+    for value in colormap.iter_mut() {
+        match remap.get(*value as usize) {
+            Some(v) => *value = *v,
+            None => return Err(Error::InvalidColorIndex(*value)),
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
-struct SNESPalette16([Bgr555; 16]);
+pub struct SNESPalette16(pub [Bgr555; 16]);
 impl SNESPalette for SNESPalette16 {
     fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
         let buf = data.as_ref();
@@ -132,10 +308,13 @@ impl SNESPalette for SNESPalette16 {
 
         Ok(self.0[index as usize])
     }
+    fn colors(&self) -> &[Bgr555] {
+        &self.0
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
-struct SNESPalette256([Bgr555; 256]);
+pub struct SNESPalette256(pub [Bgr555; 256]);
 impl SNESPalette for SNESPalette256 {
     fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
         let buf = data.as_ref();
@@ -160,11 +339,18 @@ impl SNESPalette for SNESPalette256 {
     fn get_index(&self, index: u8) -> Result<Bgr555, Error> {
         Ok(self.0[index as usize])
     }
+    fn colors(&self) -> &[Bgr555] {
+        &self.0
+    }
 }
 
 pub trait SNESTile: Sized {
+    const BPP: usize;
+    const LINEAR: bool = false;
+
     fn new() -> Self;
     fn from_data<B: AsRef<[u8]>>(data: B) -> Result<Self, Error>;
+    fn as_data(&self) -> &[u8];
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error>;
     fn get_value(&self, x: usize, y: usize) -> Result<u8, Error>;
     fn to_colormap(&self) -> Result<Vec<u8>, Error> {
@@ -275,6 +461,8 @@ impl TryFrom<&Vec<u8>> for SNESTile1BPP {
     }
 }
 impl SNESTile for SNESTile1BPP {
+    const BPP: usize = 1;
+
     fn new() -> Self {
         Self([0u8; 8])
     }
@@ -287,6 +475,9 @@ impl SNESTile for SNESTile1BPP {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -328,6 +519,8 @@ impl TryFrom<&Vec<u8>> for SNESTile2BPPPlanar {
     }
 }
 impl SNESTile for SNESTile2BPPPlanar {
+    const BPP: usize = 2;
+
     fn new() -> Self {
         Self([0u8; 8*2])
     }
@@ -340,6 +533,9 @@ impl SNESTile for SNESTile2BPPPlanar {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -388,6 +584,8 @@ impl TryFrom<&Vec<u8>> for SNESTile2BPPIntertwined {
     }
 }
 impl SNESTile for SNESTile2BPPIntertwined {
+    const BPP: usize = 2;
+
     fn new() -> Self {
         Self([0u8; 8*2])
     }
@@ -400,6 +598,9 @@ impl SNESTile for SNESTile2BPPIntertwined {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -448,6 +649,8 @@ impl TryFrom<&Vec<u8>> for SNESTile3BPPPlanar {
     }
 }
 impl SNESTile for SNESTile3BPPPlanar {
+    const BPP: usize = 3;
+
     fn new() -> Self {
         Self([0u8; 8*3])
     }
@@ -460,6 +663,9 @@ impl SNESTile for SNESTile3BPPPlanar {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -511,6 +717,8 @@ impl TryFrom<&Vec<u8>> for SNESTile3BPPIntertwined {
     }
 }
 impl SNESTile for SNESTile3BPPIntertwined {
+    const BPP: usize = 3;
+
     fn new() -> Self {
         Self([0u8; 8*3])
     }
@@ -523,6 +731,9 @@ impl SNESTile for SNESTile3BPPIntertwined {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -574,6 +785,8 @@ impl TryFrom<&Vec<u8>> for SNESTile4BPPPlanar {
     }
 }
 impl SNESTile for SNESTile4BPPPlanar {
+    const BPP: usize = 4;
+
     fn new() -> Self {
         Self([0u8; 8*4])
     }
@@ -586,6 +799,9 @@ impl SNESTile for SNESTile4BPPPlanar {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -640,6 +856,8 @@ impl TryFrom<&Vec<u8>> for SNESTile4BPPIntertwined {
     }
 }
 impl SNESTile for SNESTile4BPPIntertwined {
+    const BPP: usize = 4;
+
     fn new() -> Self {
         Self([0u8; 8*4])
     }
@@ -652,6 +870,9 @@ impl SNESTile for SNESTile4BPPIntertwined {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -706,6 +927,8 @@ impl TryFrom<&Vec<u8>> for SNESTile8BPPPlanar {
     }
 }
 impl SNESTile for SNESTile8BPPPlanar {
+    const BPP: usize = 8;
+
     fn new() -> Self {
         Self([0u8; 8*8])
     }
@@ -718,6 +941,9 @@ impl SNESTile for SNESTile8BPPPlanar {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -784,6 +1010,8 @@ impl TryFrom<&Vec<u8>> for SNESTile8BPPIntertwined {
     }
 }
 impl SNESTile for SNESTile8BPPIntertwined {
+    const BPP: usize = 8;
+
     fn new() -> Self {
         Self([0u8; 8*8])
     }
@@ -796,6 +1024,9 @@ impl SNESTile for SNESTile8BPPIntertwined {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -861,6 +1092,9 @@ impl TryFrom<&Vec<u8>> for SNESTileMode7 {
     }
 }
 impl SNESTile for SNESTileMode7 {
+    const BPP: usize = 8;
+    const LINEAR: bool = true;
+
     fn new() -> Self {
         Self([0u8; 8*8])
     }
@@ -873,6 +1107,9 @@ impl SNESTile for SNESTileMode7 {
 
         Ok(Self(array))
     }
+    fn as_data(&self) -> &[u8] {
+        &self.0
+    }
     fn set_value(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
         if x >= 8 { return Err(Error::OutOfBounds(x,8)); }
         if y >= 8 { return Err(Error::OutOfBounds(y,8)); }
@@ -887,3 +1124,482 @@ impl SNESTile for SNESTileMode7 {
         Ok(self.0[y*8+x])
     }
 }
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PixelBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb888>,
+}
+impl PixelBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: vec![Rgb888(0); width*height] }
+    }
+    pub fn get_pixel(&self, x: usize, y: usize) -> Result<Rgb888, Error> {
+        if x >= self.width { return Err(Error::OutOfBounds(x,self.width)); }
+        if y >= self.height { return Err(Error::OutOfBounds(y,self.height)); }
+
+        Ok(self.pixels[y*self.width+x])
+    }
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgb888) -> Result<(), Error> {
+        if x >= self.width { return Err(Error::OutOfBounds(x,self.width)); }
+        if y >= self.height { return Err(Error::OutOfBounds(y,self.height)); }
+
+        self.pixels[y*self.width+x] = color;
+        Ok(())
+    }
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb888) {
+        for py in y..(y+height).min(self.height) {
+            for px in x..(x+width).min(self.width) {
+                self.pixels[py*self.width+px] = color;
+            }
+        }
+    }
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, pixels: &[Rgb888]) {
+        /* clip against the buffer rather than erroring so partial tiles at the edges still draw */
+        for (i, color) in pixels.iter().enumerate() {
+            let px = x + i % width;
+            let py = y + i / width;
+
+            if px < self.width && py < self.height { self.pixels[py*self.width+px] = *color; }
+        }
+    }
+}
+
+pub const TILE_DIFF_SEPARATOR: Rgb888 = Rgb888(0xFF00FF);
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TileDiff {
+    pub index: usize,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+    pub changed_pixels: usize,
+    pub image: PixelBuffer,
+}
+
+/* what to do with a block that ends partway into a tile; Zero fills the last tile out, Truncate drops it */
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum TilePadding {
+    #[default]
+    Error,
+    Zero,
+    Truncate,
+}
+impl TilePadding {
+    pub fn apply<'a>(&self, data: &'a [u8], tile_size: usize) -> Result<(Cow<'a, [u8]>, usize), Error> {
+        /* also hands back how many bytes the last partial tile had, 0 if the data was already whole */
+        let remainder = data.len() % tile_size;
+        if remainder == 0 { return Ok((Cow::Borrowed(data), 0)); }
+
+        match self {
+            TilePadding::Error => Err(Error::DataLengthMismatch(data.len(), data.len() - remainder)),
+            TilePadding::Zero => {
+                let mut result = data.to_vec();
+                result.resize(data.len() + tile_size - remainder, 0);
+                Ok((Cow::Owned(result), remainder))
+            },
+            TilePadding::Truncate => Ok((Cow::Borrowed(&data[..data.len() - remainder]), remainder)),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TileSheet<T: SNESTile> {
+    pub tiles: Vec<T>,
+    pub columns: usize,
+}
+impl<T: SNESTile> TileSheet<T> {
+    pub fn new(tiles: Vec<T>, columns: usize) -> Self {
+        Self { tiles, columns: columns.max(1) }
+    }
+    pub fn from_data<B: AsRef<[u8]>>(data: B, columns: usize) -> Result<Self, Error> {
+        match Self::from_data_padded(data, columns, TilePadding::Error) {
+            Ok((s, _)) => Ok(s),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn from_data_padded<B: AsRef<[u8]>>(data: B, columns: usize, padding: TilePadding) -> Result<(Self, usize), Error> {
+        let tile_size = T::BPP * 8;
+        let (buf, remainder) = match padding.apply(data.as_ref(), tile_size) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+
+        let mut tiles = Vec::<T>::new();
+
+        for chunk in buf.chunks(tile_size) {
+            match T::from_data(chunk) {
+                Ok(t) => tiles.push(t),
+                Err(e) => return Err(e),
+            }
+        }
+
+        metrics::record(MetricCounter::BytesDecoded, buf.len());
+        metrics::record(MetricCounter::TilesDecoded, tiles.len());
+        Ok((Self::new(tiles, columns), remainder))
+    }
+    pub fn from_rom(rom: &Rom, address: Addr24, count: usize, columns: usize) -> Result<Self, Error> {
+        match rom.read_mapped(address, count * T::BPP * 8) {
+            Ok(d) => Self::from_data(d, columns),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn from_rom_region(rom: &Rom, address: Addr24, length: usize, columns: usize, padding: TilePadding) -> Result<(Self, usize), Error> {
+        /* a byte length rather than a tile count, for blocks whose size came from a table and may end mid-tile */
+        match rom.read_mapped(address, length) {
+            Ok(d) => Self::from_data_padded(d, columns, padding),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+    pub fn rows(&self) -> usize {
+        self.tiles.len().div_ceil(self.columns)
+    }
+    pub fn render<P: SNESPalette>(&self, palette: &P) -> Result<PixelBuffer, Error> {
+        let mut result = PixelBuffer::new(self.columns * 8, self.rows() * 8);
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            match tile.to_rgb888(palette) {
+                Ok(p) => result.blit((i % self.columns) * 8, (i / self.columns) * 8, 8, &p),
+                Err(e) => return Err(e),
+            }
+        }
+
+        metrics::record(MetricCounter::TilesRendered, self.tiles.len());
+        Ok(result)
+    }
+    pub fn render_greyscale(&self) -> Result<PixelBuffer, Error> {
+        let mut result = PixelBuffer::new(self.columns * 8, self.rows() * 8);
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            match tile.to_colormap() {
+                Ok(c) => result.blit((i % self.columns) * 8, (i / self.columns) * 8, 8, &greyscale::<T>(&c)),
+                Err(e) => return Err(e),
+            }
+        }
+
+        metrics::record(MetricCounter::TilesRendered, self.tiles.len());
+        Ok(result)
+    }
+    pub fn color_usage(&self) -> [u32; 256] {
+        /* pixel counts per palette index across the whole sheet */
+        let mut result = [0u32; 256];
+
+        for colormap in self.tiles.iter().filter_map(|t| t.to_colormap().ok()) {
+            for &index in &colormap { result[index as usize] += 1; }
+        }
+
+        result
+    }
+    pub fn max_indices(&self) -> Vec<u8> {
+        self.tiles.iter().map(|t| t.to_colormap().ok().and_then(|c| c.into_iter().max()).unwrap_or(0)).collect()
+    }
+    pub fn tiles_exceeding(&self, bpp: usize) -> Vec<usize> {
+        /* the tiles that would lose colors if this sheet were converted down to bpp */
+        if bpp >= 8 { return Vec::new(); }
+
+        self.max_indices().iter().enumerate().filter(|(_, &m)| m as usize >= 1 << bpp).map(|(i, _)| i).collect()
+    }
+    pub fn fits_bpp(&self, bpp: usize) -> bool {
+        self.tiles_exceeding(bpp).is_empty()
+    }
+    pub fn diff(&self, other: &Self) -> Result<Vec<TileDiff>, Error> {
+        let mut result = Vec::<TileDiff>::new();
+
+        for index in 0..self.tiles.len().max(other.tiles.len()) {
+            let before = match self.tiles.get(index).map(|t| t.to_colormap()) {
+                Some(Ok(c)) => Some(c),
+                Some(Err(e)) => return Err(e),
+                None => None,
+            };
+            let after = match other.tiles.get(index).map(|t| t.to_colormap()) {
+                Some(Ok(c)) => Some(c),
+                Some(Err(e)) => return Err(e),
+                None => None,
+            };
+
+            if before == after { continue; }
+
+            let changed_pixels = match (&before, &after) {
+                (Some(b), Some(a)) => b.iter().zip(a.iter()).filter(|(x, y)| x != y).count(),
+                _ => 64,
+            };
+
+            /* before on the left, after on the right, split by a one pixel separator; a missing side stays separator colored */
+            let mut image = PixelBuffer::new(17, 8);
+            image.fill_rect(0, 0, 17, 8, TILE_DIFF_SEPARATOR);
+
+            if let Some(b) = &before { image.blit(0, 0, 8, &greyscale::<T>(b)); }
+            if let Some(a) = &after { image.blit(9, 0, 8, &greyscale::<T>(a)); }
+
+            result.push(TileDiff { index, before, after, changed_pixels, image });
+        }
+
+        Ok(result)
+    }
+}
+
+fn greyscale<T: SNESTile>(colormap: &[u8]) -> Vec<Rgb888> {
+    let max = ((1usize << T::BPP) - 1) as u32;
+
+    colormap.iter().map(|&v| {
+        let level = ((v as u32).min(max) * 255 / max) as u8;
+        Rgb888::new(level, level, level)
+    }).collect()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileAlignment {
+    pub offset: usize,
+    pub score: f32,
+    pub scores: Vec<f32>,
+}
+impl TileAlignment {
+    pub fn margin(&self) -> f32 {
+        /* how far the winner stands above the runner-up; near zero means the region didn't decide it */
+        let runner_up = self.scores.iter().enumerate().filter(|(i, _)| *i != self.offset).map(|(_, &s)| s).fold(0.0f32, f32::max);
+
+        self.score - runner_up
+    }
+}
+
+fn tile_coherence(colormap: &[u8]) -> Option<f32> {
+    /* the share of neighbouring pixel pairs that match, spread over the colors used: a sheared tile builds its pixels
+       from bitplanes of different rows, which both breaks up flat areas and invents colors the artist never picked */
+    let distinct = colormap.iter().collect::<std::collections::BTreeSet<&u8>>().len();
+    if distinct < 2 { return None; }
+
+    let mut matches = 0usize;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            if x < 7 && colormap[y*8+x] == colormap[y*8+x+1] { matches += 1; }
+            if y < 7 && colormap[y*8+x] == colormap[(y+1)*8+x] { matches += 1; }
+        }
+    }
+
+    Some(matches as f32 / 112.0 / distinct as f32)
+}
+
+pub fn detect_tile_alignment<T: SNESTile, B: AsRef<[u8]>>(data: B) -> Result<TileAlignment, Error> {
+    /* blank tiles look the same at every offset, so only tiles with some detail get a vote */
+    let data = data.as_ref();
+    let tile_size = T::BPP * 8;
+
+    if data.len() < tile_size * 2 { return Err(Error::TruncatedData(data.len())); }
+
+    let mut scores = Vec::<f32>::with_capacity(tile_size);
+
+    for offset in 0..tile_size {
+        let mut total = 0.0f32;
+        let mut counted = 0usize;
+
+        for chunk in data[offset..].chunks_exact(tile_size) {
+            let colormap = match T::from_data(chunk).and_then(|t| t.to_colormap()) {
+                Ok(c) => c,
+                Err(e) => return Err(e),
+            };
+
+            if let Some(score) = tile_coherence(&colormap) {
+                total += score;
+                counted += 1;
+            }
+        }
+
+        scores.push(if counted == 0 { 0.0 } else { total / counted as f32 });
+    }
+
+    let mut offset = 0usize;
+
+    for (i, &score) in scores.iter().enumerate() {
+        if score > scores[offset] { offset = i; }
+    }
+
+    Ok(TileAlignment { offset, score: scores[offset], scores })
+}
+
+impl Rom {
+    pub fn detect_tile_alignment<T: SNESTile>(&self, offset: usize, length: usize) -> Result<TileAlignment, Error> {
+        match self.read(offset, length) {
+            Ok(d) => detect_tile_alignment::<T, _>(d),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/* SNESTile's associated constants and generic methods keep it out of trait objects; this is the same tile
+   behind a vtable, for viewers that hold tiles of several formats side by side */
+pub trait DynTile {
+    fn format(&self) -> TileFormat;
+    fn data(&self) -> &[u8];
+    fn pixel(&self, x: usize, y: usize) -> Result<u8, Error>;
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error>;
+    fn colormap(&self) -> Result<Vec<u8>, Error>;
+    fn set_colormap(&mut self, colormap: &[u8]) -> Result<(), Error>;
+    fn render(&self, palette: &[Bgr555]) -> Result<Vec<Rgb888>, Error>;
+    fn boxed_clone(&self) -> Box<dyn DynTile>;
+}
+impl Clone for Box<dyn DynTile> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TileFormat {
+    Bpp1,
+    Bpp2Planar,
+    Bpp2Intertwined,
+    Bpp3Planar,
+    Bpp3Intertwined,
+    Bpp4Planar,
+    Bpp4Intertwined,
+    Bpp8Planar,
+    Bpp8Intertwined,
+    Mode7,
+}
+impl TileFormat {
+    pub const ALL: [TileFormat; 10] = [TileFormat::Bpp1, TileFormat::Bpp2Planar, TileFormat::Bpp2Intertwined, TileFormat::Bpp3Planar, TileFormat::Bpp3Intertwined,
+                                       TileFormat::Bpp4Planar, TileFormat::Bpp4Intertwined, TileFormat::Bpp8Planar, TileFormat::Bpp8Intertwined, TileFormat::Mode7];
+
+    pub fn bpp(&self) -> usize {
+        match self {
+            TileFormat::Bpp1 => 1,
+            TileFormat::Bpp2Planar | TileFormat::Bpp2Intertwined => 2,
+            TileFormat::Bpp3Planar | TileFormat::Bpp3Intertwined => 3,
+            TileFormat::Bpp4Planar | TileFormat::Bpp4Intertwined => 4,
+            TileFormat::Bpp8Planar | TileFormat::Bpp8Intertwined | TileFormat::Mode7 => 8,
+        }
+    }
+    pub fn tile_size(&self) -> usize {
+        self.bpp() * 8
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileFormat::Bpp1 => "1bpp",
+            TileFormat::Bpp2Planar => "2bpp planar",
+            TileFormat::Bpp2Intertwined => "2bpp intertwined",
+            TileFormat::Bpp3Planar => "3bpp planar",
+            TileFormat::Bpp3Intertwined => "3bpp intertwined",
+            TileFormat::Bpp4Planar => "4bpp planar",
+            TileFormat::Bpp4Intertwined => "4bpp intertwined",
+            TileFormat::Bpp8Planar => "8bpp planar",
+            TileFormat::Bpp8Intertwined => "8bpp intertwined",
+            TileFormat::Mode7 => "mode 7",
+        }
+    }
+    pub fn decode<B: AsRef<[u8]>>(&self, data: B) -> Result<Box<dyn DynTile>, Error> {
+        fn boxed<T: SNESTile + DynTile + 'static>(data: &[u8]) -> Result<Box<dyn DynTile>, Error> {
+            match T::from_data(data) {
+                Ok(t) => Ok(Box::new(t)),
+                Err(e) => Err(e),
+            }
+        }
+
+        let data = data.as_ref();
+
+        match self {
+            TileFormat::Bpp1 => boxed::<SNESTile1BPP>(data),
+            TileFormat::Bpp2Planar => boxed::<SNESTile2BPPPlanar>(data),
+            TileFormat::Bpp2Intertwined => boxed::<SNESTile2BPPIntertwined>(data),
+            TileFormat::Bpp3Planar => boxed::<SNESTile3BPPPlanar>(data),
+            TileFormat::Bpp3Intertwined => boxed::<SNESTile3BPPIntertwined>(data),
+            TileFormat::Bpp4Planar => boxed::<SNESTile4BPPPlanar>(data),
+            TileFormat::Bpp4Intertwined => boxed::<SNESTile4BPPIntertwined>(data),
+            TileFormat::Bpp8Planar => boxed::<SNESTile8BPPPlanar>(data),
+            TileFormat::Bpp8Intertwined => boxed::<SNESTile8BPPIntertwined>(data),
+            TileFormat::Mode7 => boxed::<SNESTileMode7>(data),
+        }
+    }
+    pub fn decode_all<B: AsRef<[u8]>>(&self, data: B) -> Result<Vec<Box<dyn DynTile>>, Error> {
+        match self.decode_all_padded(data, TilePadding::Error) {
+            Ok((t, _)) => Ok(t),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn decode_all_padded<B: AsRef<[u8]>>(&self, data: B, padding: TilePadding) -> Result<(Vec<Box<dyn DynTile>>, usize), Error> {
+        let (data, remainder) = match padding.apply(data.as_ref(), self.tile_size()) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+
+        match data.chunks(self.tile_size()).map(|c| self.decode(c)).collect() {
+            Ok(t) => Ok((t, remainder)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn blank(&self) -> Box<dyn DynTile> {
+        /* a zeroed tile is valid in every format */
+        self.decode(vec![0u8; self.tile_size()]).unwrap()
+    }
+    pub fn from_colormap<B: AsRef<[u8]>>(&self, colormap: B) -> Result<Box<dyn DynTile>, Error> {
+        let mut result = self.blank();
+
+        match result.set_colormap(colormap.as_ref()) {
+            Ok(()) => Ok(result),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+trait HasTileFormat {
+    const FORMAT: TileFormat;
+}
+impl HasTileFormat for SNESTile1BPP { const FORMAT: TileFormat = TileFormat::Bpp1; }
+impl HasTileFormat for SNESTile2BPPPlanar { const FORMAT: TileFormat = TileFormat::Bpp2Planar; }
+impl HasTileFormat for SNESTile2BPPIntertwined { const FORMAT: TileFormat = TileFormat::Bpp2Intertwined; }
+impl HasTileFormat for SNESTile3BPPPlanar { const FORMAT: TileFormat = TileFormat::Bpp3Planar; }
+impl HasTileFormat for SNESTile3BPPIntertwined { const FORMAT: TileFormat = TileFormat::Bpp3Intertwined; }
+impl HasTileFormat for SNESTile4BPPPlanar { const FORMAT: TileFormat = TileFormat::Bpp4Planar; }
+impl HasTileFormat for SNESTile4BPPIntertwined { const FORMAT: TileFormat = TileFormat::Bpp4Intertwined; }
+impl HasTileFormat for SNESTile8BPPPlanar { const FORMAT: TileFormat = TileFormat::Bpp8Planar; }
+impl HasTileFormat for SNESTile8BPPIntertwined { const FORMAT: TileFormat = TileFormat::Bpp8Intertwined; }
+impl HasTileFormat for SNESTileMode7 { const FORMAT: TileFormat = TileFormat::Mode7; }
+
+impl<T: SNESTile + HasTileFormat + Clone + 'static> DynTile for T {
+    fn format(&self) -> TileFormat {
+        T::FORMAT
+    }
+    fn data(&self) -> &[u8] {
+        self.as_data()
+    }
+    fn pixel(&self, x: usize, y: usize) -> Result<u8, Error> {
+        self.get_value(x, y)
+    }
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8) -> Result<(), Error> {
+        self.set_value(x, y, value)
+    }
+    fn colormap(&self) -> Result<Vec<u8>, Error> {
+        self.to_colormap()
+    }
+    fn set_colormap(&mut self, colormap: &[u8]) -> Result<(), Error> {
+        match T::from_colormap(colormap) {
+            Ok(t) => { *self = t; Ok(()) },
+            Err(e) => Err(e),
+        }
+    }
+    fn render(&self, palette: &[Bgr555]) -> Result<Vec<Rgb888>, Error> {
+        let colormap = match self.to_colormap() {
+            Ok(c) => c,
+            Err(e) => return Err(e),
+        };
+        let mut result = Vec::<Rgb888>::with_capacity(colormap.len());
+
+        for index in colormap {
+            match palette.get(index as usize) {
+                Some(c) => result.push((*c).into()),
+                None => return Err(Error::InvalidColorIndex(index)),
+            }
+        }
+
+        Ok(result)
+    }
+    fn boxed_clone(&self) -> Box<dyn DynTile> {
+        Box::new(self.clone())
+    }
+}
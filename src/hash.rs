@@ -0,0 +1,130 @@
+use crate::{metrics, Error, MetricCounter, Rom};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    /* reflected CRC-32 (polynomial 0xEDB88320), the one IPS/BPS tools and ROM databases use */
+    let mut crc = 0xFFFFFFFFu32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    metrics::record(MetricCounter::BytesChecksummed, data.len());
+    !crc
+}
+
+fn md_padding(data: &[u8], big_endian: bool) -> Vec<u8> {
+    /* MD5 and SHA-1 share the Merkle-Damgard padding; only the byte order of the bit length differs */
+    let bits = (data.len() as u64).wrapping_mul(8);
+    let mut result = data.to_vec();
+
+    result.push(0x80);
+    while result.len() % 64 != 56 { result.push(0); }
+    result.extend_from_slice(&if big_endian { bits.to_be_bytes() } else { bits.to_le_bytes() });
+    result
+}
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+        5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+        4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+        6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+
+    /* the sine table, floor(abs(sin(i + 1)) * 2^32) */
+    let constants = (0..64).map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32).collect::<Vec<u32>>();
+    let mut state = [0x67452301u32, 0xEFCDAB89, 0x98BADCFE, 0x10325476];
+
+    for block in md_padding(data, false).chunks_exact(64) {
+        let words = block.chunks_exact(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect::<Vec<u32>>();
+        let [mut a, mut b, mut c, mut d] = state;
+
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f).wrapping_add(constants[i]).wrapping_add(words[g]).rotate_left(SHIFTS[i]);
+
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d].iter()) { *s = s.wrapping_add(*v); }
+    }
+
+    metrics::record(MetricCounter::BytesChecksummed, data.len());
+
+    let mut result = [0u8; 16];
+    for (chunk, word) in result.chunks_exact_mut(4).zip(state.iter()) { chunk.copy_from_slice(&word.to_le_bytes()); }
+    result
+}
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state = [0x67452301u32, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    for block in md_padding(data, true).chunks_exact(64) {
+        let mut words = [0u32; 80];
+
+        for (i, w) in block.chunks_exact(4).enumerate() { words[i] = u32::from_be_bytes([w[0], w[1], w[2], w[3]]); }
+        for i in 16..80 { words[i] = (words[i-3] ^ words[i-8] ^ words[i-14] ^ words[i-16]).rotate_left(1); }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5A827999),
+                1 => (b ^ c ^ d, 0x6ED9EBA1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6u32),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e].iter()) { *s = s.wrapping_add(*v); }
+    }
+
+    metrics::record(MetricCounter::BytesChecksummed, data.len());
+
+    let mut result = [0u8; 20];
+    for (chunk, word) in result.chunks_exact_mut(4).zip(state.iter()) { chunk.copy_from_slice(&word.to_be_bytes()); }
+    result
+}
+
+pub fn hex_digest(digest: &[u8]) -> String {
+    /* lowercase, the way No-Intro DATs and RetroAchievements print them */
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Rom {
+    pub fn crc32(&self) -> u32 {
+        /* copier headers vary between dumps of the same game, so hash the ROM data only */
+        crc32(&self.as_slice()[self.header_size()..])
+    }
+    pub fn md5(&self) -> [u8; 16] {
+        md5(&self.as_slice()[self.header_size()..])
+    }
+    pub fn sha1(&self) -> [u8; 20] {
+        sha1(&self.as_slice()[self.header_size()..])
+    }
+    pub fn region_crc32(&self, offset: usize, size: usize) -> Result<u32, Error> {
+        match self.read(offset, size) {
+            Ok(d) => Ok(crc32(d)),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{BitOrder, BitWriter, Codec, Error, Rom, RoundTripCheck, Script, ScriptEntry, TextTable};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub const HUFFMAN_MAX_LENGTH: usize = 16;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct HuffmanCode {
+    symbols: Vec<u8>,
+    counts: [u16; HUFFMAN_MAX_LENGTH + 1],
+    codes: Vec<Option<(u32, u8)>>,
+    bit_order: BitOrder,
+    round_trip: RoundTripCheck,
+}
+impl HuffmanCode {
+    pub fn from_lengths(lengths: &[(u8, u8)]) -> Result<Self, Error> {
+        /* canonical codes are fully determined by each symbol's code length, so that is all a tree description needs */
+        let mut sorted = lengths.iter().filter(|(_, l)| *l > 0).copied().collect::<Vec<(u8, u8)>>();
+        sorted.sort_by_key(|&(symbol, length)| (length, symbol));
+
+        let mut counts = [0u16; HUFFMAN_MAX_LENGTH + 1];
+        let mut codes = vec![None; 256];
+
+        for &(symbol, length) in &sorted {
+            if length as usize > HUFFMAN_MAX_LENGTH || codes[symbol as usize].is_some() { return Err(Error::InvalidHuffmanTable); }
+
+            counts[length as usize] += 1;
+            codes[symbol as usize] = Some((0, length));
+        }
+
+        /* Kraft: an over-subscribed set of lengths cannot form a prefix code */
+        let kraft = (1..=HUFFMAN_MAX_LENGTH).map(|l| (counts[l] as u32) << (HUFFMAN_MAX_LENGTH - l)).sum::<u32>();
+        if kraft > 1 << HUFFMAN_MAX_LENGTH { return Err(Error::InvalidHuffmanTable); }
+
+        let mut code = 0u32;
+        let mut previous = 0u8;
+
+        for &(symbol, length) in &sorted {
+            code <<= length - previous;
+            codes[symbol as usize] = Some((code, length));
+            code += 1;
+            previous = length;
+        }
+
+        Ok(Self { symbols: sorted.into_iter().map(|(s, _)| s).collect(), counts, codes, bit_order: BitOrder::MsbFirst, round_trip: RoundTripCheck::default() })
+    }
+    pub fn from_frequencies(frequencies: &[usize]) -> Result<Self, Error> {
+        let mut weights = frequencies.iter().take(256).copied().collect::<Vec<usize>>();
+
+        loop {
+            let lengths = huffman_lengths(&weights);
+
+            if lengths.iter().all(|&(_, l)| l as usize <= HUFFMAN_MAX_LENGTH) { return Self::from_lengths(&lengths); }
+
+            /* flattening the weights is cruder than package-merge but always converges on a shallow enough tree */
+            for w in weights.iter_mut().filter(|w| **w > 0) { *w = (*w).div_ceil(2); }
+        }
+    }
+    pub fn from_data(data: &[u8]) -> Result<Self, Error> {
+        let mut frequencies = vec![0usize; 256];
+
+        for &b in data { frequencies[b as usize] += 1; }
+
+        Self::from_frequencies(&frequencies)
+    }
+    pub fn from_description(data: &[u8]) -> Result<(Self, usize), Error> {
+        /* JPEG DHT layout: one count byte for each length 1..=16, then the symbols in canonical order */
+        if data.len() < HUFFMAN_MAX_LENGTH { return Err(Error::TruncatedData(data.len())); }
+
+        let total = data[..HUFFMAN_MAX_LENGTH].iter().map(|&c| c as usize).sum::<usize>();
+        let size = HUFFMAN_MAX_LENGTH + total;
+
+        if total > 256 { return Err(Error::InvalidHuffmanTable); }
+        if data.len() < size { return Err(Error::TruncatedData(data.len())); }
+
+        let mut lengths = Vec::<(u8, u8)>::new();
+        let mut cursor = HUFFMAN_MAX_LENGTH;
+
+        for length in 1..=HUFFMAN_MAX_LENGTH {
+            for _ in 0..data[length-1] {
+                lengths.push((data[cursor], length as u8));
+                cursor += 1;
+            }
+        }
+
+        match Self::from_lengths(&lengths) {
+            Ok(c) => Ok((c, size)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn to_description(&self) -> Result<Vec<u8>, Error> {
+        /* the count bytes top out at 255, so all 256 symbols at one length (a flat 8-bit code) has no description */
+        if self.counts[1..].iter().any(|&c| c > 0xFF) { return Err(Error::InvalidHuffmanTable); }
+
+        let mut result = self.counts[1..].iter().map(|&c| c as u8).collect::<Vec<u8>>();
+
+        result.extend_from_slice(&self.symbols);
+        Ok(result)
+    }
+    pub fn bit_order(mut self, bit_order: BitOrder) -> Self {
+        self.bit_order = bit_order;
+        self
+    }
+    pub fn round_trip(mut self, round_trip: RoundTripCheck) -> Self {
+        self.round_trip = round_trip;
+        self
+    }
+    pub fn lengths(&self) -> Vec<(u8, u8)> {
+        self.symbols.iter().map(|&s| (s, self.codes[s as usize].unwrap().1)).collect()
+    }
+    pub fn code(&self, symbol: u8) -> Option<(u32, u8)> {
+        self.codes[symbol as usize]
+    }
+    pub fn encoded_bits(&self, symbols: &[u8]) -> Result<usize, Error> {
+        let mut bits = 0usize;
+
+        for &symbol in symbols {
+            match self.codes[symbol as usize] {
+                Some((_, length)) => bits += length as usize,
+                None => return Err(Error::UnknownSymbol(symbol)),
+            }
+        }
+
+        Ok(bits)
+    }
+    pub fn encode(&self, symbols: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut writer = BitWriter::new().order(self.bit_order);
+
+        for &symbol in symbols {
+            let (code, length) = match self.codes[symbol as usize] {
+                Some(c) => c,
+                None => return Err(Error::UnknownSymbol(symbol)),
+            };
+
+            for i in (0..length).rev() { writer.write_bit((code >> i) & 1 == 1); }
+        }
+
+        let encoded = writer.into_inner();
+
+        if self.round_trip.is_enabled() {
+            if let Err(e) = self.round_trip.check(symbols, &encoded, |d, size| self.decode(d, size)) { return Err(e); }
+        }
+
+        Ok(encoded)
+    }
+    fn decode_symbol(&self, data: &[u8], bit: &mut usize) -> Result<u8, Error> {
+        let mut code = 0u32;
+        let mut first = 0u32;
+        let mut index = 0usize;
+
+        for length in 1..=HUFFMAN_MAX_LENGTH {
+            let byte = match data.get(*bit / 8) {
+                Some(b) => *b,
+                None => return Err(Error::TruncatedData(data.len())),
+            };
+            let shift = match self.bit_order {
+                BitOrder::MsbFirst => 7 - (*bit % 8),
+                BitOrder::LsbFirst => *bit % 8,
+            };
+
+            code |= ((byte >> shift) & 1) as u32;
+            *bit += 1;
+
+            let count = self.counts[length] as u32;
+
+            if code >= first && code - first < count { return Ok(self.symbols[index + (code - first) as usize]); }
+
+            index += count as usize;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(Error::InvalidHuffmanTable)
+    }
+    pub fn decode(&self, data: &[u8], count: usize) -> Result<Vec<u8>, Error> {
+        let mut bit = 0usize;
+        let mut result = Vec::<u8>::new();
+
+        for _ in 0..count {
+            match self.decode_symbol(data, &mut bit) {
+                Ok(s) => result.push(s),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn decode_string(&self, table: &TextTable, data: &[u8]) -> Result<(String, usize), Error> {
+        /* symbols come out as table bytes; stop once they close with an end token and report whole bytes consumed */
+        let mut bit = 0usize;
+        let mut bytes = Vec::<u8>::new();
+
+        loop {
+            match self.decode_symbol(data, &mut bit) {
+                Ok(s) => bytes.push(s),
+                Err(e) => return Err(e),
+            }
+
+            if table.end_tokens().any(|t| bytes.ends_with(t)) { break; }
+        }
+
+        let (text, _) = table.decode_string(&bytes);
+
+        Ok((text, bit.div_ceil(8)))
+    }
+    pub fn encode_string(&self, table: &TextTable, text: &str) -> Result<Vec<u8>, Error> {
+        match table.encode_string(text) {
+            Ok(bytes) => self.encode(&bytes),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Codec for HuffmanCode {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.encode(data)
+    }
+    fn decompress(&self, data: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+        self.decode(data, size)
+    }
+}
+
+fn huffman_lengths(weights: &[usize]) -> Vec<(u8, u8)> {
+    let used = weights.iter().enumerate().filter(|(_, &w)| w > 0).map(|(s, _)| s as u8).collect::<Vec<u8>>();
+
+    /* a lone symbol still needs one bit so the decoder can advance */
+    if used.len() == 1 { return vec![(used[0], 1)]; }
+
+    let mut parents = Vec::<usize>::new();
+    let mut heap = BinaryHeap::<Reverse<(usize, usize)>>::new();
+
+    for (node, &symbol) in used.iter().enumerate() {
+        heap.push(Reverse((weights[symbol as usize], node)));
+        parents.push(usize::MAX);
+    }
+
+    while heap.len() > 1 {
+        let Reverse((wa, a)) = heap.pop().unwrap();
+        let Reverse((wb, b)) = heap.pop().unwrap();
+        let node = parents.len();
+
+        parents.push(usize::MAX);
+        parents[a] = node;
+        parents[b] = node;
+        heap.push(Reverse((wa + wb, node)));
+    }
+
+    used.iter().enumerate().map(|(node, &symbol)| {
+        let mut depth = 0usize;
+        let mut current = node;
+
+        while parents[current] != usize::MAX {
+            current = parents[current];
+            depth += 1;
+        }
+
+        (symbol, depth.min(u8::MAX as usize) as u8)
+    }).collect()
+}
+
+impl Script {
+    pub fn dump_huffman(rom: &Rom, table: &TextTable, code: &HuffmanCode, offsets: &[usize]) -> Result<Self, Error> {
+        let mut entries = Vec::<ScriptEntry>::new();
+
+        for &offset in offsets {
+            if offset >= rom.len() { return Err(Error::OutOfBounds(offset,rom.len())); }
+
+            let (text, length) = match code.decode_string(table, &rom.as_slice()[offset..]) {
+                Ok(r) => r,
+                Err(e) => return Err(e),
+            };
+
+            entries.push(ScriptEntry { offset, length, text, speaker: None, translation: None });
+        }
+
+        Ok(Self { entries })
+    }
+    pub fn write_huffman_translations(&self, rom: &mut Rom, table: &TextTable, code: &HuffmanCode, fill: u8) -> Result<usize, Error> {
+        let mut written = 0usize;
+
+        for entry in &self.entries {
+            let translation = match &entry.translation {
+                Some(t) => t,
+                None => continue,
+            };
+            let mut encoded = match code.encode_string(table, translation) {
+                Ok(e) => e,
+                Err(e) => return Err(e),
+            };
+
+            if encoded.len() > entry.length { return Err(Error::DataLengthMismatch(encoded.len(),entry.length)); }
+
+            encoded.resize(entry.length, fill);
+
+            match rom.write(entry.offset, encoded) {
+                Ok(()) => written += 1,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(written)
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{Addr24, AddrNotation, Error, Mapper, Rom};
+use std::ops::Range;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum BankCrossPolicy {
+    Wrap,
+    #[default]
+    Carry,
+    LoRomCarry,
+    Forbid,
+}
+impl BankCrossPolicy {
+    pub fn offset(&self, base: Addr24, delta: u32) -> Result<Addr24, Error> {
+        match self {
+            BankCrossPolicy::Wrap => Ok(Addr24::new(base.bank, base.address.wrapping_add(delta as u16))),
+            BankCrossPolicy::Carry => {
+                let result = base.as_u32() as u64 + delta as u64;
+                if result > 0xFFFFFF { return Err(Error::BankBoundaryCrossed(base)); }
+
+                Ok(Addr24::from_u32(result as u32))
+            },
+            BankCrossPolicy::LoRomCarry => {
+                /* LoROM banks only expose ROM in their upper half, so a carry lands at $8000 of the next bank */
+                let linear = base.bank as u64 * 0x8000 + (base.address & 0x7FFF) as u64 + delta as u64;
+                let bank = linear / 0x8000;
+                if bank > 0xFF { return Err(Error::BankBoundaryCrossed(base)); }
+
+                Ok(Addr24::new(bank as u8, 0x8000 | (linear % 0x8000) as u16))
+            },
+            BankCrossPolicy::Forbid => {
+                if base.address as u64 + delta as u64 > 0xFFFF { return Err(Error::BankBoundaryCrossed(base)); }
+
+                Ok(Addr24::new(base.bank, base.address + delta as u16))
+            },
+        }
+    }
+    fn distance(&self, base: Addr24, address: Addr24) -> Option<u32> {
+        match self {
+            BankCrossPolicy::Wrap => {
+                if address.bank != base.bank { return None; }
+
+                Some(address.address.wrapping_sub(base.address) as u32)
+            },
+            BankCrossPolicy::Carry => address.as_u32().checked_sub(base.as_u32()),
+            BankCrossPolicy::LoRomCarry => {
+                if address.address < 0x8000 { return None; }
+
+                let base_linear = base.bank as u32 * 0x8000 + (base.address & 0x7FFF) as u32;
+                let linear = address.bank as u32 * 0x8000 + (address.address & 0x7FFF) as u32;
+
+                linear.checked_sub(base_linear)
+            },
+            BankCrossPolicy::Forbid => {
+                if address.bank != base.bank { return None; }
+
+                address.address.checked_sub(base.address).map(|d| d as u32)
+            },
+        }
+    }
+}
+
+/* governs write_checked only; plain write never looks at banks */
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum BankWriteMode {
+    #[default]
+    Allow,
+    Reject,
+    Split,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct IndexedAccess {
+    pub base: Addr24,
+    pub stride: u32,
+    pub element_size: u32,
+    pub wrap: BankCrossPolicy,
+}
+impl IndexedAccess {
+    pub fn new(base: Addr24, stride: u32) -> Self {
+        Self { base, stride, element_size: stride, wrap: BankCrossPolicy::Carry }
+    }
+    pub fn element_size(mut self, element_size: u32) -> Self {
+        self.element_size = element_size;
+        self
+    }
+    pub fn wrap(mut self, wrap: BankCrossPolicy) -> Self {
+        self.wrap = wrap;
+        self
+    }
+    pub fn address(&self, index: usize) -> Result<Addr24, Error> {
+        self.byte_address(index, 0)
+    }
+    pub fn byte_address(&self, index: usize, byte: u32) -> Result<Addr24, Error> {
+        let delta = index as u64 * self.stride as u64 + byte as u64;
+        if delta > 0xFFFFFF { return Err(Error::OutOfBounds(delta as usize, 0x1000000)); }
+
+        self.wrap.offset(self.base, delta as u32)
+    }
+    pub fn element_addresses(&self, index: usize) -> Result<Vec<Addr24>, Error> {
+        let mut result = Vec::<Addr24>::new();
+
+        for byte in 0..self.element_size {
+            match self.byte_address(index, byte) {
+                Ok(a) => result.push(a),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn touched(&self, indices: Range<usize>) -> Result<Vec<Addr24>, Error> {
+        let mut result = Vec::<Addr24>::new();
+
+        for index in indices {
+            match self.element_addresses(index) {
+                Ok(a) => result.extend(a),
+                Err(e) => return Err(e),
+            }
+        }
+
+        /* overlapping strides and wrapped indices can hit the same byte more than once */
+        result.sort_by_key(|a| a.as_u32());
+        result.dedup();
+
+        Ok(result)
+    }
+    pub fn touched_offsets(&self, rom: &Rom, indices: Range<usize>) -> Result<Vec<usize>, Error> {
+        match self.touched(indices) {
+            Ok(a) => a.iter().map(|a| a.to_mapped_offset(rom)).collect(),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn index_of(&self, address: Addr24) -> Option<(usize, u32)> {
+        if self.stride == 0 { return None; }
+
+        let distance = match self.wrap.distance(self.base, address) {
+            Some(d) => d,
+            None => return None,
+        };
+        let byte = distance % self.stride;
+
+        if byte >= self.element_size { return None; }
+
+        Some(((distance / self.stride) as usize, byte))
+    }
+}
+
+impl Rom {
+    pub fn bank_policy(&self) -> BankCrossPolicy {
+        self.bank_policy
+    }
+    pub fn set_bank_policy(&mut self, policy: BankCrossPolicy) {
+        self.bank_policy = policy;
+    }
+    pub fn bank_write_mode(&self) -> BankWriteMode {
+        self.bank_write_mode
+    }
+    pub fn set_bank_write_mode(&mut self, mode: BankWriteMode) {
+        self.bank_write_mode = mode;
+    }
+    pub fn bank_bounds(&self, offset: usize) -> Result<Range<usize>, Error> {
+        /* buffer offsets of the bank holding offset: 32KB banks for the LoROM-style boards, 64KB for the rest */
+        if offset < self.header_size() || offset >= self.len() { return Err(Error::OutOfBounds(offset,self.len())); }
+
+        let bank_size = match self.memory_map() {
+            Ok(Mapper::LoRom(_)) | Ok(Mapper::SuperFx(_)) | Ok(Mapper::Sdd1(_)) => 0x8000,
+            Ok(_) => 0x10000,
+            Err(e) => return Err(e),
+        };
+        let start = (offset - self.header_size()) / bank_size * bank_size + self.header_size();
+
+        Ok(start..(start + bank_size).min(self.len()))
+    }
+    pub fn write_checked<B: AsRef<[u8]>>(&mut self, offset: usize, data: B) -> Result<Vec<Range<usize>>, Error> {
+        /* returns the pieces written, one per bank touched; Reject writes nothing if the data would cross */
+        let data = data.as_ref();
+        let end = offset + data.len();
+        let mut pieces = Vec::<Range<usize>>::new();
+
+        if self.bank_write_mode == BankWriteMode::Allow || data.is_empty() {
+            pieces.push(offset..end);
+        }
+        else {
+            let mut start = offset;
+
+            while start < end {
+                let bank = match self.bank_bounds(start) {
+                    Ok(b) => b,
+                    Err(e) => return Err(e),
+                };
+
+                if self.bank_write_mode == BankWriteMode::Reject && end > bank.end { return Err(Error::BankCrossing(offset,bank.end)); }
+
+                pieces.push(start..end.min(bank.end));
+                start = bank.end;
+            }
+        }
+
+        for piece in &pieces {
+            if let Err(e) = self.write(piece.start, &data[piece.start - offset..piece.end - offset]) { return Err(e); }
+        }
+
+        Ok(pieces)
+    }
+    pub fn mapped_offset(&self, address: Addr24) -> Result<usize, Error> {
+        /* mapped notations know where their banks live; otherwise let the header pick the memory map */
+        match self.notation {
+            AddrNotation::LoRom | AddrNotation::HiRom => self.notation_to_offset(address, self.notation),
+            _ => {
+                let offset = match address.to_mapped_offset(self) {
+                    Ok(o) => o,
+                    Err(e) => return Err(e),
+                };
+                if offset >= self.len() { return Err(Error::OutOfBounds(offset,self.len())); }
+
+                Ok(offset)
+            },
+        }
+    }
+    pub fn read_mapped(&self, address: Addr24, size: usize) -> Result<Vec<u8>, Error> {
+        self.read_mapped_with(address, size, self.bank_policy)
+    }
+    pub fn read_mapped_with(&self, address: Addr24, size: usize, policy: BankCrossPolicy) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::<u8>::with_capacity(size);
+
+        while result.len() < size {
+            let current = match policy.offset(address, result.len() as u32) {
+                Ok(a) => a,
+                Err(e) => return Err(e),
+            };
+            let offset = match self.mapped_offset(current) {
+                Ok(o) => o,
+                Err(e) => return Err(e),
+            };
+
+            /* every policy keeps bytes contiguous up to the end of the current bank */
+            let run = (size - result.len()).min(0x10000 - current.address as usize);
+
+            match self.read(offset, run) {
+                Ok(d) => result.extend_from_slice(d),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+}
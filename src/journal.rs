@@ -0,0 +1,120 @@
+use crate::{Error, Rom};
+
+/* one mutating call: the bytes at offset before and after, plus the image length around it so resizes undo too */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct JournalEntry {
+    pub operation: String,
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    pub old_len: usize,
+    pub new_len: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct EditJournal {
+    undo: Vec<JournalEntry>,
+    redo: Vec<JournalEntry>,
+    pub limit: Option<usize>,
+}
+impl EditJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn limit(mut self, limit: usize) -> Self {
+        /* oldest entries fall off first once the history is full */
+        self.limit = Some(limit);
+        self
+    }
+    pub fn history(&self) -> &[JournalEntry] {
+        &self.undo
+    }
+    pub fn redo_history(&self) -> &[JournalEntry] {
+        &self.redo
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+    fn push(&mut self, entry: JournalEntry) {
+        /* a fresh edit forks history, so whatever was undone can't come back */
+        self.redo.clear();
+        self.undo.push(entry);
+
+        if let Some(limit) = self.limit {
+            if self.undo.len() > limit { self.undo.drain(..self.undo.len() - limit); }
+        }
+    }
+}
+
+impl Rom {
+    pub fn start_journal(&mut self) {
+        self.start_journal_with(EditJournal::new());
+    }
+    pub fn start_journal_with(&mut self, journal: EditJournal) {
+        self.journal = Some(journal);
+    }
+    pub fn stop_journal(&mut self) -> Option<EditJournal> {
+        self.journal.take()
+    }
+    pub fn journal(&self) -> Option<&EditJournal> {
+        self.journal.as_ref()
+    }
+    pub fn history(&self) -> &[JournalEntry] {
+        match &self.journal {
+            Some(j) => j.history(),
+            None => &[],
+        }
+    }
+    pub(crate) fn record_edit(&mut self, operation: &str, offset: usize, old: Vec<u8>, old_len: usize) {
+        /* callers hand over what they're about to overwrite; the new side is read back once the write has landed */
+        if self.journal.is_none() { return; }
+
+        let new_len = self.len();
+        let end = (offset + old.len().max(new_len.saturating_sub(old_len))).min(new_len);
+        let new = self.as_slice()[offset.min(new_len)..end].to_vec();
+
+        if let Some(journal) = &mut self.journal {
+            journal.push(JournalEntry { operation: operation.to_string(), offset, old, new, old_len, new_len });
+        }
+    }
+    fn replay(&mut self, offset: usize, data: &[u8], len: usize) -> Result<(), Error> {
+        /* the journal steps aside so putting bytes back isn't itself recorded */
+        let journal = self.journal.take();
+
+        if self.len() != len { self.resize(len); }
+
+        let result = if data.is_empty() { Ok(()) } else { self.write(offset, data) };
+
+        self.journal = journal;
+        result
+    }
+    pub fn undo(&mut self) -> Result<bool, Error> {
+        let entry = match self.journal.as_mut().and_then(|j| j.undo.pop()) {
+            Some(e) => e,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = self.replay(entry.offset, &entry.old, entry.old_len) { return Err(e); }
+
+        self.journal.as_mut().unwrap().redo.push(entry);
+        Ok(true)
+    }
+    pub fn redo(&mut self) -> Result<bool, Error> {
+        let entry = match self.journal.as_mut().and_then(|j| j.redo.pop()) {
+            Some(e) => e,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = self.replay(entry.offset, &entry.new, entry.new_len) { return Err(e); }
+
+        self.journal.as_mut().unwrap().undo.push(entry);
+        Ok(true)
+    }
+}
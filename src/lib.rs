@@ -1,12 +1,178 @@
+/* early returns are spelled out with match and if let throughout rather than with ? */
+#![allow(clippy::question_mark)]
+
 #[cfg(test)]
 mod tests;
 
-use pkbuffer::{self, Buffer, VecBuffer};
-use std::path::Path;
+use pkbuffer::{self, Buffer};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 pub mod graphics;
 pub use graphics::*;
 
+pub mod regions;
+pub use regions::*;
+
+pub mod multicart;
+pub use multicart::*;
+
+pub mod ppu;
+
+pub mod dma;
+pub use dma::*;
+
+pub mod cpu;
+pub use cpu::*;
+
+pub mod indexed;
+pub use indexed::*;
+
+pub mod notation;
+pub use notation::*;
+
+pub mod cancel;
+pub use cancel::*;
+
+pub mod search;
+pub use search::*;
+
+pub mod survey;
+pub use survey::*;
+
+pub mod analysis;
+pub use analysis::*;
+
+pub mod schema;
+pub use schema::*;
+
+pub mod text;
+pub use text::*;
+
+pub mod script;
+pub use script::*;
+
+pub mod dte;
+pub use dte::*;
+
+pub mod bits;
+pub use bits::*;
+
+pub mod codec;
+pub use codec::*;
+
+pub mod huffman;
+pub use huffman::*;
+
+pub mod hash;
+pub use hash::*;
+
+pub mod patch;
+pub use patch::*;
+
+pub mod verify;
+pub use verify::*;
+
+pub mod png;
+pub use png::*;
+
+pub mod assets;
+pub use assets::*;
+
+pub mod build;
+pub use build::*;
+
+pub mod metrics;
+pub use metrics::*;
+
+pub mod normalize;
+pub use normalize::*;
+
+pub mod copier;
+pub use copier::*;
+
+pub mod peripherals;
+pub use peripherals::*;
+
+pub mod games;
+
+pub mod protection;
+pub use protection::*;
+
+pub mod mapper;
+pub use mapper::*;
+
+pub mod boards;
+pub use boards::*;
+
+pub mod fastrom;
+pub use fastrom::*;
+
+pub mod remap;
+pub use remap::*;
+
+pub mod pointers;
+pub use pointers::*;
+
+pub mod allocator;
+pub use allocator::*;
+
+pub mod commands;
+pub use commands::*;
+
+pub mod project;
+pub use project::*;
+
+pub mod usage;
+pub use usage::*;
+
+pub mod symbols;
+pub use symbols::*;
+
+pub mod mesen;
+pub use mesen::*;
+
+pub mod testing;
+pub use testing::*;
+
+pub mod sidecar;
+pub use sidecar::*;
+
+pub mod demo;
+pub use demo::*;
+
+pub mod database;
+pub use database::*;
+
+pub mod variants;
+pub use variants::*;
+
+pub mod diff;
+pub use diff::*;
+
+pub mod overlay;
+pub use overlay::*;
+
+pub mod context;
+pub use context::*;
+
+pub mod journal;
+pub use journal::*;
+
+pub mod cursor;
+pub use cursor::*;
+
+pub mod mmap;
+pub use mmap::*;
+
+pub mod watch;
+pub use watch::*;
+
+pub mod parallax;
+pub use parallax::*;
+
+pub mod prelude;
+
 #[derive(Debug)]
 pub enum Error {
     PKBufferError(pkbuffer::Error),
@@ -19,7 +185,58 @@ pub enum Error {
     InvalidROMAddress(Addr24),
     InvalidDiskAddress(Addr24),
     OutOfBounds(usize,usize),
+    TruncatedData(usize),
+    BadMagic,
+    UnsupportedVersion(u16),
+    InvalidLineCount(usize),
+    InvalidHdmaEntry,
+    BankBoundaryCrossed(Addr24),
+    InvalidAddressText(String),
+    Cancelled,
+    InvalidPattern(String),
+    UnknownField(String),
+    FieldOutOfRange(String,i64),
+    InvalidTableLine(usize),
+    UnencodableText(String),
+    InvalidScriptFormat(usize),
+    InvalidHuffmanTable,
+    UnknownSymbol(u8),
+    IncompatibleLayerFormat(u8,usize,usize),
+    UnsupportedMode(u8),
+    OffsetPerTileConflict(usize),
+    IoError(std::io::Error),
+    InvalidBpp(usize),
+    InvalidManifestLine(usize),
+    InvalidImage(String),
+    NoFreeSpace(usize),
+    PatchChecksumMismatch(u32,u32),
+    Overdump(usize,usize),
+    Underdump(usize,usize),
+    InvalidLabelLine(usize),
+    NoSourcePath,
+    InvalidBitWidth(usize),
+    ChecksumMismatch(u16,u16),
+    UnaddressableRomSize(usize,usize),
+    InvalidPartCount(usize),
+    InvalidMetadataLine(usize),
+    AllocationOverlap(usize,usize),
+    NotAllocated(Addr24),
+    InvalidButton(String),
+    BankCrossing(usize,usize),
+    UnmatchedColor(Rgb888),
+    InvalidDatLine(usize),
+    Context(ErrorContext, Box<Error>),
+    RoundTripMismatch(usize),
+    InvalidRomSizeByte(u8),
+    InvalidRegionKind(u8),
+    EmptyPatchAction(usize),
+    ReservedPatchOffset(usize),
+    AnalysisPassPanicked(SurveyPass),
+    ReversedRange(usize,usize),
 }
+/* only pkbuffer's InvalidPointer variant keeps these from being derived, and that pointer is reported, never dereferenced */
+unsafe impl Send for Error {}
+unsafe impl Sync for Error {}
 
 #[repr(packed)]
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
@@ -70,6 +287,28 @@ impl Addr24 {
             self.as_u32() as usize + rom.header_size()
         }
     }
+    pub fn to_lorom_offset(&self, rom: &Rom) -> Result<usize, Error> {
+        /* 32KB banks seen through $8000-$FFFF, so $00:8000 and $01:8000 are 0x8000 bytes apart on disk */
+        match LoRom.address_to_pc(*self) {
+            Ok(pc) => Ok(pc + rom.header_size()),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn from_lorom_offset(rom: &Rom, offset: usize) -> Result<Self, Error> {
+        if offset < rom.header_size() { return Err(Error::OutOfBounds(offset,rom.len())); }
+
+        LoRom.pc_to_address(offset - rom.header_size())
+    }
+    pub fn to_mapped_offset(&self, rom: &Rom) -> Result<usize, Error> {
+        /* follow the cart's own memory map; an address it doesn't decode (or no map at all) is an error, not a guess at the plain bank math */
+        match rom.memory_map().and_then(|m| m.address_to_pc(*self)) {
+            Ok(pc) => Ok(pc + rom.header_size()),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn from_mapped_offset(rom: &Rom, offset: usize) -> Result<Self, Error> {
+        rom.offset_to_address(offset)
+    }
     pub fn is_rom_address(&self) -> bool {
         self.bank >= 0xC0
     }
@@ -126,6 +365,112 @@ pub struct EmulationModeVectors {
     /* +e */ irq_or_brk: u16,
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum InterruptVector {
+    NativeCop,
+    NativeBrk,
+    NativeAbort,
+    NativeNmi,
+    NativeIrq,
+    EmulationCop,
+    EmulationAbort,
+    EmulationNmi,
+    Reset,
+    EmulationIrqBrk,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum VideoStandard {
+    Ntsc,
+    Pal,
+}
+
+/* the destination code at $FFD9; codes nobody assigned are kept as they are so a header round-trips */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Region {
+    Japan,
+    Usa,
+    Europe,
+    Scandinavia,
+    Finland,
+    Denmark,
+    France,
+    Netherlands,
+    Spain,
+    Germany,
+    Italy,
+    China,
+    Indonesia,
+    Korea,
+    Common,
+    Canada,
+    Brazil,
+    Australia,
+    Unknown(u8),
+}
+impl Region {
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => Region::Japan,
+            0x01 => Region::Usa,
+            0x02 => Region::Europe,
+            0x03 => Region::Scandinavia,
+            0x04 => Region::Finland,
+            0x05 => Region::Denmark,
+            0x06 => Region::France,
+            0x07 => Region::Netherlands,
+            0x08 => Region::Spain,
+            0x09 => Region::Germany,
+            0x0A => Region::Italy,
+            0x0B => Region::China,
+            0x0C => Region::Indonesia,
+            0x0D => Region::Korea,
+            0x0E => Region::Common,
+            0x0F => Region::Canada,
+            0x10 => Region::Brazil,
+            0x11 => Region::Australia,
+            _ => Region::Unknown(code),
+        }
+    }
+    pub fn code(&self) -> u8 {
+        match self {
+            Region::Japan => 0x00,
+            Region::Usa => 0x01,
+            Region::Europe => 0x02,
+            Region::Scandinavia => 0x03,
+            Region::Finland => 0x04,
+            Region::Denmark => 0x05,
+            Region::France => 0x06,
+            Region::Netherlands => 0x07,
+            Region::Spain => 0x08,
+            Region::Germany => 0x09,
+            Region::Italy => 0x0A,
+            Region::China => 0x0B,
+            Region::Indonesia => 0x0C,
+            Region::Korea => 0x0D,
+            Region::Common => 0x0E,
+            Region::Canada => 0x0F,
+            Region::Brazil => 0x10,
+            Region::Australia => 0x11,
+            Region::Unknown(code) => *code,
+        }
+    }
+    pub fn video_standard(&self) -> VideoStandard {
+        /* Brazil's PAL-M runs at 60Hz, so only the European, Asian PAL and Australian codes count as PAL */
+        match self {
+            Region::Europe | Region::Scandinavia | Region::Finland | Region::Denmark | Region::France | Region::Netherlands
+                | Region::Spain | Region::Germany | Region::Italy | Region::China | Region::Indonesia | Region::Australia => VideoStandard::Pal,
+            _ => VideoStandard::Ntsc,
+        }
+    }
+    pub fn is_pal(&self) -> bool {
+        self.video_standard() == VideoStandard::Pal
+    }
+}
+
+/* $0D is 8MB, the most any board maps */
+pub const MAX_ROM_SIZE_BYTE: u8 = 0x0D;
+
 #[repr(packed)]
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct SNESHeader {
@@ -134,7 +479,8 @@ pub struct SNESHeader {
     /* +fd6 */ rom_type: u8,
     /* +fd7 */ rom_size: u8,
     /* +fd8 */ sram_size: u8,
-    /* +fd9 */ developer_id: u16,
+    /* +fd9 */ destination_code: u8,
+    /* +fda */ developer_id: u8,
     /* +fdb */ version: u8,
     /* +fdc */ checksum_compliment: u16,
     /* +fde */ checksum: u16,
@@ -144,18 +490,155 @@ pub struct SNESHeader {
     /* +ff4 */ emulation: EmulationModeVectors,
 }
 impl SNESHeader {
-    pub fn validate(&self, rom: &Rom) -> Result<(), Error> {
+    pub fn get_title(&self) -> String {
+        String::from_utf8_lossy(&self.game_title).trim_end().to_string()
+    }
+    pub fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        let bytes = title.as_bytes();
+        if bytes.len() > 21 { return Err(Error::DataLengthMismatch(bytes.len(),21)); }
+
+        for c in bytes {
+            if *c < 32 || *c >= 127 { return Err(Error::TitleNotASCII); }
+        }
+
+        let mut title_data = [0x20u8; 21];
+        title_data[..bytes.len()].copy_from_slice(bytes);
+        self.game_title = title_data;
+
+        Ok(())
+    }
+    pub fn get_mapping_mode(&self) -> u8 {
+        self.mapping_mode
+    }
+    pub fn set_mapping_mode(&mut self, mapping_mode: u8) {
+        self.mapping_mode = mapping_mode;
+    }
+    pub fn get_rom_type(&self) -> u8 {
+        self.rom_type
+    }
+    pub fn set_rom_type(&mut self, rom_type: u8) {
+        self.rom_type = rom_type;
+    }
+    pub fn get_rom_size(&self) -> u8 {
+        self.rom_size
+    }
+    pub fn set_rom_size(&mut self, rom_size: u8) {
+        self.rom_size = rom_size;
+    }
+    pub fn get_sram_size(&self) -> u8 {
+        self.sram_size
+    }
+    pub fn set_sram_size(&mut self, sram_size: u8) {
+        self.sram_size = sram_size;
+    }
+    pub fn get_destination_code(&self) -> u8 {
+        self.destination_code
+    }
+    pub fn set_destination_code(&mut self, destination_code: u8) {
+        self.destination_code = destination_code;
+    }
+    pub fn get_region(&self) -> Region {
+        Region::from_code(self.destination_code)
+    }
+    pub fn set_region(&mut self, region: Region) {
+        self.destination_code = region.code();
+    }
+    pub fn video_standard(&self) -> VideoStandard {
+        self.get_region().video_standard()
+    }
+    pub fn get_developer_id(&self) -> u8 {
+        /* $33 means the real licensee code is in the extended header at $FFB0 */
+        self.developer_id
+    }
+    pub fn set_developer_id(&mut self, developer_id: u8) {
+        self.developer_id = developer_id;
+    }
+    pub fn has_extended_header(&self) -> bool {
+        self.developer_id == 0x33
+    }
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+    pub fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+    pub fn get_checksum(&self) -> u16 {
+        u16::from_le(self.checksum)
+    }
+    pub fn get_checksum_compliment(&self) -> u16 {
+        u16::from_le(self.checksum_compliment)
+    }
+    pub fn set_checksum(&mut self, checksum: u16) {
+        self.checksum = checksum.to_le();
+        self.checksum_compliment = (checksum ^ 0xFFFF).to_le();
+    }
+    pub fn get_vector(&self, vector: InterruptVector) -> u16 {
+        /* the header is a view straight onto rom bytes, so every multi-byte field is stored little-endian */
+        let raw = match vector {
+            InterruptVector::NativeCop => self.native.cop,
+            InterruptVector::NativeBrk => self.native.brk,
+            InterruptVector::NativeAbort => self.native.abort,
+            InterruptVector::NativeNmi => self.native.nmi,
+            InterruptVector::NativeIrq => self.native.irq,
+            InterruptVector::EmulationCop => self.emulation.cop,
+            InterruptVector::EmulationAbort => self.emulation.abort,
+            InterruptVector::EmulationNmi => self.emulation.nmi,
+            InterruptVector::Reset => self.emulation.res,
+            InterruptVector::EmulationIrqBrk => self.emulation.irq_or_brk,
+        };
+
+        u16::from_le(raw)
+    }
+    pub fn set_vector(&mut self, vector: InterruptVector, target: u16) {
+        let target = target.to_le();
+
+        match vector {
+            InterruptVector::NativeCop => self.native.cop = target,
+            InterruptVector::NativeBrk => self.native.brk = target,
+            InterruptVector::NativeAbort => self.native.abort = target,
+            InterruptVector::NativeNmi => self.native.nmi = target,
+            InterruptVector::NativeIrq => self.native.irq = target,
+            InterruptVector::EmulationCop => self.emulation.cop = target,
+            InterruptVector::EmulationAbort => self.emulation.abort = target,
+            InterruptVector::EmulationNmi => self.emulation.nmi = target,
+            InterruptVector::Reset => self.emulation.res = target,
+            InterruptVector::EmulationIrqBrk => self.emulation.irq_or_brk = target,
+        }
+    }
+    pub fn validate_fields(&self) -> Result<(), Error> {
+        self.validate_fields_with(ChecksumPolicy::Strict)
+    }
+    pub fn validate_fields_with(&self, policy: ChecksumPolicy) -> Result<(), Error> {
         for c in &self.game_title {
             if *c < 32 || *c >= 127 { return Err(Error::TitleNotASCII); }
         }
 
-        if self.checksum_compliment.wrapping_add(self.checksum) != 0xFFFF {
+        if self.rom_size > MAX_ROM_SIZE_BYTE { return Err(Error::InvalidRomSizeByte(self.rom_size)); }
+
+        if policy == ChecksumPolicy::Strict && self.get_checksum_compliment().wrapping_add(self.get_checksum()) != 0xFFFF {
             return Err(Error::ChecksumComplimentMismatch);
         }
 
-        let rom_size = 0x400 << self.rom_size as usize;
+        Ok(())
+    }
+    pub fn checked_rom_size(&self) -> Option<usize> {
+        if self.rom_size > MAX_ROM_SIZE_BYTE { return None; }
+
+        0x400usize.checked_shl(self.rom_size as u32)
+    }
+    pub fn declared_rom_size(&self) -> usize {
+        /* 0 for a size byte no board uses; validate_fields turns those away before anything relies on the size */
+        self.checked_rom_size().unwrap_or(0)
+    }
+    pub fn validate(&self, rom: &Rom) -> Result<(), Error> {
+        if let Err(e) = self.validate_fields_with(rom.checksum_policy()) { return Err(e); }
+
+        let rom_size = self.declared_rom_size();
 
         if rom.rom_size() > rom_size {
+            /* say so when the extra data is a mirror or padding, since trim_overdump() can fix that */
+            if let DumpSize::Overdump { junk: true, .. } = rom.dump_size() { return Err(Error::Overdump(rom_size, rom.rom_size())); }
+
             return Err(Error::ROMSizeMismatch(rom_size, rom.rom_size()));
         }
 
@@ -163,115 +646,343 @@ impl SNESHeader {
     }
 }
     
-#[derive(Clone, Eq, PartialEq, Debug)]
+/* some hacks ship with a deliberately wrong checksum; these let header validation look past it */
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ChecksumPolicy {
+    #[default]
+    Strict,
+    WarnOnly,
+    Ignore,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ChecksumFix {
+    pub old: u16,
+    pub new: u16,
+}
+impl ChecksumFix {
+    pub fn changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+fn byte_sum(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |s, &b| s.wrapping_add(b as u16))
+}
+
+fn mirrored_sum(data: &[u8], mask: usize) -> u16 {
+    /* what the console sees of a non-power-of-two rom: the largest power-of-two part once, then the rest mirrored
+       until it fills the same span again, which is how a 3MB board shows its last 1MB twice */
+    if mask == 0 { return byte_sum(data); }
+
+    /* no board mirrors past its largest span, so anything beyond it is added up as it stands */
+    if data.len() > mask { return mirrored_sum(&data[..mask], mask).wrapping_add(byte_sum(&data[mask..])); }
+
+    let mut mask = mask;
+    while mask > 1 && data.len() & mask == 0 { mask >>= 1; }
+
+    let split = mask.min(data.len());
+    let mut sum = byte_sum(&data[..split]);
+    let rest = &data[split..];
+
+    if !rest.is_empty() {
+        let mut part = mirrored_sum(rest, mask >> 1);
+        let mut length = rest.len();
+
+        while length < mask {
+            part = part.wrapping_add(part);
+            length += length;
+        }
+
+        sum = sum.wrapping_add(part);
+    }
+
+    sum
+}
+
+#[derive(Clone, Debug)]
 pub struct Rom {
-    buffer: VecBuffer,
+    buffer: RomBuffer,
+    notation: AddrNotation,
+    bank_policy: BankCrossPolicy,
+    bank_write_mode: BankWriteMode,
+    build_log: Option<BuildLog>,
+    journal: Option<EditJournal>,
+    watches: WatchList,
+    mapper: Option<Mapper>,
+    detected_mapper: OnceLock<Mapper>,
+    path: Option<PathBuf>,
+    checksum_policy: ChecksumPolicy,
+    metadata: RomMetadata,
+}
+/* two ROMs are equal when their images are: where one came from, how it's configured and what it remembers doing don't count */
+impl PartialEq for Rom {
+    fn eq(&self, other: &Self) -> bool {
+        self.buffer == other.buffer
+    }
 }
+impl Eq for Rom {}
 impl Rom {
     pub fn new<B: AsRef<[u8]>>(data: B) -> Self {
-        Self { buffer: VecBuffer::from_data(data) }
+        Self { buffer: RomBuffer::from_data(data), notation: AddrNotation::default(), bank_policy: BankCrossPolicy::default(), bank_write_mode: BankWriteMode::default(), build_log: None, journal: None, watches: WatchList::new(), mapper: None, detected_mapper: OnceLock::new(), path: None, checksum_policy: ChecksumPolicy::default(), metadata: RomMetadata::default() }
     }
     pub fn from_file<P: AsRef<Path>>(filename: P) -> Result<Self, Error> {
-        let buffer = match VecBuffer::from_file(filename) {
-            Ok(b) => b,
-            Err(e) => return Err(Error::PKBufferError(e)),
-        };
+        match RomBuffer::from_file(filename.as_ref()) {
+            Ok(b) => Self::from_buffer(b, filename.as_ref()),
+            Err(e) => Err(e),
+        }
+    }
+    pub(crate) fn from_buffer(buffer: RomBuffer, path: &Path) -> Result<Self, Error> {
+        /* a sidecar next to the ROM carries over whatever was recorded last session; a broken one mustn't keep the ROM
+           from opening, so it's left for load_metadata to report */
+        let mut result = Self { buffer, notation: AddrNotation::default(), bank_policy: BankCrossPolicy::default(), bank_write_mode: BankWriteMode::default(), build_log: None, journal: None, watches: WatchList::new(), mapper: None, detected_mapper: OnceLock::new(), path: Some(path.to_path_buf()), checksum_policy: ChecksumPolicy::default(), metadata: RomMetadata::default() };
+
+        let _ = result.load_metadata();
+        Ok(result)
+    }
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+    pub fn save<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error> {
+        /* written beside the target and renamed over it: truncating in place would empty a file this Rom is still mapped from */
+        let filename = filename.as_ref();
+        let mut temp_name = filename.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".flyhoney-tmp");
+        let temp = filename.with_file_name(temp_name);
+
+        if let Err(e) = std::fs::write(&temp, self.as_slice()) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(Error::IoError(e));
+        }
+
+        match std::fs::rename(&temp, filename) {
+            Ok(()) => Ok(()),
+            Err(e) => { let _ = std::fs::remove_file(&temp); Err(Error::IoError(e)) },
+        }
+    }
+    pub fn save_in_place(&self) -> Result<(), Error> {
+        match &self.path {
+            Some(p) => self.save(p),
+            None => Err(Error::NoSourcePath),
+        }
+    }
+    pub fn save_with_checksum<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Error> {
+        if let Err(e) = self.fix_checksum() { return Err(e); }
+
+        self.save(filename)
+    }
+    pub fn save_in_place_with_checksum(&mut self) -> Result<(), Error> {
+        if let Err(e) = self.fix_checksum() { return Err(e); }
 
-        Ok(Self { buffer })
+        self.save_in_place()
     }
     pub fn len(&self) -> usize {
-        self.buffer.len()
+        self.buffer.view().len()
     }
     pub fn as_ptr(&self) -> *const u8 {
-        self.buffer.as_ptr()
+        self.buffer.view().as_ptr()
     }
+    /* the raw mutable views (as_mut_ptr, as_mut_slice, offset_to_mut_ptr, get_mut_ref, get_mut_slice_ref, read_mut)
+       go around write: nothing changed through them reaches the journal, the build log or the watches, so crate code
+       edits through write/write_ref instead and these are left for callers who want that */
     pub fn as_mut_ptr(&mut self) -> *mut u8 {
-        self.buffer.as_mut_ptr()
+        self.forget_mapper(0..usize::MAX);
+        self.buffer.owned().as_mut_ptr()
     }
     pub fn as_slice(&self) -> &[u8] {
-        self.buffer.as_slice()
+        self.buffer.view()
     }
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
-        self.buffer.as_mut_slice()
+        self.forget_mapper(0..usize::MAX);
+        self.buffer.owned()
     }
     pub fn offset_to_ptr(&self, offset: usize) -> Result<*const u8, Error> {
-        match self.buffer.offset_to_ptr(offset) {
-            Ok(p) => Ok(p),
-            Err(e) => Err(Error::PKBufferError(e)),
+        match self.buffer.range(offset, 0) {
+            Ok(r) => Ok(self.buffer.view()[r.start..].as_ptr()),
+            Err(e) => Err(e),
         }
     }
     pub fn offset_to_mut_ptr(&mut self, offset: usize) -> Result<*mut u8, Error> {
-        match self.buffer.offset_to_mut_ptr(offset) {
-            Ok(p) => Ok(p),
-            Err(e) => Err(Error::PKBufferError(e)),
+        self.forget_mapper(offset..usize::MAX);
+
+        match self.buffer.range(offset, 0) {
+            Ok(r) => Ok(self.buffer.owned()[r.start..].as_mut_ptr()),
+            Err(e) => Err(e),
         }
     }
     pub fn get_ref<T>(&self, offset: usize) -> Result<&T, Error> {
-        match self.buffer.get_ref::<T>(offset) {
-            Ok(r) => Ok(r),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        self.buffer.get_ref::<T>(offset)
     }
     pub fn get_mut_ref<T>(&mut self, offset: usize) -> Result<&mut T, Error> {
-        match self.buffer.get_mut_ref::<T>(offset) {
-            Ok(r) => Ok(r),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        self.forget_mapper(offset..offset.saturating_add(std::mem::size_of::<T>()));
+        self.buffer.get_mut_ref::<T>(offset)
     }
     pub fn get_slice_ref<T>(&self, offset: usize, size: usize) -> Result<&[T], Error> {
-        match self.buffer.get_slice_ref::<T>(offset, size) {
-            Ok(r) => Ok(r),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        self.buffer.get_slice_ref::<T>(offset, size)
     }
     pub fn get_mut_slice_ref<T>(&mut self, offset: usize, size: usize) -> Result<&mut [T], Error> {
-        match self.buffer.get_mut_slice_ref::<T>(offset, size) {
-            Ok(r) => Ok(r),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        self.forget_mapper(offset..offset.saturating_add(std::mem::size_of::<T>().saturating_mul(size)));
+        self.buffer.get_mut_slice_ref::<T>(offset, size)
     }
     pub fn read(&self, offset: usize, size: usize) -> Result<&[u8], Error> {
-        match self.buffer.read(offset, size) {
-            Ok(d) => Ok(d),
-            Err(e) => Err(Error::PKBufferError(e)),
+        self.buffer.get_slice_ref::<u8>(offset, size)
+    }
+    fn read_uint(&self, offset: usize, size: usize, big_endian: bool) -> Result<u32, Error> {
+        /* get_ref::<u16> reads in host order; these always spell the byte order out */
+        let data = match self.read(offset, size) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+
+        if big_endian { Ok(data.iter().fold(0u32, |v, &b| (v << 8) | b as u32)) }
+        else { Ok(data.iter().rev().fold(0u32, |v, &b| (v << 8) | b as u32)) }
+    }
+    fn write_uint(&mut self, offset: usize, value: u32, size: usize, big_endian: bool) -> Result<(), Error> {
+        let le = value.to_le_bytes();
+        let mut data = le[..size].to_vec();
+
+        if big_endian { data.reverse(); }
+
+        self.write(offset, data)
+    }
+    pub fn read_u8(&self, offset: usize) -> Result<u8, Error> {
+        self.read_uint(offset, 1, false).map(|v| v as u8)
+    }
+    pub fn read_u16_le(&self, offset: usize) -> Result<u16, Error> {
+        self.read_uint(offset, 2, false).map(|v| v as u16)
+    }
+    pub fn read_u24_le(&self, offset: usize) -> Result<u32, Error> {
+        self.read_uint(offset, 3, false)
+    }
+    pub fn read_u32_le(&self, offset: usize) -> Result<u32, Error> {
+        self.read_uint(offset, 4, false)
+    }
+    pub fn read_u16_be(&self, offset: usize) -> Result<u16, Error> {
+        self.read_uint(offset, 2, true).map(|v| v as u16)
+    }
+    pub fn read_u24_be(&self, offset: usize) -> Result<u32, Error> {
+        self.read_uint(offset, 3, true)
+    }
+    pub fn read_u32_be(&self, offset: usize) -> Result<u32, Error> {
+        self.read_uint(offset, 4, true)
+    }
+    pub fn read_u16(&self, offset: usize) -> Result<u16, Error> {
+        /* the 65816 is little-endian, so the unsuffixed names are the _le ones */
+        self.read_u16_le(offset)
+    }
+    pub fn read_u24(&self, offset: usize) -> Result<u32, Error> {
+        self.read_u24_le(offset)
+    }
+    pub fn read_u32(&self, offset: usize) -> Result<u32, Error> {
+        self.read_u32_le(offset)
+    }
+    pub fn read_addr24(&self, offset: usize) -> Result<Addr24, Error> {
+        match self.read_u24_le(offset) {
+            Ok(v) => Ok(Addr24::from_u32(v)),
+            Err(e) => Err(e),
         }
     }
+    pub fn write_u8(&mut self, offset: usize, value: u8) -> Result<(), Error> {
+        self.write(offset, [value])
+    }
+    pub fn write_u16_le(&mut self, offset: usize, value: u16) -> Result<(), Error> {
+        self.write_uint(offset, value as u32, 2, false)
+    }
+    pub fn write_u24_le(&mut self, offset: usize, value: u32) -> Result<(), Error> {
+        self.write_uint(offset, value, 3, false)
+    }
+    pub fn write_u32_le(&mut self, offset: usize, value: u32) -> Result<(), Error> {
+        self.write_uint(offset, value, 4, false)
+    }
+    pub fn write_u16_be(&mut self, offset: usize, value: u16) -> Result<(), Error> {
+        self.write_uint(offset, value as u32, 2, true)
+    }
+    pub fn write_u24_be(&mut self, offset: usize, value: u32) -> Result<(), Error> {
+        self.write_uint(offset, value, 3, true)
+    }
+    pub fn write_u32_be(&mut self, offset: usize, value: u32) -> Result<(), Error> {
+        self.write_uint(offset, value, 4, true)
+    }
+    pub fn write_u16(&mut self, offset: usize, value: u16) -> Result<(), Error> {
+        self.write_u16_le(offset, value)
+    }
+    pub fn write_u24(&mut self, offset: usize, value: u32) -> Result<(), Error> {
+        self.write_u24_le(offset, value)
+    }
+    pub fn write_u32(&mut self, offset: usize, value: u32) -> Result<(), Error> {
+        self.write_u32_le(offset, value)
+    }
+    pub fn write_addr24(&mut self, offset: usize, address: Addr24) -> Result<(), Error> {
+        self.write_u24_le(offset, address.as_u32())
+    }
     pub fn read_mut(&mut self, offset: usize, size: usize) -> Result<&mut [u8], Error> {
-        match self.buffer.read_mut(offset, size) {
-            Ok(d) => Ok(d),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        self.forget_mapper(offset..offset.saturating_add(size));
+        self.buffer.get_mut_slice_ref::<u8>(offset, size)
     }
     pub fn write<B: AsRef<[u8]>>(&mut self, offset: usize, data: B) -> Result<(), Error> {
-        match self.buffer.write(offset, data) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        let data = data.as_ref();
+        let old = if self.journal.is_some() { self.as_slice().get(offset..offset + data.len()).map(|d| d.to_vec()) } else { None };
+
+        self.forget_mapper(offset..offset.saturating_add(data.len()));
+        if let Err(e) = self.buffer.write(offset, data) { return Err(e); }
+        if self.build_log.is_some() { self.record_operation("write", offset, data); }
+
+        if let Some(old) = old { self.record_edit("write", offset, old, self.len()); }
+        self.notify_change("write", offset..offset + data.len());
+
+        Ok(())
     }
     pub fn write_ref<T>(&mut self, offset: usize, data: &T) -> Result<(), Error> {
-        match self.buffer.write_ref::<T>(offset, data) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(Error::PKBufferError(e)),
+        let old = if self.journal.is_some() { self.as_slice().get(offset..offset + std::mem::size_of::<T>()).map(|d| d.to_vec()) } else { None };
+
+        self.forget_mapper(offset..offset.saturating_add(std::mem::size_of::<T>()));
+        if let Err(e) = self.buffer.write(offset, pkbuffer::ref_to_bytes::<T>(data)) { return Err(e); }
+        if let Some(old) = old { self.record_edit("write_ref", offset, old, self.len()); }
+        self.notify_change("write_ref", offset..offset + std::mem::size_of::<T>());
+
+        if self.build_log.is_some() {
+            let written = self.as_slice()[offset..offset + std::mem::size_of::<T>()].to_vec();
+            self.record_operation("write_ref", offset, &written);
         }
+
+        Ok(())
     }
     pub fn write_slice_ref<T>(&mut self, offset: usize, data: &[T]) -> Result<(), Error> {
-        match self.buffer.write_slice_ref::<T>(offset, data) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(Error::PKBufferError(e)),
+        let old = if self.journal.is_some() { self.as_slice().get(offset..offset + std::mem::size_of_val(data)).map(|d| d.to_vec()) } else { None };
+
+        self.forget_mapper(offset..offset.saturating_add(std::mem::size_of_val(data)));
+        if let Err(e) = self.buffer.write(offset, pkbuffer::slice_ref_to_bytes::<T>(data)) { return Err(e); }
+        if let Some(old) = old { self.record_edit("write_slice_ref", offset, old, self.len()); }
+        self.notify_change("write_slice_ref", offset..offset + std::mem::size_of_val(data));
+
+        if self.build_log.is_some() {
+            let written = self.as_slice()[offset..offset + std::mem::size_of_val(data)].to_vec();
+            self.record_operation("write_slice_ref", offset, &written);
         }
+
+        Ok(())
     }
     pub fn resize(&mut self, size: usize) {
-        self.buffer.resize(size, 0);
+        let old_len = self.len();
+        let old = if self.journal.is_some() && size < old_len { self.as_slice()[size..].to_vec() } else { Vec::new() };
+
+        if self.build_log.is_some() { self.record_operation("resize", size, &[]); }
+
+        /* the copier header and the ExHiROM check both go by length, so any resize can change the answer */
+        self.forget_mapper(0..usize::MAX);
+        self.buffer.owned().resize(size, 0);
+        self.record_edit("resize", size.min(old_len), old, old_len);
+        self.notify_change("resize", size.min(old_len)..size.max(old_len));
     }
     pub fn resize_blocks(&mut self, blocks: usize) {
         self.resize(blocks * 0x10000);
     }
  
     pub fn header_size(&self) -> usize {
-        self.buffer.len() % 1024
+        self.buffer.view().len() % 1024
     }
     pub fn rom_size(&self) -> usize {
-        self.buffer.len() - self.header_size()
+        self.buffer.view().len() - self.header_size()
     }
     
     pub fn header(&self) -> Result<Buffer, Error> {
@@ -279,41 +990,68 @@ impl Rom {
             return Err(Error::NoHeader);
         }
 
-        match self.buffer.sub_buffer(0, self.header_size()) {
-            Ok(b) => Ok(b),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        Ok(Buffer::from_ref(&self.as_slice()[..self.header_size()]))
     }
     pub fn banks(&self) -> usize {
         self.rom_size() / 0x10000
     }
-    pub fn get_bank(&self, bank: u8) -> Result<Buffer, Error> {
-        let offset = Addr24::new(bank, 0).to_offset(self);
+    pub fn bank_range(&self, bank: u8) -> Result<std::ops::Range<usize>, Error> {
+        /* a LoROM bank is only the 32KB visible at $8000-$FFFF; the rest go through the memory map so ExHiROM's $40-$7D banks land in the upper half of the image */
+        let (address, size) = match self.memory_map() {
+            Ok(Mapper::LoRom(_)) | Ok(Mapper::SuperFx(_)) | Ok(Mapper::Sdd1(_)) => (Addr24::new(bank, 0x8000), 0x8000),
+            Ok(_) => (Addr24::new(bank, 0), 0x10000),
+            Err(e) => return Err(e),
+        };
 
-        match self.buffer.sub_buffer(offset, 0x10000) {
-            Ok(b) => Ok(b),
-            Err(e) => Err(Error::PKBufferError(e)),
+        /* HiROM's $00-$3F banks only show ROM from $8000 up; the number still names the 64KB block that half belongs to */
+        match address.to_mapped_offset(self) {
+            Ok(offset) => Ok(offset..offset + size),
+            Err(e) if size == 0x10000 => match Addr24::new(bank, 0x8000).to_mapped_offset(self) {
+                Ok(offset) => Ok(offset - 0x8000..offset + 0x8000),
+                Err(_) => Err(e),
+            },
+            Err(e) => Err(e),
         }
     }
-    pub fn checksum(&self) -> u16 {
-        /* this is technically incomplete, I just don't know how to handle some cases yet */
-        /* TODO look up how bsnes does it, snes9x is weird */
-        
-        let mut checksum = 0u16;
+    pub fn get_bank(&self, bank: u8) -> Result<Buffer, Error> {
+        /* the last bank of an odd-sized image is whatever is left of it */
+        let range = match self.bank_range(bank) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+        if range.start >= self.len() { return Err(Error::OutOfBounds(range.start,self.len())); }
 
-        for byte in &self.buffer {
-            checksum = checksum.wrapping_add(*byte as u16);
+        Ok(Buffer::from_ref(&self.as_slice()[range.start..range.end.min(self.len())]))
+    }
+    pub fn bank_size(&self) -> usize {
+        match self.memory_map() {
+            Ok(Mapper::LoRom(_)) | Ok(Mapper::SuperFx(_)) | Ok(Mapper::Sdd1(_)) => 0x8000,
+            _ => 0x10000,
         }
+    }
+    pub fn iter_banks(&self) -> impl Iterator<Item = (u8, Buffer)> + '_ {
+        /* in image order, each numbered the way the mapper first exposes it ($C0 up for HiROM, $80 up for FastROM LoROM) */
+        let bank_size = self.bank_size();
+        let map = self.memory_map();
 
-        if self.rom_size() == 0x300000 { checksum = checksum.wrapping_add(checksum); }
+        (0..self.rom_size().div_ceil(bank_size)).filter_map(move |index| {
+            let bank = match map.as_ref().map(|m| m.pc_to_address(index * bank_size)) {
+                Ok(Ok(a)) => a.bank,
+                _ => index as u8,
+            };
 
-        checksum
+            self.get_bank(bank).ok().map(|b| (bank, b))
+        })
+    }
+    pub fn checksum(&self) -> u16 {
+        let data = &self.as_slice()[self.header_size()..];
+
+        metrics::record(MetricCounter::BytesChecksummed, data.len());
+
+        mirrored_sum(data, 0x800000)
     }
     pub fn get_snes_header(&self, address: Addr24) -> Result<&SNESHeader, Error> {
-        match self.buffer.get_ref::<SNESHeader>(address.to_offset(self)) {
-            Ok(h) => Ok(h),
-            Err(e) => Err(Error::PKBufferError(e)),
-        }
+        self.get_ref::<SNESHeader>(address.to_offset(self))
     }
     pub fn get_valid_snes_header(&self, address: Addr24) -> Result<&SNESHeader, Error> {
         let header = match self.get_snes_header(address) {
@@ -338,15 +1076,149 @@ impl Rom {
     pub fn get_valid_hirom_snes_header(&self) -> Result<&SNESHeader, Error> {
         self.get_valid_snes_header(Addr24::new(0, 0xffc0))
     }
-    pub fn find_valid_snes_header(&self) -> Result<&SNESHeader, Error> {
-        let lo_result = self.get_valid_lorom_snes_header();
+    pub fn get_exhirom_snes_header(&self) -> Result<&SNESHeader, Error> {
+        self.get_snes_header(Addr24::new(0x40, 0xffc0))
+    }
+    pub fn get_valid_exhirom_snes_header(&self) -> Result<&SNESHeader, Error> {
+        self.get_valid_snes_header(Addr24::new(0x40, 0xffc0))
+    }
+    pub fn find_valid_snes_header_address(&self) -> Result<Addr24, Error> {
+        /* ExHiROM boards show the upper 4MB in bank $00, so the header the console reads sits at 0x40FFC0 */
+        let ex_address = Addr24::new(0x40, 0xffc0);
+
+        if self.rom_size() > 0x400000 && self.get_valid_snes_header(ex_address).is_ok() { return Ok(ex_address); }
+
+        let lo_address = Addr24::new(0, 0x7fc0);
+        let lo_result = self.get_valid_snes_header(lo_address);
+
+        if lo_result.is_ok() { return Ok(lo_address); }
+
+        let hi_address = Addr24::new(0, 0xffc0);
+        let hi_result = self.get_valid_snes_header(hi_address);
+
+        if hi_result.is_ok() { return Ok(hi_address); }
+
+        Err(lo_result.unwrap_err())
+    }
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        /* a header that only passed under the looser policy no longer picks the map */
+        self.forget_mapper(0..usize::MAX);
+        self.checksum_policy = policy;
+    }
+    pub fn checksum_warnings(&self) -> Vec<Error> {
+        /* what Strict would have refused, for callers running WarnOnly to report */
+        if self.checksum_policy == ChecksumPolicy::Ignore { return Vec::new(); }
+
+        let header = match self.find_valid_snes_header() {
+            Ok(h) => h,
+            Err(_) => return Vec::new(),
+        };
+        let mut result = Vec::<Error>::new();
+
+        if header.get_checksum_compliment().wrapping_add(header.get_checksum()) != 0xFFFF { result.push(Error::ChecksumComplimentMismatch); }
+        if header.get_checksum() != self.checksum() { result.push(Error::ChecksumMismatch(header.get_checksum(), self.checksum())); }
+
+        result
+    }
+    pub fn fix_checksum(&mut self) -> Result<ChecksumFix, Error> {
+        /* update_header already finds whichever of the LoROM/HiROM/ExHiROM headers is live and rewrites both words */
+        let old = match self.find_valid_snes_header() {
+            Ok(h) => h.get_checksum(),
+            Err(e) => return Err(e),
+        };
+
+        if let Err(e) = self.update_header(|_| {}) { return Err(e); }
+
+        match self.find_valid_snes_header() {
+            Ok(h) => Ok(ChecksumFix { old, new: h.get_checksum() }),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn update_header<F: FnOnce(&mut SNESHeader)>(&mut self, f: F) -> Result<(), Error> {
+        self.update_header_with(f, true)
+    }
+    pub fn update_header_unchecked<F: FnOnce(&mut SNESHeader)>(&mut self, f: F) -> Result<(), Error> {
+        self.update_header_with(f, false)
+    }
+    fn update_header_with<F: FnOnce(&mut SNESHeader)>(&mut self, f: F, refresh_checksum: bool) -> Result<(), Error> {
+        let offset = match self.find_valid_snes_header_address() {
+            Ok(a) => a.to_offset(self),
+            Err(e) => return Err(e),
+        };
+        let old = if self.journal.is_some() { self.as_slice().get(offset..offset + std::mem::size_of::<SNESHeader>()).map(|d| d.to_vec()) } else { None };
+
+        let mut header = match self.get_ref::<SNESHeader>(offset) {
+            Ok(h) => *h,
+            Err(e) => return Err(e),
+        };
+
+        f(&mut header);
+        self.forget_mapper(offset..offset + std::mem::size_of::<SNESHeader>());
+
+        if refresh_checksum {
+            /* the checksum covers the header itself, so sum with a neutral checksum/compliment pair in place */
+            header.set_checksum(0);
+            if let Err(e) = self.buffer.write(offset, pkbuffer::ref_to_bytes(&header)) { return Err(e); }
+
+            header.set_checksum(self.checksum());
+        }
+
+        if let Err(e) = self.buffer.write(offset, pkbuffer::ref_to_bytes(&header)) { return Err(e); }
 
-        if lo_result.is_ok() { return lo_result; }
+        if self.build_log.is_some() {
+            let header = self.as_slice()[offset..offset + std::mem::size_of::<SNESHeader>()].to_vec();
+            self.record_operation("update_header", offset, &header);
+        }
+        if let Some(old) = old { self.record_edit("update_header", offset, old, self.len()); }
+        self.notify_change("update_header", offset..offset + std::mem::size_of::<SNESHeader>());
 
-        let hi_result = self.get_valid_hirom_snes_header();
+        Ok(())
+    }
+    pub fn get_vector(&self, vector: InterruptVector) -> Result<u16, Error> {
+        match self.find_valid_snes_header() {
+            Ok(h) => Ok(h.get_vector(vector)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn validate_vector_target(&self, target: u16) -> Result<(), Error> {
+        /* the CPU fetches vectors in bank 0, where both LoROM and HiROM only show ROM from $8000 up */
+        let header = match self.find_valid_snes_header_address() {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+        let pc = if header.address < 0x8000 { (target & 0x7FFF) as usize } else { target as usize };
 
-        if hi_result.is_ok() { return hi_result; }
+        if target < 0x8000 || pc >= self.rom_size() { return Err(Error::InvalidROMAddress(Addr24::new(0, target))); }
+
+        Ok(())
+    }
+    pub fn set_vector(&mut self, vector: InterruptVector, target: u16) -> Result<(), Error> {
+        if let Err(e) = self.validate_vector_target(target) { return Err(e); }
 
-        lo_result
+        self.update_header(|h| h.set_vector(vector, target))
+    }
+    pub fn set_reset_vector(&mut self, target: u16) -> Result<(), Error> {
+        self.set_vector(InterruptVector::Reset, target)
+    }
+    pub fn set_nmi_vector(&mut self, target: u16) -> Result<(), Error> {
+        self.set_vector(InterruptVector::NativeNmi, target)
+    }
+    pub fn set_irq_vector(&mut self, target: u16) -> Result<(), Error> {
+        self.set_vector(InterruptVector::NativeIrq, target)
+    }
+    pub fn set_brk_vector(&mut self, target: u16) -> Result<(), Error> {
+        self.set_vector(InterruptVector::NativeBrk, target)
+    }
+    pub fn set_cop_vector(&mut self, target: u16) -> Result<(), Error> {
+        self.set_vector(InterruptVector::NativeCop, target)
+    }
+    pub fn find_valid_snes_header(&self) -> Result<&SNESHeader, Error> {
+        match self.find_valid_snes_header_address() {
+            Ok(a) => self.get_snes_header(a),
+            Err(e) => Err(e),
+        }
     }
 }
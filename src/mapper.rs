@@ -0,0 +1,350 @@
+use crate::{Addr24, AddrNotation, Error, Rom, SNESHeader};
+use std::sync::OnceLock;
+
+pub trait MemoryMap {
+    fn name(&self) -> &'static str;
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error>;
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error>;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct LoRom;
+impl MemoryMap for LoRom {
+    fn name(&self) -> &'static str {
+        "lorom"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        AddrNotation::LoRom.address_to_pc(address)
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        AddrNotation::LoRom.pc_to_address(pc)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct HiRom;
+impl MemoryMap for HiRom {
+    fn name(&self) -> &'static str {
+        "hirom"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        AddrNotation::HiRom.address_to_pc(address)
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        AddrNotation::HiRom.pc_to_address(pc)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct ExHiRom;
+impl MemoryMap for ExHiRom {
+    fn name(&self) -> &'static str {
+        "exhirom"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        /* the first 4MB sits in $C0-$FF (mirrored to $80-$BF), the rest in $40-$7D (mirrored to $00-$3F) */
+        let (bank, addr) = (address.bank, address.address as usize);
+
+        if bank == 0x7E || bank == 0x7F { return Err(Error::InvalidROMAddress(address)); }
+        if bank & 0x7F < 0x40 && addr < 0x8000 { return Err(Error::InvalidROMAddress(address)); }
+
+        let upper = if bank & 0x80 != 0 { 0 } else { 0x400000 };
+
+        Ok(upper + (bank & 0x3F) as usize * 0x10000 + addr)
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        if pc >= 0x800000 { return Err(Error::OutOfBounds(pc,0x800000)); }
+        if pc < 0x400000 { return Ok(Addr24::new(0xC0 | (pc >> 16) as u8, (pc & 0xFFFF) as u16)); }
+
+        let bank = ((pc - 0x400000) >> 16) as u8;
+        let addr = (pc & 0xFFFF) as u16;
+
+        /* $7E/$7F are WRAM, so the last two banks are only reachable through the upper halves of $3E/$3F */
+        if bank < 0x3E { Ok(Addr24::new(0x40 | bank, addr)) }
+        else if addr >= 0x8000 { Ok(Addr24::new(bank, addr)) }
+        else { Err(Error::OutOfBounds(pc,0x7E0000)) }
+    }
+}
+
+fn switched_address(banks: &[u8], pc: usize, first_bank: u8) -> Option<Addr24> {
+    /* only blocks currently switched into a window have an address; each register owns 16 banks from first_bank up */
+    (0..banks.len()).find(|&r| (banks[r] & 0x07) as usize == pc >> 20)
+        .map(|r| Addr24::new(first_bank + (r << 4) as u8 + ((pc >> 16) & 0x0F) as u8, (pc & 0xFFFF) as u16))
+}
+
+/* the SA-1 MMC maps four switchable 1MB blocks, picked by CXB/DXB/EXB/FXB ($2220-$2223) */
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Sa1 {
+    pub banks: [u8; 4],
+}
+impl Sa1 {
+    pub fn new(banks: [u8; 4]) -> Self {
+        Self { banks }
+    }
+    fn block(&self, register: usize) -> usize {
+        (self.banks[register] & 0x07) as usize
+    }
+}
+impl Default for Sa1 {
+    fn default() -> Self {
+        /* the power-on values give a plain 4MB image */
+        Self { banks: [0, 1, 2, 3] }
+    }
+}
+impl MemoryMap for Sa1 {
+    fn name(&self) -> &'static str {
+        "sa1"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        let (bank, addr) = (address.bank, address.address as usize);
+
+        match bank {
+            /* the LoROM-style windows follow their register only while its bit 7 is set */
+            0x00..=0x3F | 0x80..=0xBF if addr >= 0x8000 => {
+                let register = ((bank >> 5) & 0x01 | (bank >> 6) & 0x02) as usize;
+                let block = if self.banks[register] & 0x80 != 0 { self.block(register) } else { register };
+
+                Ok(block * 0x100000 + (bank & 0x1F) as usize * 0x8000 + (addr - 0x8000))
+            },
+            0xC0..=0xFF => Ok(self.block(((bank >> 4) & 0x03) as usize) * 0x100000 + (bank & 0x0F) as usize * 0x10000 + addr),
+            _ => Err(Error::InvalidROMAddress(address)),
+        }
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        if pc >= 0x800000 { return Err(Error::OutOfBounds(pc,0x800000)); }
+
+        match switched_address(&self.banks, pc, 0xC0) {
+            Some(a) => Ok(a),
+            None => Err(Error::OutOfBounds(pc,0x400000)),
+        }
+    }
+}
+
+/* S-DD1 keeps LoROM program banks but switches the whole $C0-$FF window through $4804-$4807 */
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Sdd1 {
+    pub banks: [u8; 4],
+}
+impl Sdd1 {
+    pub fn new(banks: [u8; 4]) -> Self {
+        Self { banks }
+    }
+}
+impl Default for Sdd1 {
+    fn default() -> Self {
+        Self { banks: [0, 1, 2, 3] }
+    }
+}
+impl MemoryMap for Sdd1 {
+    fn name(&self) -> &'static str {
+        "sdd1"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        let (bank, addr) = (address.bank, address.address as usize);
+
+        match bank {
+            0x00..=0x3F | 0x80..=0xBF if addr >= 0x8000 => Ok((bank & 0x3F) as usize * 0x8000 + (addr - 0x8000)),
+            0xC0..=0xFF => Ok((self.banks[((bank >> 4) & 0x03) as usize] & 0x07) as usize * 0x100000 + (bank & 0x0F) as usize * 0x10000 + addr),
+            _ => Err(Error::InvalidROMAddress(address)),
+        }
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        if pc >= 0x800000 { return Err(Error::OutOfBounds(pc,0x800000)); }
+
+        match switched_address(&self.banks, pc, 0xC0) {
+            Some(a) => Ok(a),
+            None => Err(Error::OutOfBounds(pc,0x400000)),
+        }
+    }
+}
+
+/* SPC7110 carts hold a fixed 1MB program rom followed by the data rom, which $D0-$FF see through $4831-$4833 */
+pub const SPC7110_PROGRAM_SIZE: usize = 0x100000;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Spc7110 {
+    pub banks: [u8; 3],
+}
+impl Spc7110 {
+    pub fn new(banks: [u8; 3]) -> Self {
+        Self { banks }
+    }
+}
+impl Default for Spc7110 {
+    fn default() -> Self {
+        Self { banks: [0, 1, 2] }
+    }
+}
+impl MemoryMap for Spc7110 {
+    fn name(&self) -> &'static str {
+        "spc7110"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        let (bank, addr) = (address.bank, address.address as usize);
+
+        match bank {
+            0x00..=0x0F | 0x80..=0x8F if addr >= 0x8000 => Ok((bank & 0x0F) as usize * 0x10000 + addr),
+            0xC0..=0xCF => Ok((bank & 0x0F) as usize * 0x10000 + addr),
+            0xD0..=0xFF => {
+                let block = (self.banks[((bank - 0xD0) >> 4) as usize] & 0x07) as usize;
+                Ok(SPC7110_PROGRAM_SIZE + block * 0x100000 + (bank & 0x0F) as usize * 0x10000 + addr)
+            },
+            _ => Err(Error::InvalidROMAddress(address)),
+        }
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        if pc < SPC7110_PROGRAM_SIZE { return Ok(Addr24::new(0xC0 | (pc >> 16) as u8, (pc & 0xFFFF) as u16)); }
+        if pc >= SPC7110_PROGRAM_SIZE + 0x800000 { return Err(Error::OutOfBounds(pc,SPC7110_PROGRAM_SIZE + 0x800000)); }
+
+        match switched_address(&self.banks, pc - SPC7110_PROGRAM_SIZE, 0xD0) {
+            Some(a) => Ok(a),
+            None => Err(Error::OutOfBounds(pc,SPC7110_PROGRAM_SIZE + 0x300000)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SuperFx;
+impl MemoryMap for SuperFx {
+    fn name(&self) -> &'static str {
+        "superfx"
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        /* the CPU sees LoROM-style program banks at $00-$3F and the same 2MB linearly at $40-$5F; $70-$71 is GSU RAM */
+        let (bank, addr) = (address.bank & 0x7F, address.address as usize);
+
+        match bank {
+            0x00..=0x3F if addr >= 0x8000 => Ok(bank as usize * 0x8000 + (addr - 0x8000)),
+            0x40..=0x5F => Ok((bank - 0x40) as usize * 0x10000 + addr),
+            _ => Err(Error::InvalidROMAddress(address)),
+        }
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        if pc >= 0x200000 { return Err(Error::OutOfBounds(pc,0x200000)); }
+
+        Ok(Addr24::new((pc / 0x8000) as u8, 0x8000 | (pc % 0x8000) as u16))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Mapper {
+    LoRom(LoRom),
+    HiRom(HiRom),
+    ExHiRom(ExHiRom),
+    Sa1(Sa1),
+    SuperFx(SuperFx),
+    Sdd1(Sdd1),
+    Spc7110(Spc7110),
+}
+impl Mapper {
+    pub fn from_map_mode(map_mode: u8) -> Option<Self> {
+        /* bit 4 is the FastROM flag, the low nibble picks the board layout */
+        match map_mode & 0xEF {
+            0x20 => Some(Mapper::LoRom(LoRom)),
+            0x21 => Some(Mapper::HiRom(HiRom)),
+            0x23 => Some(Mapper::Sa1(Sa1::default())),
+            0x25 => Some(Mapper::ExHiRom(ExHiRom)),
+            0x2A => Some(Mapper::Spc7110(Spc7110::default())),
+            _ => None,
+        }
+    }
+    pub fn detect(rom: &Rom) -> Result<Self, Error> {
+        let address = match rom.find_valid_snes_header_address() {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+        let (map_mode, rom_type) = match rom.get_snes_header(address) {
+            Ok(h) => (h.get_mapping_mode(), h.get_rom_type()),
+            Err(e) => return Err(e),
+        };
+
+        /* coprocessor carts declare a plain map mode; only the chipset byte gives away GSU ($13-$15, $1A), S-DD1 ($43, $45) and SPC7110 ($F5, $F9) */
+        if address.address == 0x7FC0 && matches!(rom_type, 0x13..=0x15 | 0x1A) { return Ok(Mapper::SuperFx(SuperFx)); }
+        if address.address == 0x7FC0 && matches!(rom_type, 0x43 | 0x45) { return Ok(Mapper::Sdd1(Sdd1::default())); }
+        if matches!(rom_type, 0xF5 | 0xF9) { return Ok(Mapper::Spc7110(Spc7110::default())); }
+
+        /* the header's location is the stronger evidence, since plenty of carts misreport their map mode */
+        match (address.bank, address.address, Self::from_map_mode(map_mode)) {
+            (0x40, _, _) => Ok(Mapper::ExHiRom(ExHiRom)),
+            (_, 0xFFC0, Some(Mapper::ExHiRom(m))) => Ok(Mapper::ExHiRom(m)),
+            (_, 0xFFC0, _) if rom.rom_size() > 0x400000 => Ok(Mapper::ExHiRom(ExHiRom)),
+            (_, 0xFFC0, _) => Ok(Mapper::HiRom(HiRom)),
+            (_, _, Some(Mapper::Sa1(m))) => Ok(Mapper::Sa1(m)),
+            _ => Ok(Mapper::LoRom(LoRom)),
+        }
+    }
+    pub fn max_rom_size(&self) -> usize {
+        /* the largest image each board's address decoding can reach; the bank-switched chips see more than they map at once */
+        match self {
+            Mapper::LoRom(_) | Mapper::HiRom(_) => 0x400000,
+            Mapper::SuperFx(_) => 0x200000,
+            Mapper::ExHiRom(_) | Mapper::Sa1(_) | Mapper::Sdd1(_) | Mapper::Spc7110(_) => 0x800000,
+        }
+    }
+    fn map(&self) -> &dyn MemoryMap {
+        match self {
+            Mapper::LoRom(m) => m,
+            Mapper::HiRom(m) => m,
+            Mapper::ExHiRom(m) => m,
+            Mapper::Sa1(m) => m,
+            Mapper::SuperFx(m) => m,
+            Mapper::Sdd1(m) => m,
+            Mapper::Spc7110(m) => m,
+        }
+    }
+}
+impl MemoryMap for Mapper {
+    fn name(&self) -> &'static str {
+        self.map().name()
+    }
+    fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        self.map().address_to_pc(address)
+    }
+    fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        self.map().pc_to_address(pc)
+    }
+}
+
+impl Rom {
+    pub fn memory_map(&self) -> Result<Mapper, Error> {
+        /* an explicitly chosen map wins; otherwise the header's answer is kept until something writes over a header */
+        if let Some(m) = self.mapper { return Ok(m); }
+        if let Some(m) = self.detected_mapper.get() { return Ok(*m); }
+
+        match Mapper::detect(self) {
+            Ok(m) => { let _ = self.detected_mapper.set(m); Ok(m) },
+            Err(e) => Err(e),
+        }
+    }
+    pub fn set_memory_map(&mut self, mapper: Option<Mapper>) {
+        self.mapper = mapper;
+    }
+    pub(crate) fn forget_mapper(&mut self, range: std::ops::Range<usize>) {
+        /* detection reads nothing but the three places a header can sit */
+        let header_size = self.header_size();
+        let size = std::mem::size_of::<SNESHeader>();
+
+        if [0x7FC0usize, 0xFFC0, 0x40FFC0].iter().any(|&o| range.start < header_size + o + size && header_size + o < range.end) {
+            self.detected_mapper = OnceLock::new();
+        }
+    }
+    pub fn address_to_offset(&self, address: Addr24) -> Result<usize, Error> {
+        let pc = match self.memory_map().and_then(|m| m.address_to_pc(address)) {
+            Ok(p) => p,
+            Err(e) => return Err(e),
+        };
+        let offset = pc + self.header_size();
+
+        if offset >= self.len() { return Err(Error::OutOfBounds(offset,self.len())); }
+
+        Ok(offset)
+    }
+    pub fn offset_to_address(&self, offset: usize) -> Result<Addr24, Error> {
+        if offset < self.header_size() || offset >= self.len() { return Err(Error::OutOfBounds(offset,self.len())); }
+
+        match self.memory_map() {
+            Ok(m) => m.pc_to_address(offset - self.header_size()),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{Error, Rom, Symbol, SymbolSpace, SymbolTable, UsageLog, USAGE_EXEC, USAGE_FLAG_M, USAGE_FLAG_X, USAGE_OPCODE, USAGE_READ};
+use std::path::Path;
+
+pub const MESEN_CDL_MAGIC: [u8; 5] = *b"CDLv2";
+
+/* Mesen-S code/data logger flags */
+pub const CDL_CODE: u8 = 0x01;
+pub const CDL_DATA: u8 = 0x02;
+pub const CDL_JUMP_TARGET: u8 = 0x04;
+pub const CDL_SUB_ENTRY: u8 = 0x08;
+pub const CDL_INDEX_8: u8 = 0x10;
+pub const CDL_MEMORY_8: u8 = 0x20;
+
+fn mesen_space(name: &str) -> Option<SymbolSpace> {
+    match name {
+        "PRG" => Some(SymbolSpace::Rom),
+        "WORK" => Some(SymbolSpace::WorkRam),
+        "SAVE" => Some(SymbolSpace::SaveRam),
+        "REG" => Some(SymbolSpace::Register),
+        _ => None,
+    }
+}
+fn mesen_type(space: SymbolSpace) -> &'static str {
+    match space {
+        SymbolSpace::Rom => "PRG",
+        SymbolSpace::WorkRam => "WORK",
+        SymbolSpace::SaveRam => "SAVE",
+        SymbolSpace::Register => "REG",
+    }
+}
+
+impl SymbolTable {
+    pub fn from_mesen_labels(text: &str) -> Result<Self, Error> {
+        /* .mlb lines are TYPE:START[-END]:NAME[:COMMENT]; coprocessor spaces we don't model are skipped */
+        let mut result = Self::new();
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim_end_matches('\r');
+            if line.trim().is_empty() { continue; }
+
+            let mut fields = line.splitn(4, ':');
+            let (kind, range, name) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(k), Some(r), Some(n)) => (k, r, n),
+                _ => return Err(Error::InvalidLabelLine(index + 1)),
+            };
+            let comment = fields.next().filter(|c| !c.is_empty());
+
+            let space = match mesen_space(kind) {
+                Some(s) => s,
+                None => continue,
+            };
+            let (start, end) = match range.split_once('-') {
+                Some((s, e)) => (usize::from_str_radix(s, 16), usize::from_str_radix(e, 16)),
+                None => (usize::from_str_radix(range, 16), usize::from_str_radix(range, 16)),
+            };
+            let (start, end) = match (start, end) {
+                (Ok(s), Ok(e)) if e >= s => (s, e),
+                _ => return Err(Error::InvalidLabelLine(index + 1)),
+            };
+
+            /* Mesen allows comment-only entries; they have nothing to name */
+            if name.is_empty() && comment.is_none() { continue; }
+
+            let mut symbol = Symbol::new(space, start, name).length(end - start + 1);
+            if let Some(c) = comment { symbol = symbol.comment(c); }
+
+            result.insert(symbol);
+        }
+
+        Ok(result)
+    }
+    pub fn to_mesen_labels(&self) -> String {
+        let mut symbols = self.iter().collect::<Vec<&Symbol>>();
+        symbols.sort_by_key(|s| (mesen_type(s.space), s.offset));
+
+        let mut result = String::new();
+
+        for symbol in symbols {
+            result.push_str(mesen_type(symbol.space));
+
+            if symbol.length > 1 { result.push_str(&format!(":{:X}-{:X}", symbol.offset, symbol.offset + symbol.length - 1)); }
+            else { result.push_str(&format!(":{:X}", symbol.offset)); }
+
+            result.push_str(&format!(":{}", symbol.name));
+            if let Some(comment) = &symbol.comment { result.push_str(&format!(":{}", comment)); }
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+impl UsageLog {
+    pub fn from_mesen_cdl<B: AsRef<[u8]>>(rom: &Rom, data: B) -> Result<Self, Error> {
+        let data = data.as_ref();
+        let rom_size = rom.rom_size();
+
+        /* newer files carry a magic and the rom's CRC; a bare flag array is what older builds wrote */
+        let flags = if data.len() == MESEN_CDL_MAGIC.len() + 4 + rom_size && data[..MESEN_CDL_MAGIC.len()] == MESEN_CDL_MAGIC {
+            let stored = u32::from_le_bytes([data[5], data[6], data[7], data[8]]);
+            let actual = rom.crc32();
+
+            if stored != actual { return Err(Error::PatchChecksumMismatch(stored,actual)); }
+
+            &data[MESEN_CDL_MAGIC.len() + 4..]
+        }
+        else if data.len() == rom_size { data }
+        else { return Err(Error::DataLengthMismatch(rom_size,data.len())); };
+
+        let usage = flags.iter().map(|&f| {
+            let mut u = 0u8;
+
+            if f & CDL_CODE != 0 { u |= USAGE_READ | USAGE_EXEC; }
+            if f & CDL_DATA != 0 { u |= USAGE_READ; }
+            /* Mesen only sets the width flags on an instruction's first byte, so they double as an opcode marker;
+               opcodes run with 16-bit A and index carry neither and stay unmarked */
+            if f & CDL_CODE != 0 && f & (CDL_INDEX_8 | CDL_MEMORY_8) != 0 { u |= USAGE_OPCODE; }
+            if f & CDL_MEMORY_8 != 0 { u |= USAGE_FLAG_M; }
+            if f & CDL_INDEX_8 != 0 { u |= USAGE_FLAG_X; }
+
+            u
+        }).collect::<Vec<u8>>();
+
+        Ok(Self::from_flags(usage))
+    }
+    pub fn to_mesen_cdl(&self, rom: &Rom) -> Result<Vec<u8>, Error> {
+        if self.len() != rom.rom_size() { return Err(Error::DataLengthMismatch(rom.rom_size(),self.len())); }
+
+        let mut result = MESEN_CDL_MAGIC.to_vec();
+        result.extend_from_slice(&rom.crc32().to_le_bytes());
+
+        for &u in self.as_slice() {
+            let mut f = 0u8;
+
+            if u & USAGE_EXEC != 0 { f |= CDL_CODE; }
+            else if u & USAGE_READ != 0 { f |= CDL_DATA; }
+            if u & USAGE_OPCODE != 0 && u & USAGE_FLAG_M != 0 { f |= CDL_MEMORY_8; }
+            if u & USAGE_OPCODE != 0 && u & USAGE_FLAG_X != 0 { f |= CDL_INDEX_8; }
+
+            result.push(f);
+        }
+
+        Ok(result)
+    }
+}
+
+impl Rom {
+    pub fn import_mesen_cdl<P: AsRef<Path>>(&self, filename: P) -> Result<UsageLog, Error> {
+        match std::fs::read(filename) {
+            Ok(d) => UsageLog::from_mesen_cdl(self, d),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+}
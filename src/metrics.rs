@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static BYTES_DECODED: AtomicU64 = AtomicU64::new(0);
+static TILES_DECODED: AtomicU64 = AtomicU64::new(0);
+static TILES_RENDERED: AtomicU64 = AtomicU64::new(0);
+static BYTES_CHECKSUMMED: AtomicU64 = AtomicU64::new(0);
+static BYTES_SEARCHED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum MetricCounter {
+    BytesDecoded,
+    TilesDecoded,
+    TilesRendered,
+    BytesChecksummed,
+    BytesSearched,
+}
+impl MetricCounter {
+    fn atomic(&self) -> &'static AtomicU64 {
+        match self {
+            MetricCounter::BytesDecoded => &BYTES_DECODED,
+            MetricCounter::TilesDecoded => &TILES_DECODED,
+            MetricCounter::TilesRendered => &TILES_RENDERED,
+            MetricCounter::BytesChecksummed => &BYTES_CHECKSUMMED,
+            MetricCounter::BytesSearched => &BYTES_SEARCHED,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Metrics {
+    pub bytes_decoded: u64,
+    pub tiles_decoded: u64,
+    pub tiles_rendered: u64,
+    pub bytes_checksummed: u64,
+    pub bytes_searched: u64,
+}
+impl Metrics {
+    pub fn since(&self, earlier: &Self) -> Self {
+        /* counters are process-wide, so callers measure a single job by differencing two snapshots */
+        Self {
+            bytes_decoded: self.bytes_decoded.wrapping_sub(earlier.bytes_decoded),
+            tiles_decoded: self.tiles_decoded.wrapping_sub(earlier.tiles_decoded),
+            tiles_rendered: self.tiles_rendered.wrapping_sub(earlier.tiles_rendered),
+            bytes_checksummed: self.bytes_checksummed.wrapping_sub(earlier.bytes_checksummed),
+            bytes_searched: self.bytes_searched.wrapping_sub(earlier.bytes_searched),
+        }
+    }
+}
+
+pub fn metrics() -> Metrics {
+    Metrics {
+        bytes_decoded: BYTES_DECODED.load(Ordering::Relaxed),
+        tiles_decoded: TILES_DECODED.load(Ordering::Relaxed),
+        tiles_rendered: TILES_RENDERED.load(Ordering::Relaxed),
+        bytes_checksummed: BYTES_CHECKSUMMED.load(Ordering::Relaxed),
+        bytes_searched: BYTES_SEARCHED.load(Ordering::Relaxed),
+    }
+}
+pub fn reset_metrics() {
+    for counter in &[MetricCounter::BytesDecoded, MetricCounter::TilesDecoded, MetricCounter::TilesRendered, MetricCounter::BytesChecksummed, MetricCounter::BytesSearched] {
+        counter.atomic().store(0, Ordering::Relaxed);
+    }
+}
+pub(crate) fn record(counter: MetricCounter, amount: usize) {
+    counter.atomic().fetch_add(amount as u64, Ordering::Relaxed);
+}
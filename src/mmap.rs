@@ -0,0 +1,146 @@
+use crate::{Error, Rom};
+use std::ops::Range;
+use std::path::Path;
+#[cfg(feature = "mmap")]
+use std::sync::Arc;
+
+/* what a Rom's bytes live in: its own copy, or (with the mmap feature) a read-only view of the file on disk.
+   plain owned storage on both sides keeps Rom Send and Sync without having to promise it */
+pub enum RomBuffer {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<memmap2::Mmap>),
+}
+impl RomBuffer {
+    pub fn from_data<B: AsRef<[u8]>>(data: B) -> Self {
+        RomBuffer::Owned(data.as_ref().to_vec())
+    }
+    pub fn from_file<P: AsRef<Path>>(filename: P) -> Result<Self, Error> {
+        match std::fs::read(filename) {
+            Ok(d) => Ok(RomBuffer::Owned(d)),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+    #[cfg(feature = "mmap")]
+    pub fn map_file<P: AsRef<Path>>(filename: P) -> Result<Self, Error> {
+        let file = match std::fs::File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::IoError(e)),
+        };
+
+        /* the mapping aliases the file: anything that truncates or rewrites it while a mapped Rom (or a clone) is alive
+           changes bytes under a shared borrow, or faults the process once reads run past the new end. Rom::save
+           only ever writes a new file and renames it into place, leaving this inode alone; nothing else may touch it */
+        match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(m) => Ok(RomBuffer::Mapped(Arc::new(m))),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self, RomBuffer::Owned(_))
+    }
+    pub fn view(&self) -> &[u8] {
+        match self {
+            RomBuffer::Owned(b) => b,
+            #[cfg(feature = "mmap")]
+            RomBuffer::Mapped(map) => map,
+        }
+    }
+    pub fn owned(&mut self) -> &mut Vec<u8> {
+        /* copy on first write: a mapped image becomes an ordinary one, and the file itself is never touched */
+        #[cfg(feature = "mmap")]
+        {
+            if let RomBuffer::Mapped(map) = self { *self = RomBuffer::Owned(map.to_vec()); }
+        }
+
+        match self {
+            RomBuffer::Owned(b) => b,
+            #[cfg(feature = "mmap")]
+            RomBuffer::Mapped(_) => unreachable!(),
+        }
+    }
+    pub fn range(&self, offset: usize, size: usize) -> Result<Range<usize>, Error> {
+        /* same rules and errors as pkbuffer: the offset itself has to land inside, even for an empty read */
+        let len = self.view().len();
+
+        if offset >= len { return Err(Error::PKBufferError(pkbuffer::Error::OutOfBounds(len,offset))); }
+
+        match offset.checked_add(size) {
+            Some(end) if end <= len => Ok(offset..end),
+            _ => Err(Error::PKBufferError(pkbuffer::Error::OutOfBounds(len,offset.saturating_add(size)))),
+        }
+    }
+    fn typed_range<T>(&self, offset: usize, count: usize) -> Result<Range<usize>, Error> {
+        match std::mem::size_of::<T>().checked_mul(count) {
+            Some(size) => self.range(offset, size),
+            None => Err(Error::PKBufferError(pkbuffer::Error::OutOfBounds(self.view().len(),usize::MAX))),
+        }
+    }
+    pub fn get_ref<T>(&self, offset: usize) -> Result<&T, Error> {
+        /* as with pkbuffer, T has to be plain bytes; the header and table structs are packed, so any offset suits them */
+        match self.typed_range::<T>(offset, 1) {
+            Ok(r) => Ok(unsafe { &*(self.view()[r].as_ptr() as *const T) }),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn get_mut_ref<T>(&mut self, offset: usize) -> Result<&mut T, Error> {
+        match self.typed_range::<T>(offset, 1) {
+            Ok(r) => Ok(unsafe { &mut *(self.owned()[r].as_mut_ptr() as *mut T) }),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn get_slice_ref<T>(&self, offset: usize, count: usize) -> Result<&[T], Error> {
+        match self.typed_range::<T>(offset, count) {
+            Ok(r) => Ok(unsafe { std::slice::from_raw_parts(self.view()[r].as_ptr() as *const T, count) }),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn get_mut_slice_ref<T>(&mut self, offset: usize, count: usize) -> Result<&mut [T], Error> {
+        match self.typed_range::<T>(offset, count) {
+            Ok(r) => Ok(unsafe { std::slice::from_raw_parts_mut(self.owned()[r].as_mut_ptr() as *mut T, count) }),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        match self.range(offset, data.len()) {
+            Ok(r) => { self.owned()[r].copy_from_slice(data); Ok(()) },
+            Err(e) => Err(e),
+        }
+    }
+}
+impl Clone for RomBuffer {
+    fn clone(&self) -> Self {
+        /* clones of a mapped image share the one mapping */
+        match self {
+            RomBuffer::Owned(b) => RomBuffer::Owned(b.clone()),
+            #[cfg(feature = "mmap")]
+            RomBuffer::Mapped(map) => RomBuffer::Mapped(map.clone()),
+        }
+    }
+}
+impl PartialEq for RomBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.view() == other.view()
+    }
+}
+impl Eq for RomBuffer {}
+impl std::fmt::Debug for RomBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let kind = if self.is_mapped() { "Mapped" } else { "Owned" };
+        write!(f, "RomBuffer::{}({} bytes)", kind, self.view().len())
+    }
+}
+
+impl Rom {
+    #[cfg(feature = "mmap")]
+    pub fn from_file_mapped<P: AsRef<Path>>(filename: P) -> Result<Self, Error> {
+        /* for batch jobs over big images: nothing is copied until something writes */
+        match RomBuffer::map_file(filename.as_ref()) {
+            Ok(b) => Self::from_buffer(b, filename.as_ref()),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn is_mapped(&self) -> bool {
+        self.buffer.is_mapped()
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Error, Rom, SNESHeader};
+
+pub const MULTICART_ALIGNMENT: usize = 0x8000;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MulticartMapping {
+    LoRom,
+    HiRom,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MulticartGame {
+    pub offset: usize,
+    pub size: usize,
+    pub mapping: MulticartMapping,
+    pub title: String,
+}
+
+impl Rom {
+    fn get_plausible_snes_header(&self, offset: usize) -> Option<&SNESHeader> {
+        if offset + std::mem::size_of::<SNESHeader>() > self.rom_size() { return None; }
+
+        match self.get_ref::<SNESHeader>(self.header_size() + offset) {
+            Ok(h) if h.validate_fields_with(self.checksum_policy()).is_ok() => Some(h),
+            _ => None,
+        }
+    }
+    pub fn find_multicart_games(&self) -> Vec<MulticartGame> {
+        let mut games = Vec::<MulticartGame>::new();
+        let mut offset = 0usize;
+
+        while offset + MULTICART_ALIGNMENT <= self.rom_size() {
+            let found = match self.get_plausible_snes_header(offset + 0x7fc0) {
+                Some(h) => Some((h, MulticartMapping::LoRom)),
+                None => self.get_plausible_snes_header(offset + 0xffc0).map(|h| (h, MulticartMapping::HiRom)),
+            };
+
+            let (header, mapping) = match found {
+                Some(f) => f,
+                None => { offset += MULTICART_ALIGNMENT; continue; }
+            };
+
+            let remaining = self.rom_size() - offset;
+            let declared = header.declared_rom_size();
+            let size = if declared < MULTICART_ALIGNMENT || declared > remaining { remaining } else { declared };
+
+            games.push(MulticartGame { offset, size, mapping, title: header.get_title() });
+
+            offset += size.div_ceil(MULTICART_ALIGNMENT) * MULTICART_ALIGNMENT;
+        }
+
+        /* a game's declared size may run into the next embedded header, so clip to it */
+        for i in 1..games.len() {
+            let next_offset = games[i].offset;
+            let previous = &mut games[i-1];
+
+            if previous.offset + previous.size > next_offset { previous.size = next_offset - previous.offset; }
+        }
+
+        games
+    }
+    pub fn is_multicart(&self) -> bool {
+        self.find_multicart_games().len() > 1
+    }
+    pub fn split_multicart(&self) -> Result<Vec<Rom>, Error> {
+        let mut result = Vec::<Rom>::new();
+
+        for game in self.find_multicart_games() {
+            match self.read(self.header_size() + game.offset, game.size) {
+                Ok(d) => result.push(Rom::new(d)),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MulticartBuilder {
+    games: Vec<Vec<u8>>,
+    fill: u8,
+}
+impl MulticartBuilder {
+    pub fn new() -> Self {
+        Self { games: Vec::new(), fill: 0xFF }
+    }
+    pub fn fill(mut self, fill: u8) -> Self {
+        self.fill = fill;
+        self
+    }
+    pub fn add_game(mut self, rom: &Rom) -> Self {
+        self.games.push(rom.as_slice()[rom.header_size()..].to_vec());
+        self
+    }
+    pub fn build(&self) -> Rom {
+        let mut data = Vec::<u8>::new();
+
+        for game in &self.games {
+            /* each game starts on a multiple of its own padded size so its banks mirror the way they did standalone */
+            let padded_size = game.len().max(MULTICART_ALIGNMENT).next_power_of_two();
+            let start = data.len().div_ceil(padded_size) * padded_size;
+
+            data.resize(start, self.fill);
+            data.extend_from_slice(game);
+            data.resize(start + padded_size, self.fill);
+        }
+
+        Rom::new(data)
+    }
+}
+impl Default for MulticartBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
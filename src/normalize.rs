@@ -0,0 +1,417 @@
+use crate::{Error, InterruptVector, Rom, SNESHeader};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NormalizeStep {
+    RemovedCopierHeader(usize),
+    Deinterleaved,
+    TrimmedOverdump { from: usize, to: usize },
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NormalizeReport {
+    pub original_size: usize,
+    pub normalized_size: usize,
+    pub steps: Vec<NormalizeStep>,
+}
+impl NormalizeReport {
+    pub fn was_canonical(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+fn header_at(data: &[u8], offset: usize) -> Option<SNESHeader> {
+    /* candidates come from loose bytes rather than a Rom, so wrap just the header's worth in one and read it the usual way */
+    let bytes = match data.get(offset..offset.saturating_add(std::mem::size_of::<SNESHeader>())) {
+        Some(b) => b,
+        None => return None,
+    };
+    let header = match Rom::new(bytes).get_ref::<SNESHeader>(0) {
+        Ok(h) => *h,
+        Err(_) => return None,
+    };
+
+    /* only the fields are checked here, the size check is exactly what an overdump fails */
+    if header.validate_fields().is_ok() { Some(header) } else { None }
+}
+
+/* SMC and SWC headers both carry this at bytes 8-10 */
+pub const COPIER_SIGNATURE: [u8; 3] = [0xAA, 0xBB, 0x04];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CopierHeaderDetection {
+    pub size: usize,
+    pub confidence: f32,
+    pub evidence: Vec<&'static str>,
+}
+
+fn copier_header_evidence(file: &[u8], size: usize) -> (u32, Vec<&'static str>) {
+    let mut score = 0u32;
+    let mut evidence = Vec::<&'static str>::new();
+    let data = &file[size.min(file.len())..];
+
+    /* rom images are whole 32KB banks, so a copier header is exactly what's left over */
+    if file.len() % 0x8000 == size { score += 2; evidence.push("file size leaves exactly this remainder"); }
+
+    if size != 0 && file.len() >= size && file[8..11] == COPIER_SIGNATURE { score += 2; evidence.push("copier signature bytes"); }
+
+    let header = [0x7FC0, 0xFFC0, 0x40FFC0].iter().filter_map(|&o| header_at(data, o)).next();
+
+    if let Some(h) = header {
+        score += 2;
+        evidence.push("internal header fields validate");
+
+        /* candidates are only field-checked, so the size byte may not describe any real board */
+        if let Some(declared) = h.checked_rom_size() {
+            if declared >= data.len() && data.len() * 2 > declared { score += 1; evidence.push("declared size fits the image"); }
+        }
+        if h.get_vector(InterruptVector::Reset) >= 0x8000 { score += 1; evidence.push("reset vector points into rom"); }
+    }
+
+    (score, evidence)
+}
+
+pub fn detect_copier_header(file: &[u8]) -> CopierHeaderDetection {
+    /* weigh the 512-byte and headerless readings against each other rather than trusting len % 1024 */
+    let (bare_score, bare_evidence) = copier_header_evidence(file, 0);
+    let (copier_score, copier_evidence) = if file.len() >= 0x200 { copier_header_evidence(file, 0x200) } else { (0, Vec::new()) };
+    let total = (bare_score + copier_score) as f32;
+
+    if copier_score > bare_score {
+        CopierHeaderDetection { size: 0x200, confidence: copier_score as f32 / total, evidence: copier_evidence }
+    }
+    else {
+        CopierHeaderDetection { size: 0, confidence: if total == 0.0 { 0.0 } else { bare_score as f32 / total }, evidence: bare_evidence }
+    }
+}
+
+pub fn build_copier_header(rom_size: usize) -> Vec<u8> {
+    /* the common SWC/SMC layout: size in 8KB blocks, then the signature; everything else zero */
+    let mut result = vec![0u8; 0x200];
+    let blocks = (rom_size / 0x2000) as u16;
+
+    result[0..2].copy_from_slice(&blocks.to_le_bytes());
+    result[8..11].copy_from_slice(&COPIER_SIGNATURE);
+    result
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ExpandFill {
+    Mirror,
+    Byte(u8),
+}
+
+fn mirror_offset(offset: usize, size: usize) -> usize {
+    /* where the bus finds offset in an image of this size: strip the largest power of two that still leaves
+       offset past the end, and step into the remainder whenever it is smaller than that power */
+    let mut offset = offset;
+    let mut size = size;
+    let mut base = 0usize;
+    let mut mask = 1usize << 23;
+
+    if size == 0 { return 0; }
+
+    while offset >= size {
+        while offset & mask == 0 { mask >>= 1; }
+
+        offset -= mask;
+
+        if size > mask {
+            size -= mask;
+            base += mask;
+        }
+
+        mask >>= 1;
+    }
+
+    base + offset
+}
+
+fn cart_size(used: usize, bank_size: usize) -> usize {
+    /* the smallest image a board could hold this in: one power-of-two chip, or one plus a half or quarter sized
+       second chip that the bus mirrors up to the size byte (the 12, 20 and 24 megabit carts) */
+    let mut size = bank_size;
+
+    loop {
+        for &candidate in &[size, size + size / 4, size + size / 2] {
+            if candidate >= used && candidate % bank_size == 0 { return candidate; }
+        }
+
+        size *= 2;
+    }
+}
+
+fn is_interleaved(data: &[u8]) -> bool {
+    /* copier-interleaved HiROM puts the upper half of bank 0 first, so its header shows up where LoROM's would be */
+    if data.len() < 0x10000 || !data.len().is_multiple_of(0x10000) { return false; }
+    if header_at(data, 0xFFC0).is_some() { return false; }
+
+    match header_at(data, 0x7FC0) {
+        Some(h) => h.get_mapping_mode() & 1 != 0,
+        None => false,
+    }
+}
+
+fn deinterleave(data: &[u8]) -> Vec<u8> {
+    /* the file holds every bank's upper half, then every lower half; zip them back together */
+    let banks = data.len() / 0x10000;
+    let mut result = Vec::<u8>::with_capacity(data.len());
+
+    for bank in 0..banks {
+        let lower = (banks + bank) * 0x8000;
+        let upper = bank * 0x8000;
+
+        result.extend_from_slice(&data[lower..lower+0x8000]);
+        result.extend_from_slice(&data[upper..upper+0x8000]);
+    }
+
+    result
+}
+
+fn interleave(data: &[u8]) -> Vec<u8> {
+    let banks = data.len() / 0x10000;
+    let mut result = vec![0u8; data.len()];
+
+    for bank in 0..banks {
+        let lower = (banks + bank) * 0x8000;
+        let upper = bank * 0x8000;
+
+        result[lower..lower+0x8000].copy_from_slice(&data[bank*0x10000..bank*0x10000+0x8000]);
+        result[upper..upper+0x8000].copy_from_slice(&data[bank*0x10000+0x8000..(bank+1)*0x10000]);
+    }
+
+    result
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DumpSize {
+    Unknown,
+    Matches { declared: usize, actual: usize },
+    Overdump { declared: usize, actual: usize, junk: bool },
+    Underdump { declared: usize, actual: usize },
+}
+impl DumpSize {
+    pub fn is_bad_dump(&self) -> bool {
+        matches!(self, DumpSize::Overdump { .. } | DumpSize::Underdump { .. })
+    }
+}
+
+fn dump_size(data: &[u8]) -> DumpSize {
+    let header = match header_at(data, 0x7FC0).or_else(|| header_at(data, 0xFFC0)).or_else(|| header_at(data, 0x40FFC0)) {
+        Some(h) => h,
+        None => return DumpSize::Unknown,
+    };
+    let (declared, actual) = (header.declared_rom_size(), data.len());
+
+    /* the size byte rounds up to a power of two, so e.g. a 3MB game legitimately declares 4MB */
+    if actual * 2 <= declared { return DumpSize::Underdump { declared, actual }; }
+    if actual <= declared { return DumpSize::Matches { declared, actual }; }
+
+    /* only call the excess junk when we can prove it: a single fill byte or a mirror of the real image */
+    let excess = &data[declared..];
+    let filled = excess.iter().all(|&b| b == excess[0]);
+    let mirrored = excess.iter().enumerate().all(|(i, &b)| b == data[i % declared]);
+
+    DumpSize::Overdump { declared, actual, junk: filled || mirrored }
+}
+
+impl Rom {
+    pub fn detect_copier_header(&self) -> CopierHeaderDetection {
+        detect_copier_header(self.as_slice())
+    }
+    pub fn strip_copier_header(&mut self) -> Result<usize, Error> {
+        /* header_size() misses a header when padding evens out the length, so fall back on the detector */
+        let size = match self.header_size() {
+            0 => {
+                let detection = self.detect_copier_header();
+                if detection.confidence >= 0.5 { detection.size } else { 0 }
+            },
+            s => s,
+        };
+        if size == 0 { return Ok(0); }
+
+        let mut data = self.as_slice()[size..].to_vec();
+
+        /* whatever padding hid the header would read as one once it's gone, so it has to go too */
+        let excess = data.len() % 1024;
+        if excess != 0 {
+            let tail = &data[data.len() - excess..];
+            if tail.iter().any(|&b| b != tail[0]) { return Err(Error::DataLengthMismatch(data.len() - excess,data.len())); }
+
+            data.truncate(data.len() - excess);
+        }
+
+        self.resize(data.len());
+
+        match self.write(0, data) {
+            Ok(()) => Ok(size),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn add_copier_header(&mut self) -> Result<usize, Error> {
+        if self.header_size() != 0 { return Ok(0); }
+
+        let mut data = build_copier_header(self.rom_size());
+        data.extend_from_slice(self.as_slice());
+
+        self.resize(data.len());
+
+        match self.write(0, data) {
+            Ok(()) => Ok(0x200),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn is_interleaved(&self) -> bool {
+        is_interleaved(&self.as_slice()[self.header_size()..])
+    }
+    pub fn deinterleave(&mut self) -> Result<bool, Error> {
+        if !self.is_interleaved() { return Ok(false); }
+
+        let header_size = self.header_size();
+        let data = deinterleave(&self.as_slice()[header_size..]);
+
+        match self.write(header_size, data) {
+            Ok(()) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn interleave(&mut self) -> Result<(), Error> {
+        /* the inverse, for copiers that still want their HiROM images this way round */
+        let header_size = self.header_size();
+        if !self.rom_size().is_multiple_of(0x10000) { return Err(Error::DataLengthMismatch(self.rom_size(),self.rom_size().div_ceil(0x10000) * 0x10000)); }
+
+        let data = interleave(&self.as_slice()[header_size..]);
+        self.write(header_size, data)
+    }
+    pub fn dump_size(&self) -> DumpSize {
+        dump_size(&self.as_slice()[self.header_size()..])
+    }
+    pub fn trim_overdump(&mut self) -> Result<usize, Error> {
+        /* real data past the declared size (an expanded hack with a stale header, say) is left alone */
+        match self.dump_size() {
+            DumpSize::Overdump { declared, actual, junk: true } => {
+                self.resize(self.header_size() + declared);
+                Ok(actual - declared)
+            },
+            DumpSize::Overdump { declared, actual, junk: false } => Err(Error::ROMSizeMismatch(declared,actual)),
+            DumpSize::Underdump { declared, actual } => Err(Error::Underdump(declared,actual)),
+            _ => Ok(0),
+        }
+    }
+    pub fn trim(&mut self) -> Result<usize, Error> {
+        /* unlike trim_overdump this trusts the padding rather than the header, so it also cleans up images whose
+           size byte was never right; the header is found by its fields alone since an overdump fails the size check */
+        let header_size = self.header_size();
+        let data = &self.as_slice()[header_size..];
+
+        let header_offset = match [0x40FFC0, 0x7FC0, 0xFFC0].iter().copied().filter(|&o| o != 0x40FFC0 || data.len() > 0x400000).find(|&o| header_at(data, o).is_some()) {
+            Some(o) => o,
+            None => return Err(Error::NoHeader),
+        };
+        let bank_size = if header_offset == 0x7FC0 { 0x8000 } else { 0x10000 };
+
+        let pad = data[data.len() - 1];
+        if pad != 0x00 && pad != 0xFF { return Ok(0); }
+
+        let used = data.iter().rposition(|&b| b != pad).map_or(0, |p| p + 1).max(header_offset + std::mem::size_of::<SNESHeader>());
+        let size = cart_size(used, bank_size);
+        if size >= data.len() { return Ok(0); }
+
+        let removed = data.len() - size;
+        self.resize(header_size + size);
+
+        let exponent = (size / 0x400).next_power_of_two().trailing_zeros() as u8;
+
+        let mut header = match self.get_ref::<SNESHeader>(header_size + header_offset) {
+            Ok(h) => *h,
+            Err(e) => return Err(e),
+        };
+        header.set_rom_size(exponent);
+
+        if let Err(e) = self.write_ref(header_size + header_offset, &header) { return Err(e); }
+
+        match self.fix_checksum() {
+            Ok(_) => Ok(removed),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn expand_to(&mut self, size: usize, fill: ExpandFill) -> Result<usize, Error> {
+        /* the header has to be found before growing, since a stale size byte makes it fail validation afterwards */
+        let old_size = self.rom_size();
+
+        if size <= old_size { return Ok(0); }
+        if !size.is_multiple_of(0x8000) { return Err(Error::DataLengthMismatch(size,size.div_ceil(0x8000) * 0x8000)); }
+
+        let mapper = match self.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        if size > mapper.max_rom_size() { return Err(Error::UnaddressableRomSize(size,mapper.max_rom_size())); }
+
+        let header_offset = match self.find_valid_snes_header_address() {
+            Ok(a) => a.to_offset(self),
+            Err(e) => return Err(e),
+        };
+
+        let header_size = self.header_size();
+        let extension = match fill {
+            ExpandFill::Mirror => (old_size..size).map(|o| self.as_slice()[header_size + mirror_offset(o, old_size)]).collect::<Vec<u8>>(),
+            ExpandFill::Byte(b) => vec![b; size - old_size],
+        };
+
+        self.resize(header_size + size);
+        if let Err(e) = self.write(header_size + old_size, extension) { return Err(e); }
+
+        /* rom size byte is log2 of the size in KB, rounded up for the odd sizes */
+        let exponent = (size / 0x400).next_power_of_two().trailing_zeros() as u8;
+
+        let mut header = match self.get_ref::<SNESHeader>(header_offset) {
+            Ok(h) => *h,
+            Err(e) => return Err(e),
+        };
+        header.set_rom_size(exponent);
+
+        if let Err(e) = self.write_ref(header_offset, &header) { return Err(e); }
+
+        match self.fix_checksum() {
+            Ok(_) => Ok(size - old_size),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn normalize(&mut self) -> Result<NormalizeReport, Error> {
+        let original_size = self.len();
+        let mut steps = Vec::<NormalizeStep>::new();
+        let mut data = self.as_slice().to_vec();
+
+        let header_size = self.header_size();
+        if header_size != 0 {
+            data.drain(..header_size);
+            steps.push(NormalizeStep::RemovedCopierHeader(header_size));
+        }
+
+        if is_interleaved(&data) {
+            data = deinterleave(&data);
+            steps.push(NormalizeStep::Deinterleaved);
+        }
+
+        if let DumpSize::Overdump { declared, actual, junk: true } = dump_size(&data) {
+            steps.push(NormalizeStep::TrimmedOverdump { from: actual, to: declared });
+            data.truncate(declared);
+        }
+
+        if !steps.is_empty() {
+            self.resize(data.len());
+
+            if let Err(e) = self.write(0, data) { return Err(e); }
+        }
+
+        Ok(NormalizeReport { original_size, normalized_size: self.len(), steps })
+    }
+    pub fn normalized(&self) -> Result<(Self, NormalizeReport), Error> {
+        let mut result = self.clone();
+
+        match result.normalize() {
+            Ok(r) => Ok((result, r)),
+            Err(e) => Err(e),
+        }
+    }
+}
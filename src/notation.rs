@@ -0,0 +1,154 @@
+use crate::{Addr24, Error, Rom};
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum AddrNotation {
+    #[default]
+    Headered,
+    Pc,
+    LoRom,
+    HiRom,
+}
+impl AddrNotation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AddrNotation::Headered => "headered",
+            AddrNotation::Pc => "pc",
+            AddrNotation::LoRom => "lorom",
+            AddrNotation::HiRom => "hirom",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "headered" => Some(AddrNotation::Headered),
+            "pc" | "unheadered" => Some(AddrNotation::Pc),
+            "lorom" | "snes" => Some(AddrNotation::LoRom),
+            "hirom" => Some(AddrNotation::HiRom),
+            _ => None,
+        }
+    }
+    pub fn pc_to_address(&self, pc: usize) -> Result<Addr24, Error> {
+        match self {
+            AddrNotation::Headered | AddrNotation::Pc => {
+                if pc > 0xFFFFFF { return Err(Error::OutOfBounds(pc,0x1000000)); }
+
+                Ok(Addr24::from_u32(pc as u32))
+            },
+            AddrNotation::LoRom => {
+                let bank = pc / 0x8000;
+                if bank > 0x7F { return Err(Error::OutOfBounds(pc,0x400000)); }
+
+                /* banks $7E and $7F are WRAM, so the last 64KB of a 4MB LoROM is only visible through the $FE/$FF mirrors */
+                let bank = if bank >= 0x7E { bank | 0x80 } else { bank };
+
+                Ok(Addr24::new(bank as u8, 0x8000 | (pc % 0x8000) as u16))
+            },
+            AddrNotation::HiRom => {
+                if pc >= 0x400000 { return Err(Error::OutOfBounds(pc,0x400000)); }
+
+                Ok(Addr24::new(0xC0 | (pc >> 16) as u8, (pc & 0xFFFF) as u16))
+            },
+        }
+    }
+    pub fn address_to_pc(&self, address: Addr24) -> Result<usize, Error> {
+        let bank = address.bank;
+        let addr = address.address;
+
+        match self {
+            AddrNotation::Headered | AddrNotation::Pc => Ok(address.as_u32() as usize),
+            AddrNotation::LoRom => {
+                /* boards over 2MB also decode the lower halves of $40-$6F (and their $C0-$EF mirrors) as rom */
+                let lower_rom = (0x40..=0x6F).contains(&(bank & 0x7F));
+
+                if (addr < 0x8000 && !lower_rom) || bank == 0x7E || bank == 0x7F { return Err(Error::InvalidROMAddress(address)); }
+
+                Ok((bank & 0x7F) as usize * 0x8000 + (addr & 0x7FFF) as usize)
+            },
+            AddrNotation::HiRom => {
+                if bank & 0x7F < 0x40 && addr < 0x8000 { return Err(Error::InvalidROMAddress(address)); }
+                if bank == 0x7E || bank == 0x7F { return Err(Error::InvalidROMAddress(address)); }
+
+                Ok((bank & 0x3F) as usize * 0x10000 + addr as usize)
+            },
+        }
+    }
+    pub fn format_address(&self, address: Addr24) -> String {
+        match self {
+            AddrNotation::Headered | AddrNotation::Pc => format!("0x{:06X}", address.as_u32()),
+            AddrNotation::LoRom | AddrNotation::HiRom => format!("${:02X}:{:04X}", address.bank, { address.address }),
+        }
+    }
+    pub fn parse_address(&self, text: &str) -> Result<Addr24, Error> {
+        let text = text.trim();
+        let invalid = || Error::InvalidAddressText(text.to_string());
+
+        /* accept the usual spellings: $80:8000, 80:8000, $808000, 0x808000 and plain hex */
+        let digits = if let Some(t) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) { t }
+                     else if let Some(t) = text.strip_prefix('$') { t }
+                     else { text };
+
+        let value = match digits.split_once([':', '/']) {
+            Some((bank, addr)) => {
+                let bank = match u8::from_str_radix(bank, 16) {
+                    Ok(b) => b,
+                    Err(_) => return Err(invalid()),
+                };
+                let addr = match u16::from_str_radix(addr, 16) {
+                    Ok(a) => a,
+                    Err(_) => return Err(invalid()),
+                };
+
+                Addr24::new(bank, addr).as_u32()
+            },
+            None => match u32::from_str_radix(digits, 16) {
+                Ok(v) if v <= 0xFFFFFF => v,
+                _ => return Err(invalid()),
+            },
+        };
+
+        Ok(Addr24::from_u32(value))
+    }
+}
+
+impl Rom {
+    pub fn notation(&self) -> AddrNotation {
+        self.notation
+    }
+    pub fn set_notation(&mut self, notation: AddrNotation) {
+        self.notation = notation;
+    }
+    pub fn offset_to_notation(&self, offset: usize, notation: AddrNotation) -> Result<Addr24, Error> {
+        if offset >= self.len() { return Err(Error::OutOfBounds(offset,self.len())); }
+
+        match notation {
+            AddrNotation::Headered => notation.pc_to_address(offset),
+            _ => {
+                if offset < self.header_size() { return Err(Error::OutOfBounds(offset,self.header_size())); }
+
+                notation.pc_to_address(offset - self.header_size())
+            },
+        }
+    }
+    pub fn notation_to_offset(&self, address: Addr24, notation: AddrNotation) -> Result<usize, Error> {
+        let offset = match notation.address_to_pc(address) {
+            Ok(pc) if notation == AddrNotation::Headered => pc,
+            Ok(pc) => pc + self.header_size(),
+            Err(e) => return Err(e),
+        };
+
+        if offset >= self.len() { return Err(Error::OutOfBounds(offset,self.len())); }
+
+        Ok(offset)
+    }
+    pub fn format_offset(&self, offset: usize) -> Result<String, Error> {
+        match self.offset_to_notation(offset, self.notation) {
+            Ok(a) => Ok(self.notation.format_address(a)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn parse_offset(&self, text: &str) -> Result<usize, Error> {
+        match self.notation.parse_address(text) {
+            Ok(a) => self.notation_to_offset(a, self.notation),
+            Err(e) => Err(e),
+        }
+    }
+}
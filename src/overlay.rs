@@ -0,0 +1,178 @@
+use crate::{diff_bytes, DiffRange, Error, IpsPatch, Rom};
+use std::collections::BTreeMap;
+
+/* one set of sparse writes, kept as non-overlapping runs keyed by buffer offset */
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct OverlayLayer {
+    runs: BTreeMap<usize, Vec<u8>>,
+}
+impl OverlayLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn write(&mut self, offset: usize, data: &[u8]) {
+        /* later writes win; touching or overlapping runs are folded into one */
+        if data.is_empty() { return; }
+
+        let mut start = offset;
+        let mut end = offset + data.len();
+        let touching: Vec<usize> = self.runs.range(..=end).filter(|(o, d)| **o + d.len() >= start).map(|(o, _)| *o).collect();
+        let mut merged = Vec::<(usize, Vec<u8>)>::new();
+
+        for key in touching {
+            let run = self.runs.remove(&key).unwrap();
+
+            start = start.min(key);
+            end = end.max(key + run.len());
+            merged.push((key, run));
+        }
+
+        let mut combined = vec![0u8; end - start];
+
+        for (key, run) in merged {
+            combined[key - start..key - start + run.len()].copy_from_slice(&run);
+        }
+
+        combined[offset - start..offset - start + data.len()].copy_from_slice(data);
+        self.runs.insert(start, combined);
+    }
+    pub fn apply(&self, offset: usize, window: &mut [u8]) {
+        /* copies whatever this layer holds over window, which starts at offset */
+        let end = offset + window.len();
+
+        for (key, run) in self.runs.range(..end) {
+            let run_end = key + run.len();
+            if run_end <= offset { continue; }
+
+            let from = (*key).max(offset);
+            let to = run_end.min(end);
+
+            window[from - offset..to - offset].copy_from_slice(&run[from - key..to - key]);
+        }
+    }
+    pub fn runs(&self) -> impl Iterator<Item = (usize, &[u8])> {
+        self.runs.iter().map(|(o, d)| (*o, d.as_slice()))
+    }
+    pub fn len(&self) -> usize {
+        self.runs.values().map(|d| d.len()).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+}
+
+/* non-destructive edits over a base ROM: writes land in the top layer, reads see every layer applied in order */
+#[derive(Clone, Debug)]
+pub struct Overlay<'a> {
+    base: &'a Rom,
+    layers: Vec<OverlayLayer>,
+}
+impl<'a> Overlay<'a> {
+    pub fn new(base: &'a Rom) -> Self {
+        Self { base, layers: vec![OverlayLayer::new()] }
+    }
+    pub fn base(&self) -> &'a Rom {
+        self.base
+    }
+    pub fn layers(&self) -> &[OverlayLayer] {
+        &self.layers
+    }
+    pub fn push_layer(&mut self) {
+        self.layers.push(OverlayLayer::new());
+    }
+    pub fn pop_layer(&mut self) -> Option<OverlayLayer> {
+        /* dropping the top layer discards its edits; the next write starts a fresh one if none are left */
+        self.layers.pop()
+    }
+    pub fn squash(&mut self) {
+        self.layers = vec![self.merged()];
+    }
+    fn merged(&self) -> OverlayLayer {
+        let mut result = OverlayLayer::new();
+
+        for layer in &self.layers {
+            for (offset, data) in layer.runs() { result.write(offset, data); }
+        }
+
+        result
+    }
+    pub fn len(&self) -> usize {
+        self.base.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.base.len() == 0
+    }
+    pub fn is_modified(&self) -> bool {
+        self.layers.iter().any(|l| !l.is_empty())
+    }
+    pub fn write<B: AsRef<[u8]>>(&mut self, offset: usize, data: B) -> Result<(), Error> {
+        /* the overlay can't grow the image; resize the base first */
+        let data = data.as_ref();
+        if offset + data.len() > self.base.len() { return Err(Error::OutOfBounds(offset + data.len(),self.base.len())); }
+
+        if self.layers.is_empty() { self.push_layer(); }
+
+        self.layers.last_mut().unwrap().write(offset, data);
+        Ok(())
+    }
+    pub fn read(&self, offset: usize, size: usize) -> Result<Vec<u8>, Error> {
+        let mut result = match self.base.read(offset, size) {
+            Ok(d) => d.to_vec(),
+            Err(e) => return Err(e),
+        };
+
+        for layer in &self.layers { layer.apply(offset, &mut result); }
+
+        Ok(result)
+    }
+    pub fn read_u8(&self, offset: usize) -> Result<u8, Error> {
+        match self.read(offset, 1) {
+            Ok(d) => Ok(d[0]),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn read_u16(&self, offset: usize) -> Result<u16, Error> {
+        match self.read(offset, 2) {
+            Ok(d) => Ok(u16::from_le_bytes([d[0], d[1]])),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn changes(&self) -> Vec<DiffRange> {
+        /* only bytes that really differ from the base; writing back the original value isn't a change */
+        let mut result = Vec::<DiffRange>::new();
+
+        for (offset, data) in self.merged().runs() {
+            let old = &self.base.as_slice()[offset..offset + data.len()];
+
+            for mut range in diff_bytes(old, data, 0) {
+                range.offset += offset;
+                result.push(range);
+            }
+        }
+
+        result
+    }
+    pub fn flatten(&self) -> Result<Rom, Error> {
+        let mut result = self.base.clone();
+
+        for layer in &self.layers {
+            for (offset, data) in layer.runs() {
+                if let Err(e) = result.write(offset, data) { return Err(e); }
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn to_ips(&self) -> Result<IpsPatch, Error> {
+        match self.flatten() {
+            Ok(r) => IpsPatch::from_ranges(&self.changes(), r.as_slice()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Rom {
+    pub fn overlay(&self) -> Overlay<'_> {
+        Overlay::new(self)
+    }
+}
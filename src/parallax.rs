@@ -0,0 +1,185 @@
+use crate::{Addr24, DmaParams, Error, HdmaEntry, HdmaEntryData, HdmaTable, Rom, TransferMode};
+
+/* BGnHOFS/BGnVOFS are write-twice registers: one axis is mode 2 into a single register, both is mode 3 into the pair */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ScrollAxis {
+    Horizontal,
+    Vertical,
+    Both,
+}
+impl ScrollAxis {
+    pub fn transfer_mode(&self) -> TransferMode {
+        match self {
+            ScrollAxis::Both => TransferMode::Mode3,
+            _ => TransferMode::Mode2,
+        }
+    }
+    pub fn b_bus_address(&self, bg: usize) -> Option<u8> {
+        /* bg is zero-based, like BgMode::layer_format; BG1HOFS is $210D and each layer takes two registers */
+        if bg > 3 { return None; }
+
+        let horizontal = 0x0D + 2 * bg as u8;
+
+        match self {
+            ScrollAxis::Vertical => Some(horizontal + 1),
+            _ => Some(horizontal),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct ScrollLine {
+    pub horizontal: Option<u16>,
+    pub vertical: Option<u16>,
+}
+
+/* a strip of scanlines scrolling at speed times the camera position, plus a fixed offset */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ParallaxBand {
+    pub lines: usize,
+    pub speed: f32,
+    pub offset: i32,
+}
+impl ParallaxBand {
+    pub fn scroll(&self, camera: i32) -> u16 {
+        ((camera as f32 * self.speed).round() as i32).wrapping_add(self.offset) as u16
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParallaxLayout {
+    pub axis: ScrollAxis,
+    pub bands: Vec<ParallaxBand>,
+}
+impl ParallaxLayout {
+    pub fn new(axis: ScrollAxis) -> Self {
+        Self { axis, bands: Vec::new() }
+    }
+    pub fn band(self, lines: usize, speed: f32) -> Self {
+        self.band_with_offset(lines, speed, 0)
+    }
+    pub fn band_with_offset(mut self, lines: usize, speed: f32, offset: i32) -> Self {
+        self.bands.push(ParallaxBand { lines, speed, offset });
+        self
+    }
+    pub fn total_lines(&self) -> usize {
+        self.bands.iter().map(|b| b.lines).sum()
+    }
+    pub fn unit_size(&self) -> usize {
+        self.axis.transfer_mode().unit_size()
+    }
+    fn band_bytes(&self, band: &ParallaxBand, camera_x: i32, camera_y: i32) -> Vec<u8> {
+        let (x, y) = (band.scroll(camera_x).to_le_bytes(), band.scroll(camera_y).to_le_bytes());
+
+        match self.axis {
+            ScrollAxis::Horizontal => x.to_vec(),
+            ScrollAxis::Vertical => y.to_vec(),
+            ScrollAxis::Both => vec![x[0], x[1], y[0], y[1]],
+        }
+    }
+    fn split<F: FnMut(usize, u8)>(&self, mut entry: F) -> Result<(), Error> {
+        /* a non-repeat entry holds its value for up to 127 lines, so taller bands become several entries */
+        for (index, band) in self.bands.iter().enumerate() {
+            if band.lines == 0 { return Err(Error::InvalidLineCount(0)); }
+
+            let mut remaining = band.lines;
+
+            while remaining > 0 {
+                let lines = remaining.min(127);
+
+                entry(index, lines as u8);
+                remaining -= lines;
+            }
+        }
+
+        Ok(())
+    }
+    pub fn scroll_data(&self, camera_x: i32, camera_y: i32) -> Vec<u8> {
+        /* what the game copies to the data area each frame: one scroll unit per band, in band order */
+        self.bands.iter().flat_map(|b| self.band_bytes(b, camera_x, camera_y)).collect()
+    }
+    pub fn indirect_table(&self, data_address: u16) -> Result<HdmaTable, Error> {
+        /* the table never changes; each entry points at its band's slot in the data area scroll_data fills */
+        let unit = self.unit_size();
+        let mut result = HdmaTable::new(self.axis.transfer_mode(), true);
+
+        match self.split(|index, lines| {
+            let pointer = data_address.wrapping_add((index * unit) as u16);
+            result.entries.push(HdmaEntry { line_count: lines, repeat: false, data: HdmaEntryData::Indirect(pointer) });
+        }) {
+            Ok(()) => Ok(result),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn indirect_table_bytes(&self, data_address: u16) -> Result<Vec<u8>, Error> {
+        match self.indirect_table(data_address) {
+            Ok(t) => t.to_bytes(),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn direct_table(&self, camera_x: i32, camera_y: i32) -> Result<HdmaTable, Error> {
+        /* for a scene that never moves, the values can go straight into the table */
+        let mut result = HdmaTable::new(self.axis.transfer_mode(), false);
+        let data: Vec<Vec<u8>> = self.bands.iter().map(|b| self.band_bytes(b, camera_x, camera_y)).collect();
+
+        match self.split(|index, lines| {
+            result.entries.push(HdmaEntry { line_count: lines, repeat: false, data: HdmaEntryData::Direct(data[index].clone()) });
+        }) {
+            Ok(()) => Ok(result),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn channel_params(&self, bg: usize, table: Addr24, data_bank: u8) -> Result<DmaParams, Error> {
+        /* the $43n0-$43n7 block for an indirect channel running the table from indirect_table */
+        let b_bus = match self.axis.b_bus_address(bg) {
+            Some(b) => b,
+            None => return Err(Error::OutOfBounds(bg,3)),
+        };
+        let mut result = DmaParams::new(self.axis.transfer_mode(), b_bus, table, 0);
+
+        result.control |= 0x40;
+        result.indirect_bank = data_bank;
+        Ok(result)
+    }
+}
+
+impl HdmaTable {
+    pub fn scroll_lines(&self, axis: ScrollAxis, rom: &Rom, bank: u8) -> Result<Vec<ScrollLine>, Error> {
+        /* one entry per scanline the table covers; bank is only used to follow an indirect table's pointers */
+        let unit = self.mode.unit_size();
+        if unit != axis.transfer_mode().unit_size() { return Err(Error::DataLengthMismatch(unit,axis.transfer_mode().unit_size())); }
+
+        let blocks = if self.indirect {
+            match self.resolve_indirect(rom, bank) {
+                Ok(b) => b,
+                Err(e) => return Err(e),
+            }
+        }
+        else {
+            self.entries.iter().map(|e| match &e.data {
+                HdmaEntryData::Direct(bytes) => bytes.clone(),
+                HdmaEntryData::Indirect(_) => Vec::new(),
+            }).collect()
+        };
+        let mut result = Vec::<ScrollLine>::with_capacity(self.total_lines());
+
+        for (entry, block) in self.entries.iter().zip(&blocks) {
+            let expected = if entry.repeat { unit * entry.line_count as usize } else { unit };
+            if block.len() != expected { return Err(Error::InvalidHdmaEntry); }
+
+            for line in 0..entry.line_count as usize {
+                /* a repeat entry writes on every line; otherwise the one write holds for the whole entry */
+                let start = if entry.repeat { line * unit } else { 0 };
+                let word = |at: usize| u16::from_le_bytes([block[start + at], block[start + at + 1]]);
+
+                result.push(match axis {
+                    ScrollAxis::Horizontal => ScrollLine { horizontal: Some(word(0)), vertical: None },
+                    ScrollAxis::Vertical => ScrollLine { horizontal: None, vertical: Some(word(0)) },
+                    ScrollAxis::Both => ScrollLine { horizontal: Some(word(0)), vertical: Some(word(2)) },
+                });
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,547 @@
+use crate::{crc32, Error, Rom};
+
+pub const IPS_MAGIC: &[u8; 5] = b"PATCH";
+pub const IPS_EOF: &[u8; 3] = b"EOF";
+/* the offset whose three bytes spell IPS_EOF; a record can never start there */
+pub const IPS_EOF_OFFSET: usize = 0x454F46;
+pub const BPS_MAGIC: &[u8; 4] = b"BPS1";
+/* the target size is only a varint in the patch: nothing past the largest image a cart (plus copier header) can hold gets allocated */
+pub const BPS_MAX_TARGET_SIZE: usize = 0x800000 + 0x200;
+
+pub trait Patch {
+    fn apply(&self, rom: &mut Rom) -> Result<(), Error>;
+    fn metadata(&self) -> Option<PatchMetadata> {
+        None
+    }
+    fn target_size(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PatchMetadata {
+    pub fields: Vec<(String, String)>,
+}
+impl PatchMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn parse(text: &str) -> Self {
+        /* BPS leaves the format open; tools write either BML ("author: name") or XML (<author>name</author>) lines */
+        let mut result = Self::new();
+
+        for line in text.lines().map(|l| l.trim()) {
+            if line.starts_with('<') {
+                let open = match line.find('>') {
+                    Some(i) => i,
+                    None => continue,
+                };
+                let key = &line[1..open];
+                let close = format!("</{}>", key);
+
+                if let Some(end) = line.rfind(&close) {
+                    if end > open { result.set(key, &line[open+1..end]); }
+                }
+            }
+            else if let Some(colon) = line.find(':') {
+                result.set(line[..colon].trim(), line[colon+1..].trim());
+            }
+        }
+
+        result
+    }
+    pub fn to_text(&self) -> String {
+        self.fields.iter().map(|(k, v)| format!("{}: {}\n", k, v)).collect()
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some(field) => field.1 = value.to_string(),
+            None => self.fields.push((key.to_string(), value.to_string())),
+        }
+    }
+    pub fn author(&self) -> Option<&str> {
+        self.get("author")
+    }
+    pub fn description(&self) -> Option<&str> {
+        self.get("description")
+    }
+    pub fn with_author(mut self, author: &str) -> Self {
+        self.set("author", author);
+        self
+    }
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.set("description", description);
+        self
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum IpsRecord {
+    Data { offset: usize, data: Vec<u8> },
+    Rle { offset: usize, length: usize, value: u8 },
+}
+impl IpsRecord {
+    pub fn offset(&self) -> usize {
+        match self {
+            IpsRecord::Data { offset, .. } | IpsRecord::Rle { offset, .. } => *offset,
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            IpsRecord::Data { data, .. } => data.len(),
+            IpsRecord::Rle { length, .. } => *length,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct IpsPatch {
+    pub records: Vec<IpsRecord>,
+    pub truncate: Option<usize>,
+}
+impl IpsPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn parse<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        let buf = data.as_ref();
+        if buf.len() < IPS_MAGIC.len() { return Err(Error::TruncatedData(buf.len())); }
+        if &buf[..IPS_MAGIC.len()] != IPS_MAGIC { return Err(Error::BadMagic); }
+
+        let mut result = Self::new();
+        let mut cursor = IPS_MAGIC.len();
+
+        loop {
+            if cursor + 3 > buf.len() { return Err(Error::TruncatedData(buf.len())); }
+            if &buf[cursor..cursor+3] == IPS_EOF { cursor += 3; break; }
+            if cursor + 5 > buf.len() { return Err(Error::TruncatedData(buf.len())); }
+
+            let offset = read_u24_be(&buf[cursor..]);
+            let size = read_u16_be(&buf[cursor+3..]);
+            cursor += 5;
+
+            if size == 0 {
+                if cursor + 3 > buf.len() { return Err(Error::TruncatedData(buf.len())); }
+
+                result.records.push(IpsRecord::Rle { offset, length: read_u16_be(&buf[cursor..]), value: buf[cursor+2] });
+                cursor += 3;
+            }
+            else {
+                if cursor + size > buf.len() { return Err(Error::TruncatedData(buf.len())); }
+
+                result.records.push(IpsRecord::Data { offset, data: buf[cursor..cursor+size].to_vec() });
+                cursor += size;
+            }
+        }
+
+        /* the truncation extension is three more bytes after EOF */
+        if cursor + 3 <= buf.len() { result.truncate = Some(read_u24_be(&buf[cursor..])); }
+
+        Ok(result)
+    }
+    pub fn from_diff<A: AsRef<[u8]>, B: AsRef<[u8]>>(original: A, modified: B) -> Result<Self, Error> {
+        let (original, modified) = (original.as_ref(), modified.as_ref());
+        if modified.len() > 0x1000000 { return Err(Error::OutOfBounds(modified.len(),0x1000000)); }
+
+        let mut result = Self::new();
+        let mut cursor = 0usize;
+
+        while cursor < modified.len() {
+            if cursor < original.len() && original[cursor] == modified[cursor] { cursor += 1; continue; }
+
+            let mut start = cursor;
+
+            /* a record at 0x454F46 would read back as the EOF marker, so start one byte early */
+            if start == IPS_EOF_OFFSET { start -= 1; }
+
+            let mut end = cursor;
+
+            while end < modified.len() && end - start < 0xFFFF && (end >= original.len() || original[end] != modified[end]) { end += 1; }
+
+            result.records.push(IpsRecord::Data { offset: start, data: modified[start..end].to_vec() });
+            cursor = end;
+        }
+
+        if modified.len() < original.len() { result.truncate = Some(modified.len()); }
+
+        Ok(result)
+    }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut result = IPS_MAGIC.to_vec();
+
+        for record in &self.records {
+            if record.offset() > 0xFFFFFF { return Err(Error::OutOfBounds(record.offset(),0x1000000)); }
+            if record.offset() == IPS_EOF_OFFSET { return Err(Error::ReservedPatchOffset(record.offset())); }
+            if record.len() > 0xFFFF { return Err(Error::OutOfBounds(record.len(),0x10000)); }
+
+            result.extend_from_slice(&(record.offset() as u32).to_be_bytes()[1..]);
+
+            match record {
+                IpsRecord::Data { data, .. } => {
+                    result.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                    result.extend_from_slice(data);
+                },
+                IpsRecord::Rle { length, value, .. } => {
+                    result.extend_from_slice(&[0, 0]);
+                    result.extend_from_slice(&(*length as u16).to_be_bytes());
+                    result.push(*value);
+                },
+            }
+        }
+
+        result.extend_from_slice(IPS_EOF);
+
+        if let Some(size) = self.truncate { result.extend_from_slice(&(size as u32).to_be_bytes()[1..]); }
+
+        Ok(result)
+    }
+    pub fn truncate_to(mut self, size: Option<usize>) -> Self {
+        self.truncate = size;
+        self
+    }
+}
+impl Patch for IpsPatch {
+    fn apply(&self, rom: &mut Rom) -> Result<(), Error> {
+        /* IPS offsets are raw file offsets, copier header included */
+        for record in &self.records {
+            let end = record.offset() + record.len();
+            if end > rom.len() { rom.resize(end); }
+
+            let result = match record {
+                IpsRecord::Data { offset, data } => rom.write(*offset, data),
+                IpsRecord::Rle { offset, length, value } => rom.write(*offset, vec![*value; *length]),
+            };
+
+            if let Err(e) = result { return Err(e); }
+        }
+
+        if let Some(size) = self.truncate { rom.resize(size); }
+
+        Ok(())
+    }
+    fn target_size(&self) -> Option<usize> {
+        self.truncate
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BpsAction {
+    SourceRead(usize),
+    TargetRead(Vec<u8>),
+    SourceCopy { length: usize, offset: isize },
+    TargetCopy { length: usize, offset: isize },
+}
+impl BpsAction {
+    pub fn len(&self) -> usize {
+        /* bytes the action adds to the target */
+        match self {
+            BpsAction::SourceRead(length) => *length,
+            BpsAction::TargetRead(data) => data.len(),
+            BpsAction::SourceCopy { length, .. } | BpsAction::TargetCopy { length, .. } => *length,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct BpsPatch {
+    pub source_size: usize,
+    pub target_size: usize,
+    pub metadata: Vec<u8>,
+    pub actions: Vec<BpsAction>,
+    pub source_crc: u32,
+    pub target_crc: u32,
+}
+impl BpsPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn parse<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        let buf = data.as_ref();
+        if buf.len() < BPS_MAGIC.len() + 12 { return Err(Error::TruncatedData(buf.len())); }
+        if &buf[..BPS_MAGIC.len()] != BPS_MAGIC { return Err(Error::BadMagic); }
+
+        let footer = buf.len() - 12;
+        let patch_crc = read_u32_le(&buf[footer+8..]);
+        let actual = crc32(&buf[..footer+8]);
+
+        if patch_crc != actual { return Err(Error::PatchChecksumMismatch(patch_crc,actual)); }
+
+        let mut result = Self::new();
+        let mut cursor = BPS_MAGIC.len();
+        let mut header = [0usize; 3];
+
+        for value in header.iter_mut() {
+            match read_varint(&buf[..footer], &mut cursor) {
+                Ok(v) => *value = v,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let [source_size, target_size, metadata_size] = header;
+        if metadata_size > footer - cursor { return Err(Error::TruncatedData(buf.len())); }
+
+        result.source_size = source_size;
+        result.target_size = target_size;
+        result.metadata = buf[cursor..cursor+metadata_size].to_vec();
+        cursor += metadata_size;
+
+        while cursor < footer {
+            let data = match read_varint(&buf[..footer], &mut cursor) {
+                Ok(d) => d,
+                Err(e) => return Err(e),
+            };
+            let length = (data >> 2) + 1;
+
+            let action = match data & 3 {
+                0 => BpsAction::SourceRead(length),
+                1 => {
+                    if cursor + length > footer { return Err(Error::TruncatedData(buf.len())); }
+
+                    cursor += length;
+                    BpsAction::TargetRead(buf[cursor-length..cursor].to_vec())
+                },
+                kind => {
+                    let offset = match read_signed_varint(&buf[..footer], &mut cursor) {
+                        Ok(o) => o,
+                        Err(e) => return Err(e),
+                    };
+
+                    if kind == 2 { BpsAction::SourceCopy { length, offset } }
+                    else { BpsAction::TargetCopy { length, offset } }
+                },
+            };
+
+            result.actions.push(action);
+        }
+
+        result.source_crc = read_u32_le(&buf[footer..]);
+        result.target_crc = read_u32_le(&buf[footer+4..]);
+
+        Ok(result)
+    }
+    pub fn from_diff<A: AsRef<[u8]>, B: AsRef<[u8]>>(original: A, modified: B) -> Self {
+        /* a linear diff: unchanged stretches come from the source, everything else is stored literally */
+        let (original, modified) = (original.as_ref(), modified.as_ref());
+        let same = |i: usize| i < original.len() && original[i] == modified[i];
+        let mut result = Self::new();
+        let mut cursor = 0usize;
+
+        while cursor < modified.len() {
+            let start = cursor;
+
+            if same(cursor) {
+                while cursor < modified.len() && same(cursor) { cursor += 1; }
+
+                result.actions.push(BpsAction::SourceRead(cursor - start));
+            }
+            else {
+                while cursor < modified.len() && !same(cursor) { cursor += 1; }
+
+                result.actions.push(BpsAction::TargetRead(modified[start..cursor].to_vec()));
+            }
+        }
+
+        result.source_size = original.len();
+        result.target_size = modified.len();
+        result.source_crc = crc32(original);
+        result.target_crc = crc32(modified);
+        result
+    }
+    pub fn metadata_text(&self) -> String {
+        String::from_utf8_lossy(&self.metadata).into_owned()
+    }
+    pub fn set_metadata(&mut self, metadata: &PatchMetadata) {
+        self.metadata = metadata.to_text().into_bytes();
+    }
+    pub fn with_metadata(mut self, metadata: &PatchMetadata) -> Self {
+        self.set_metadata(metadata);
+        self
+    }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        /* lengths are stored minus one, so an empty action has no encoding */
+        if let Some(index) = self.actions.iter().position(|a| a.is_empty()) { return Err(Error::EmptyPatchAction(index)); }
+
+        let mut result = BPS_MAGIC.to_vec();
+
+        write_varint(&mut result, self.source_size);
+        write_varint(&mut result, self.target_size);
+        write_varint(&mut result, self.metadata.len());
+        result.extend_from_slice(&self.metadata);
+
+        for action in &self.actions {
+            match action {
+                BpsAction::SourceRead(length) => write_varint(&mut result, (length - 1) << 2),
+                BpsAction::TargetRead(data) => {
+                    write_varint(&mut result, ((data.len() - 1) << 2) | 1);
+                    result.extend_from_slice(data);
+                },
+                BpsAction::SourceCopy { length, offset } => {
+                    write_varint(&mut result, ((length - 1) << 2) | 2);
+                    write_signed_varint(&mut result, *offset);
+                },
+                BpsAction::TargetCopy { length, offset } => {
+                    write_varint(&mut result, ((length - 1) << 2) | 3);
+                    write_signed_varint(&mut result, *offset);
+                },
+            }
+        }
+
+        result.extend_from_slice(&self.source_crc.to_le_bytes());
+        result.extend_from_slice(&self.target_crc.to_le_bytes());
+
+        let patch_crc = crc32(&result);
+        result.extend_from_slice(&patch_crc.to_le_bytes());
+        Ok(result)
+    }
+    pub fn apply_to(&self, source: &[u8]) -> Result<Vec<u8>, Error> {
+        if source.len() != self.source_size { return Err(Error::DataLengthMismatch(source.len(),self.source_size)); }
+
+        let actual = crc32(source);
+        if actual != self.source_crc { return Err(Error::PatchChecksumMismatch(self.source_crc,actual)); }
+
+        if self.target_size > BPS_MAX_TARGET_SIZE { return Err(Error::UnaddressableRomSize(self.target_size,BPS_MAX_TARGET_SIZE)); }
+
+        /* the actions have to add up to the declared size before anything is allocated or copied */
+        let planned = self.actions.iter().try_fold(0usize, |total, a| total.checked_add(a.len()));
+        if planned != Some(self.target_size) { return Err(Error::DataLengthMismatch(planned.unwrap_or(usize::MAX),self.target_size)); }
+
+        let mut target = Vec::<u8>::with_capacity(self.target_size);
+        let (mut source_relative, mut target_relative) = (0isize, 0isize);
+
+        for action in &self.actions {
+            match action {
+                BpsAction::SourceRead(length) => {
+                    let start = target.len();
+                    if start + length > source.len() { return Err(Error::OutOfBounds(start+length,source.len())); }
+
+                    target.extend_from_slice(&source[start..start+length]);
+                },
+                BpsAction::TargetRead(data) => target.extend_from_slice(data),
+                BpsAction::SourceCopy { length, offset } => {
+                    source_relative = match source_relative.checked_add(*offset) {
+                        Some(r) => r,
+                        None => return Err(Error::OutOfBounds(usize::MAX,source.len())),
+                    };
+
+                    let start = source_relative as usize;
+                    if source_relative < 0 || start + length > source.len() { return Err(Error::OutOfBounds(start+length,source.len())); }
+
+                    target.extend_from_slice(&source[start..start+length]);
+                    source_relative += *length as isize;
+                },
+                BpsAction::TargetCopy { length, offset } => {
+                    target_relative = match target_relative.checked_add(*offset) {
+                        Some(r) => r,
+                        None => return Err(Error::OutOfBounds(usize::MAX,target.len())),
+                    };
+
+                    if target_relative < 0 || target_relative as usize >= target.len() { return Err(Error::OutOfBounds(target_relative.max(0) as usize,target.len())); }
+
+                    /* the copy may overlap what it is writing, which is how BPS encodes runs */
+                    for _ in 0..*length {
+                        let byte = target[target_relative as usize];
+                        target.push(byte);
+                        target_relative += 1;
+                    }
+                },
+            }
+        }
+
+        if target.len() != self.target_size { return Err(Error::DataLengthMismatch(target.len(),self.target_size)); }
+
+        let actual = crc32(&target);
+        if actual != self.target_crc { return Err(Error::PatchChecksumMismatch(self.target_crc,actual)); }
+
+        Ok(target)
+    }
+}
+impl Patch for BpsPatch {
+    fn apply(&self, rom: &mut Rom) -> Result<(), Error> {
+        /* like IPS, BPS covers the whole file, copier header included */
+        let target = match self.apply_to(rom.as_slice()) {
+            Ok(t) => t,
+            Err(e) => return Err(e),
+        };
+
+        rom.resize(target.len());
+        rom.write(0, target)
+    }
+    fn metadata(&self) -> Option<PatchMetadata> {
+        if self.metadata.is_empty() { None }
+        else { Some(PatchMetadata::parse(&self.metadata_text())) }
+    }
+    fn target_size(&self) -> Option<usize> {
+        Some(self.target_size)
+    }
+}
+
+fn read_u16_be(data: &[u8]) -> usize {
+    ((data[0] as usize) << 8) | data[1] as usize
+}
+
+fn read_u24_be(data: &[u8]) -> usize {
+    ((data[0] as usize) << 16) | ((data[1] as usize) << 8) | data[2] as usize
+}
+
+fn read_u32_le(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn read_varint(data: &[u8], cursor: &mut usize) -> Result<usize, Error> {
+    /* BPS numbers: 7 bits per byte, high bit ends the number, with an implied +1 on every continuation */
+    let mut result = 0usize;
+    let mut shift = 1usize;
+
+    loop {
+        let byte = match data.get(*cursor) {
+            Some(b) => *b as usize,
+            None => return Err(Error::TruncatedData(data.len())),
+        };
+
+        *cursor += 1;
+
+        if shift > usize::MAX >> 8 { return Err(Error::OutOfBounds(shift,usize::MAX >> 8)); }
+
+        result += (byte & 0x7F) * shift;
+
+        if byte & 0x80 != 0 { return Ok(result); }
+
+        shift <<= 7;
+        result += shift;
+    }
+}
+
+fn read_signed_varint(data: &[u8], cursor: &mut usize) -> Result<isize, Error> {
+    match read_varint(data, cursor) {
+        Ok(v) => Ok(if v & 1 != 0 { -((v >> 1) as isize) } else { (v >> 1) as isize }),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let low = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            output.push(0x80 | low);
+            return;
+        }
+
+        output.push(low);
+        value -= 1;
+    }
+}
+
+fn write_signed_varint(output: &mut Vec<u8>, value: isize) {
+    write_varint(output, (value.unsigned_abs() << 1) | (value < 0) as usize);
+}
@@ -0,0 +1,166 @@
+use crate::{Error, Rom};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Peripherals(pub u8);
+impl Peripherals {
+    pub const JOYPAD: u8 = 0x01;
+    pub const MOUSE: u8 = 0x02;
+    pub const SUPER_SCOPE: u8 = 0x04;
+    pub const MULTITAP: u8 = 0x08;
+    pub const JUSTIFIER: u8 = 0x10;
+
+    pub fn has(&self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+    pub fn mouse(&self) -> bool {
+        self.has(Self::MOUSE)
+    }
+    pub fn super_scope(&self) -> bool {
+        self.has(Self::SUPER_SCOPE)
+    }
+    pub fn multitap(&self) -> bool {
+        self.has(Self::MULTITAP)
+    }
+    pub fn justifier(&self) -> bool {
+        self.has(Self::JUSTIFIER)
+    }
+}
+impl std::fmt::Display for Peripherals {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names = [(Self::JOYPAD, "Joypad"), (Self::MOUSE, "Mouse"), (Self::SUPER_SCOPE, "Super Scope"), (Self::MULTITAP, "Multitap"), (Self::JUSTIFIER, "Justifier")];
+        let present = names.iter().filter(|(flag, _)| self.has(*flag)).map(|(_, name)| *name).collect::<Vec<&str>>();
+
+        if present.is_empty() { write!(f, "none") }
+        else { write!(f, "{}", present.join(", ")) }
+    }
+}
+
+/* the header has no peripheral field, so this comes from what the games themselves are known to support.
+   keys are internal header titles (matched as prefixes) or extended header game codes */
+pub const KNOWN_PERIPHERALS: [(&str, u8); 7] = [
+    ("MARIO PAINT", Peripherals::MOUSE),
+    ("SUPER SCOPE 6", Peripherals::SUPER_SCOPE),
+    ("BATTLE CLASH", Peripherals::SUPER_SCOPE),
+    ("METAL COMBAT", Peripherals::SUPER_SCOPE),
+    ("YOSHI'S SAFARI", Peripherals::SUPER_SCOPE),
+    ("LETHAL ENFORCERS", Peripherals::JUSTIFIER),
+    ("SUPER BOMBERMAN", Peripherals::MULTITAP),
+];
+
+/* the 16 bytes at $FFB0; $FFB6-$FFBB are reserved and always zero */
+pub const EXTENDED_HEADER_SIZE: usize = 0x10;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ExtendedHeader {
+    pub maker_code: String,
+    pub game_code: String,
+    pub expansion_flash_size: u8,
+    pub expansion_ram_size: u8,
+    pub special_version: u8,
+    pub chipset_subtype: u8,
+}
+impl ExtendedHeader {
+    pub fn from_data(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < EXTENDED_HEADER_SIZE { return Err(Error::DataLengthMismatch(data.len(),EXTENDED_HEADER_SIZE)); }
+
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).trim_end().to_string();
+
+        Ok(Self {
+            maker_code: text(&data[..2]),
+            game_code: text(&data[2..6]),
+            expansion_flash_size: data[0xC],
+            expansion_ram_size: data[0xD],
+            special_version: data[0xE],
+            chipset_subtype: data[0xF],
+        })
+    }
+    pub fn expansion_flash_bytes(&self) -> usize {
+        /* same 1KB << n scale as the main header's sizes, with 0 meaning there isn't any */
+        match self.expansion_flash_size {
+            0 => 0,
+            size => 0x400usize << size.min(24),
+        }
+    }
+    pub fn expansion_ram_bytes(&self) -> usize {
+        match self.expansion_ram_size {
+            0 => 0,
+            size => 0x400usize << size.min(24),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PeripheralDatabase {
+    pub entries: Vec<(String, Peripherals)>,
+}
+impl PeripheralDatabase {
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+    pub fn insert(&mut self, key: &str, peripherals: Peripherals) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = peripherals,
+            None => self.entries.push((key.to_string(), peripherals)),
+        }
+    }
+    pub fn lookup(&self, title: &str, game_code: Option<&str>) -> Option<Peripherals> {
+        /* an exact game code beats a title prefix, and the longest matching prefix wins so sequels can differ */
+        if let Some(code) = game_code {
+            if let Some((_, p)) = self.entries.iter().find(|(k, _)| k == code) { return Some(*p); }
+        }
+
+        self.entries.iter()
+            .filter(|(k, _)| title.starts_with(k.as_str()))
+            .max_by_key(|(k, _)| k.len())
+            .map(|(_, p)| *p)
+    }
+}
+impl Default for PeripheralDatabase {
+    fn default() -> Self {
+        Self { entries: KNOWN_PERIPHERALS.iter().map(|(k, p)| (k.to_string(), Peripherals(*p))).collect() }
+    }
+}
+
+impl Rom {
+    pub fn extended_header(&self) -> Result<Option<ExtendedHeader>, Error> {
+        /* the 16 bytes in front of the header only mean anything when the old maker code says so */
+        let address = match self.find_valid_snes_header_address() {
+            Ok(a) => a,
+            Err(e) => return Err(e),
+        };
+        let header = match self.get_snes_header(address) {
+            Ok(h) => h,
+            Err(e) => return Err(e),
+        };
+
+        if !header.has_extended_header() { return Ok(None); }
+
+        let offset = address.to_offset(self) - EXTENDED_HEADER_SIZE;
+        let data = match self.read(offset, EXTENDED_HEADER_SIZE) {
+            Ok(d) => d,
+            Err(e) => return Err(e),
+        };
+
+        match ExtendedHeader::from_data(data) {
+            Ok(h) => Ok(Some(h)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn supported_peripherals(&self) -> Result<Peripherals, Error> {
+        self.supported_peripherals_with(&PeripheralDatabase::default())
+    }
+    pub fn supported_peripherals_with(&self, database: &PeripheralDatabase) -> Result<Peripherals, Error> {
+        let title = match self.find_valid_snes_header() {
+            Ok(h) => h.get_title(),
+            Err(e) => return Err(e),
+        };
+        let extended = match self.extended_header() {
+            Ok(e) => e,
+            Err(e) => return Err(e),
+        };
+        let known = database.lookup(&title, extended.as_ref().map(|e| e.game_code.as_str()));
+
+        /* every game takes a standard pad, whatever else it supports */
+        Ok(Peripherals(Peripherals::JOYPAD | known.map_or(0, |p| p.0)))
+    }
+}
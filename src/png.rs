@@ -0,0 +1,490 @@
+use crate::{crc32, Bgr555, BitOrder, BitReader, Error, PaletteDelta, PixelBuffer, Rgb888};
+
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/* far past anything a SNES asset needs; only a hostile or corrupt file gets near either */
+pub const ZLIB_MAX_OUTPUT: usize = 0x4000000;
+pub const PNG_MAX_DECODED_SIZE: usize = 0x4000000;
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    /* stored deflate blocks only: images here are small and exactness matters more than size */
+    let mut result = vec![0x78, 0x01];
+    let mut chunks = data.chunks(0xFFFF).peekable();
+
+    if chunks.peek().is_none() { result.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]); }
+
+    while let Some(chunk) = chunks.next() {
+        let length = chunk.len() as u16;
+
+        result.push(if chunks.peek().is_none() { 1 } else { 0 });
+        result.extend_from_slice(&length.to_le_bytes());
+        result.extend_from_slice(&(!length).to_le_bytes());
+        result.extend_from_slice(chunk);
+    }
+
+    result.extend_from_slice(&adler32(data).to_be_bytes());
+    result
+}
+
+fn push_chunk(png: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    let mut body = name.to_vec();
+    body.extend_from_slice(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(&body);
+    png.extend_from_slice(&crc32(&body).to_be_bytes());
+}
+
+fn encode(width: usize, height: usize, color_type: u8, channels: usize, pixels: &[u8], palette: Option<&[Rgb888]>) -> Vec<u8> {
+    let mut png = PNG_SIGNATURE.to_vec();
+    let mut ihdr = Vec::<u8>::new();
+
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, color_type, 0, 0, 0]);
+    push_chunk(&mut png, b"IHDR", &ihdr);
+
+    if let Some(colors) = palette {
+        let plte = colors.iter().flat_map(|c| vec![(c.0 >> 16) as u8, (c.0 >> 8) as u8, c.0 as u8]).collect::<Vec<u8>>();
+        push_chunk(&mut png, b"PLTE", &plte);
+    }
+
+    /* every scanline gets filter type 0 */
+    let mut raw = Vec::<u8>::with_capacity((width * channels + 1) * height);
+
+    for row in pixels.chunks(width * channels).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    push_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    push_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn encode_indexed_png(width: usize, height: usize, indices: &[u8], palette: &[Rgb888]) -> Vec<u8> {
+    encode(width, height, 3, 1, indices, Some(&palette[..palette.len().min(256)]))
+}
+
+impl PixelBuffer {
+    pub fn to_png(&self) -> Vec<u8> {
+        let rgb = self.pixels.iter().flat_map(|c| vec![(c.0 >> 16) as u8, (c.0 >> 8) as u8, c.0 as u8]).collect::<Vec<u8>>();
+
+        encode(self.width, self.height, 2, 3, &rgb, None)
+    }
+    pub fn from_png(data: &[u8]) -> Result<Self, Error> {
+        match decode_png(data) {
+            Ok(p) => p.to_pixel_buffer(),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+struct InflateTable {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+impl InflateTable {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &l in lengths { counts[l as usize] += 1; }
+        counts[0] = 0;
+
+        let mut symbols = (0..lengths.len() as u16).filter(|&s| lengths[s as usize] != 0).collect::<Vec<u16>>();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+
+        Self { counts, symbols }
+    }
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        /* deflate packs Huffman codes most significant bit first, unlike everything else in the stream */
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for length in 1..16 {
+            code |= match reader.read(1) {
+                Ok(b) => b as i32,
+                Err(e) => return Err(e),
+            };
+
+            let count = self.counts[length] as i32;
+            if code - count < first { return Ok(self.symbols[(index + code - first) as usize]); }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(Error::InvalidImage("bad deflate code".to_string()))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &InflateTable, distances: &InflateTable, limit: usize) -> Result<(), Error> {
+    loop {
+        let symbol = match literals.decode(reader) {
+            Ok(s) => s as usize,
+            Err(e) => return Err(e),
+        };
+
+        if symbol < 256 && output.len() >= limit { return Err(Error::InvalidImage(format!("inflates past 0x{:X} bytes", limit))); }
+        if symbol < 256 { output.push(symbol as u8); continue; }
+        if symbol == 256 { return Ok(()); }
+        if symbol - 257 >= LENGTH_BASE.len() { return Err(Error::InvalidImage("bad length symbol".to_string())); }
+
+        let length = match reader.read(LENGTH_EXTRA[symbol-257] as usize) {
+            Ok(e) => LENGTH_BASE[symbol-257] as usize + e as usize,
+            Err(e) => return Err(e),
+        };
+        let code = match distances.decode(reader) {
+            Ok(d) if (d as usize) < DISTANCE_BASE.len() => d as usize,
+            Ok(_) => return Err(Error::InvalidImage("bad distance symbol".to_string())),
+            Err(e) => return Err(e),
+        };
+        let distance = match reader.read(DISTANCE_EXTRA[code] as usize) {
+            Ok(e) => DISTANCE_BASE[code] as usize + e as usize,
+            Err(e) => return Err(e),
+        };
+
+        if distance > output.len() { return Err(Error::InvalidImage("distance before start".to_string())); }
+        if length > limit - output.len() { return Err(Error::InvalidImage(format!("inflates past 0x{:X} bytes", limit))); }
+
+        for _ in 0..length { output.push(output[output.len() - distance]); }
+    }
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(InflateTable, InflateTable), Error> {
+    const ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+    let header = match reader.read(14) {
+        Ok(h) => h as usize,
+        Err(e) => return Err(e),
+    };
+    let (literal_count, distance_count, code_count) = ((header & 0x1F) + 257, ((header >> 5) & 0x1F) + 1, (header >> 10) + 4);
+    let mut code_lengths = [0u8; 19];
+
+    for &index in ORDER.iter().take(code_count) {
+        match reader.read(3) {
+            Ok(l) => code_lengths[index] = l as u8,
+            Err(e) => return Err(e),
+        }
+    }
+
+    let code_table = InflateTable::new(&code_lengths);
+    let mut lengths = Vec::<u8>::new();
+
+    while lengths.len() < literal_count + distance_count {
+        let symbol = match code_table.decode(reader) {
+            Ok(s) => s,
+            Err(e) => return Err(e),
+        };
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match (lengths.last(), reader.read(2)) {
+                (Some(&last), Ok(r)) => (last, 3 + r as usize),
+                (None, _) => return Err(Error::InvalidImage("repeat with no previous length".to_string())),
+                (_, Err(e)) => return Err(e),
+            },
+            17 => match reader.read(3) {
+                Ok(r) => (0, 3 + r as usize),
+                Err(e) => return Err(e),
+            },
+            _ => match reader.read(7) {
+                Ok(r) => (0, 11 + r as usize),
+                Err(e) => return Err(e),
+            },
+        };
+
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+
+    if lengths.len() > literal_count + distance_count { return Err(Error::InvalidImage("code lengths overrun".to_string())); }
+
+    Ok((InflateTable::new(&lengths[..literal_count]), InflateTable::new(&lengths[literal_count..])))
+}
+
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    zlib_decompress_limited(data, ZLIB_MAX_OUTPUT)
+}
+
+pub fn zlib_decompress_limited(data: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+    /* a few hundred bytes of deflate can claim gigabytes, so the output stops at limit */
+    if data.len() < 2 { return Err(Error::TruncatedData(data.len())); }
+    if data[0] & 0x0F != 8 || !((data[0] as u16) << 8 | data[1] as u16).is_multiple_of(31) { return Err(Error::BadMagic); }
+
+    let mut reader = BitReader::new(&data[2..]).order(BitOrder::LsbFirst);
+    let mut output = Vec::<u8>::new();
+
+    loop {
+        let (last, kind) = match (reader.read(1), reader.read(2)) {
+            (Ok(l), Ok(k)) => (l == 1, k),
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        };
+
+        let result = match kind {
+            0 => {
+                reader.align();
+
+                let start = reader.position() / 8;
+                let bytes = reader.data();
+                if start + 4 > bytes.len() { return Err(Error::TruncatedData(bytes.len())); }
+
+                let length = u16::from_le_bytes([bytes[start], bytes[start+1]]) as usize;
+                if start + 4 + length > bytes.len() { return Err(Error::TruncatedData(bytes.len())); }
+                if length > limit - output.len() { return Err(Error::InvalidImage(format!("inflates past 0x{:X} bytes", limit))); }
+
+                output.extend_from_slice(&bytes[start+4..start+4+length]);
+                reader.seek((start + 4 + length) * 8);
+                Ok(())
+            },
+            1 => {
+                let mut lengths = vec![8u8; 288];
+                for l in lengths.iter_mut().take(256).skip(144) { *l = 9; }
+                for l in lengths.iter_mut().take(280).skip(256) { *l = 7; }
+
+                inflate_block(&mut reader, &mut output, &InflateTable::new(&lengths), &InflateTable::new(&[5u8; 30]), limit)
+            },
+            2 => match dynamic_tables(&mut reader) {
+                Ok((literals, distances)) => inflate_block(&mut reader, &mut output, &literals, &distances, limit),
+                Err(e) => Err(e),
+            },
+            _ => Err(Error::InvalidImage("reserved deflate block type".to_string())),
+        };
+
+        if let Err(e) = result { return Err(e); }
+        if last { break; }
+    }
+
+    Ok(output)
+}
+
+/* how image colors become indices into the target palette: Keep trusts the file's own indices, Nearest
+   matches each color to the closest entry, Strict matches exactly or fails */
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum PaletteRemap {
+    #[default]
+    Keep,
+    Nearest,
+    Strict,
+}
+impl PaletteRemap {
+    pub fn index_of(&self, color: Rgb888, palette: &[Bgr555], preferred: Option<usize>) -> Result<u8, Error> {
+        /* colors are compared at SNES precision; a file index that already holds the color wins over the first
+           entry with it, so duplicate colors (transparent black, usually) stay where the artist put them */
+        let target = Bgr555::from(color);
+
+        if let Some(i) = preferred {
+            if palette.get(i) == Some(&target) { return Ok(i as u8); }
+        }
+        if let Some(i) = palette.iter().position(|&c| c == target) { return Ok(i as u8); }
+
+        match self {
+            PaletteRemap::Nearest if !palette.is_empty() => Ok(palette.iter().enumerate()
+                .min_by_key(|(i, &c)| PaletteDelta { index: *i as u8, before: c, after: target }.distance())
+                .map_or(0, |(i, _)| i as u8)),
+            _ => Err(Error::UnmatchedColor(color)),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DecodedPng {
+    pub width: usize,
+    pub height: usize,
+    pub color_type: u8,
+    pub bit_depth: u8,
+    pub palette: Vec<Rgb888>,
+    pub samples: Vec<u8>,
+}
+impl DecodedPng {
+    pub fn indices(&self) -> Result<Vec<u8>, Error> {
+        /* indexed images give their indices directly; greyscale ones are read back as a ramp over the bit depth */
+        match self.color_type {
+            3 => Ok(self.samples.clone()),
+            0 => Ok(self.samples.clone()),
+            _ => Err(Error::InvalidImage(format!("color type {} has no palette indices", self.color_type))),
+        }
+    }
+    pub fn remap_indices(&self, palette: &[Bgr555], remap: PaletteRemap) -> Result<Vec<u8>, Error> {
+        /* indexed images are remapped entry by entry through their PLTE; anything else pixel by pixel */
+        if remap == PaletteRemap::Keep { return self.indices(); }
+
+        if self.color_type == 3 {
+            let mut table = Vec::<Option<u8>>::with_capacity(self.palette.len());
+
+            for (i, &color) in self.palette.iter().enumerate() {
+                /* entries no pixel uses don't have to match anything */
+                if !self.samples.iter().any(|&s| s as usize == i) { table.push(None); continue; }
+
+                match remap.index_of(color, palette, Some(i)) {
+                    Ok(index) => table.push(Some(index)),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            return self.samples.iter().map(|&s| match table.get(s as usize) {
+                Some(Some(index)) => Ok(*index),
+                _ => Err(Error::InvalidColorIndex(s)),
+            }).collect();
+        }
+
+        let pixels = match self.to_pixel_buffer() {
+            Ok(p) => p,
+            Err(e) => return Err(e),
+        };
+
+        pixels.pixels.iter().map(|&color| remap.index_of(color, palette, None)).collect()
+    }
+    pub fn to_pixel_buffer(&self) -> Result<PixelBuffer, Error> {
+        /* alpha is dropped; SNES output never has any */
+        let channels = match self.color_type {
+            0 | 3 => 1,
+            2 => 3,
+            4 => 2,
+            _ => 4,
+        };
+        let max = ((1u32 << self.bit_depth) - 1).max(1);
+        let mut result = PixelBuffer::new(self.width, self.height);
+
+        if self.samples.len() < self.width * self.height * channels { return Err(Error::TruncatedData(self.samples.len())); }
+
+        for (pixel, sample) in result.pixels.iter_mut().zip(self.samples.chunks_exact(channels)) {
+            *pixel = match self.color_type {
+                3 => match self.palette.get(sample[0] as usize) {
+                    Some(c) => *c,
+                    None => return Err(Error::InvalidColorIndex(sample[0])),
+                },
+                0 | 4 => {
+                    let level = (sample[0] as u32 * 255 / max) as u8;
+                    Rgb888::new(level, level, level)
+                },
+                _ => Rgb888::new(sample[0], sample[1], sample[2]),
+            };
+        }
+
+        Ok(result)
+    }
+}
+
+pub fn decode_png(data: &[u8]) -> Result<DecodedPng, Error> {
+    if data.len() < 8 || data[..8] != PNG_SIGNATURE { return Err(Error::BadMagic); }
+
+    let mut cursor = 8usize;
+    let mut header: Option<(usize, usize, u8, u8, u8)> = None;
+    let mut palette = Vec::<Rgb888>::new();
+    let mut compressed = Vec::<u8>::new();
+
+    while cursor + 12 <= data.len() {
+        let length = u32::from_be_bytes([data[cursor], data[cursor+1], data[cursor+2], data[cursor+3]]) as usize;
+        if cursor + 12 + length > data.len() { return Err(Error::TruncatedData(data.len())); }
+
+        let name = &data[cursor+4..cursor+8];
+        let body = &data[cursor+8..cursor+8+length];
+
+        match name {
+            b"IHDR" if length >= 13 => header = Some((
+                u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize,
+                u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize,
+                body[8], body[9], body[12],
+            )),
+            b"PLTE" => palette = body.chunks_exact(3).map(|c| Rgb888(((c[0] as u32) << 16) | ((c[1] as u32) << 8) | c[2] as u32)).collect(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => (),
+        }
+
+        cursor += 12 + length;
+    }
+
+    let (width, height, bit_depth, color_type, interlace) = match header {
+        Some(h) => h,
+        None => return Err(Error::InvalidImage("missing IHDR".to_string())),
+    };
+
+    if interlace != 0 { return Err(Error::InvalidImage("interlaced images are not supported".to_string())); }
+
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(Error::InvalidImage(format!("unknown color type {}", color_type))),
+    };
+
+    if !matches!(bit_depth, 1 | 2 | 4 | 8) || (channels > 1 && bit_depth != 8) { return Err(Error::InvalidImage(format!("bit depth {} is not supported", bit_depth))); }
+
+    /* the header says exactly how much the image data inflates to, so nothing past that is ever produced */
+    let stride = width.checked_mul(channels * bit_depth as usize).map(|bits| bits / 8 + (bits % 8 != 0) as usize);
+    let (stride, expected) = match stride.and_then(|s| (s + 1).checked_mul(height).map(|e| (s, e))) {
+        Some((s, e)) if e <= PNG_MAX_DECODED_SIZE => (s, e),
+        _ => return Err(Error::InvalidImage(format!("{}x{} is too large", width, height))),
+    };
+    let raw = match zlib_decompress_limited(&compressed, expected) {
+        Ok(r) => r,
+        Err(e) => return Err(e),
+    };
+    let pixel_bytes = ((channels * bit_depth as usize) / 8).max(1);
+
+    if raw.len() < (stride + 1) * height { return Err(Error::TruncatedData(raw.len())); }
+
+    let mut rows = vec![0u8; stride * height];
+
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+
+        for x in 0..stride {
+            let a = if x >= pixel_bytes { rows[y*stride + x - pixel_bytes] as i16 } else { 0 };
+            let b = if y > 0 { rows[(y-1)*stride + x] as i16 } else { 0 };
+            let c = if x >= pixel_bytes && y > 0 { rows[(y-1)*stride + x - pixel_bytes] as i16 } else { 0 };
+
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => (a + b) / 2,
+                4 => {
+                    let p = a + b - c;
+                    let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+                    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+                },
+                _ => return Err(Error::InvalidImage(format!("unknown filter {}", filter))),
+            };
+
+            rows[y*stride + x] = line[x].wrapping_add(predicted as u8);
+        }
+    }
+
+    /* unpack sub-byte depths so samples always hold one value per byte */
+    let samples = if bit_depth == 8 { rows } else {
+        let per_byte = 8 / bit_depth as usize;
+        let mask = (1u8 << bit_depth) - 1;
+        let mut result = Vec::<u8>::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let byte = rows[y*stride + x / per_byte];
+                let shift = 8 - bit_depth as usize * (x % per_byte + 1);
+
+                result.push((byte >> shift) & mask);
+            }
+        }
+
+        result
+    };
+
+    Ok(DecodedPng { width, height, color_type, bit_depth, palette, samples })
+}
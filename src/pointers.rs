@@ -0,0 +1,96 @@
+use crate::{Addr24, Error, Mapper, MemoryMap, PointerFormat, PointerTable, Rom};
+
+/* how far a candidate table is followed either side of a hit before giving up */
+pub const MAX_POINTER_TABLE_ENTRIES: usize = 0x400;
+
+/* same_bank says whether the pointer sits in a bank mirroring the target's, the only place a short pointer
+   works without the code changing the data bank first; the low two bytes of every long hit are reported as a short one too */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PointerReference {
+    pub offset: usize,
+    pub format: PointerFormat,
+    pub same_bank: bool,
+}
+
+fn plausible_pointer(mapper: &Mapper, rom_size: usize, data: &[u8], format: PointerFormat) -> bool {
+    let address = match format {
+        PointerFormat::Long => Addr24::new(data[2], u16::from_le_bytes([data[0], data[1]])),
+        PointerFormat::Short(bank) => Addr24::new(bank, u16::from_le_bytes([data[0], data[1]])),
+    };
+
+    matches!(mapper.address_to_pc(address), Ok(pc) if pc < rom_size)
+}
+
+impl Rom {
+    pub fn find_pointers(&self, target: Addr24) -> Result<Vec<PointerReference>, Error> {
+        let mapper = match self.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        let target_pc = match mapper.address_to_pc(target) {
+            Ok(p) if p < self.rom_size() => p,
+            Ok(p) => return Err(Error::OutOfBounds(p,self.rom_size())),
+            Err(e) => return Err(e),
+        };
+
+        /* mirrors keep the low 16 bits, so only those positions need the bank looked at */
+        let low = target.address.to_le_bytes();
+        let data = &self.as_slice()[self.header_size()..];
+        let mut result = Vec::<PointerReference>::new();
+
+        for pc in 0..data.len().saturating_sub(1) {
+            if data[pc] != low[0] || data[pc+1] != low[1] { continue; }
+
+            let bank = match mapper.pc_to_address(pc) {
+                Ok(a) => a.bank,
+                Err(_) => continue,
+            };
+            let same_bank = matches!(mapper.address_to_pc(Addr24::new(bank, target.address)), Ok(p) if p == target_pc);
+
+            result.push(PointerReference { offset: pc + self.header_size(), format: PointerFormat::Short(target.bank), same_bank });
+
+            if let Some(&long_bank) = data.get(pc+2) {
+                if let Ok(p) = mapper.address_to_pc(Addr24::new(long_bank, target.address)) {
+                    if p == target_pc { result.push(PointerReference { offset: pc + self.header_size(), format: PointerFormat::Long, same_bank }); }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn find_pointer_tables(&self, target: Addr24) -> Result<Vec<PointerTable>, Error> {
+        /* a table is a run of at least two entries that all point somewhere in ROM; with no end marker to go on
+           the bounds are a guess, and short tables in particular will often run long */
+        let references = match self.find_pointers(target) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+        let mapper = match self.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        let data = self.as_slice();
+        let mut result = Vec::<PointerTable>::new();
+
+        for reference in references {
+            let size = reference.format.size();
+            let valid = |offset: usize| offset >= self.header_size() && offset + size <= data.len() && plausible_pointer(&mapper, self.rom_size(), &data[offset..offset+size], reference.format);
+
+            let mut start = reference.offset;
+            while start >= size && reference.offset - (start - size) < MAX_POINTER_TABLE_ENTRIES * size && valid(start - size) { start -= size; }
+
+            let mut end = reference.offset + size;
+            while end - start < MAX_POINTER_TABLE_ENTRIES * size && valid(end) { end += size; }
+
+            let count = (end - start) / size;
+            if count < 2 { continue; }
+
+            if result.iter().any(|t| t.offset == start && t.format == reference.format) { continue; }
+
+            let name = format!("table_{:06X}", start);
+            result.push(PointerTable::new(&name, start, count, reference.format));
+        }
+
+        Ok(result)
+    }
+}
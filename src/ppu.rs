@@ -0,0 +1,14 @@
+pub mod registers;
+pub use registers::*;
+
+pub mod modes;
+pub use modes::*;
+
+pub mod opt;
+pub use opt::*;
+
+pub mod mode7;
+pub use mode7::*;
+
+pub mod frame;
+pub use frame::*;
@@ -0,0 +1,278 @@
+use crate::{Bgr555, CpuEvent, Error, PixelBuffer, Rgb888};
+use super::*;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FrameLayer {
+    Bg1,
+    Bg2,
+    Bg3,
+    Bg4,
+    Obj,
+}
+impl FrameLayer {
+    pub fn bit(&self) -> u8 {
+        match self {
+            FrameLayer::Bg1 => LayerMask::BG1,
+            FrameLayer::Bg2 => LayerMask::BG2,
+            FrameLayer::Bg3 => LayerMask::BG3,
+            FrameLayer::Bg4 => LayerMask::BG4,
+            FrameLayer::Obj => LayerMask::OBJ,
+        }
+    }
+    fn window_index(&self) -> usize {
+        match self {
+            FrameLayer::Bg1 => 0,
+            FrameLayer::Bg2 => 1,
+            FrameLayer::Bg3 => 2,
+            FrameLayer::Bg4 => 3,
+            FrameLayer::Obj => 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct LayerPixel {
+    pub color: Bgr555,
+    pub rank: u8,
+    pub palette: u8,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LayerImage {
+    pub layer: FrameLayer,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Option<LayerPixel>>,
+}
+impl LayerImage {
+    pub fn new(layer: FrameLayer, width: usize, height: usize) -> Self {
+        Self { layer, width, height, pixels: vec![None; width*height] }
+    }
+    pub fn get(&self, x: usize, y: usize) -> Option<LayerPixel> {
+        if x >= self.width || y >= self.height { return None; }
+
+        self.pixels[y*self.width+x]
+    }
+    pub fn set(&mut self, x: usize, y: usize, pixel: Option<LayerPixel>) -> Result<(), Error> {
+        if x >= self.width { return Err(Error::OutOfBounds(x,self.width)); }
+        if y >= self.height { return Err(Error::OutOfBounds(y,self.height)); }
+
+        self.pixels[y*self.width+x] = pixel;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PpuState {
+    registers: [u8; 0x40],
+    fixed_color: Bgr555,
+}
+impl PpuState {
+    pub fn new() -> Self {
+        /* start unblanked at full brightness so a preview without an INIDISP write is still visible */
+        let mut registers = [0u8; 0x40];
+        registers[0] = 0x0F;
+
+        Self { registers, fixed_color: Bgr555(0) }
+    }
+    pub fn from_events(events: &[CpuEvent]) -> Self {
+        let mut result = Self::new();
+
+        for event in events {
+            if let CpuEvent::RegisterWrite { register, value, .. } = event { result.write(*register, *value); }
+        }
+
+        result
+    }
+    pub fn write(&mut self, register: u16, value: u8) {
+        if !(INIDISP..=STAT78).contains(&register) { return; }
+
+        /* COLDATA sets the chosen components of the fixed color rather than being a plain latch */
+        if register == COLDATA {
+            let intensity = (value & 0x1F) as u16;
+            let mut color = self.fixed_color.0;
+
+            if value & 0x20 != 0 { color = (color & !0x001F) | intensity; }
+            if value & 0x40 != 0 { color = (color & !0x03E0) | (intensity << 5); }
+            if value & 0x80 != 0 { color = (color & !0x7C00) | (intensity << 10); }
+
+            self.fixed_color = Bgr555(color);
+        }
+
+        self.registers[(register - INIDISP) as usize] = value;
+    }
+    pub fn read(&self, register: u16) -> u8 {
+        if !(INIDISP..=STAT78).contains(&register) { return 0; }
+
+        self.registers[(register - INIDISP) as usize]
+    }
+    pub fn fixed_color(&self) -> Bgr555 {
+        self.fixed_color
+    }
+    pub fn set_fixed_color(&mut self, color: Bgr555) {
+        self.fixed_color = color;
+    }
+    fn window_settings(&self, index: usize) -> (u8, u8) {
+        /* index 0-3 are BG1-BG4, 4 is OBJ and 5 the color window; returns the 4-bit select and 2-bit logic */
+        let select = self.read(W12SEL + (index / 2) as u16) >> ((index % 2) * 4);
+        let logic = if index < 4 { self.read(WBGLOG) >> (index * 2) } else { self.read(WOBJLOG) >> ((index - 4) * 2) };
+
+        (select & 0xF, logic & 3)
+    }
+    fn in_window(&self, index: usize, x: usize) -> bool {
+        let (select, logic) = self.window_settings(index);
+        let inside = |left: u16, right: u16, invert: bool| ((left as usize..=right as usize).contains(&x)) != invert;
+        let w1 = if select & 2 != 0 { Some(inside(self.read(WH0) as u16, self.read(WH1) as u16, select & 1 != 0)) } else { None };
+        let w2 = if select & 8 != 0 { Some(inside(self.read(WH2) as u16, self.read(WH3) as u16, select & 4 != 0)) } else { None };
+
+        match (w1, w2) {
+            (None, None) => false,
+            (Some(a), None) | (None, Some(a)) => a,
+            (Some(a), Some(b)) => match logic {
+                0 => a || b,
+                1 => a && b,
+                2 => a != b,
+                _ => a == b,
+            },
+        }
+    }
+    fn region_active(&self, region: ColorWindowRegion, x: usize) -> bool {
+        match region {
+            ColorWindowRegion::Never => false,
+            ColorWindowRegion::OutsideWindow => !self.in_window(5, x),
+            ColorWindowRegion::InsideWindow => self.in_window(5, x),
+            ColorWindowRegion::Always => true,
+        }
+    }
+    pub fn hires(&self) -> bool {
+        /* modes 5 and 6 are always 512 wide; pseudo-hires gets the same output from any mode */
+        BgMode(self.read(BGMODE)).hires() || Setini(self.read(SETINI)).pseudo_hires()
+    }
+    pub fn interlace(&self) -> bool {
+        Setini(self.read(SETINI)).interlace()
+    }
+    pub fn layer_pixel(&self, layer: FrameLayer, color: Bgr555, priority: u8, palette: u8) -> Option<LayerPixel> {
+        /* None when the current mode has no such layer */
+        BgMode(self.read(BGMODE)).layer_rank(layer, priority).map(|rank| LayerPixel { color, rank, palette })
+    }
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (if self.hires() { width * 2 } else { width }, if self.interlace() { height * 2 } else { height })
+    }
+    fn top_pixel(&self, layers: &[LayerImage], enabled: u8, windowed: u8, (x, y): (usize, usize), scale: (usize, usize), size: (usize, usize)) -> Option<(FrameLayer, LayerPixel)> {
+        /* x and y are output coordinates; a layer wider or taller than the frame is already at output resolution,
+           anything else is stretched. windows always work in 256-pixel space */
+        let mut result: Option<(FrameLayer, LayerPixel)> = None;
+
+        for layer in layers {
+            let bit = layer.layer.bit();
+            if enabled & bit == 0 || (windowed & bit != 0 && self.in_window(layer.layer.window_index(), x / scale.0)) { continue; }
+
+            let lx = if layer.width > size.0 { x } else { x / scale.0 };
+            let ly = if layer.height > size.1 { y } else { y / scale.1 };
+
+            if let Some(pixel) = layer.get(lx, ly) {
+                if result.is_none_or(|(_, p)| pixel.rank > p.rank) { result = Some((layer.layer, pixel)); }
+            }
+        }
+
+        result
+    }
+    pub fn compose(&self, layers: &[LayerImage], backdrop: Bgr555, width: usize, height: usize) -> PixelBuffer {
+        /* layers carry a rank already resolved from the mode's priority order; higher ranks sit in front.
+           width and height are the normal 256-pixel frame; hires doubles the output width and interlace the height */
+        let scale = (if self.hires() { 2 } else { 1 }, if self.interlace() { 2 } else { 1 });
+        let (out_width, out_height) = (width * scale.0, height * scale.1);
+        let mut result = PixelBuffer::new(out_width, out_height);
+        let inidisp = Inidisp(self.read(INIDISP));
+
+        if inidisp.force_blank() { return result; }
+
+        let (tm, ts, tmw, tsw) = (self.read(TM), self.read(TS), self.read(TMW), self.read(TSW));
+        let cgwsel = CgWsel(self.read(CGWSEL));
+        let cgadsub = CgAdsub(self.read(CGADSUB));
+
+        for y in 0..out_height {
+            for x in 0..out_width {
+                let wx = x / scale.0;
+                let main = self.top_pixel(layers, tm, tmw, (x, y), scale, (width, height));
+                let sub = self.top_pixel(layers, ts, tsw, (x, y), scale, (width, height));
+                let clipped = self.region_active(cgwsel.force_black(), wx);
+
+                /* in hires the subscreen is output on the even half-pixels, with the fixed color as its backdrop */
+                if scale.0 == 2 && x % 2 == 0 {
+                    let color = if clipped { Bgr555(0) } else { sub.map_or(self.fixed_color, |(_, p)| p.color) };
+
+                    result.pixels[y*out_width+x] = apply_brightness(color, inidisp.brightness());
+                    continue;
+                }
+
+                let mut color = main.map_or(backdrop, |(_, p)| p.color);
+                let math_layer = match main {
+                    None => cgadsub.backdrop(),
+                    /* only sprites using palettes 4-7 take part in color math */
+                    Some((FrameLayer::Obj, p)) => cgadsub.layers().obj() && p.palette >= 4,
+                    Some((layer, _)) => cgadsub.0 & layer.bit() != 0,
+                };
+
+                if clipped { color = Bgr555(0); }
+
+                if math_layer && !self.region_active(cgwsel.prevent_math(), wx) {
+                    let (operand, halve) = match (cgwsel.add_subscreen(), sub) {
+                        (true, Some((_, p))) => (p.color, cgadsub.half() && !clipped),
+                        /* an empty subscreen falls through to the fixed color and is never halved */
+                        _ => (self.fixed_color, cgadsub.half() && !clipped && !cgwsel.add_subscreen()),
+                    };
+
+                    color = color.cmath(operand, cgadsub.subtract(), halve);
+                }
+
+                result.pixels[y*out_width+x] = apply_brightness(color, inidisp.brightness());
+            }
+        }
+
+        result
+    }
+}
+impl Default for PpuState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn apply_brightness(color: Bgr555, brightness: u8) -> Rgb888 {
+    if brightness >= 15 { return Rgb888::from(color); }
+
+    let mut result = 0u16;
+
+    for shift in &[0u16, 5, 10] {
+        let c = (color.0 >> shift) & 0x1F;
+
+        result |= (c * (brightness as u16 + 1) / 16) << shift;
+    }
+
+    Rgb888::from(Bgr555(result))
+}
+
+pub fn blend_hires(frame: &PixelBuffer) -> PixelBuffer {
+    /* averages each pair of half-pixels the way a TV smears them, giving the pseudo-hires transparency
+       effect at 256 wide; an odd trailing column is kept as it is */
+    let width = frame.width.div_ceil(2);
+    let mut result = PixelBuffer::new(width, frame.height);
+
+    for y in 0..frame.height {
+        for x in 0..width {
+            let left = frame.pixels[y*frame.width+x*2];
+            let right = if x*2+1 < frame.width { frame.pixels[y*frame.width+x*2+1] } else { left };
+            let mut color = 0u32;
+
+            for shift in &[0u32, 8, 16] {
+                let c = (((left.0 >> shift) & 0xFF) + ((right.0 >> shift) & 0xFF)) / 2;
+                color |= c << shift;
+            }
+
+            result.pixels[y*width+x] = Rgb888(color);
+        }
+    }
+
+    result
+}
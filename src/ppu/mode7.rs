@@ -0,0 +1,133 @@
+use crate::{Error, PixelBuffer, Rgb888, SNESPalette, SNESTile, SNESTileMode7};
+
+pub const MODE7_MAP_SIZE: usize = 128;
+pub const MODE7_TILES: usize = 256;
+pub const MODE7_VRAM_SIZE: usize = 0x8000;
+
+impl SNESTileMode7 {
+    pub fn get_extbg(&self, x: usize, y: usize) -> Result<(u8, bool), Error> {
+        /* under EXTBG the top bit of each pixel is BG2's priority, leaving 7 bits of color */
+        match self.get_value(x, y) {
+            Ok(v) => Ok((v & 0x7F, v & 0x80 != 0)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn set_extbg(&mut self, x: usize, y: usize, color: u8, priority: bool) -> Result<(), Error> {
+        if color > 0x7F { return Err(Error::InvalidColorIndex(color)); }
+
+        self.set_value(x, y, color | ((priority as u8) << 7))
+    }
+    pub fn set_color_keep_priority(&mut self, x: usize, y: usize, color: u8) -> Result<(), Error> {
+        /* editing the color of an EXTBG pixel must not drop the priority bit riding along with it */
+        match self.get_extbg(x, y) {
+            Ok((_, priority)) => self.set_extbg(x, y, color, priority),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Mode7Plane {
+    pub tilemap: Vec<u8>,
+    pub tiles: Vec<SNESTileMode7>,
+}
+impl Mode7Plane {
+    pub fn new() -> Self {
+        Self { tilemap: vec![0u8; MODE7_MAP_SIZE*MODE7_MAP_SIZE], tiles: vec![SNESTileMode7::new(); MODE7_TILES] }
+    }
+    pub fn from_vram<B: AsRef<[u8]>>(vram: B) -> Result<Self, Error> {
+        /* mode 7 VRAM interleaves the map in the low bytes with the tile pixels in the high bytes */
+        let data = vram.as_ref();
+        if data.len() < MODE7_VRAM_SIZE { return Err(Error::DataLengthMismatch(data.len(),MODE7_VRAM_SIZE)); }
+
+        let tilemap = data[..MODE7_VRAM_SIZE].iter().step_by(2).copied().collect::<Vec<u8>>();
+        let pixels = data[1..MODE7_VRAM_SIZE].iter().step_by(2).copied().collect::<Vec<u8>>();
+        let mut tiles = Vec::<SNESTileMode7>::new();
+
+        for chunk in pixels.chunks(64) {
+            match SNESTileMode7::from_data(chunk) {
+                Ok(t) => tiles.push(t),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Self { tilemap, tiles })
+    }
+    pub fn to_vram(&self) -> Vec<u8> {
+        let mut result = vec![0u8; MODE7_VRAM_SIZE];
+
+        for (i, entry) in self.tilemap.iter().take(MODE7_MAP_SIZE*MODE7_MAP_SIZE).enumerate() { result[i*2] = *entry; }
+
+        for (t, tile) in self.tiles.iter().take(MODE7_TILES).enumerate() {
+            for (p, pixel) in tile.0.iter().enumerate() { result[(t*64+p)*2+1] = *pixel; }
+        }
+
+        result
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let size = MODE7_MAP_SIZE * 8;
+        let (x, y) = (x % size, y % size);
+        let tile = self.tilemap[(y/8)*MODE7_MAP_SIZE + x/8] as usize;
+
+        self.tiles[tile].0[(y%8)*8 + x%8]
+    }
+    pub fn render<P: SNESPalette>(&self, palette: &P) -> Result<PixelBuffer, Error> {
+        /* the untransformed 1024x1024 plane; color 0 is transparent and left as the backdrop */
+        let size = MODE7_MAP_SIZE * 8;
+        let colors = palette.colors();
+        let mut result = PixelBuffer::new(size, size);
+        let backdrop = colors.first().map(|&c| Rgb888::from(c)).unwrap_or(Rgb888(0));
+
+        for y in 0..size {
+            for x in 0..size {
+                let index = self.pixel(x, y) as usize;
+                let color = match colors.get(index) {
+                    Some(&c) => Rgb888::from(c),
+                    None => return Err(Error::InvalidColorIndex(index as u8)),
+                };
+
+                result.pixels[y*size+x] = if index == 0 { backdrop } else { color };
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn render_extbg<P: SNESPalette>(&self, palette: &P, transparent: Rgb888) -> Result<(PixelBuffer, PixelBuffer), Error> {
+        /* returns BG2's low and high priority layers; pixels belonging to the other layer, or color 0, are filled with transparent */
+        let size = MODE7_MAP_SIZE * 8;
+        let colors = palette.colors();
+        let mut low = PixelBuffer::new(size, size);
+        let mut high = PixelBuffer::new(size, size);
+
+        low.fill_rect(0, 0, size, size, transparent);
+        high.fill_rect(0, 0, size, size, transparent);
+
+        for y in 0..size {
+            for x in 0..size {
+                let value = self.pixel(x, y);
+                let index = (value & 0x7F) as usize;
+                if index == 0 { continue; }
+
+                let color = match colors.get(index) {
+                    Some(&c) => Rgb888::from(c),
+                    None => return Err(Error::InvalidColorIndex(index as u8)),
+                };
+
+                if value & 0x80 != 0 { high.pixels[y*size+x] = color; }
+                else { low.pixels[y*size+x] = color; }
+            }
+        }
+
+        Ok((low, high))
+    }
+    pub fn priority_mask(&self) -> Vec<bool> {
+        let size = MODE7_MAP_SIZE * 8;
+
+        (0..size*size).map(|i| self.pixel(i % size, i / size) & 0x80 != 0).collect()
+    }
+}
+impl Default for Mode7Plane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
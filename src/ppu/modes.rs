@@ -0,0 +1,105 @@
+use crate::{Error, SNESTile};
+use super::{BgMode, FrameLayer};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LayerFormat {
+    Planar(usize),
+    Mode7,
+    Mode7Extbg,
+}
+impl LayerFormat {
+    pub fn bpp(&self) -> usize {
+        match self {
+            LayerFormat::Planar(bpp) => *bpp,
+            LayerFormat::Mode7 => 8,
+            LayerFormat::Mode7Extbg => 7,
+        }
+    }
+    pub fn colors(&self) -> usize {
+        1 << self.bpp()
+    }
+    pub fn accepts<T: SNESTile>(&self) -> bool {
+        /* EXTBG reads the same linear mode 7 tiles, only the top bit becomes priority */
+        match self {
+            LayerFormat::Planar(bpp) => !T::LINEAR && T::BPP == *bpp,
+            LayerFormat::Mode7 | LayerFormat::Mode7Extbg => T::LINEAR,
+        }
+    }
+}
+
+impl BgMode {
+    pub fn layer_count(&self) -> usize {
+        match self.mode() {
+            0 => 4,
+            1 => 3,
+            2..=5 => 2,
+            _ => 1,
+        }
+    }
+    pub fn layer_format(&self, bg: usize) -> Option<LayerFormat> {
+        /* bg is zero-based, matching large_tiles */
+        let bpp: &[usize] = match self.mode() {
+            0 => &[2, 2, 2, 2],
+            1 => &[4, 4, 2],
+            2 => &[4, 4],
+            3 => &[8, 4],
+            4 => &[8, 2],
+            5 => &[4, 2],
+            6 => &[4],
+            _ => return match bg { 0 => Some(LayerFormat::Mode7), _ => None },
+        };
+
+        bpp.get(bg).map(|b| LayerFormat::Planar(*b))
+    }
+    pub fn layer_format_extbg(&self, bg: usize, extbg: bool) -> Option<LayerFormat> {
+        /* SETINI bit 6 exposes the mode 7 image a second time as BG2 */
+        match (self.mode(), bg) {
+            (7, 1) if extbg => Some(LayerFormat::Mode7Extbg),
+            _ => self.layer_format(bg),
+        }
+    }
+    pub fn offset_per_tile(&self) -> bool {
+        matches!(self.mode(), 2 | 4 | 6)
+    }
+    pub fn hires(&self) -> bool {
+        matches!(self.mode(), 5 | 6)
+    }
+    pub fn supports_extbg(&self) -> bool {
+        self.mode() == 7
+    }
+    pub fn supports_direct_color(&self, bg: usize) -> bool {
+        matches!((self.mode(), bg), (3, 0) | (4, 0) | (7, 0))
+    }
+    pub fn priority_order(&self) -> Vec<(FrameLayer, u8)> {
+        /* front to back; the number is the tile's priority bit for backgrounds and the 0-3 priority for sprites */
+        use FrameLayer::*;
+
+        match self.mode() {
+            0 => vec![(Obj, 3), (Bg1, 1), (Bg2, 1), (Obj, 2), (Bg1, 0), (Bg2, 0), (Obj, 1), (Bg3, 1), (Bg4, 1), (Obj, 0), (Bg3, 0), (Bg4, 0)],
+            /* BGMODE bit 3 pulls high priority BG3 tiles in front of everything, sprites included */
+            1 if self.bg3_priority() => vec![(Bg3, 1), (Obj, 3), (Bg1, 1), (Bg2, 1), (Obj, 2), (Bg1, 0), (Bg2, 0), (Obj, 1), (Obj, 0), (Bg3, 0)],
+            1 => vec![(Obj, 3), (Bg1, 1), (Bg2, 1), (Obj, 2), (Bg1, 0), (Bg2, 0), (Obj, 1), (Bg3, 1), (Obj, 0), (Bg3, 0)],
+            2..=5 => vec![(Obj, 3), (Bg1, 1), (Obj, 2), (Bg2, 1), (Obj, 1), (Bg1, 0), (Obj, 0), (Bg2, 0)],
+            6 => vec![(Obj, 3), (Bg1, 1), (Obj, 2), (Obj, 1), (Bg1, 0), (Obj, 0)],
+            /* BG1 has no priority bit in mode 7; BG2 only exists with EXTBG and takes it from the pixel's top bit */
+            _ => vec![(Obj, 3), (Obj, 2), (Bg2, 1), (Obj, 1), (Bg1, 0), (Obj, 0), (Bg2, 0)],
+        }
+    }
+    pub fn layer_rank(&self, layer: FrameLayer, priority: u8) -> Option<u8> {
+        /* higher ranks sit in front, matching LayerPixel::rank; mode 7 BG1 ignores the priority it's given */
+        let priority = match (self.mode(), layer) {
+            (7, FrameLayer::Bg1) => 0,
+            (_, FrameLayer::Obj) => priority & 3,
+            _ => priority & 1,
+        };
+        let order = self.priority_order();
+
+        order.iter().position(|&(l, p)| l == layer && p == priority).map(|i| (order.len() - i) as u8)
+    }
+    pub fn validate_tiles<T: SNESTile>(&self, bg: usize) -> Result<LayerFormat, Error> {
+        match self.layer_format(bg) {
+            Some(f) if f.accepts::<T>() => Ok(f),
+            _ => Err(Error::IncompatibleLayerFormat(self.mode(), bg, T::BPP)),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::Error;
+use super::BgMode;
+
+pub const OPT_COLUMNS: usize = 32;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct OptEntry {
+    pub scroll: u16,
+    pub bg1: bool,
+    pub bg2: bool,
+    pub vertical: bool,
+}
+impl OptEntry {
+    pub fn from_u16(raw: u16) -> Self {
+        Self { scroll: raw & 0x3FF, bg1: raw & 0x2000 != 0, bg2: raw & 0x4000 != 0, vertical: raw & 0x8000 != 0 }
+    }
+    pub fn as_u16(&self) -> u16 {
+        (self.scroll & 0x3FF) | ((self.bg1 as u16) << 13) | ((self.bg2 as u16) << 14) | ((self.vertical as u16) << 15)
+    }
+    pub fn applies_to(&self, bg: usize) -> bool {
+        match bg {
+            0 => self.bg1,
+            1 => self.bg2,
+            _ => false,
+        }
+    }
+    fn enable(&mut self, bg: usize, enabled: bool) {
+        match bg {
+            0 => self.bg1 = enabled,
+            _ => self.bg2 = enabled,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OffsetPerTile {
+    pub mode: u8,
+    pub horizontal: Vec<OptEntry>,
+    pub vertical: Vec<OptEntry>,
+}
+impl OffsetPerTile {
+    pub fn new(mode: BgMode) -> Result<Self, Error> {
+        /* modes 2 and 6 read two BG3 rows (H then V); mode 4 reads one row and picks the direction per entry */
+        match mode.mode() {
+            2 | 6 => Ok(Self { mode: mode.mode(), horizontal: vec![OptEntry::default(); OPT_COLUMNS], vertical: vec![OptEntry::default(); OPT_COLUMNS] }),
+            4 => Ok(Self { mode: 4, horizontal: vec![OptEntry::default(); OPT_COLUMNS], vertical: Vec::new() }),
+            m => Err(Error::UnsupportedMode(m)),
+        }
+    }
+    pub fn size(&self) -> usize {
+        (self.horizontal.len() + self.vertical.len()) * 2
+    }
+    pub fn parse<B: AsRef<[u8]>>(mode: BgMode, bg3_tilemap: B) -> Result<Self, Error> {
+        let data = bg3_tilemap.as_ref();
+        let mut result = match Self::new(mode) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+
+        if data.len() < result.size() { return Err(Error::DataLengthMismatch(data.len(),result.size())); }
+
+        let entries = data[..result.size()].chunks(2).map(|c| OptEntry::from_u16(u16::from_le_bytes([c[0], c[1]]))).collect::<Vec<OptEntry>>();
+
+        result.horizontal = entries[..OPT_COLUMNS].to_vec();
+
+        if result.mode != 4 {
+            result.vertical = entries[OPT_COLUMNS..].to_vec();
+
+            /* bit 15 only means something in mode 4 */
+            for entry in result.horizontal.iter_mut().chain(result.vertical.iter_mut()) { entry.vertical = false; }
+        }
+
+        Ok(result)
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.horizontal.iter().chain(self.vertical.iter()).flat_map(|e| e.as_u16().to_le_bytes()).collect()
+    }
+    pub fn write<B: AsMut<[u8]>>(&self, mut bg3_tilemap: B) -> Result<(), Error> {
+        let data = bg3_tilemap.as_mut();
+        if data.len() < self.size() { return Err(Error::DataLengthMismatch(data.len(),self.size())); }
+
+        data[..self.size()].copy_from_slice(&self.to_bytes());
+
+        Ok(())
+    }
+    pub fn column(&self, bg: usize, column: usize) -> (Option<u16>, Option<u16>) {
+        /* the first screen column never gets an offset, so column here counts from the second one */
+        let pick = |entries: &Vec<OptEntry>, vertical: bool| entries.get(column)
+            .filter(|e| e.applies_to(bg) && e.vertical == vertical)
+            .map(|e| e.scroll);
+
+        match self.mode {
+            4 => (pick(&self.horizontal, false), pick(&self.horizontal, true)),
+            _ => (pick(&self.horizontal, false), pick(&self.vertical, false)),
+        }
+    }
+    pub fn set_column(&mut self, bg: usize, column: usize, horizontal: Option<u16>, vertical: Option<u16>) -> Result<(), Error> {
+        if bg > 1 { return Err(Error::OutOfBounds(bg,2)); }
+        if column >= OPT_COLUMNS { return Err(Error::OutOfBounds(column,OPT_COLUMNS)); }
+
+        if self.mode == 4 {
+            let (scroll, is_vertical) = match (horizontal, vertical) {
+                (Some(_), Some(_)) => return Err(Error::OffsetPerTileConflict(column)),
+                (Some(h), None) => (h, false),
+                (None, Some(v)) => (v, true),
+                (None, None) => { self.horizontal[column].enable(bg, false); return Ok(()); },
+            };
+            let entry = &mut self.horizontal[column];
+
+            /* BG1 and BG2 share the single mode 4 entry, so they must agree on the value and direction */
+            if entry.applies_to(1 - bg) && (entry.scroll != scroll || entry.vertical != is_vertical) { return Err(Error::OffsetPerTileConflict(column)); }
+
+            entry.scroll = scroll;
+            entry.vertical = is_vertical;
+            entry.enable(bg, true);
+
+            return Ok(());
+        }
+
+        let conflicts = |entry: &OptEntry, value: Option<u16>| value.is_some_and(|v| entry.applies_to(1 - bg) && entry.scroll != v);
+        if conflicts(&self.horizontal[column], horizontal) || conflicts(&self.vertical[column], vertical) { return Err(Error::OffsetPerTileConflict(column)); }
+
+        for (entries, value) in [(&mut self.horizontal, horizontal), (&mut self.vertical, vertical)] {
+            let entry = &mut entries[column];
+
+            match value {
+                Some(v) => { entry.scroll = v; entry.enable(bg, true); },
+                None => entry.enable(bg, false),
+            }
+        }
+
+        Ok(())
+    }
+}
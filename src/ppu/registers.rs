@@ -0,0 +1,397 @@
+pub const INIDISP: u16 = 0x2100;
+pub const OBSEL: u16 = 0x2101;
+pub const OAMADDL: u16 = 0x2102;
+pub const OAMADDH: u16 = 0x2103;
+pub const OAMDATA: u16 = 0x2104;
+pub const BGMODE: u16 = 0x2105;
+pub const MOSAIC: u16 = 0x2106;
+pub const BG1SC: u16 = 0x2107;
+pub const BG2SC: u16 = 0x2108;
+pub const BG3SC: u16 = 0x2109;
+pub const BG4SC: u16 = 0x210A;
+pub const BG12NBA: u16 = 0x210B;
+pub const BG34NBA: u16 = 0x210C;
+pub const BG1HOFS: u16 = 0x210D;
+pub const BG1VOFS: u16 = 0x210E;
+pub const BG2HOFS: u16 = 0x210F;
+pub const BG2VOFS: u16 = 0x2110;
+pub const BG3HOFS: u16 = 0x2111;
+pub const BG3VOFS: u16 = 0x2112;
+pub const BG4HOFS: u16 = 0x2113;
+pub const BG4VOFS: u16 = 0x2114;
+pub const VMAIN: u16 = 0x2115;
+pub const VMADDL: u16 = 0x2116;
+pub const VMADDH: u16 = 0x2117;
+pub const VMDATAL: u16 = 0x2118;
+pub const VMDATAH: u16 = 0x2119;
+pub const M7SEL: u16 = 0x211A;
+pub const M7A: u16 = 0x211B;
+pub const M7B: u16 = 0x211C;
+pub const M7C: u16 = 0x211D;
+pub const M7D: u16 = 0x211E;
+pub const M7X: u16 = 0x211F;
+pub const M7Y: u16 = 0x2120;
+pub const CGADD: u16 = 0x2121;
+pub const CGDATA: u16 = 0x2122;
+pub const W12SEL: u16 = 0x2123;
+pub const W34SEL: u16 = 0x2124;
+pub const WOBJSEL: u16 = 0x2125;
+pub const WH0: u16 = 0x2126;
+pub const WH1: u16 = 0x2127;
+pub const WH2: u16 = 0x2128;
+pub const WH3: u16 = 0x2129;
+pub const WBGLOG: u16 = 0x212A;
+pub const WOBJLOG: u16 = 0x212B;
+pub const TM: u16 = 0x212C;
+pub const TS: u16 = 0x212D;
+pub const TMW: u16 = 0x212E;
+pub const TSW: u16 = 0x212F;
+pub const CGWSEL: u16 = 0x2130;
+pub const CGADSUB: u16 = 0x2131;
+pub const COLDATA: u16 = 0x2132;
+pub const SETINI: u16 = 0x2133;
+pub const MPYL: u16 = 0x2134;
+pub const MPYM: u16 = 0x2135;
+pub const MPYH: u16 = 0x2136;
+pub const SLHV: u16 = 0x2137;
+pub const RDOAM: u16 = 0x2138;
+pub const RDVRAML: u16 = 0x2139;
+pub const RDVRAMH: u16 = 0x213A;
+pub const RDCGRAM: u16 = 0x213B;
+pub const OPHCT: u16 = 0x213C;
+pub const OPVCT: u16 = 0x213D;
+pub const STAT77: u16 = 0x213E;
+pub const STAT78: u16 = 0x213F;
+
+const REGISTER_NAMES: [&str; 0x40] = ["INIDISP", "OBSEL", "OAMADDL", "OAMADDH", "OAMDATA", "BGMODE", "MOSAIC", "BG1SC",
+                                      "BG2SC", "BG3SC", "BG4SC", "BG12NBA", "BG34NBA", "BG1HOFS", "BG1VOFS", "BG2HOFS",
+                                      "BG2VOFS", "BG3HOFS", "BG3VOFS", "BG4HOFS", "BG4VOFS", "VMAIN", "VMADDL", "VMADDH",
+                                      "VMDATAL", "VMDATAH", "M7SEL", "M7A", "M7B", "M7C", "M7D", "M7X",
+                                      "M7Y", "CGADD", "CGDATA", "W12SEL", "W34SEL", "WOBJSEL", "WH0", "WH1",
+                                      "WH2", "WH3", "WBGLOG", "WOBJLOG", "TM", "TS", "TMW", "TSW",
+                                      "CGWSEL", "CGADSUB", "COLDATA", "SETINI", "MPYL", "MPYM", "MPYH", "SLHV",
+                                      "RDOAM", "RDVRAML", "RDVRAMH", "RDCGRAM", "OPHCT", "OPVCT", "STAT77", "STAT78"];
+
+pub fn register_name(address: u16) -> Option<&'static str> {
+    if !(INIDISP..=STAT78).contains(&address) { return None; }
+
+    Some(REGISTER_NAMES[(address - INIDISP) as usize])
+}
+
+pub fn format_register_write(address: u16, value: u8) -> String {
+    let name = match register_name(address) {
+        Some(n) => n,
+        None => return format!("${:04X} = ${:02X}", address, value),
+    };
+
+    let detail = match address {
+        INIDISP => Some(Inidisp(value).to_string()),
+        OBSEL => Some(ObjSel(value).to_string()),
+        BGMODE => Some(BgMode(value).to_string()),
+        MOSAIC => Some(Mosaic(value).to_string()),
+        VMAIN => Some(Vmain(value).to_string()),
+        TM | TS | TMW | TSW => Some(LayerMask(value).to_string()),
+        CGWSEL => Some(CgWsel(value).to_string()),
+        CGADSUB => Some(CgAdsub(value).to_string()),
+        SETINI => Some(Setini(value).to_string()),
+        _ => None,
+    };
+
+    match detail {
+        Some(d) => format!("{} = ${:02X} ({})", name, value, d),
+        None => format!("{} = ${:02X}", name, value),
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Inidisp(pub u8);
+impl Inidisp {
+    pub fn new(force_blank: bool, brightness: u8) -> Self {
+        Self(((force_blank as u8) << 7) | (brightness & 0xF))
+    }
+    pub fn force_blank(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+    pub fn brightness(&self) -> u8 {
+        self.0 & 0xF
+    }
+}
+impl std::fmt::Display for Inidisp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.force_blank() { write!(f, "forced blank, brightness {}", self.brightness()) }
+        else { write!(f, "brightness {}", self.brightness()) }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ObjSize {
+    Size8x8And16x16,
+    Size8x8And32x32,
+    Size8x8And64x64,
+    Size16x16And32x32,
+    Size16x16And64x64,
+    Size32x32And64x64,
+    Size16x32And32x64,
+    Size16x32And32x32,
+}
+impl ObjSize {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 7 {
+            0 => ObjSize::Size8x8And16x16,
+            1 => ObjSize::Size8x8And32x32,
+            2 => ObjSize::Size8x8And64x64,
+            3 => ObjSize::Size16x16And32x32,
+            4 => ObjSize::Size16x16And64x64,
+            5 => ObjSize::Size32x32And64x64,
+            6 => ObjSize::Size16x32And32x64,
+            _ => ObjSize::Size16x32And32x32,
+        }
+    }
+    pub fn small(&self) -> (usize, usize) {
+        match self {
+            ObjSize::Size8x8And16x16 | ObjSize::Size8x8And32x32 | ObjSize::Size8x8And64x64 => (8,8),
+            ObjSize::Size16x16And32x32 | ObjSize::Size16x16And64x64 => (16,16),
+            ObjSize::Size32x32And64x64 => (32,32),
+            ObjSize::Size16x32And32x64 | ObjSize::Size16x32And32x32 => (16,32),
+        }
+    }
+    pub fn large(&self) -> (usize, usize) {
+        match self {
+            ObjSize::Size8x8And16x16 => (16,16),
+            ObjSize::Size8x8And32x32 | ObjSize::Size16x16And32x32 | ObjSize::Size16x32And32x32 => (32,32),
+            ObjSize::Size8x8And64x64 | ObjSize::Size16x16And64x64 | ObjSize::Size32x32And64x64 => (64,64),
+            ObjSize::Size16x32And32x64 => (32,64),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ObjSel(pub u8);
+impl ObjSel {
+    pub fn size(&self) -> ObjSize {
+        ObjSize::from_bits(self.0 >> 5)
+    }
+    pub fn name_select(&self) -> u8 {
+        (self.0 >> 3) & 3
+    }
+    pub fn name_base(&self) -> u8 {
+        self.0 & 7
+    }
+    pub fn name_base_address(&self) -> u16 {
+        (self.name_base() as u16) << 13
+    }
+}
+impl std::fmt::Display for ObjSel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (sw, sh) = self.size().small();
+        let (lw, lh) = self.size().large();
+
+        write!(f, "{}x{}/{}x{}, name base ${:04X}, name select {}", sw, sh, lw, lh, self.name_base_address(), self.name_select())
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BgMode(pub u8);
+impl BgMode {
+    pub fn mode(&self) -> u8 {
+        self.0 & 7
+    }
+    pub fn bg3_priority(&self) -> bool {
+        self.0 & 8 != 0
+    }
+    pub fn large_tiles(&self, bg: usize) -> bool {
+        bg < 4 && self.0 & (0x10 << bg) != 0
+    }
+}
+impl std::fmt::Display for BgMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = vec![format!("mode {}", self.mode())];
+
+        if self.bg3_priority() { parts.push("BG3 priority".to_string()); }
+
+        for bg in 0..4 {
+            if self.large_tiles(bg) { parts.push(format!("BG{} 16x16", bg+1)); }
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Mosaic(pub u8);
+impl Mosaic {
+    pub fn size(&self) -> u8 {
+        (self.0 >> 4) + 1
+    }
+    pub fn enabled(&self, bg: usize) -> bool {
+        bg < 4 && self.0 & (1 << bg) != 0
+    }
+}
+impl std::fmt::Display for Mosaic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let layers = (0..4).filter(|&bg| self.enabled(bg)).map(|bg| format!("BG{}", bg+1)).collect::<Vec<String>>();
+
+        if layers.is_empty() { write!(f, "off") }
+        else { write!(f, "{}x{} on {}", self.size(), self.size(), layers.join("|")) }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Vmain(pub u8);
+impl Vmain {
+    pub fn increment_on_high(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+    pub fn remap(&self) -> u8 {
+        (self.0 >> 2) & 3
+    }
+    pub fn increment(&self) -> u16 {
+        match self.0 & 3 {
+            0 => 1,
+            1 => 32,
+            _ => 128,
+        }
+    }
+}
+impl std::fmt::Display for Vmain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let trigger = if self.increment_on_high() { "$2119/$213A" } else { "$2118/$2139" };
+
+        write!(f, "increment {} after {}, remap {}", self.increment(), trigger, self.remap())
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct LayerMask(pub u8);
+impl LayerMask {
+    pub const BG1: u8 = 0x01;
+    pub const BG2: u8 = 0x02;
+    pub const BG3: u8 = 0x04;
+    pub const BG4: u8 = 0x08;
+    pub const OBJ: u8 = 0x10;
+
+    pub fn bg(&self, bg: usize) -> bool {
+        bg < 4 && self.0 & (1 << bg) != 0
+    }
+    pub fn obj(&self) -> bool {
+        self.0 & Self::OBJ != 0
+    }
+}
+impl std::fmt::Display for LayerMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut layers = (0..4).filter(|&bg| self.bg(bg)).map(|bg| format!("BG{}", bg+1)).collect::<Vec<String>>();
+
+        if self.obj() { layers.push("OBJ".to_string()); }
+
+        if layers.is_empty() { write!(f, "none") }
+        else { write!(f, "{}", layers.join("|")) }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ColorWindowRegion {
+    Never,
+    OutsideWindow,
+    InsideWindow,
+    Always,
+}
+impl ColorWindowRegion {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 3 {
+            0 => ColorWindowRegion::Never,
+            1 => ColorWindowRegion::OutsideWindow,
+            2 => ColorWindowRegion::InsideWindow,
+            _ => ColorWindowRegion::Always,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CgWsel(pub u8);
+impl CgWsel {
+    pub fn force_black(&self) -> ColorWindowRegion {
+        ColorWindowRegion::from_bits(self.0 >> 6)
+    }
+    pub fn prevent_math(&self) -> ColorWindowRegion {
+        ColorWindowRegion::from_bits(self.0 >> 4)
+    }
+    pub fn add_subscreen(&self) -> bool {
+        self.0 & 2 != 0
+    }
+    pub fn direct_color(&self) -> bool {
+        self.0 & 1 != 0
+    }
+}
+impl std::fmt::Display for CgWsel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let source = if self.add_subscreen() { "subscreen" } else { "fixed color" };
+
+        let direct = if self.direct_color() { ", direct color" } else { "" };
+
+        write!(f, "black {:?}, math off {:?}, source {}{}", self.force_black(), self.prevent_math(), source, direct)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CgAdsub(pub u8);
+impl CgAdsub {
+    pub fn subtract(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+    pub fn half(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+    pub fn backdrop(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+    pub fn layers(&self) -> LayerMask {
+        LayerMask(self.0 & 0x1F)
+    }
+}
+impl std::fmt::Display for CgAdsub {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let operation = if self.subtract() { "subtract" } else { "add" };
+        let half = if self.half() { " half" } else { "" };
+        let backdrop = if self.backdrop() { "|BACK" } else { "" };
+
+        write!(f, "{}{} on {}{}", operation, half, self.layers(), backdrop)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Setini(pub u8);
+impl Setini {
+    pub fn external_sync(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+    pub fn extbg(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+    pub fn pseudo_hires(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+    pub fn overscan(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+    pub fn obj_interlace(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+    pub fn interlace(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+}
+impl std::fmt::Display for Setini {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = Vec::<&str>::new();
+
+        if self.external_sync() { parts.push("external sync"); }
+        if self.extbg() { parts.push("EXTBG"); }
+        if self.pseudo_hires() { parts.push("pseudo-hires"); }
+        if self.overscan() { parts.push("239 lines"); }
+        if self.obj_interlace() { parts.push("OBJ interlace"); }
+        if self.interlace() { parts.push("interlace"); }
+
+        if parts.is_empty() { write!(f, "none") }
+        else { write!(f, "{}", parts.join(", ")) }
+    }
+}
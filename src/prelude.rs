@@ -0,0 +1,28 @@
+/* use flyhoney::prelude::*; brings in the main workflow types and every trait their methods live on;
+   everything here is still reachable at the crate root */
+
+/* the ROM itself and its addressing */
+pub use crate::{Addr24, AddrNotation, BankCrossPolicy, BankWriteMode, ChecksumPolicy, Error, ErrorContext, IndexedAccess, Region, ResultContext, Rom, RomCursor, SNESHeader, VideoStandard};
+
+/* memory maps and copier headers */
+pub use crate::{CopierHeader, CopierHeaderFormat, HiRom, LoRom, Mapper, MemoryMap};
+
+/* colors, palettes and tiles */
+pub use crate::{Bgr555, DynTile, PixelBuffer, Rgb888, SNESGraphic, SNESPalette, SNESPalette16, SNESPalette256, SNESTile, TileFormat, TilePadding, TileSheet};
+pub use crate::{SNESTile2BPPPlanar, SNESTile4BPPPlanar, SNESTile8BPPPlanar, SNESTileMode7};
+
+/* codecs */
+pub use crate::{compress_checked, decode_png, encode_indexed_png, BitReader, BitWriter, Codec, DecodedPng, HuffmanCode, RoundTripCheck, TextTable};
+
+/* searching, patching and comparing */
+pub use crate::{BpsPatch, BytePattern, ChangeEvent, DiffRange, IpsPatch, Overlay, Patch, SearchMatch, WatchId};
+
+/* projects, assets and allocation */
+pub use crate::{AllocationStrategy, AssetBundle, AssetModule, BankConstraints, Command, FreeSpaceAllocator, FreeSpaceFilter, PackOptions, Project, SymbolTable};
+
+/* identification */
+pub use crate::{RomDatabase, RomMetadata};
+
+/* per-game tables */
+pub use crate::games::{GameTable, TableValue};
+pub use crate::game_tables;
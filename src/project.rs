@@ -0,0 +1,210 @@
+use crate::{parse_number, unquote, Error, FreeSpaceAllocator, Rom};
+use std::path::{Path, PathBuf};
+
+pub const PROJECT_RULES_FILE: &str = "rules.toml";
+pub const PROJECT_ALLOCATIONS_FILE: &str = "allocations.toml";
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ValidationRule {
+    UntouchedBank(u8),
+    Untouched { offset: usize, length: usize },
+    MaxFileSize { path: String, length: usize },
+    Title(String),
+}
+impl std::fmt::Display for ValidationRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationRule::UntouchedBank(bank) => write!(f, "bank ${:02X} must remain untouched", bank),
+            ValidationRule::Untouched { offset, length } => write!(f, "0x{:06X}-0x{:06X} must remain untouched", offset, offset.saturating_add(*length)),
+            ValidationRule::MaxFileSize { path, length } => write!(f, "{} must not exceed 0x{:X} bytes", path, length),
+            ValidationRule::Title(title) => write!(f, "header title is \"{}\"", title),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RuleViolation {
+    pub rule: ValidationRule,
+    pub message: String,
+}
+impl std::fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.rule, self.message)
+    }
+}
+
+#[derive(Default)]
+struct RuleFields {
+    line: usize,
+    kind: Option<String>,
+    bank: Option<usize>,
+    offset: Option<usize>,
+    length: Option<usize>,
+    path: Option<String>,
+    title: Option<String>,
+}
+impl RuleFields {
+    fn to_rule(&self) -> Result<ValidationRule, Error> {
+        let missing = Error::InvalidManifestLine(self.line);
+
+        match (self.kind.as_deref(), self) {
+            (Some("untouched"), RuleFields { bank: Some(b), .. }) if *b <= 0xFF => Ok(ValidationRule::UntouchedBank(*b as u8)),
+            (Some("untouched"), RuleFields { offset: Some(o), length: Some(l), .. }) => Ok(ValidationRule::Untouched { offset: *o, length: *l }),
+            (Some("max_size"), RuleFields { path: Some(p), length: Some(l), .. }) => Ok(ValidationRule::MaxFileSize { path: p.clone(), length: *l }),
+            (Some("title"), RuleFields { title: Some(t), .. }) => Ok(ValidationRule::Title(t.clone())),
+            (Some(k), _) if !matches!(k, "untouched" | "max_size" | "title") => Err(Error::UnknownField(k.to_string())),
+            _ => Err(missing),
+        }
+    }
+}
+
+pub fn parse_validation_rules(text: &str) -> Result<Vec<ValidationRule>, Error> {
+    /* the same TOML subset as the asset manifest, one [[rule]] table per constraint */
+    let mut tables = Vec::<RuleFields>::new();
+
+    for (number_index, raw_line) in text.lines().enumerate() {
+        /* titles and paths can hold a '#', so a comment only comes off a value that isn't a string */
+        let line = raw_line.trim();
+        let bare = line.split('#').next().unwrap_or("").trim();
+        let bad_line = Error::InvalidManifestLine(number_index + 1);
+
+        if bare.is_empty() { continue; }
+        if bare == "[[rule]]" { tables.push(RuleFields { line: number_index + 1, ..RuleFields::default() }); continue; }
+
+        let (key, quoted, fields) = match (line.split_once('='), tables.last_mut()) {
+            (Some((k, v)), Some(f)) => (k.trim(), v.trim(), f),
+            _ => return Err(bad_line),
+        };
+        let value = quoted.split('#').next().unwrap_or("").trim();
+        let string = unquote(quoted);
+
+        match (key, string) {
+            ("kind", Some(v)) => fields.kind = Some(v),
+            ("path", Some(v)) => fields.path = Some(v),
+            ("title", Some(v)) => fields.title = Some(v),
+            (_, None) => {
+                let n = match parse_number(value) {
+                    Some(n) => n,
+                    None => return Err(bad_line),
+                };
+
+                match key {
+                    "bank" => fields.bank = Some(n),
+                    "offset" => fields.offset = Some(n),
+                    "length" => fields.length = Some(n),
+                    _ => return Err(Error::UnknownField(key.to_string())),
+                }
+            },
+            _ => return Err(Error::UnknownField(key.to_string())),
+        }
+    }
+
+    tables.iter().map(|t| t.to_rule()).collect()
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Project {
+    pub root: PathBuf,
+    pub original: Rom,
+    pub rom: Rom,
+    pub rules: Vec<ValidationRule>,
+    pub allocator: Option<FreeSpaceAllocator>,
+}
+impl Project {
+    pub fn new<P: AsRef<Path>>(root: P, original: Rom) -> Self {
+        Self { root: root.as_ref().to_path_buf(), rom: original.clone(), original, rules: Vec::new(), allocator: None }
+    }
+    pub fn rules(mut self, rules: Vec<ValidationRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+    pub fn load_rules(mut self) -> Result<Self, Error> {
+        /* a project without a rules file simply has no constraints */
+        let text = match std::fs::read_to_string(self.root.join(PROJECT_RULES_FILE)) {
+            Ok(t) => t,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(Error::IoError(e)),
+        };
+
+        match parse_validation_rules(&text) {
+            Ok(r) => { self.rules = r; Ok(self) },
+            Err(e) => Err(e),
+        }
+    }
+    pub fn load_allocations(mut self) -> Result<Self, Error> {
+        /* without a record yet, free space comes from the original so the project's own writes never count as used */
+        let allocator = match std::fs::read_to_string(self.root.join(PROJECT_ALLOCATIONS_FILE)) {
+            Ok(t) => FreeSpaceAllocator::from_toml(&t, &self.original),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => FreeSpaceAllocator::from_rom(&self.original),
+            Err(e) => return Err(Error::IoError(e)),
+        };
+
+        match allocator {
+            Ok(a) => { self.allocator = Some(a); Ok(self) },
+            Err(e) => Err(e),
+        }
+    }
+    pub fn save_allocations(&self) -> Result<(), Error> {
+        /* call this once a build has placed its blocks, so the next load_allocations hands each name the address it had this time */
+        let allocator = match &self.allocator {
+            Some(a) => a,
+            None => return Ok(()),
+        };
+
+        match std::fs::write(self.root.join(PROJECT_ALLOCATIONS_FILE), allocator.to_toml()) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+    fn check(&self, rule: &ValidationRule) -> Result<Option<String>, Error> {
+        let untouched = |offset: usize, length: usize| {
+            /* offset and length come straight from rules.toml, so their sum can't be trusted to fit */
+            let end = match offset.checked_add(length) {
+                Some(e) => e,
+                None => return Some(format!("range ends past the rom at 0x{:X}", self.rom.len())),
+            };
+
+            if end > self.rom.len() || end > self.original.len() { return Some(format!("range ends past the rom at 0x{:X}", self.rom.len())); }
+
+            let before = &self.original.as_slice()[offset..end];
+            let after = &self.rom.as_slice()[offset..end];
+
+            before.iter().zip(after).position(|(a, b)| a != b).map(|p| format!("changed at 0x{:06X}", offset + p))
+        };
+
+        match rule {
+            /* resolve the bank against the original so a header edit can't move the goalposts */
+            ValidationRule::UntouchedBank(bank) => {
+                match self.original.bank_range(*bank) {
+                    Ok(range) => Ok(untouched(range.start, range.len())),
+                    Err(e) => Err(e),
+                }
+            },
+            ValidationRule::Untouched { offset, length } => Ok(untouched(*offset, *length)),
+            ValidationRule::MaxFileSize { path, length } => match std::fs::metadata(self.root.join(path)) {
+                Ok(m) if m.len() as usize > *length => Ok(Some(format!("is 0x{:X} bytes", m.len()))),
+                Ok(_) => Ok(None),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Some("file is missing".to_string())),
+                Err(e) => Err(Error::IoError(e)),
+            },
+            ValidationRule::Title(title) => match self.rom.find_valid_snes_header() {
+                Ok(h) if h.get_title() == *title => Ok(None),
+                Ok(h) => Ok(Some(format!("title is \"{}\"", h.get_title()))),
+                Err(_) => Ok(Some("no valid header".to_string())),
+            },
+        }
+    }
+    pub fn validate(&self) -> Result<Vec<RuleViolation>, Error> {
+        let mut result = Vec::<RuleViolation>::new();
+
+        for rule in &self.rules {
+            match self.check(rule) {
+                Ok(Some(message)) => result.push(RuleViolation { rule: rule.clone(), message }),
+                Ok(None) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+}
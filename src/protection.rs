@@ -0,0 +1,222 @@
+use crate::{code_entry_points, decode_opcode, survey_code, Addr24, AddressingMode, CancelToken, Error, Mnemonic, Rom};
+
+/* how far after the tell-tale read we look for the compare and branch that act on it */
+const PROTECTION_WINDOW: usize = 6;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ProtectionKind {
+    SramSizeCheck,
+    ChecksumVerify,
+    RegionLockout,
+}
+impl std::fmt::Display for ProtectionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProtectionKind::SramSizeCheck => write!(f, "SRAM size/mirror check"),
+            ProtectionKind::ChecksumVerify => write!(f, "header checksum self-verification"),
+            ProtectionKind::RegionLockout => write!(f, "region lockout ($213F read)"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ProtectionCheck {
+    pub kind: ProtectionKind,
+    pub offset: usize,
+    pub address: Addr24,
+    pub patch_offset: usize,
+    pub original: Vec<u8>,
+    pub patch: Vec<u8>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct RegionLockReport {
+    pub patched: Vec<ProtectionCheck>,
+    pub skipped: Vec<ProtectionCheck>,
+}
+impl RegionLockReport {
+    pub fn found(&self) -> bool {
+        !self.patched.is_empty() || !self.skipped.is_empty()
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) struct DecodedInstruction {
+    pub offset: usize,
+    pub mnemonic: Mnemonic,
+    pub mode: AddressingMode,
+    pub operand: u32,
+    pub length: usize,
+}
+impl DecodedInstruction {
+    fn long_target(&self) -> Option<(u8, u16)> {
+        /* absolute operands use the data bank, which we cannot know statically; bank 0 is the common case */
+        match self.mode {
+            AddressingMode::Absolute => Some((0, self.operand as u16)),
+            AddressingMode::AbsoluteLong => Some(((self.operand >> 16) as u8, self.operand as u16)),
+            _ => None,
+        }
+    }
+    fn reads(&self) -> bool {
+        matches!(self.mnemonic, Mnemonic::LDA | Mnemonic::CMP | Mnemonic::LDX | Mnemonic::LDY | Mnemonic::CPX | Mnemonic::CPY | Mnemonic::BIT | Mnemonic::EOR)
+    }
+}
+
+fn sram_offset(bank: u8, address: u16) -> Option<usize> {
+    /* LoROM SRAM lives in $70-$7D:0000-7FFF, HiROM SRAM in $20-$3F/$A0-$BF:6000-7FFF */
+    match bank & 0x7F {
+        0x70..=0x7D if address < 0x8000 => Some(((bank & 0x7F) as usize - 0x70) * 0x8000 + address as usize),
+        0x20..=0x3F if (0x6000..0x8000).contains(&address) => Some(((bank & 0x1F) as usize) * 0x2000 + (address as usize - 0x6000)),
+        _ => None,
+    }
+}
+
+pub(crate) fn decode_run(data: &[u8], start: usize, end: usize) -> Vec<DecodedInstruction> {
+    let mut result = Vec::<DecodedInstruction>::new();
+    let (mut offset, mut m8, mut x8) = (start, true, true);
+
+    while offset < end {
+        let (mnemonic, mode) = decode_opcode(data[offset]);
+        let length = 1 + mode.operand_size(m8, x8);
+        if offset + length > end { break; }
+
+        let mut operand = 0u32;
+        for i in 1..length { operand |= (data[offset + i] as u32) << ((i - 1) * 8); }
+
+        match mnemonic {
+            Mnemonic::REP => {
+                if operand & 0x20 != 0 { m8 = false; }
+                if operand & 0x10 != 0 { x8 = false; }
+            },
+            Mnemonic::SEP => {
+                if operand & 0x20 != 0 { m8 = true; }
+                if operand & 0x10 != 0 { x8 = true; }
+            },
+            _ => (),
+        }
+
+        result.push(DecodedInstruction { offset, mnemonic, mode, operand, length });
+        offset += length;
+    }
+
+    result
+}
+
+fn equal_path_patch(data: &[u8], branch: &DecodedInstruction) -> Option<Vec<u8>> {
+    /* on real hardware these checks pass when the compare comes out equal, so commit to that path */
+    match branch.mnemonic {
+        Mnemonic::BNE => Some(vec![0xEA, 0xEA]),
+        Mnemonic::BEQ => Some(vec![0x80, data[branch.offset + 1]]),
+        _ => None,
+    }
+}
+
+impl Rom {
+    pub fn find_copy_protection(&self, cancel: &CancelToken) -> Result<Vec<ProtectionCheck>, Error> {
+        let notation = match code_entry_points(self) {
+            Ok((n, _)) => n,
+            Err(e) => return Err(e),
+        };
+        let hits = match survey_code(self, &|_, _| (), cancel) {
+            Ok(h) => h,
+            Err(e) => return Err(e),
+        };
+        let header = match self.find_valid_snes_header() {
+            Ok(h) => *h,
+            Err(e) => return Err(e),
+        };
+        let sram_size = match header.get_sram_size() {
+            0 => 0,
+            size => 0x400usize << size,
+        };
+        let pal = header.get_region().is_pal();
+        let data = self.as_slice();
+        let mut result = Vec::<ProtectionCheck>::new();
+
+        for hit in &hits {
+            if let Err(e) = cancel.check() { return Err(e); }
+
+            let run = decode_run(data, hit.offset, hit.offset + hit.length);
+
+            for (index, ins) in run.iter().enumerate() {
+                let (bank, address) = match ins.long_target() {
+                    Some(t) if ins.reads() => t,
+                    _ => continue,
+                };
+                let window = &run[index+1..(index+1+PROTECTION_WINDOW).min(run.len())];
+                let branch = window.iter().find(|i| matches!(i.mnemonic, Mnemonic::BNE | Mnemonic::BEQ));
+
+                let found = if address == 0x213F && (bank & 0x7F) < 0x40 {
+                    /* bit 4 of STAT78 is the PAL flag; pin the AND that isolates it to this cart's own region */
+                    window.iter().find(|i| i.mnemonic == Mnemonic::AND && i.mode == AddressingMode::ImmediateM && i.operand & 0x10 != 0).map(|and| {
+                        let mut patch = if pal { vec![0xA9, 0x10] } else { vec![0x29, 0x00] };
+                        patch.resize(and.length, 0);
+
+                        (ProtectionKind::RegionLockout, and, patch)
+                    })
+                }
+                else if (0xFFDC..=0xFFDF).contains(&address) && matches!(bank, 0x00 | 0x80 | 0x40 | 0xC0) {
+                    branch.and_then(|b| equal_path_patch(data, b).map(|p| (ProtectionKind::ChecksumVerify, b, p)))
+                }
+                else {
+                    /* reading SRAM past the declared size only makes sense as a mirror test, and copiers fail it */
+                    match sram_offset(bank, address) {
+                        Some(o) if sram_size != 0 && o >= sram_size && ins.mode == AddressingMode::AbsoluteLong => {
+                            branch.and_then(|b| equal_path_patch(data, b).map(|p| (ProtectionKind::SramSizeCheck, b, p)))
+                        },
+                        _ => None,
+                    }
+                };
+
+                let (kind, target, patch) = match found {
+                    Some(f) => f,
+                    None => continue,
+                };
+
+                if result.iter().any(|c| c.patch_offset == target.offset) { continue; }
+
+                let address = match self.offset_to_notation(ins.offset, notation) {
+                    Ok(a) => a,
+                    Err(e) => return Err(e),
+                };
+
+                result.push(ProtectionCheck {
+                    kind,
+                    offset: ins.offset,
+                    address,
+                    patch_offset: target.offset,
+                    original: data[target.offset..target.offset + target.length].to_vec(),
+                    patch,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+    pub fn patch_region_lock(&mut self) -> Result<RegionLockReport, Error> {
+        let checks = match self.find_copy_protection(&CancelToken::new()) {
+            Ok(c) => c,
+            Err(e) => return Err(e),
+        };
+        let mut report = RegionLockReport::default();
+
+        for check in checks.into_iter().filter(|c| c.kind == ProtectionKind::RegionLockout) {
+            /* an earlier patch or hand edit may already have changed these bytes; leave those for a human */
+            if self.as_slice()[check.patch_offset..check.patch_offset + check.original.len()] != check.original[..] {
+                report.skipped.push(check);
+                continue;
+            }
+
+            if let Err(e) = self.write(check.patch_offset, check.patch.clone()) { return Err(e); }
+
+            report.patched.push(check);
+        }
+
+        /* some of these games also verify their checksum, so keep it matching the patched code */
+        if !report.patched.is_empty() {
+            if let Err(e) = self.update_header(|_| ()) { return Err(e); }
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{Error, Rom};
+
+pub const REGION_MAP_MAGIC: [u8; 4] = *b"FHRM";
+pub const REGION_MAP_VERSION: u16 = 1;
+/* past the largest image any mapper addresses, so a larger declared length can only be a corrupt file */
+pub const REGION_MAP_MAX_LENGTH: usize = 0x1000000;
+
+#[repr(u8)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum RegionKind {
+    Unknown = 0,
+    Code = 1,
+    Data = 2,
+    Graphics = 3,
+    Palette = 4,
+    Text = 5,
+    Free = 6,
+    Changed = 7,
+    Annotated = 8,
+}
+impl RegionKind {
+    pub const ALL: [RegionKind; 9] = [RegionKind::Unknown,
+                                      RegionKind::Code,
+                                      RegionKind::Data,
+                                      RegionKind::Graphics,
+                                      RegionKind::Palette,
+                                      RegionKind::Text,
+                                      RegionKind::Free,
+                                      RegionKind::Changed,
+                                      RegionKind::Annotated];
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegionKind::Unknown => "unknown",
+            RegionKind::Code => "code",
+            RegionKind::Data => "data",
+            RegionKind::Graphics => "graphics",
+            RegionKind::Palette => "palette",
+            RegionKind::Text => "text",
+            RegionKind::Free => "free",
+            RegionKind::Changed => "changed",
+            RegionKind::Annotated => "annotated",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RegionRun {
+    pub offset: usize,
+    pub length: usize,
+    pub kind: RegionKind,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RegionMap {
+    kinds: Vec<RegionKind>,
+}
+impl RegionMap {
+    pub fn new(length: usize) -> Self {
+        Self { kinds: vec![RegionKind::Unknown; length] }
+    }
+    pub fn from_rom(rom: &Rom) -> Self {
+        Self::new(rom.rom_size())
+    }
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+    pub fn mark(&mut self, offset: usize, length: usize, kind: RegionKind) -> Result<(), Error> {
+        let end = match offset.checked_add(length) {
+            Some(e) if e <= self.kinds.len() => e,
+            _ => return Err(Error::OutOfBounds(offset.saturating_add(length),self.kinds.len())),
+        };
+
+        for entry in &mut self.kinds[offset..end] {
+            *entry = kind;
+        }
+
+        Ok(())
+    }
+    pub fn kind_at(&self, offset: usize) -> Result<RegionKind, Error> {
+        match self.kinds.get(offset) {
+            Some(k) => Ok(*k),
+            None => Err(Error::OutOfBounds(offset,self.kinds.len())),
+        }
+    }
+    pub fn runs(&self) -> Vec<RegionRun> {
+        let mut result = Vec::<RegionRun>::new();
+
+        for (offset, kind) in self.kinds.iter().enumerate() {
+            match result.last_mut() {
+                Some(run) if run.kind == *kind => run.length += 1,
+                _ => result.push(RegionRun { offset, length: 1, kind: *kind }),
+            }
+        }
+
+        result
+    }
+    pub fn from_runs(runs: &[RegionRun]) -> Self {
+        let mut kinds = Vec::<RegionKind>::new();
+
+        for run in runs {
+            kinds.resize(kinds.len() + run.length, run.kind);
+        }
+
+        Self { kinds }
+    }
+    pub fn to_rle_bytes(&self) -> Vec<u8> {
+        /* layout: magic, u16 version, u16 reserved, varint length, varint run count, then (varint length, u8 kind) pairs */
+        let runs = self.runs();
+        let mut result = Vec::<u8>::new();
+
+        result.extend_from_slice(&REGION_MAP_MAGIC);
+        result.extend_from_slice(&REGION_MAP_VERSION.to_le_bytes());
+        result.extend_from_slice(&0u16.to_le_bytes());
+        write_varint(&mut result, self.kinds.len() as u64);
+        write_varint(&mut result, runs.len() as u64);
+
+        for run in &runs {
+            write_varint(&mut result, run.length as u64);
+            result.push(run.kind.as_u8());
+        }
+
+        result
+    }
+    pub fn from_rle_bytes<B: AsRef<[u8]>>(data: B) -> Result<Self, Error> {
+        let buf = data.as_ref();
+        if buf.len() < 8 { return Err(Error::TruncatedData(buf.len())); }
+        if buf[0..4] != REGION_MAP_MAGIC { return Err(Error::BadMagic); }
+
+        let version = u16::from_le_bytes([buf[4], buf[5]]);
+        if version > REGION_MAP_VERSION { return Err(Error::UnsupportedVersion(version)); }
+
+        let mut cursor = 8usize;
+        let length = match read_varint(buf, &mut cursor) {
+            Ok(v) => v as usize,
+            Err(e) => return Err(e),
+        };
+        if length > REGION_MAP_MAX_LENGTH { return Err(Error::UnaddressableRomSize(length,REGION_MAP_MAX_LENGTH)); }
+
+        let run_count = match read_varint(buf, &mut cursor) {
+            Ok(v) => v as usize,
+            Err(e) => return Err(e),
+        };
+        let mut runs = Vec::<RegionRun>::new();
+        let mut offset = 0usize;
+
+        for _ in 0..run_count {
+            let run_length = match read_varint(buf, &mut cursor) {
+                Ok(v) => v as usize,
+                Err(e) => return Err(e),
+            };
+
+            if cursor >= buf.len() { return Err(Error::TruncatedData(cursor)); }
+
+            let kind = match RegionKind::from_u8(buf[cursor]) {
+                Some(k) => k,
+                None => return Err(Error::InvalidRegionKind(buf[cursor])),
+            };
+            cursor += 1;
+
+            runs.push(RegionRun { offset, length: run_length, kind });
+
+            /* the runs get expanded byte by byte, so they must not claim more than the declared length before anything is allocated */
+            offset = match offset.checked_add(run_length) {
+                Some(o) if o <= length => o,
+                _ => return Err(Error::DataLengthMismatch(offset.saturating_add(run_length),length)),
+            };
+        }
+
+        if offset != length { return Err(Error::DataLengthMismatch(offset,length)); }
+
+        Ok(Self::from_runs(&runs))
+    }
+    pub fn to_json(&self) -> String {
+        let mut result = String::new();
+
+        result.push_str("{\"schema\":\"flyhoney-region-map\",");
+        result.push_str(&format!("\"version\":{},", REGION_MAP_VERSION));
+        result.push_str(&format!("\"length\":{},", self.kinds.len()));
+        result.push_str("\"kinds\":{");
+
+        for (i, kind) in RegionKind::ALL.iter().enumerate() {
+            if i != 0 { result.push(','); }
+            result.push_str(&format!("\"{}\":\"{}\"", kind.as_u8(), kind.name()));
+        }
+
+        result.push_str("},\"runs\":[");
+
+        for (i, run) in self.runs().iter().enumerate() {
+            if i != 0 { result.push(','); }
+            result.push_str(&format!("[{},{}]", run.length, run.kind.as_u8()));
+        }
+
+        result.push_str("]}");
+        result
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 { output.push(byte); break; }
+        else { output.push(byte | 0x80); }
+    }
+}
+
+fn read_varint(data: &[u8], cursor: &mut usize) -> Result<u64, Error> {
+    let mut result = 0u64;
+    let mut shift = 0;
+
+    loop {
+        if *cursor >= data.len() || shift > 63 { return Err(Error::TruncatedData(*cursor)); }
+
+        let byte = data[*cursor];
+        *cursor += 1;
+
+        result |= ((byte & 0x7F) as u64) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 { break; }
+    }
+
+    Ok(result)
+}
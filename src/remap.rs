@@ -0,0 +1,381 @@
+use crate::{Addr24, Error, ExHiRom, Mapper, MemoryMap, RegionKind, RegionMap, Rom, SNESHeader};
+
+/* fill runs shorter than this are as likely to be data as padding */
+const MIN_FREE_RUN: usize = 0x20;
+
+/* where a HiROM image grown into ExHiROM gets its copy of the extended header, header and vectors */
+const EXHIROM_HEADER_AREA: std::ops::Range<usize> = 0x40FFB0..0x410000;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PointerFormat {
+    Long,
+    Short(u8),
+}
+impl PointerFormat {
+    pub fn size(&self) -> usize {
+        match self {
+            PointerFormat::Long => 3,
+            PointerFormat::Short(_) => 2,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PointerTable {
+    pub name: String,
+    pub offset: usize,
+    pub count: usize,
+    pub format: PointerFormat,
+}
+impl PointerTable {
+    pub fn new(name: &str, offset: usize, count: usize, format: PointerFormat) -> Self {
+        Self { name: name.to_string(), offset, count, format }
+    }
+    pub fn len(&self) -> usize {
+        self.count * self.format.size()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    fn entry(&self, rom: &Rom, index: usize) -> Result<(usize, Vec<u8>, Addr24), Error> {
+        let offset = self.offset + index * self.format.size();
+        let data = match rom.read(offset, self.format.size()) {
+            Ok(d) => d.to_vec(),
+            Err(e) => return Err(e),
+        };
+        let address = match self.format {
+            PointerFormat::Long => Addr24::new(data[2], u16::from_le_bytes([data[0], data[1]])),
+            PointerFormat::Short(bank) => Addr24::new(bank, u16::from_le_bytes([data[0], data[1]])),
+        };
+
+        Ok((offset, data, address))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BankOccupancy {
+    pub bank: usize,
+    pub offset: usize,
+    pub size: usize,
+    pub used: usize,
+}
+impl BankOccupancy {
+    pub fn free(&self) -> usize {
+        self.size - self.used
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RelocatedBlock {
+    pub from: usize,
+    pub to: usize,
+    pub length: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PointerUpdate {
+    pub table: String,
+    pub index: usize,
+    pub offset: usize,
+    pub original: Vec<u8>,
+    pub replacement: Vec<u8>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RemapPlan {
+    pub original_size: usize,
+    pub target_size: usize,
+    pub mapper: Mapper,
+    pub occupancy: Vec<BankOccupancy>,
+    pub moves: Vec<RelocatedBlock>,
+    pub pointer_updates: Vec<PointerUpdate>,
+    pub pinned: Vec<(usize, String)>,
+}
+impl RemapPlan {
+    pub fn report(&self) -> String {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+        let mut result = format!("expand 0x{:X} -> 0x{:X} bytes ({})\n", self.original_size, self.target_size, self.mapper.name());
+
+        for bank in &self.occupancy {
+            result.push_str(&format!("bank {:02X}: 0x{:X}/0x{:X} used\n", bank.bank, bank.used, bank.size));
+        }
+        for block in &self.moves {
+            result.push_str(&format!("move 0x{:06X} -> 0x{:06X} (0x{:X} bytes)\n", block.from, block.to, block.length));
+        }
+        for update in &self.pointer_updates {
+            result.push_str(&format!("repoint {}[{}] at 0x{:06X}: {} -> {}\n", update.table, update.index, update.offset, hex(&update.original), hex(&update.replacement)));
+        }
+        for (offset, reason) in &self.pinned {
+            result.push_str(&format!("pinned 0x{:06X}: {}\n", offset, reason));
+        }
+
+        result
+    }
+}
+
+struct Block {
+    start: usize,
+    end: usize,
+    movable: bool,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RemapPlanner {
+    pub tables: Vec<PointerTable>,
+    pub reserve: usize,
+    pub regions: Option<RegionMap>,
+}
+impl RemapPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn table(mut self, table: PointerTable) -> Self {
+        self.tables.push(table);
+        self
+    }
+    pub fn reserve(mut self, reserve: usize) -> Self {
+        self.reserve = reserve;
+        self
+    }
+    pub fn regions(mut self, regions: RegionMap) -> Self {
+        self.regions = Some(regions);
+        self
+    }
+    pub fn plan(&self, rom: &Rom, target_size: usize) -> Result<RemapPlan, Error> {
+        let original_size = rom.rom_size();
+        if target_size <= original_size { return Err(Error::ROMSizeMismatch(target_size,original_size)); }
+
+        let current = match rom.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        /* past 4MB only the ExHiROM layout has room; LoROM boards need an ExLoROM map we don't model */
+        let mapper = match current {
+            Mapper::LoRom(_) if target_size > 0x400000 => return Err(Error::ROMSizeMismatch(0x400000,target_size)),
+            Mapper::HiRom(_) if target_size > 0x400000 => Mapper::ExHiRom(ExHiRom),
+            m => m,
+        };
+        if target_size > 0x800000 { return Err(Error::ROMSizeMismatch(0x800000,target_size)); }
+
+        let bank_size = match current {
+            Mapper::LoRom(_) | Mapper::SuperFx(_) | Mapper::Sdd1(_) => 0x8000,
+            _ => 0x10000,
+        };
+        let regions = match &self.regions {
+            Some(r) => r.clone(),
+            None => rom.fill_regions(),
+        };
+        let occupancy = rom.bank_occupancy(&regions, bank_size);
+        let mut pinned = Vec::<(usize, String)>::new();
+
+        /* every pointer target starts a block, which runs to the next target, the end of its region or the end of its bank */
+        let mut targets = Vec::<(usize, bool)>::new();
+
+        for table in &self.tables {
+            for index in 0..table.count {
+                let (_, _, address) = match table.entry(rom, index) {
+                    Ok(e) => e,
+                    Err(e) => return Err(e),
+                };
+                let pc = match current.address_to_pc(address) {
+                    Ok(p) if p < original_size => p,
+                    _ => continue,
+                };
+
+                targets.push((pc, table.format == PointerFormat::Long));
+            }
+        }
+
+        targets.sort_unstable();
+
+        let mut blocks = Vec::<Block>::new();
+
+        for (i, &(start, long)) in targets.iter().enumerate() {
+            if let Some(last) = blocks.last_mut() {
+                /* one block reached through a short pointer anywhere has to stay in its bank */
+                if last.start == start { last.movable &= long; continue; }
+            }
+
+            let bank_end = (start / bank_size + 1) * bank_size;
+            let mut end = targets[i+1..].iter().map(|t| t.0).find(|&t| t != start).unwrap_or(bank_end).min(bank_end);
+
+            if let Ok(RegionKind::Free) = regions.kind_at(start) { continue; }
+            if let Some(free) = (start..end).find(|&o| matches!(regions.kind_at(o), Ok(RegionKind::Free))) { end = free; }
+
+            blocks.push(Block { start, end, movable: long });
+        }
+
+        for block in &mut blocks {
+            let inside = self.tables.iter().find(|t| {
+                let table_pc = t.offset.saturating_sub(rom.header_size());
+                table_pc < block.end && table_pc + t.len() > block.start
+            });
+
+            if let Some(table) = inside {
+                pinned.push((block.start, format!("holds pointer table {}", table.name)));
+                block.movable = false;
+            }
+            else if !block.movable {
+                pinned.push((block.start, "reached through a short pointer".to_string()));
+            }
+        }
+
+        let mut moves = Vec::<RelocatedBlock>::new();
+        let mut cursor = original_size;
+        let relocates_header = matches!(current, Mapper::HiRom(_)) && matches!(mapper, Mapper::ExHiRom(_));
+
+        for bank in occupancy.iter().filter(|b| b.free() < self.reserve) {
+            let mut candidates = blocks.iter()
+                .filter(|b| b.movable && b.start >= bank.offset && b.start < bank.offset + bank.size)
+                .collect::<Vec<&Block>>();
+
+            /* the biggest blocks first, so each crowded bank gives up as few pointers as possible */
+            candidates.sort_by_key(|b| (std::cmp::Reverse(b.end - b.start), b.start));
+
+            let mut free = bank.free();
+
+            for block in candidates {
+                if free >= self.reserve { break; }
+
+                let length = block.end - block.start;
+                if relocates_header && cursor < EXHIROM_HEADER_AREA.end && cursor + length > EXHIROM_HEADER_AREA.start { cursor = EXHIROM_HEADER_AREA.end; }
+                if cursor % bank_size + length > bank_size { cursor = (cursor / bank_size + 1) * bank_size; }
+                if cursor + length > target_size { return Err(Error::NoFreeSpace(length)); }
+
+                moves.push(RelocatedBlock { from: block.start, to: cursor, length });
+                cursor += length;
+                free += length;
+            }
+
+            if free < self.reserve {
+                pinned.push((bank.offset, format!("bank {:02X} still has only 0x{:X} bytes free", bank.bank, free)));
+            }
+        }
+
+        let mut pointer_updates = Vec::<PointerUpdate>::new();
+
+        for table in self.tables.iter().filter(|t| t.format == PointerFormat::Long) {
+            for index in 0..table.count {
+                let (offset, original, address) = match table.entry(rom, index) {
+                    Ok(e) => e,
+                    Err(e) => return Err(e),
+                };
+                let pc = match current.address_to_pc(address) {
+                    Ok(p) => p,
+                    Err(_) => continue,
+                };
+                let moved = match moves.iter().find(|m| pc >= m.from && pc < m.from + m.length) {
+                    Some(m) => m,
+                    None => continue,
+                };
+                let target = match mapper.pc_to_address(moved.to + (pc - moved.from)) {
+                    Ok(a) => a,
+                    Err(e) => return Err(e),
+                };
+
+                pointer_updates.push(PointerUpdate {
+                    table: table.name.clone(),
+                    index,
+                    offset,
+                    original,
+                    replacement: target.as_u32().to_le_bytes()[..3].to_vec(),
+                });
+            }
+        }
+
+        Ok(RemapPlan { original_size, target_size, mapper, occupancy, moves, pointer_updates, pinned })
+    }
+}
+impl Default for RemapPlanner {
+    fn default() -> Self {
+        Self { tables: Vec::new(), reserve: 0x1000, regions: None }
+    }
+}
+
+impl Rom {
+    pub fn fill_regions(&self) -> RegionMap {
+        /* without an analysis to go on, long runs of $00 or $FF are the best guess at free space */
+        let data = &self.as_slice()[self.header_size()..];
+        let mut map = RegionMap::new(data.len());
+        let mut start = 0;
+
+        while start < data.len() {
+            let fill = data[start];
+            let end = data[start..].iter().position(|&b| b != fill).map_or(data.len(), |p| start + p);
+
+            if (fill == 0x00 || fill == 0xFF) && end - start >= MIN_FREE_RUN {
+                let _ = map.mark(start, end - start, RegionKind::Free);
+            }
+
+            start = end;
+        }
+
+        map
+    }
+    pub fn bank_occupancy(&self, regions: &RegionMap, bank_size: usize) -> Vec<BankOccupancy> {
+        (0..self.rom_size()).step_by(bank_size).enumerate().map(|(bank, offset)| {
+            let size = bank_size.min(self.rom_size() - offset);
+            let used = (offset..offset + size).filter(|&o| !matches!(regions.kind_at(o), Ok(RegionKind::Free))).count();
+
+            BankOccupancy { bank, offset, size, used }
+        }).collect()
+    }
+    pub fn apply_remap_plan(&mut self, plan: &RemapPlan) -> Result<(), Error> {
+        if self.rom_size() != plan.original_size { return Err(Error::ROMSizeMismatch(plan.original_size,self.rom_size())); }
+
+        /* like expand_to, the header has to be found before growing, since the stale size byte fails validation afterwards */
+        let current = match self.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        let header_offset = match self.find_valid_snes_header_address() {
+            Ok(a) => a.to_offset(self),
+            Err(e) => return Err(e),
+        };
+
+        self.resize(self.header_size() + plan.target_size);
+
+        /* the old copies stay put: code we were not told about may still point at them */
+        for block in &plan.moves {
+            let data = match self.read(self.header_size() + block.from, block.length) {
+                Ok(d) => d.to_vec(),
+                Err(e) => return Err(e),
+            };
+
+            if let Err(e) = self.write(self.header_size() + block.to, data) { return Err(e); }
+        }
+
+        for update in &plan.pointer_updates {
+            if let Err(e) = self.write(update.offset, update.replacement.clone()) { return Err(e); }
+        }
+
+        let mut header = match self.get_ref::<SNESHeader>(header_offset) {
+            Ok(h) => *h,
+            Err(e) => return Err(e),
+        };
+
+        /* rom size byte is log2 of the size in KB, rounded up for the odd sizes */
+        header.set_rom_size((plan.target_size / 0x400).next_power_of_two().trailing_zeros() as u8);
+        if let Err(e) = self.write_ref(header_offset, &header) { return Err(e); }
+
+        if matches!(current, Mapper::HiRom(_)) && matches!(plan.mapper, Mapper::ExHiRom(_)) {
+            /* the console now boots from bank $40: the extended header, header and vectors all have to be there too */
+            let header_area = match self.read(header_offset - 0x10, EXHIROM_HEADER_AREA.len()) {
+                Ok(d) => d.to_vec(),
+                Err(e) => return Err(e),
+            };
+            if let Err(e) = self.write(self.header_size() + EXHIROM_HEADER_AREA.start, header_area) { return Err(e); }
+
+            header.set_mapping_mode((header.get_mapping_mode() & 0x10) | 0x25);
+            if let Err(e) = self.write_ref(self.header_size() + 0x40FFC0, &header) { return Err(e); }
+        }
+
+        /* an explicitly chosen map would still describe the old layout */
+        if self.mapper.is_some() { self.set_memory_map(Some(plan.mapper)); }
+
+        match self.fix_checksum() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
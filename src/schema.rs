@@ -0,0 +1,215 @@
+use crate::{Error, Rom};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FieldType {
+    U8,
+    U16,
+    U24,
+    U32,
+    I8,
+    I16,
+    Bits(u8, u8),
+}
+impl FieldType {
+    pub fn size(&self) -> usize {
+        match self {
+            FieldType::U8 | FieldType::I8 | FieldType::Bits(_, _) => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U24 => 3,
+            FieldType::U32 => 4,
+        }
+    }
+    pub fn natural_range(&self) -> (i64, i64) {
+        match self {
+            FieldType::U8 => (0, 0xFF),
+            FieldType::U16 => (0, 0xFFFF),
+            FieldType::U24 => (0, 0xFFFFFF),
+            FieldType::U32 => (0, 0xFFFFFFFF),
+            FieldType::I8 => (i8::MIN as i64, i8::MAX as i64),
+            FieldType::I16 => (i16::MIN as i64, i16::MAX as i64),
+            FieldType::Bits(_, width) => (0, 1i64.checked_shl(*width as u32).map_or(0, |v| v - 1)),
+        }
+    }
+    pub fn validate(&self) -> Result<(), Error> {
+        /* a bit field lives inside its one byte: anything wider, or shifted past it, would read and mask the wrong bits */
+        match self {
+            FieldType::Bits(shift, width) if *width == 0 || *width >= 8 || *shift >= 8 || shift + width > 8 => Err(Error::InvalidBitWidth(*width as usize)),
+            _ => Ok(()),
+        }
+    }
+    fn decode(&self, data: &[u8]) -> i64 {
+        let mut raw = 0u32;
+
+        for (i, byte) in data.iter().enumerate() {
+            raw |= (*byte as u32) << (i * 8);
+        }
+
+        match self {
+            FieldType::I8 => raw as u8 as i8 as i64,
+            FieldType::I16 => raw as u16 as i16 as i64,
+            FieldType::Bits(shift, width) => ((raw >> shift) & ((1u32 << width) - 1)) as i64,
+            _ => raw as i64,
+        }
+    }
+    fn encode(&self, value: i64, original: &[u8]) -> Vec<u8> {
+        match self {
+            FieldType::Bits(shift, width) => {
+                /* bit fields share their byte with other flags, so merge into what is already there */
+                let mask = (((1u16 << width) - 1) << shift) as u8;
+                vec![(original[0] & !mask) | (((value as u8) << shift) & mask)]
+            },
+            _ => (value as u32).to_le_bytes()[..self.size()].to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FieldSchema {
+    pub name: String,
+    pub offset: usize,
+    pub field_type: FieldType,
+    pub min: i64,
+    pub max: i64,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TableSchema {
+    pub name: String,
+    pub offset: usize,
+    pub row_size: usize,
+    pub row_count: usize,
+    pub fields: Vec<FieldSchema>,
+}
+impl TableSchema {
+    pub fn new(name: &str, offset: usize, row_size: usize, row_count: usize) -> Self {
+        /* offsets are into ROM data without a copier header, so one schema works for headered and unheadered dumps */
+        Self { name: name.to_string(), offset, row_size, row_count, fields: Vec::new() }
+    }
+    pub fn field(mut self, name: &str, offset: usize, field_type: FieldType) -> Self {
+        let (min, max) = field_type.natural_range();
+
+        self.fields.push(FieldSchema { name: name.to_string(), offset, field_type, min, max });
+        self
+    }
+    pub fn range(mut self, min: i64, max: i64) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            let (natural_min, natural_max) = field.field_type.natural_range();
+
+            field.min = min.max(natural_min);
+            field.max = max.min(natural_max);
+        }
+
+        self
+    }
+    pub fn get_field(&self, name: &str) -> Result<&FieldSchema, Error> {
+        /* a schema nobody validated still must not hand out a field that decode and encode can't shift safely */
+        match self.fields.iter().find(|f| f.name == name) {
+            Some(f) => match f.field_type.validate() {
+                Ok(()) => Ok(f),
+                Err(e) => Err(e),
+            },
+            None => Err(Error::UnknownField(name.to_string())),
+        }
+    }
+    pub fn validate(&self) -> Result<(), Error> {
+        for field in &self.fields {
+            if let Err(e) = field.field_type.validate() { return Err(e); }
+
+            let end = field.offset + field.field_type.size();
+            if end > self.row_size { return Err(Error::OutOfBounds(end,self.row_size)); }
+        }
+
+        Ok(())
+    }
+    pub fn row_offset(&self, rom: &Rom, row: usize) -> Result<usize, Error> {
+        if row >= self.row_count { return Err(Error::OutOfBounds(row,self.row_count)); }
+
+        Ok(rom.header_size() + self.offset + row * self.row_size)
+    }
+    pub fn get(&self, rom: &Rom, row: usize, name: &str) -> Result<i64, Error> {
+        let field = match self.get_field(name) {
+            Ok(f) => f,
+            Err(e) => return Err(e),
+        };
+        let offset = match self.row_offset(rom, row) {
+            Ok(o) => o + field.offset,
+            Err(e) => return Err(e),
+        };
+
+        match rom.read(offset, field.field_type.size()) {
+            Ok(d) => Ok(field.field_type.decode(d)),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn set(&self, rom: &mut Rom, row: usize, name: &str, value: i64) -> Result<(), Error> {
+        let field = match self.get_field(name) {
+            Ok(f) => f,
+            Err(e) => return Err(e),
+        };
+
+        if value < field.min || value > field.max { return Err(Error::FieldOutOfRange(field.name.clone(), value)); }
+
+        let offset = match self.row_offset(rom, row) {
+            Ok(o) => o + field.offset,
+            Err(e) => return Err(e),
+        };
+        let encoded = match rom.read(offset, field.field_type.size()) {
+            Ok(d) => field.field_type.encode(value, d),
+            Err(e) => return Err(e),
+        };
+
+        rom.write(offset, encoded)
+    }
+}
+
+pub struct Table<'a> {
+    rom: &'a mut Rom,
+    schema: &'a TableSchema,
+}
+impl<'a> Table<'a> {
+    pub fn schema(&self) -> &TableSchema {
+        self.schema
+    }
+    pub fn len(&self) -> usize {
+        self.schema.row_count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.schema.row_count == 0
+    }
+    pub fn row(&mut self, row: usize) -> TableRow<'_> {
+        TableRow { rom: self.rom, schema: self.schema, row }
+    }
+}
+
+pub struct TableRow<'a> {
+    rom: &'a mut Rom,
+    schema: &'a TableSchema,
+    row: usize,
+}
+impl<'a> TableRow<'a> {
+    pub fn index(&self) -> usize {
+        self.row
+    }
+    pub fn get(&self, name: &str) -> Result<i64, Error> {
+        self.schema.get(self.rom, self.row, name)
+    }
+    pub fn set(&mut self, name: &str, value: i64) -> Result<(), Error> {
+        self.schema.set(self.rom, self.row, name, value)
+    }
+}
+
+impl Rom {
+    pub fn table<'a>(&'a mut self, schema: &'a TableSchema) -> Table<'a> {
+        Table { rom: self, schema }
+    }
+}
+
+#[macro_export]
+macro_rules! table_schema {
+    ($name:expr, offset: $offset:expr, row_size: $row_size:expr, rows: $rows:expr,
+     { $($field:literal: $ty:ident $(($shift:literal, $width:literal))? @ $field_offset:literal $(in $min:literal ..= $max:literal)?),* $(,)? }) => {{
+        let schema = $crate::TableSchema::new($name, $offset, $row_size, $rows);
+        $( let schema = schema.field($field, $field_offset, $crate::FieldType::$ty$(($shift, $width))?)$(.range($min, $max))?; )*
+        schema
+    }};
+}
@@ -0,0 +1,380 @@
+use crate::{Error, Rom, TextTable};
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ScriptEntry {
+    pub offset: usize,
+    pub length: usize,
+    pub text: String,
+    pub speaker: Option<String>,
+    pub translation: Option<String>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TranslationIssue {
+    Unencodable { index: usize, text: String },
+    TooLong { index: usize, needed: usize, available: usize },
+    UnknownEntry { context: String },
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Script {
+    pub entries: Vec<ScriptEntry>,
+}
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn dump(rom: &Rom, table: &TextTable, offsets: &[usize]) -> Result<Self, Error> {
+        let mut entries = Vec::<ScriptEntry>::new();
+
+        for &offset in offsets {
+            if offset >= rom.len() { return Err(Error::OutOfBounds(offset,rom.len())); }
+
+            let (text, length) = table.decode_string(&rom.as_slice()[offset..]);
+
+            entries.push(ScriptEntry { offset, length, text, speaker: None, translation: None });
+        }
+
+        Ok(Self { entries })
+    }
+    fn find_by_context(&self, rom: &Rom, context: &str) -> Option<usize> {
+        match rom.parse_offset(context) {
+            Ok(offset) => self.entries.iter().position(|e| e.offset == offset),
+            Err(_) => None,
+        }
+    }
+    pub fn to_po(&self, rom: &Rom) -> Result<String, Error> {
+        /* msgctxt carries the address in the ROM's notation so identical lines stay distinct entries */
+        let mut result = String::from("msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+
+        for entry in &self.entries {
+            let context = match rom.format_offset(entry.offset) {
+                Ok(c) => c,
+                Err(e) => return Err(e),
+            };
+
+            result.push('\n');
+            if let Some(speaker) = &entry.speaker { result.push_str(&format!("#. speaker: {}\n", speaker)); }
+            result.push_str(&format!("#. slot: {} bytes\n", entry.length));
+            result.push_str(&format!("msgctxt \"{}\"\n", po_escape(&context)));
+            result.push_str(&format!("msgid \"{}\"\n", po_escape(&entry.text)));
+            result.push_str(&format!("msgstr \"{}\"\n", po_escape(entry.translation.as_deref().unwrap_or(""))));
+        }
+
+        Ok(result)
+    }
+    pub fn to_csv(&self, rom: &Rom) -> Result<String, Error> {
+        let mut result = String::from("address,speaker,source,translation\r\n");
+
+        for entry in &self.entries {
+            let context = match rom.format_offset(entry.offset) {
+                Ok(c) => c,
+                Err(e) => return Err(e),
+            };
+
+            result.push_str(&[context.as_str(),
+                              entry.speaker.as_deref().unwrap_or(""),
+                              entry.text.as_str(),
+                              entry.translation.as_deref().unwrap_or("")].iter().map(|f| csv_escape(f)).collect::<Vec<String>>().join(","));
+            result.push_str("\r\n");
+        }
+
+        Ok(result)
+    }
+    pub fn import_po(&mut self, rom: &Rom, table: &TextTable, po: &str) -> Result<Vec<TranslationIssue>, Error> {
+        let translations = match parse_po(po) {
+            Ok(t) => t,
+            Err(e) => return Err(e),
+        };
+
+        Ok(self.apply_translations(rom, table, translations))
+    }
+    pub fn import_csv(&mut self, rom: &Rom, table: &TextTable, csv: &str) -> Result<Vec<TranslationIssue>, Error> {
+        let rows = match parse_csv(csv) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+        let translations = rows.into_iter().skip(1)
+            .filter(|r| r.len() >= 4)
+            .map(|r| (r[0].clone(), r[3].clone()))
+            .collect::<Vec<(String, String)>>();
+
+        Ok(self.apply_translations(rom, table, translations))
+    }
+    fn apply_translations(&mut self, rom: &Rom, table: &TextTable, translations: Vec<(String, String)>) -> Vec<TranslationIssue> {
+        /* only strings that encode and fit their original slot are accepted; everything else is reported */
+        let mut issues = Vec::<TranslationIssue>::new();
+
+        for (context, text) in translations {
+            if text.is_empty() { continue; }
+
+            let index = match self.find_by_context(rom, &context) {
+                Some(i) => i,
+                None => { issues.push(TranslationIssue::UnknownEntry { context }); continue; }
+            };
+            let encoded = match table.encode_string(&text) {
+                Ok(e) => e,
+                Err(_) => { issues.push(TranslationIssue::Unencodable { index, text }); continue; }
+            };
+
+            if encoded.len() > self.entries[index].length {
+                issues.push(TranslationIssue::TooLong { index, needed: encoded.len(), available: self.entries[index].length });
+                continue;
+            }
+
+            self.entries[index].translation = Some(text);
+        }
+
+        issues
+    }
+    pub fn write_translations(&self, rom: &mut Rom, table: &TextTable, fill: u8) -> Result<usize, Error> {
+        let mut written = 0usize;
+
+        for entry in &self.entries {
+            let translation = match &entry.translation {
+                Some(t) => t,
+                None => continue,
+            };
+            let mut encoded = match table.encode_string(translation) {
+                Ok(e) => e,
+                Err(e) => return Err(e),
+            };
+
+            if encoded.len() > entry.length { return Err(Error::DataLengthMismatch(encoded.len(),entry.length)); }
+
+            encoded.resize(entry.length, fill);
+
+            match rom.write(entry.offset, encoded) {
+                Ok(()) => written += 1,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+fn po_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t")
+}
+
+fn po_unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' { result.push(c); continue; }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => (),
+        }
+    }
+
+    result
+}
+
+fn parse_po(po: &str) -> Result<Vec<(String, String)>, Error> {
+    /* just enough PO to round-trip our own output: msgctxt/msgid/msgstr with continuation lines */
+    let mut result = Vec::<(String, String)>::new();
+    let mut context = String::new();
+    let mut msgstr = String::new();
+    let mut current: Option<&str> = None;
+
+    let mut flush = |context: &mut String, msgstr: &mut String| {
+        if !context.is_empty() { result.push((context.clone(), msgstr.clone())); }
+
+        context.clear();
+        msgstr.clear();
+    };
+
+    for (number, raw_line) in po.lines().enumerate() {
+        let line = raw_line.trim();
+
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let (keyword, rest) = match line.split_once(' ') {
+            Some((k, r)) if !line.starts_with('"') => (Some(k), r.trim()),
+            _ => (None, line),
+        };
+
+        if !rest.starts_with('"') || !rest.ends_with('"') || rest.len() < 2 { return Err(Error::InvalidScriptFormat(number + 1)); }
+
+        let value = po_unescape(&rest[1..rest.len()-1]);
+
+        match keyword {
+            Some("msgctxt") => { flush(&mut context, &mut msgstr); context = value; current = Some("msgctxt"); },
+            Some("msgid") => current = Some("msgid"),
+            Some("msgstr") => { msgstr = value; current = Some("msgstr"); },
+            Some(_) => return Err(Error::InvalidScriptFormat(number + 1)),
+            None => match current {
+                Some("msgctxt") => context.push_str(&value),
+                Some("msgstr") => msgstr.push_str(&value),
+                _ => (),
+            },
+        }
+    }
+
+    flush(&mut context, &mut msgstr);
+    Ok(result)
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) { format!("\"{}\"", field.replace('"', "\"\"")) }
+    else { field.to_string() }
+}
+
+fn parse_csv(csv: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut rows = Vec::<Vec<String>>::new();
+    let mut row = Vec::<String>::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1usize;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => { field.push('"'); chars.next(); },
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+            },
+            ('\n', true) => { field.push(c); line += 1; },
+            _ => field.push(c),
+        }
+    }
+
+    if quoted { return Err(Error::InvalidScriptFormat(line)); }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TextBudget {
+    OriginalSlots,
+    Allocated(Vec<usize>),
+    SharedPool(usize),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Abbreviation {
+    pub original: String,
+    pub replacement: String,
+    pub saved: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LengthCheck {
+    pub index: usize,
+    pub needed: Option<usize>,
+    pub available: usize,
+    pub suggestions: Vec<Abbreviation>,
+}
+impl LengthCheck {
+    pub fn overflow(&self) -> usize {
+        match self.needed {
+            Some(n) => n.saturating_sub(self.available),
+            None => 0,
+        }
+    }
+    pub fn fits(&self) -> bool {
+        self.needed.is_some_and(|n| n <= self.available)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LengthReport {
+    pub checks: Vec<LengthCheck>,
+    pub total_needed: usize,
+    pub total_available: usize,
+}
+impl LengthReport {
+    pub fn overflows(&self) -> Vec<&LengthCheck> {
+        self.checks.iter().filter(|c| !c.fits()).collect()
+    }
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.fits())
+    }
+}
+
+pub fn suggest_abbreviations(table: &TextTable, text: &str) -> Vec<Abbreviation> {
+    /* a multi-character entry that matches apart from case is usually the abbreviation the game's writers meant */
+    let mut result = Vec::<Abbreviation>::new();
+    let lower = text.to_lowercase();
+
+    for (bytes, value) in table.entries() {
+        if value.chars().count() < 2 || table.is_end_token(bytes) || text.contains(value.as_str()) { continue; }
+
+        let position = match lower.find(&value.to_lowercase()) {
+            Some(p) => p,
+            None => continue,
+        };
+        let original = match text.get(position..position + value.len()) {
+            Some(o) => o.to_string(),
+            None => continue,
+        };
+        let current = match table.encode(&original) {
+            Ok(e) => e.len(),
+            Err(_) => continue,
+        };
+
+        if current > bytes.len() {
+            result.push(Abbreviation { original, replacement: value.clone(), saved: current - bytes.len() });
+        }
+    }
+
+    result.sort_by(|a, b| b.saved.cmp(&a.saved).then(a.original.cmp(&b.original)));
+    result
+}
+
+impl Script {
+    pub fn validate_lengths(&self, table: &TextTable, budget: &TextBudget) -> LengthReport {
+        /* translations are measured where present, otherwise the dumped text, so the report covers a partially translated script */
+        let mut checks = Vec::<LengthCheck>::new();
+        let mut total_needed = 0usize;
+        let mut pool_remaining = match budget {
+            TextBudget::SharedPool(size) => *size,
+            _ => 0,
+        };
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let text = entry.translation.as_deref().unwrap_or(&entry.text);
+            let needed = table.encode_string(text).ok().map(|e| e.len());
+
+            total_needed += needed.unwrap_or(0);
+
+            let available = match budget {
+                TextBudget::OriginalSlots => entry.length,
+                TextBudget::Allocated(sizes) => sizes.get(index).copied().unwrap_or(0),
+                TextBudget::SharedPool(_) => pool_remaining,
+            };
+
+            if let (TextBudget::SharedPool(_), Some(n)) = (budget, needed) { pool_remaining = pool_remaining.saturating_sub(n); }
+
+            let overflowing = needed.is_none_or(|n| n > available);
+            let suggestions = if overflowing { suggest_abbreviations(table, text) } else { Vec::new() };
+
+            checks.push(LengthCheck { index, needed, available, suggestions });
+        }
+
+        let total_available = match budget {
+            TextBudget::OriginalSlots => self.entries.iter().map(|e| e.length).sum(),
+            TextBudget::Allocated(sizes) => sizes.iter().sum(),
+            TextBudget::SharedPool(size) => *size,
+        };
+
+        LengthReport { checks, total_needed, total_available }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{metrics, Addr24, AddrNotation, CancelToken, Error, MetricCounter, Rom};
+use std::ops::RangeInclusive;
+
+pub const SEARCH_CANCEL_INTERVAL: usize = 0x10000;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BytePattern(pub Vec<Option<u8>>);
+impl BytePattern {
+    pub fn from_bytes<B: AsRef<[u8]>>(data: B) -> Self {
+        Self(data.as_ref().iter().map(|b| Some(*b)).collect())
+    }
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        /* whitespace separated hex bytes, with ?? or ** standing in for any byte */
+        let mut result = Vec::<Option<u8>>::new();
+
+        for token in text.split_whitespace() {
+            match token {
+                "??" | "**" | "?" => result.push(None),
+                _ => match u8::from_str_radix(token, 16) {
+                    Ok(b) if token.len() <= 2 => result.push(Some(b)),
+                    _ => return Err(Error::InvalidPattern(text.to_string())),
+                },
+            }
+        }
+
+        if result.is_empty() { return Err(Error::InvalidPattern(text.to_string())); }
+
+        Ok(Self(result))
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.0.len() && self.0.iter().zip(data.iter()).all(|(p, b)| match p {
+            Some(v) => v == b,
+            None => true,
+        })
+    }
+}
+
+/* address is the CPU bus address under the ROM's mapper, None when the match starts inside the copier header */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SearchMatch {
+    pub offset: usize,
+    pub address: Option<Addr24>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplaceConstraints {
+    pub alignment: usize,
+    pub banks: Option<RangeInclusive<u8>>,
+    pub notation: AddrNotation,
+    pub max_replacements: Option<usize>,
+    pub cancel: CancelToken,
+}
+impl ReplaceConstraints {
+    pub fn new() -> Self {
+        Self { alignment: 1, banks: None, notation: AddrNotation::Pc, max_replacements: None, cancel: CancelToken::new() }
+    }
+    pub fn alignment(mut self, alignment: usize) -> Self {
+        self.alignment = alignment.max(1);
+        self
+    }
+    pub fn banks(mut self, banks: RangeInclusive<u8>, notation: AddrNotation) -> Self {
+        self.banks = Some(banks);
+        self.notation = notation;
+        self
+    }
+    pub fn max_replacements(mut self, max_replacements: usize) -> Self {
+        self.max_replacements = Some(max_replacements);
+        self
+    }
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+    fn allows(&self, rom: &Rom, offset: usize) -> bool {
+        /* alignment is measured from the start of ROM data, not the copier header */
+        if !(offset - rom.header_size()).is_multiple_of(self.alignment) { return false; }
+
+        match &self.banks {
+            Some(banks) => match rom.offset_to_notation(offset, self.notation) {
+                Ok(a) => banks.contains(&a.bank),
+                Err(_) => false,
+            },
+            None => true,
+        }
+    }
+}
+impl Default for ReplaceConstraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rom {
+    pub fn find_bytes<B: AsRef<[u8]>>(&self, needle: B, cancel: &CancelToken) -> Result<Vec<usize>, Error> {
+        self.find_pattern(&BytePattern::from_bytes(needle), cancel)
+    }
+    pub fn find_pattern(&self, pattern: &BytePattern, cancel: &CancelToken) -> Result<Vec<usize>, Error> {
+        let data = self.as_slice();
+        let mut result = Vec::<usize>::new();
+
+        if pattern.is_empty() || pattern.len() > data.len() { return Ok(result); }
+
+        for offset in 0..=data.len() - pattern.len() {
+            if offset % SEARCH_CANCEL_INTERVAL == 0 {
+                if let Err(e) = cancel.check() { return Err(e); }
+            }
+
+            if pattern.matches(&data[offset..]) { result.push(offset); }
+        }
+
+        metrics::record(MetricCounter::BytesSearched, data.len() - pattern.len() + 1);
+        Ok(result)
+    }
+    fn search_match(&self, offset: usize) -> SearchMatch {
+        SearchMatch { offset, address: self.offset_to_address(offset).ok() }
+    }
+    pub fn find(&self, pattern: &str) -> Result<Option<SearchMatch>, Error> {
+        let pattern = match BytePattern::parse(pattern) {
+            Ok(p) => p,
+            Err(e) => return Err(e),
+        };
+        let data = self.as_slice();
+
+        if pattern.len() > data.len() { return Ok(None); }
+
+        for offset in 0..=data.len() - pattern.len() {
+            if pattern.matches(&data[offset..]) {
+                metrics::record(MetricCounter::BytesSearched, offset + 1);
+                return Ok(Some(self.search_match(offset)));
+            }
+        }
+
+        metrics::record(MetricCounter::BytesSearched, data.len() - pattern.len() + 1);
+        Ok(None)
+    }
+    pub fn find_all(&self, pattern: &str) -> Result<Vec<SearchMatch>, Error> {
+        let pattern = match BytePattern::parse(pattern) {
+            Ok(p) => p,
+            Err(e) => return Err(e),
+        };
+
+        match self.find_pattern(&pattern, &CancelToken::new()) {
+            Ok(offsets) => Ok(offsets.into_iter().map(|o| self.search_match(o)).collect()),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn replace_bytes(&mut self, pattern: &BytePattern, replacement: &BytePattern, constraints: &ReplaceConstraints) -> Result<Vec<usize>, Error> {
+        /* wildcards in the replacement keep whatever byte was matched there */
+        if pattern.len() != replacement.len() { return Err(Error::DataLengthMismatch(replacement.len(),pattern.len())); }
+        if pattern.is_empty() || pattern.len() > self.len() { return Ok(Vec::new()); }
+
+        let mut result = Vec::<usize>::new();
+        let mut offset = self.header_size();
+
+        while offset + pattern.len() <= self.len() {
+            if let Some(max) = constraints.max_replacements {
+                if result.len() >= max { break; }
+            }
+
+            if offset.is_multiple_of(SEARCH_CANCEL_INTERVAL) {
+                if let Err(e) = constraints.cancel.check() { return Err(e); }
+            }
+
+            if !pattern.matches(&self.as_slice()[offset..]) || !constraints.allows(self, offset) {
+                offset += 1;
+                continue;
+            }
+
+            /* through write, so the journal, build log and watches all see each replacement */
+            let mut data = self.as_slice()[offset..offset+pattern.len()].to_vec();
+
+            for (byte, value) in data.iter_mut().zip(replacement.0.iter()) {
+                if let Some(v) = value { *byte = *v; }
+            }
+
+            if let Err(e) = self.write(offset, data) { return Err(e); }
+
+            result.push(offset);
+            offset += pattern.len();
+        }
+
+        metrics::record(MetricCounter::BytesSearched, offset - self.header_size());
+        Ok(result)
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{parse_number, Error, MemoryMap, Rom};
+use std::path::{Path, PathBuf};
+
+pub const SIDECAR_EXTENSION: &str = "flyhoney.toml";
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Bookmark {
+    pub offset: usize,
+    pub name: String,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Annotation {
+    pub offset: usize,
+    pub length: usize,
+    pub text: String,
+}
+
+/* crc32 is the ROM's after the patch went on, so a record that no longer matches the file stands out */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AppliedPatch {
+    pub name: String,
+    pub crc32: u32,
+}
+
+/* offsets are buffer offsets, copier header included, like everything else on Rom */
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct RomMetadata {
+    pub title: Option<String>,
+    pub mapper: Option<String>,
+    pub crc32: Option<u32>,
+    pub size: Option<usize>,
+    pub bookmarks: Vec<Bookmark>,
+    pub annotations: Vec<Annotation>,
+    pub patches: Vec<AppliedPatch>,
+}
+impl RomMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    pub fn detect(&mut self, rom: &Rom) {
+        self.title = rom.find_valid_snes_header().ok().map(|h| h.get_title());
+        self.mapper = rom.memory_map().ok().map(|m| m.name().to_string());
+        self.crc32 = Some(rom.crc32());
+        self.size = Some(rom.rom_size());
+    }
+    pub fn bookmark(&mut self, offset: usize, name: &str) {
+        match self.bookmarks.iter_mut().find(|b| b.offset == offset) {
+            Some(b) => b.name = name.to_string(),
+            None => self.bookmarks.push(Bookmark { offset, name: name.to_string() }),
+        }
+    }
+    pub fn annotate(&mut self, offset: usize, length: usize, text: &str) {
+        self.annotations.push(Annotation { offset, length: length.max(1), text: text.to_string() });
+    }
+    pub fn record_patch(&mut self, name: &str, rom: &Rom) {
+        self.patches.push(AppliedPatch { name: name.to_string(), crc32: rom.crc32() });
+    }
+    pub fn to_toml(&self) -> String {
+        /* the same TOML subset as the asset manifest: flat keys, then one array table per record */
+        let mut result = String::from("# flyhoney metadata\n");
+
+        if let Some(title) = &self.title { result.push_str(&format!("title = {}\n", quote(title))); }
+        if let Some(mapper) = &self.mapper { result.push_str(&format!("mapper = {}\n", quote(mapper))); }
+        if let Some(crc) = self.crc32 { result.push_str(&format!("crc32 = 0x{:08X}\n", crc)); }
+        if let Some(size) = self.size { result.push_str(&format!("size = 0x{:X}\n", size)); }
+
+        for bookmark in &self.bookmarks {
+            result.push_str("\n[[bookmark]]\n");
+            result.push_str(&format!("offset = 0x{:06X}\n", bookmark.offset));
+            result.push_str(&format!("name = {}\n", quote(&bookmark.name)));
+        }
+        for annotation in &self.annotations {
+            result.push_str("\n[[annotation]]\n");
+            result.push_str(&format!("offset = 0x{:06X}\n", annotation.offset));
+            result.push_str(&format!("length = 0x{:X}\n", annotation.length));
+            result.push_str(&format!("text = {}\n", quote(&annotation.text)));
+        }
+        for patch in &self.patches {
+            result.push_str("\n[[patch]]\n");
+            result.push_str(&format!("name = {}\n", quote(&patch.name)));
+            result.push_str(&format!("crc32 = 0x{:08X}\n", patch.crc32));
+        }
+
+        result
+    }
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        let mut result = Self::new();
+        let mut section = "";
+
+        for (number_index, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim();
+            let bad_line = Error::InvalidMetadataLine(number_index + 1);
+
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            match line {
+                "[[bookmark]]" => { result.bookmarks.push(Bookmark { offset: 0, name: String::new() }); section = "bookmark"; continue; },
+                "[[annotation]]" => { result.annotations.push(Annotation { offset: 0, length: 1, text: String::new() }); section = "annotation"; continue; },
+                "[[patch]]" => { result.patches.push(AppliedPatch { name: String::new(), crc32: 0 }); section = "patch"; continue; },
+                _ => (),
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(bad_line),
+            };
+            let string = unquote(value);
+            let number = || match parse_number(value.split('#').next().unwrap_or("").trim()) {
+                Some(n) => Ok(n),
+                None => Err(Error::InvalidMetadataLine(number_index + 1)),
+            };
+
+            match (section, key) {
+                ("", "title") => result.title = match string { Some(s) => Some(s), None => return Err(bad_line) },
+                ("", "mapper") => result.mapper = match string { Some(s) => Some(s), None => return Err(bad_line) },
+                ("", "crc32") => result.crc32 = Some(match number() { Ok(n) => n as u32, Err(e) => return Err(e) }),
+                ("", "size") => result.size = Some(match number() { Ok(n) => n, Err(e) => return Err(e) }),
+                ("bookmark", "offset") => result.bookmarks.last_mut().unwrap().offset = match number() { Ok(n) => n, Err(e) => return Err(e) },
+                ("bookmark", "name") => result.bookmarks.last_mut().unwrap().name = match string { Some(s) => s, None => return Err(bad_line) },
+                ("annotation", "offset") => result.annotations.last_mut().unwrap().offset = match number() { Ok(n) => n, Err(e) => return Err(e) },
+                ("annotation", "length") => result.annotations.last_mut().unwrap().length = match number() { Ok(n) => n, Err(e) => return Err(e) },
+                ("annotation", "text") => result.annotations.last_mut().unwrap().text = match string { Some(s) => s, None => return Err(bad_line) },
+                ("patch", "name") => result.patches.last_mut().unwrap().name = match string { Some(s) => s, None => return Err(bad_line) },
+                ("patch", "crc32") => result.patches.last_mut().unwrap().crc32 = match number() { Ok(n) => n as u32, Err(e) => return Err(e) },
+                _ => return Err(Error::UnknownField(key.to_string())),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+pub(crate) fn quote(text: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+pub(crate) fn unquote(value: &str) -> Option<String> {
+    /* a basic string, then nothing but an optional comment */
+    let mut chars = value.strip_prefix('"')?.chars();
+    let mut result = String::new();
+
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                'n' => result.push('\n'),
+                't' => result.push('\t'),
+                c => result.push(c),
+            },
+            c => result.push(c),
+        }
+    }
+
+    let rest = chars.as_str().trim();
+    if rest.is_empty() || rest.starts_with('#') { Some(result) } else { None }
+}
+
+pub fn sidecar_path<P: AsRef<Path>>(rom_path: P) -> PathBuf {
+    rom_path.as_ref().with_extension(SIDECAR_EXTENSION)
+}
+
+impl Rom {
+    pub fn metadata(&self) -> &RomMetadata {
+        &self.metadata
+    }
+    pub fn metadata_mut(&mut self) -> &mut RomMetadata {
+        &mut self.metadata
+    }
+    pub fn set_metadata(&mut self, metadata: RomMetadata) {
+        self.metadata = metadata;
+    }
+    pub fn load_metadata(&mut self) -> Result<bool, Error> {
+        /* a missing sidecar isn't an error, just nothing recorded yet */
+        let path = match &self.path {
+            Some(p) => sidecar_path(p),
+            None => return Err(Error::NoSourcePath),
+        };
+        if !path.exists() { return Ok(false); }
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(e) => return Err(Error::IoError(e)),
+        };
+
+        match RomMetadata::from_toml(&text) {
+            Ok(m) => { self.metadata = m; Ok(true) },
+            Err(e) => Err(e),
+        }
+    }
+    pub fn save_metadata(&mut self) -> Result<(), Error> {
+        /* detected fields are refreshed on the way out so they describe the ROM as it is now */
+        let path = match &self.path {
+            Some(p) => sidecar_path(p),
+            None => return Err(Error::NoSourcePath),
+        };
+
+        /* from_file shrugs off a sidecar it can't parse, so don't let saving quietly replace whatever was in it */
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            if let Err(e) = RomMetadata::from_toml(&text) { return Err(e); }
+        }
+
+        let mut metadata = self.metadata.clone();
+        metadata.detect(self);
+        self.metadata = metadata;
+
+        match std::fs::write(path, self.metadata.to_toml()) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use crate::{decode_opcode, Addr24, AddrNotation, AddressingMode, CancelToken, Error, Mnemonic, RegionKind, RegionMap, Rom};
+use std::collections::{BTreeSet, VecDeque};
+
+pub const SURVEY_PROGRESS_INTERVAL: usize = 0x10000;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SurveyPass {
+    Graphics,
+    Palette,
+    Text,
+    Code,
+}
+impl SurveyPass {
+    pub const ALL: [SurveyPass; 4] = [SurveyPass::Graphics, SurveyPass::Palette, SurveyPass::Text, SurveyPass::Code];
+
+    pub fn kind(&self) -> RegionKind {
+        match self {
+            SurveyPass::Graphics => RegionKind::Graphics,
+            SurveyPass::Palette => RegionKind::Palette,
+            SurveyPass::Text => RegionKind::Text,
+            SurveyPass::Code => RegionKind::Code,
+        }
+    }
+    pub fn run(&self, rom: &Rom, progress: &dyn Fn(usize, usize), cancel: &CancelToken) -> Result<Vec<SurveyHit>, Error> {
+        match self {
+            SurveyPass::Graphics => survey_graphics(rom, progress, cancel),
+            SurveyPass::Palette => survey_palettes(rom, progress, cancel),
+            SurveyPass::Text => survey_text(rom, progress, cancel),
+            SurveyPass::Code => survey_code(rom, progress, cancel),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SurveyHit {
+    pub offset: usize,
+    pub length: usize,
+    pub kind: RegionKind,
+    pub score: f32,
+}
+
+pub fn hits_to_region_map(rom: &Rom, hits: &[SurveyHit]) -> Result<RegionMap, Error> {
+    let mut map = RegionMap::from_rom(rom);
+
+    for hit in hits {
+        match map.mark(hit.offset - rom.header_size(), hit.length, hit.kind) {
+            Ok(()) => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(map)
+}
+
+fn check_progress(done: usize, total: usize, progress: &dyn Fn(usize, usize), cancel: &CancelToken) -> Result<(), Error> {
+    if let Err(e) = cancel.check() { return Err(e); }
+
+    progress(done, total);
+    Ok(())
+}
+
+fn merge_hits(hits: Vec<SurveyHit>) -> Vec<SurveyHit> {
+    let mut result = Vec::<SurveyHit>::new();
+
+    for hit in hits {
+        match result.last_mut() {
+            Some(last) if last.kind == hit.kind && last.offset + last.length == hit.offset => {
+                let total = (last.length + hit.length) as f32;
+                last.score = (last.score * last.length as f32 + hit.score * hit.length as f32) / total;
+                last.length += hit.length;
+            },
+            _ => result.push(hit),
+        }
+    }
+
+    result
+}
+
+pub fn survey_graphics(rom: &Rom, progress: &dyn Fn(usize, usize), cancel: &CancelToken) -> Result<Vec<SurveyHit>, Error> {
+    /* tiles are vertically coherent: in the intertwined formats the same bitplane of the next row sits two bytes later */
+    const BLOCK: usize = 0x100;
+    const THRESHOLD: f32 = 0.25;
+
+    let data = &rom.as_slice()[rom.header_size()..];
+    let mut hits = Vec::<SurveyHit>::new();
+
+    for (index, block) in data.chunks_exact(BLOCK).enumerate() {
+        let start = index * BLOCK;
+
+        if start.is_multiple_of(SURVEY_PROGRESS_INTERVAL) {
+            match check_progress(start, data.len(), progress, cancel) {
+                Ok(()) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let distinct = block.iter().collect::<BTreeSet<&u8>>().len();
+        if distinct < 3 { continue; }
+
+        let mut matches = 0usize;
+        let mut comparisons = 0usize;
+
+        for i in 0..block.len() {
+            if i % 16 >= 14 || i + 2 >= block.len() { continue; }
+
+            comparisons += 1;
+            if block[i] == block[i+2] { matches += 1; }
+        }
+
+        let score = matches as f32 / comparisons as f32;
+
+        if score >= THRESHOLD {
+            hits.push(SurveyHit { offset: rom.header_size() + start, length: BLOCK, kind: RegionKind::Graphics, score });
+        }
+    }
+
+    Ok(merge_hits(hits))
+}
+
+pub fn survey_palettes(rom: &Rom, progress: &dyn Fn(usize, usize), cancel: &CancelToken) -> Result<Vec<SurveyHit>, Error> {
+    /* a palette row is sixteen BGR555 words: bit 15 clear, several distinct colors, and not just printable text */
+    const BLOCK: usize = 32;
+
+    let data = &rom.as_slice()[rom.header_size()..];
+    let mut hits = Vec::<SurveyHit>::new();
+
+    for (index, block) in data.chunks_exact(BLOCK).enumerate() {
+        let start = index * BLOCK;
+
+        if start.is_multiple_of(SURVEY_PROGRESS_INTERVAL) {
+            match check_progress(start, data.len(), progress, cancel) {
+                Ok(()) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let words = block.chunks_exact(2).map(|w| u16::from_le_bytes([w[0], w[1]])).collect::<Vec<u16>>();
+
+        if words.iter().any(|w| w & 0x8000 != 0) { continue; }
+        if block.iter().all(|b| (0x20..0x7F).contains(b) || *b == 0) { continue; }
+
+        let distinct = words.iter().collect::<BTreeSet<&u16>>().len();
+        if distinct < 6 { continue; }
+
+        /* pointer and counter tables climb steadily, palettes rarely do */
+        if words.windows(2).all(|w| w[0] < w[1]) { continue; }
+
+        hits.push(SurveyHit { offset: rom.header_size() + start, length: BLOCK, kind: RegionKind::Palette, score: distinct as f32 / 16.0 });
+    }
+
+    Ok(merge_hits(hits))
+}
+
+pub fn survey_text(rom: &Rom, progress: &dyn Fn(usize, usize), cancel: &CancelToken) -> Result<Vec<SurveyHit>, Error> {
+    const MINIMUM_LENGTH: usize = 8;
+
+    let data = &rom.as_slice()[rom.header_size()..];
+    let mut hits = Vec::<SurveyHit>::new();
+    let mut run_start: Option<usize> = None;
+
+    for i in 0..=data.len() {
+        if i % SURVEY_PROGRESS_INTERVAL == 0 {
+            match check_progress(i, data.len(), progress, cancel) {
+                Ok(()) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let printable = i < data.len() && (0x20..0x7F).contains(&data[i]);
+
+        match (printable, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                let run = &data[start..i];
+                let letters = run.iter().filter(|b| b.is_ascii_alphabetic() || **b == b' ').count();
+                let score = letters as f32 / run.len() as f32;
+
+                if run.len() >= MINIMUM_LENGTH && score >= 0.6 {
+                    hits.push(SurveyHit { offset: rom.header_size() + start, length: run.len(), kind: RegionKind::Text, score });
+                }
+
+                run_start = None;
+            },
+            _ => (),
+        }
+    }
+
+    Ok(hits)
+}
+
+pub fn code_entry_points(rom: &Rom) -> Result<(AddrNotation, Vec<usize>), Error> {
+    let header = match rom.find_valid_snes_header_address() {
+        Ok(a) => a,
+        Err(e) => return Err(e),
+    };
+    let notation = if header.address < 0x8000 { AddrNotation::LoRom } else { AddrNotation::HiRom };
+    let header_offset = header.to_offset(rom);
+    let mut result = Vec::<usize>::new();
+
+    /* native NMI, native IRQ, emulation NMI and reset, relative to the header at $FFC0 */
+    for vector in &[0x2Ausize, 0x2E, 0x3A, 0x3C] {
+        let pointer = match rom.read(header_offset + vector, 2) {
+            Ok(d) => u16::from_le_bytes([d[0], d[1]]),
+            Err(e) => return Err(e),
+        };
+
+        if pointer < 0x8000 { continue; }
+
+        match notation.address_to_pc(Addr24::new(0, pointer)) {
+            Ok(pc) if pc + rom.header_size() < rom.len() => result.push(pc + rom.header_size()),
+            _ => (),
+        }
+    }
+
+    result.sort();
+    result.dedup();
+
+    Ok((notation, result))
+}
+
+pub fn survey_code(rom: &Rom, progress: &dyn Fn(usize, usize), cancel: &CancelToken) -> Result<Vec<SurveyHit>, Error> {
+    /* recursive descent from the interrupt vectors, tracking REP/SEP so immediate operand sizes stay right */
+    const INSTRUCTION_LIMIT: usize = 0x40000;
+
+    let (notation, entries) = match code_entry_points(rom) {
+        Ok(e) => e,
+        Err(e) => return Err(e),
+    };
+    let data = rom.as_slice();
+    let mut visited = vec![false; data.len()];
+    let mut queued = BTreeSet::<(usize, bool, bool)>::new();
+    let mut queue = entries.iter().map(|&o| (o, true, true)).collect::<VecDeque<(usize, bool, bool)>>();
+    let mut executed = 0usize;
+
+    let to_offset = |bank: u8, address: u16| -> Option<usize> {
+        match notation.address_to_pc(Addr24::new(bank, address)) {
+            Ok(pc) if pc + rom.header_size() < data.len() => Some(pc + rom.header_size()),
+            _ => None,
+        }
+    };
+    let to_address = |offset: usize| rom.offset_to_notation(offset, notation).ok();
+
+    while let Some((start, m8, x8)) = queue.pop_front() {
+        if !queued.insert((start, m8, x8)) { continue; }
+
+        let (mut offset, mut m8, mut x8) = (start, m8, x8);
+
+        loop {
+            if offset >= data.len() || visited[offset] || executed >= INSTRUCTION_LIMIT { break; }
+
+            executed += 1;
+
+            if executed.is_multiple_of(0x1000) {
+                match check_progress(executed, INSTRUCTION_LIMIT, progress, cancel) {
+                    Ok(()) => (),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            let (mnemonic, mode) = decode_opcode(data[offset]);
+            let length = 1 + mode.operand_size(m8, x8);
+            if offset + length > data.len() { break; }
+
+            let mut operand = 0u32;
+            for i in 1..length { operand |= (data[offset + i] as u32) << ((i - 1) * 8); }
+
+            for entry in &mut visited[offset..offset+length] { *entry = true; }
+
+            let address = match to_address(offset) {
+                Some(a) => a,
+                None => break,
+            };
+            let next = address.address.wrapping_add(length as u16);
+
+            match (mnemonic, mode) {
+                (Mnemonic::REP, _) => {
+                    if operand & 0x20 != 0 { m8 = false; }
+                    if operand & 0x10 != 0 { x8 = false; }
+                },
+                (Mnemonic::SEP, _) => {
+                    if operand & 0x20 != 0 { m8 = true; }
+                    if operand & 0x10 != 0 { x8 = true; }
+                },
+                (_, AddressingMode::Relative) => {
+                    let target = next.wrapping_add(operand as u8 as i8 as u16);
+                    if let Some(o) = to_offset(address.bank, target) { queue.push_back((o, m8, x8)); }
+                },
+                (Mnemonic::BRL, _) => {
+                    let target = next.wrapping_add(operand as u16);
+                    if let Some(o) = to_offset(address.bank, target) { queue.push_back((o, m8, x8)); }
+                },
+                (Mnemonic::JSR, AddressingMode::Absolute) | (Mnemonic::JMP, AddressingMode::Absolute) => {
+                    if let Some(o) = to_offset(address.bank, operand as u16) { queue.push_back((o, m8, x8)); }
+                },
+                (Mnemonic::JSL, _) | (Mnemonic::JML, AddressingMode::AbsoluteLong) => {
+                    if let Some(o) = to_offset((operand >> 16) as u8, operand as u16) { queue.push_back((o, m8, x8)); }
+                },
+                _ => (),
+            }
+
+            match mnemonic {
+                Mnemonic::RTS | Mnemonic::RTL | Mnemonic::RTI | Mnemonic::JMP | Mnemonic::JML |
+                Mnemonic::BRA | Mnemonic::BRL | Mnemonic::BRK | Mnemonic::STP => break,
+                _ => offset += length,
+            }
+        }
+    }
+
+    let mut hits = Vec::<SurveyHit>::new();
+
+    for (offset, is_code) in visited.iter().enumerate() {
+        if !is_code { continue; }
+
+        hits.push(SurveyHit { offset, length: 1, kind: RegionKind::Code, score: 1.0 });
+    }
+
+    Ok(merge_hits(hits))
+}
@@ -0,0 +1,88 @@
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SymbolSpace {
+    Rom,
+    WorkRam,
+    SaveRam,
+    Register,
+}
+impl SymbolSpace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolSpace::Rom => "rom",
+            SymbolSpace::WorkRam => "wram",
+            SymbolSpace::SaveRam => "sram",
+            SymbolSpace::Register => "register",
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Symbol {
+    pub space: SymbolSpace,
+    pub offset: usize,
+    pub length: usize,
+    pub name: String,
+    pub comment: Option<String>,
+}
+impl Symbol {
+    pub fn new(space: SymbolSpace, offset: usize, name: &str) -> Self {
+        Self { space, offset, length: 1, name: name.to_string(), comment: None }
+    }
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = length.max(1);
+        self
+    }
+    pub fn comment(mut self, comment: &str) -> Self {
+        self.comment = Some(comment.to_string());
+        self
+    }
+    pub fn contains(&self, space: SymbolSpace, offset: usize) -> bool {
+        self.space == space && offset >= self.offset && offset < self.offset + self.length
+    }
+}
+
+/* rom symbols are keyed by rom offset without the copier header, the same as region maps */
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+    pub fn insert(&mut self, symbol: Symbol) {
+        /* one name per location: a later import renames rather than stacking a second label */
+        match self.symbols.iter_mut().find(|s| s.space == symbol.space && s.offset == symbol.offset) {
+            Some(existing) => *existing = symbol,
+            None => self.symbols.push(symbol),
+        }
+    }
+    pub fn remove(&mut self, space: SymbolSpace, offset: usize) -> Option<Symbol> {
+        match self.symbols.iter().position(|s| s.space == space && s.offset == offset) {
+            Some(i) => Some(self.symbols.remove(i)),
+            None => None,
+        }
+    }
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+    pub fn at(&self, space: SymbolSpace, offset: usize) -> Option<&Symbol> {
+        /* an exact start beats a range that merely covers the offset */
+        self.symbols.iter().find(|s| s.space == space && s.offset == offset)
+            .or_else(|| self.symbols.iter().find(|s| s.contains(space, offset)))
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Symbol> {
+        self.symbols.iter()
+    }
+    pub fn merge(&mut self, other: &SymbolTable) {
+        for symbol in &other.symbols {
+            self.insert(symbol.clone());
+        }
+    }
+}
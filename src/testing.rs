@@ -0,0 +1,95 @@
+use crate::{Error, PixelBuffer, Rgb888, TILE_DIFF_SEPARATOR};
+use std::path::{Path, PathBuf};
+
+/* set to anything to have assert_pixels_eq write the actual image as the new golden instead of comparing */
+pub const UPDATE_GOLDEN_VAR: &str = "FLYHONEY_UPDATE_GOLDEN";
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PixelDiff {
+    pub mismatches: usize,
+    pub first: Option<(usize, usize)>,
+    pub image: PixelBuffer,
+}
+impl PixelDiff {
+    pub fn is_match(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+pub fn compare_pixels(actual: &PixelBuffer, expected: &PixelBuffer) -> PixelDiff {
+    /* expected, actual and a mask side by side with a separator column between each; the mask dims matching
+       pixels and marks differing ones in the separator color. pixels outside either image count as differing */
+    let width = actual.width.max(expected.width);
+    let height = actual.height.max(expected.height);
+    let mut image = PixelBuffer::new(width * 3 + 2, height);
+    let mut mismatches = 0;
+    let mut first: Option<(usize, usize)> = None;
+
+    image.fill_rect(0, 0, image.width, height, TILE_DIFF_SEPARATOR);
+
+    for y in 0..height {
+        for x in 0..width {
+            let before = expected.get_pixel(x, y).ok();
+            let after = actual.get_pixel(x, y).ok();
+
+            if let Some(c) = before { image.pixels[y*image.width+x] = c; }
+            if let Some(c) = after { image.pixels[y*image.width+width+1+x] = c; }
+
+            let mask = match (before, after) {
+                (Some(b), Some(a)) if a == b => Rgb888(((b.0 >> 2) & 0x3F3F3F) + 0x202020),
+                _ => {
+                    mismatches += 1;
+                    if first.is_none() { first = Some((x, y)); }
+                    TILE_DIFF_SEPARATOR
+                },
+            };
+
+            image.pixels[y*image.width+(width+1)*2+x] = mask;
+        }
+    }
+
+    PixelDiff { mismatches, first, image }
+}
+
+fn sibling_path(golden: &Path, suffix: &str) -> PathBuf {
+    let stem = golden.file_stem().map_or("golden".to_string(), |s| s.to_string_lossy().to_string());
+    golden.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+pub fn compare_golden<P: AsRef<Path>>(actual: &PixelBuffer, golden: P) -> Result<PixelDiff, Error> {
+    let data = match std::fs::read(golden.as_ref()) {
+        Ok(d) => d,
+        Err(e) => return Err(Error::IoError(e)),
+    };
+
+    match PixelBuffer::from_png(&data) {
+        Ok(expected) => Ok(compare_pixels(actual, &expected)),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn assert_pixels_eq<P: AsRef<Path>>(actual: &PixelBuffer, golden: P) {
+    /* on a mismatch the actual image and the diff land next to the golden as name.actual.png and name.diff.png */
+    let golden = golden.as_ref();
+
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        if let Err(e) = std::fs::write(golden, actual.to_png()) { panic!("couldn't write golden {}: {:?}", golden.display(), e); }
+        return;
+    }
+
+    let diff = match compare_golden(actual, golden) {
+        Ok(d) => d,
+        Err(e) => panic!("couldn't load golden {}: {:?} (set {} to create it)", golden.display(), e, UPDATE_GOLDEN_VAR),
+    };
+
+    if diff.is_match() { return; }
+
+    let actual_path = sibling_path(golden, "actual");
+    let diff_path = sibling_path(golden, "diff");
+    let _ = std::fs::write(&actual_path, actual.to_png());
+    let _ = std::fs::write(&diff_path, diff.image.to_png());
+
+    let (x, y) = diff.first.unwrap_or((0, 0));
+    panic!("{} pixels differ from {}, first at ({}, {}); actual written to {}, diff to {}",
+           diff.mismatches, golden.display(), x, y, actual_path.display(), diff_path.display());
+}
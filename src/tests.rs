@@ -58,3 +58,3522 @@ fn test_graphics() {
     let intertwined_2bpp = intertwined_2bpp_result.unwrap();
     assert_eq!(intertwined_2bpp.0.to_vec(), hex::decode("3ffc1fe027f930ef27f83fe0c0e700e0").unwrap());
 }
+
+#[test]
+fn test_region_map() {
+    let mut region_map = RegionMap::new(0x1000);
+    assert!(region_map.mark(0x100, 0x200, RegionKind::Code).is_ok());
+    assert!(region_map.mark(0x800, 0x10, RegionKind::Graphics).is_ok());
+    assert!(region_map.mark(0xFF0, 0x20, RegionKind::Data).is_err());
+
+    let runs = region_map.runs();
+    assert_eq!(runs.len(), 5);
+    assert_eq!(runs[1], RegionRun { offset: 0x100, length: 0x200, kind: RegionKind::Code });
+
+    let rle = region_map.to_rle_bytes();
+    let decoded_result = RegionMap::from_rle_bytes(&rle);
+    assert!(decoded_result.is_ok());
+    assert_eq!(decoded_result.unwrap(), region_map);
+
+    let mut bad_kind = rle.clone();
+    *bad_kind.last_mut().unwrap() = 0xEE;
+    assert!(matches!(RegionMap::from_rle_bytes(&bad_kind), Err(Error::InvalidRegionKind(0xEE))));
+
+    /* two runs of u64::MAX/2+1 bytes each would wrap back around to the declared length of zero */
+    let mut overflow = rle[..8].to_vec();
+    overflow.extend_from_slice(&[0x00, 0x02]);
+    for _ in 0..2 {
+        overflow.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01, 0x00]);
+    }
+    assert!(RegionMap::from_rle_bytes(&overflow).is_err());
+
+    /* one consistent run of 2^40 bytes is still far more than any image, and is refused before it's expanded */
+    let mut huge = rle[..8].to_vec();
+    huge.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x20, 0x01, 0x80, 0x80, 0x80, 0x80, 0x80, 0x20, 0x00]);
+    assert!(matches!(RegionMap::from_rle_bytes(&huge), Err(Error::UnaddressableRomSize(0x10000000000, REGION_MAP_MAX_LENGTH))));
+    assert!(matches!(region_map.mark(0x10, usize::MAX, RegionKind::Code), Err(Error::OutOfBounds(usize::MAX, 0x1000))));
+
+    let json = region_map.to_json();
+    assert!(json.starts_with("{\"schema\":\"flyhoney-region-map\",\"version\":1,\"length\":4096,"));
+    assert!(json.ends_with("\"runs\":[[256,0],[512,1],[1280,0],[16,3],[2032,0]]}"));
+}
+
+#[test]
+fn test_update_header() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let update_result = rom.update_header(|h| {
+        assert!(h.set_title("MOTHER 2").is_ok());
+        h.set_version(1);
+    });
+    assert!(update_result.is_ok());
+
+    let checksum = rom.checksum();
+    let snes_header_result = rom.find_valid_snes_header();
+    assert!(snes_header_result.is_ok());
+
+    let snes_header = snes_header_result.unwrap();
+    assert_eq!(snes_header.get_title(), "MOTHER 2");
+    assert_eq!(snes_header.get_version(), 1);
+    assert_eq!(snes_header.get_checksum(), checksum);
+    assert_eq!(snes_header.get_checksum_compliment(), checksum ^ 0xFFFF);
+}
+
+#[test]
+fn test_multicart() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let earthbound = rom_result.unwrap();
+    assert!(!earthbound.is_multicart());
+
+    let mut test_data = vec![0u8; 0x8000];
+    test_data[0x7fc0..0x7fd5].copy_from_slice(b"TEST GAME            ");
+    test_data[0x7fd7] = 5;
+    test_data[0x7fdc] = 0xFF;
+    test_data[0x7fdd] = 0xFF;
+
+    let test_game = Rom::new(&test_data);
+    let multicart = MulticartBuilder::new().add_game(&earthbound).add_game(&test_game).build();
+    assert!(multicart.is_multicart());
+
+    let games = multicart.find_multicart_games();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].title, "EARTH BOUND");
+    assert_eq!(games[0].mapping, MulticartMapping::HiRom);
+    assert_eq!(games[1], MulticartGame { offset: 0x400000, size: 0x8000, mapping: MulticartMapping::LoRom, title: "TEST GAME".to_string() });
+
+    let split_result = multicart.split_multicart();
+    assert!(split_result.is_ok());
+
+    let split = split_result.unwrap();
+    assert_eq!(split[1], test_game);
+    assert_eq!(&split[0].as_slice()[..0x300000], &earthbound.as_slice()[earthbound.header_size()..]);
+}
+
+#[test]
+fn test_fade_tables() {
+    let palette = [Bgr555::new(31,31,31), Bgr555::new(16,8,0)];
+
+    let to_black = generate_fade_tables(&palette, 4, FadeDirection::ToBlack);
+    assert_eq!(to_black.len(), 4);
+    assert_eq!(to_black[0], palette.to_vec());
+    assert_eq!(to_black[1], vec![Bgr555::new(21,21,21), Bgr555::new(11,5,0)]);
+    assert_eq!(to_black[3], vec![Bgr555(0), Bgr555(0)]);
+
+    let from_white = generate_fade_tables(&palette, 2, FadeDirection::FromWhite);
+    assert_eq!(from_white[0], vec![Bgr555::new(31,31,31), Bgr555::new(31,31,31)]);
+    assert_eq!(from_white[1], palette.to_vec());
+
+    let mut layout = FadeTableLayout::new(FadeTableOrder::ByColor);
+    layout.stride = 6;
+
+    let serialized = serialize_fade_tables(&from_white, &layout);
+    assert_eq!(serialized, hex::decode("ff7fff7f0000ff7f10010000").unwrap());
+}
+
+#[test]
+fn test_ppu_registers() {
+    assert_eq!(ppu::register_name(0x2105), Some("BGMODE"));
+    assert_eq!(ppu::register_name(0x2140), None);
+
+    assert_eq!(ppu::format_register_write(ppu::BGMODE, 0x19), "BGMODE = $19 (mode 1, BG3 priority, BG1 16x16)");
+    assert_eq!(ppu::format_register_write(ppu::TM, 0x15), "TM = $15 (BG1|BG3|OBJ)");
+    assert_eq!(ppu::format_register_write(ppu::INIDISP, 0x8F), "INIDISP = $8F (forced blank, brightness 15)");
+    assert_eq!(ppu::ObjSel(0xC3).size().large(), (32,64));
+
+    let mode1 = ppu::BgMode(0x09);
+    assert_eq!(mode1.layer_count(), 3);
+    assert_eq!(mode1.layer_format(2), Some(ppu::LayerFormat::Planar(2)));
+    assert!(mode1.validate_tiles::<SNESTile4BPPPlanar>(1).is_ok());
+    assert!(mode1.validate_tiles::<SNESTile8BPPPlanar>(1).is_err());
+    assert!(ppu::BgMode(0x02).offset_per_tile());
+    assert!(ppu::BgMode(0x07).validate_tiles::<SNESTileMode7>(0).is_ok());
+    assert!(ppu::BgMode(0x07).validate_tiles::<SNESTile8BPPPlanar>(0).is_err());
+    assert_eq!(ppu::BgMode(0x07).layer_format_extbg(1, true).unwrap().colors(), 128);
+}
+
+#[test]
+fn test_hdma_table() {
+    let direct_data = hex::decode("2001028304050607080900").unwrap();
+    let direct_result = HdmaTable::parse(&direct_data, TransferMode::Mode2, false);
+    assert!(direct_result.is_ok());
+
+    let direct = direct_result.unwrap();
+    assert_eq!(direct.entries.len(), 2);
+    assert_eq!(direct.entries[0], HdmaEntry { line_count: 0x20, repeat: false, data: HdmaEntryData::Direct(vec![1,2]) });
+    assert_eq!(direct.entries[1].line_count, 3);
+    assert!(direct.entries[1].repeat);
+    assert_eq!(direct.total_lines(), 0x23);
+    assert_eq!(direct.to_bytes().unwrap(), direct_data);
+
+    let indirect_data = hex::decode("7f0080800080").unwrap();
+    assert!(HdmaTable::parse(&indirect_data, TransferMode::Mode0, true).is_err());
+
+    let indirect_result = HdmaTable::parse(&[0x7f, 0x00, 0x80, 0x80, 0x00, 0x90, 0x00], TransferMode::Mode0, true);
+    assert!(indirect_result.is_ok());
+
+    let indirect = indirect_result.unwrap();
+    assert_eq!(indirect.entries[1], HdmaEntry { line_count: 128, repeat: true, data: HdmaEntryData::Indirect(0x9000) });
+    assert_eq!(indirect.to_bytes().unwrap(), vec![0x7f, 0x00, 0x80, 0x80, 0x00, 0x90, 0x00]);
+
+    let params_result = DmaParams::from_data(hex::decode("01180080c10008").unwrap());
+    assert!(params_result.is_ok());
+
+    let params = params_result.unwrap();
+    assert_eq!(params.mode(), TransferMode::Mode1);
+    assert_eq!(params.b_bus_register(), 0x2118);
+    assert_eq!(params.a_bus_address, Addr24::new(0xC1, 0x8000));
+    assert_eq!(params.transfer_size(), 0x800);
+}
+
+#[test]
+fn test_cpu_trace() {
+    let program = hex::decode("18fbc230a900108d1621a20000bf0090008d0000a901188d0043a900908d0243e220a9008d0443c220a900028d0543e220a9018d0b4260").unwrap();
+    let mut data = vec![0u8; 0x10000];
+    data[0x8000..0x8000+program.len()].copy_from_slice(&program);
+    data[0x9000] = 0x34;
+    data[0x9001] = 0x12;
+
+    let mut rom = Rom::new(&data);
+    rom.set_memory_map(Some(Mapper::HiRom(HiRom)));
+    let mut cpu = Cpu::new(&rom);
+    let result = cpu.call(Addr24::new(0, 0x8000), 100);
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), StopReason::Returned);
+
+    assert_eq!(cpu.rom_reads(), vec![Addr24::new(0, 0x9000), Addr24::new(0, 0x9001)]);
+    assert_eq!(cpu.peek(Addr24::new(0x7E, 0x0000)), 0x34);
+
+    let transfers = cpu.dma_transfers();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].0.a_bus_address, Addr24::new(0, 0x9000));
+    assert_eq!(transfers[0].0.byte_count, 0x200);
+    assert_eq!(transfers[0].1, Some(0x1000));
+
+    let instruction = disassemble(&rom, Addr24::new(0, 0x800D), false, false).unwrap();
+    assert_eq!(instruction.to_string(), "LDA $009000,X");
+    assert_eq!(instruction.length, 4);
+}
+
+#[test]
+fn test_indexed_access() {
+    let table = IndexedAccess::new(Addr24::new(0xC0, 0xFFF0), 8).element_size(2);
+    assert_eq!(table.address(2).unwrap(), Addr24::new(0xC1, 0x0000));
+    assert_eq!(table.touched(0..2).unwrap(), vec![Addr24::new(0xC0, 0xFFF0), Addr24::new(0xC0, 0xFFF1), Addr24::new(0xC0, 0xFFF8), Addr24::new(0xC0, 0xFFF9)]);
+    assert_eq!(table.index_of(Addr24::new(0xC1, 0x0009)), Some((3, 1)));
+    assert_eq!(table.index_of(Addr24::new(0xC1, 0x000C)), None);
+
+    assert_eq!(table.wrap(BankCrossPolicy::Wrap).address(2).unwrap(), Addr24::new(0xC0, 0x0000));
+    assert!(table.wrap(BankCrossPolicy::Forbid).address(2).is_err());
+
+    let lorom = IndexedAccess::new(Addr24::new(0x02, 0xFFFE), 4).wrap(BankCrossPolicy::LoRomCarry);
+    assert_eq!(lorom.address(1).unwrap(), Addr24::new(0x03, 0x8002));
+    assert_eq!(lorom.index_of(Addr24::new(0x03, 0x8002)), Some((1, 0)));
+}
+
+#[test]
+fn test_tile_sheet_diff() {
+    let mut before_data = vec![0u8; 16*3];
+    before_data[16] = 0x80;
+    let mut after_data = before_data.clone();
+    after_data[16] = 0xC0;
+    after_data.extend_from_slice(&[0xFFu8; 16]);
+
+    let before = TileSheet::<SNESTile2BPPPlanar>::from_data(&before_data, 2).unwrap();
+    let after = TileSheet::<SNESTile2BPPPlanar>::from_data(&after_data, 2).unwrap();
+    assert_eq!(after.rows(), 2);
+
+    let diff_result = before.diff(&after);
+    assert!(diff_result.is_ok());
+
+    let diff = diff_result.unwrap();
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff[0].index, 1);
+    assert_eq!(diff[0].changed_pixels, 1);
+    assert_eq!(diff[0].before.as_ref().unwrap()[0], 1);
+    assert_eq!(diff[0].after.as_ref().unwrap()[1], 1);
+    assert_eq!(diff[0].image.get_pixel(9, 0).unwrap(), Rgb888::new(85, 85, 85));
+    assert_eq!(diff[0].image.get_pixel(8, 0).unwrap(), TILE_DIFF_SEPARATOR);
+    assert_eq!(diff[1].before, None);
+    assert_eq!(diff[1].image.get_pixel(0, 0).unwrap(), TILE_DIFF_SEPARATOR);
+    assert_eq!(diff[1].image.get_pixel(16, 7).unwrap(), Rgb888::new(255, 255, 255));
+}
+
+#[test]
+fn test_palette_diff() {
+    let before = SNESPalette16::from_data(hex::decode("0000ff7f1f00e003007c1f000000000000000000000000000000000000000000").unwrap()).unwrap();
+    let after = SNESPalette16::from_data(hex::decode("00001f00ff7f007ce0031f000000000000000000000000000000000000000000").unwrap()).unwrap();
+
+    let diff = before.diff(&after);
+    assert_eq!(diff.len(), 4);
+    assert_eq!(diff[0].index, 1);
+    assert_eq!(diff[0].red(), 0);
+    assert_eq!(diff[0].green(), -31);
+
+    let remap = before.suggest_remap(&after).unwrap();
+    assert_eq!(&remap[0..6], &[0, 2, 1, 4, 3, 5]);
+
+    let mut colormap = vec![1, 2, 3, 4];
+    assert!(apply_index_remap(&mut colormap, &remap).is_ok());
+    assert_eq!(colormap, vec![2, 1, 4, 3]);
+}
+
+#[test]
+fn test_addr_notation() {
+    let mut rom = Rom::new(vec![0u8; 0x200 + 0x20000]);
+    assert_eq!(rom.format_offset(0x8200).unwrap(), "0x008200");
+
+    rom.set_notation(AddrNotation::Pc);
+    assert_eq!(rom.format_offset(0x8200).unwrap(), "0x008000");
+    assert_eq!(rom.parse_offset("0x8000").unwrap(), 0x8200);
+
+    rom.set_notation(AddrNotation::LoRom);
+    assert_eq!(rom.format_offset(0x8200).unwrap(), "$01:8000");
+    assert_eq!(rom.parse_offset("$81:8000").unwrap(), 0x8200);
+    assert_eq!(rom.parse_offset("018010").unwrap(), 0x8210);
+    assert!(rom.parse_offset("$01:7FFF").is_err());
+    assert!(rom.parse_offset("$01:zz").is_err());
+
+    rom.set_notation(AddrNotation::HiRom);
+    assert_eq!(rom.format_offset(0x10200).unwrap(), "$C1:0000");
+    assert_eq!(rom.parse_offset("$01:8000").unwrap(), 0x18200);
+    assert!(rom.format_offset(0x100).is_err());
+}
+
+#[test]
+fn test_analysis_session() {
+    /* sessions share &Rom across threads and hand errors back from them */
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Rom>();
+    assert_send_sync::<Error>();
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let progress_calls = std::sync::atomic::AtomicUsize::new(0);
+    let session = AnalysisSession::new(&rom).on_progress(|_| { progress_calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed); });
+    let report_result = session.run();
+    assert!(report_result.is_ok());
+
+    let report = report_result.unwrap();
+    assert!(progress_calls.load(std::sync::atomic::Ordering::Relaxed) > 0);
+    assert!(!report.hits_for(SurveyPass::Code).is_empty());
+    assert!(!report.hits_for(SurveyPass::Graphics).is_empty());
+    assert!(report.hits_for(SurveyPass::Text).iter().any(|h| h.offset == 0x200 + 0xFFC0));
+
+    let map = report.to_region_map(&rom).unwrap();
+    assert_eq!(map.len(), rom.rom_size());
+
+    let token = CancelToken::new();
+    let cancelled = AnalysisSession::new(&rom).cancel_token(token.clone());
+    token.cancel();
+    assert!(matches!(cancelled.run(), Err(Error::Cancelled)));
+    assert!(matches!(rom.find_bytes(b"EARTH BOUND", &token), Err(Error::Cancelled)));
+    assert_eq!(rom.find_bytes(b"EARTH BOUND", &CancelToken::new()).unwrap(), vec![0x200 + 0xFFC0]);
+
+    /* a child hears its parent, never the other way round */
+    let parent = CancelToken::new();
+    let child = parent.child();
+    child.cancel();
+    assert!(child.is_cancelled() && !parent.is_cancelled());
+    let child = parent.child();
+    parent.cancel();
+    assert!(child.is_cancelled());
+
+    /* a pass failing on its own stops the session, not whatever else shares the caller's token */
+    let token = CancelToken::new();
+    let broken = Rom::new(vec![0u8; 0x10]);
+    let failed = AnalysisSession::new(&broken).cancel_token(token.clone()).run();
+    assert!(failed.is_err());
+    assert!(!matches!(failed, Err(Error::Cancelled)));
+    assert!(!token.is_cancelled());
+}
+
+#[test]
+fn test_find_wildcard() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let found = rom.find("45 41 52 54 48 ?? 42 4F 55 4E 44");
+    assert!(found.is_ok());
+
+    let found = found.unwrap();
+    assert!(found.is_some());
+
+    let found = found.unwrap();
+    assert_eq!(found.offset, 0x200 + 0xFFC0);
+    assert_eq!(found.address, Some(Addr24::new(0xC0, 0xFFC0)));
+
+    let all = rom.find_all("45 41 52 54 48 ?? 42 4F 55 4E 44").unwrap();
+    assert_eq!(all, vec![found]);
+    assert!(rom.find("45 41 52 54 48 ?? 42 4F 55 4E 45").unwrap().is_none());
+    assert!(matches!(rom.find("A9 ?? 8D 00 2G"), Err(Error::InvalidPattern(_))));
+    assert!(rom.find_all("A9 ?? 8D 00 21").unwrap().len() > 1);
+}
+
+#[test]
+fn test_replace_bytes() {
+    let mut data = vec![0u8; 0x200 + 0x30000];
+    for offset in &[0x1000usize, 0x1003, 0x10000, 0x20000, 0x20010] {
+        data[0x200 + offset..0x200 + offset + 3].copy_from_slice(&[0xA9, 0x12, 0x8D]);
+    }
+
+    let mut rom = Rom::new(&data);
+    let pattern = BytePattern::parse("A9 ?? 8D").unwrap();
+    let replacement = BytePattern::parse("A9 ?? 9C").unwrap();
+    assert!(BytePattern::parse("A9 XYZ").is_err());
+
+    let constraints = ReplaceConstraints::new().alignment(2).banks(0xC1..=0xC2, AddrNotation::HiRom).max_replacements(2);
+    let result = rom.replace_bytes(&pattern, &replacement, &constraints);
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), vec![0x200 + 0x10000, 0x200 + 0x20000]);
+    assert_eq!(rom.read(0x200 + 0x10000, 3).unwrap(), &[0xA9, 0x12, 0x9C]);
+    assert_eq!(rom.read(0x200 + 0x20010, 3).unwrap(), &[0xA9, 0x12, 0x8D]);
+
+    rom.start_journal();
+    let remaining = rom.replace_bytes(&pattern, &replacement, &ReplaceConstraints::new()).unwrap();
+    assert_eq!(remaining, vec![0x200 + 0x1000, 0x200 + 0x1003, 0x200 + 0x20010]);
+    assert_eq!(rom.history().len(), 3);
+
+    assert!(rom.undo().unwrap());
+    assert_eq!(rom.read(0x200 + 0x20010, 3).unwrap(), &[0xA9, 0x12, 0x8D]);
+}
+
+#[test]
+fn test_table_schema() {
+    let schema = table_schema!("enemies", offset: 0x100, row_size: 8, rows: 4, {
+        "hp": U16 @ 0 in 1..=999,
+        "attack": I8 @ 2,
+        "boss": Bits(7, 1) @ 3,
+        "element": Bits(0, 3) @ 3,
+    });
+    assert!(schema.validate().is_ok());
+
+    let mut rom = Rom::new(vec![0u8; 0x200 + 0x400]);
+    let mut table = rom.table(&schema);
+    let mut row = table.row(2);
+    assert!(row.set("hp", 400).is_ok());
+    assert!(row.set("attack", -3).is_ok());
+    assert!(row.set("boss", 1).is_ok());
+    assert!(row.set("element", 5).is_ok());
+    assert!(matches!(row.set("hp", 1000), Err(Error::FieldOutOfRange(_, 1000))));
+    assert!(matches!(row.set("defense", 1), Err(Error::UnknownField(_))));
+    assert_eq!(row.get("hp").unwrap(), 400);
+    assert_eq!(row.get("attack").unwrap(), -3);
+    assert!(table.row(4).get("hp").is_err());
+
+    assert_eq!(rom.read(0x200 + 0x100 + 16, 4).unwrap(), &[0x90, 0x01, 0xFD, 0x85]);
+
+    for bits in [FieldType::Bits(0, 8), FieldType::Bits(8, 1), FieldType::Bits(6, 3), FieldType::Bits(0, 0), FieldType::Bits(0, 200)] {
+        let bad = TableSchema::new("flags", 0x100, 1, 1).field("flag", 0, bits);
+        assert!(matches!(bad.validate(), Err(Error::InvalidBitWidth(_))));
+        assert!(bad.get(&rom, 0, "flag").is_err());
+    }
+}
+
+#[test]
+fn test_script_export() {
+    let table = TextTable::parse("41=A\n42=B\n43=C\n20= \n80=the \n/00=<END>\n*01\n").unwrap();
+    assert_eq!(table.encode("the CAB").unwrap(), vec![0x80, 0x43, 0x41, 0x42]);
+    assert_eq!(table.decode(&[0x41, 0x01, 0x7F]), "A\n[7F]");
+
+    let mut data = vec![0xFFu8; 0x400];
+    data[0x100..0x105].copy_from_slice(&[0x80, 0x41, 0x42, 0x43, 0x00]);
+    data[0x200..0x204].copy_from_slice(&[0x43, 0x01, 0x41, 0x00]);
+
+    let mut rom = Rom::new(&data);
+    rom.set_notation(AddrNotation::Pc);
+
+    let mut script = Script::dump(&rom, &table, &[0x100, 0x200]).unwrap();
+    script.entries[1].speaker = Some("Ness".to_string());
+    assert_eq!(script.entries[0].text, "the ABC");
+    assert_eq!(script.entries[1].length, 4);
+
+    let po = script.to_po(&rom).unwrap();
+    assert!(po.contains("#. speaker: Ness\n#. slot: 4 bytes\nmsgctxt \"0x000200\"\nmsgid \"C\\nA\"\nmsgstr \"\"\n"));
+
+    let translated = po.replace("msgid \"the ABC\"\nmsgstr \"\"", "msgid \"the ABC\"\nmsgstr \"BAA\"")
+                       .replace("msgid \"C\\nA\"\nmsgstr \"\"", "msgid \"C\\nA\"\nmsgstr \"ABBA\"");
+    let issues = script.import_po(&rom, &table, &translated).unwrap();
+    assert_eq!(issues, vec![TranslationIssue::TooLong { index: 1, needed: 5, available: 4 }]);
+    assert_eq!(script.entries[0].translation, Some("BAA".to_string()));
+
+    let csv = script.to_csv(&rom).unwrap();
+    assert!(csv.ends_with("\r\n0x000200,Ness,\"C\nA\",\r\n"));
+
+    let issues = script.import_csv(&rom, &table, "address,speaker,source,translation\r\n0x000200,Ness,x,\"A, B\"\r\n").unwrap();
+    assert!(matches!(issues[0], TranslationIssue::Unencodable { index: 1, .. }));
+
+    assert_eq!(script.write_translations(&mut rom, &table, 0x00).unwrap(), 1);
+    assert_eq!(rom.read(0x100, 5).unwrap(), &[0x42, 0x41, 0x41, 0x00, 0x00]);
+}
+
+#[test]
+fn test_text_length_budget() {
+    let table = TextTable::parse("41=A\n42=B\n43=C\n54=T\n20= \n61=a\n62=b\n63=c\n65=e\n68=h\n80=the \n81=Cab\n/00=<END>\n").unwrap();
+    let mut script = Script::new();
+    script.entries.push(ScriptEntry { offset: 0, length: 4, text: "ABC".to_string(), speaker: None, translation: None });
+    script.entries.push(ScriptEntry { offset: 4, length: 4, text: "ABC".to_string(), speaker: None, translation: Some("The cab".to_string()) });
+
+    let report = script.validate_lengths(&table, &TextBudget::OriginalSlots);
+    assert!(!report.is_ok());
+    assert_eq!(report.total_available, 8);
+    assert_eq!(report.overflows().len(), 1);
+
+    let overflow = report.overflows()[0];
+    assert_eq!(overflow.index, 1);
+    assert_eq!(overflow.overflow(), 4);
+    assert_eq!(overflow.suggestions[0], Abbreviation { original: "The ".to_string(), replacement: "the ".to_string(), saved: 3 });
+    assert_eq!(overflow.suggestions[1], Abbreviation { original: "cab".to_string(), replacement: "Cab".to_string(), saved: 2 });
+
+    let pooled = script.validate_lengths(&table, &TextBudget::SharedPool(10));
+    assert_eq!(pooled.checks[1].available, 6);
+    assert!(!pooled.checks[1].fits());
+}
+
+#[test]
+fn test_dictionary_optimizer() {
+    let table = TextTable::parse("20= \n61=a\n65=e\n68=h\n6E=n\n74=t\n/00=<END>\n").unwrap();
+    let script = ["the hen", "then the hat", "aaaa"];
+
+    let proposal = DictionaryOptimizer::dte(vec![0x80, 0x81, 0x82]).optimize(&table, &script);
+    assert!(proposal.is_ok());
+
+    let proposal = proposal.unwrap();
+    assert_eq!(proposal.original_size, 23);
+    assert_eq!(proposal.entries[0], DictionaryEntry { code: vec![0x80], text: "he".to_string(), uses: 4 });
+    assert_eq!(proposal.savings(), proposal.entries.iter().map(|e| e.uses).sum::<usize>());
+
+    let mte = DictionaryOptimizer::dte(vec![0x80, 0x81]).max_length(3).optimize(&table, &script).unwrap();
+    assert_eq!(mte.entries[1].text, "the");
+
+    let mut extended = table.clone();
+    proposal.apply(&mut extended);
+    assert!(extended.encode("then").unwrap().len() < table.encode("then").unwrap().len());
+}
+
+#[test]
+fn test_huffman_text() {
+    let table = TextTable::parse("20= \n61=a\n62=b\n63=c\n64=d\n/00=<END>\n").unwrap();
+    let sample = table.encode_string("abacabad abacaba").unwrap();
+
+    let code = HuffmanCode::from_data(&sample);
+    assert!(code.is_ok());
+
+    let code = code.unwrap();
+    assert_eq!(code.code(0x61).unwrap().1, 1);
+
+    let description = code.to_description();
+    assert!(description.is_ok());
+
+    let description = description.unwrap();
+    let (parsed, size) = HuffmanCode::from_description(&description).unwrap();
+    assert_eq!(size, description.len());
+    assert_eq!(parsed, code);
+    assert!(HuffmanCode::from_lengths(&[(0x61, 1), (0x62, 1), (0x63, 1)]).is_err());
+
+    let flat = HuffmanCode::from_lengths(&(0..=255u8).map(|s| (s, 8)).collect::<Vec<(u8, u8)>>()).unwrap();
+    assert!(flat.to_description().is_err());
+
+    for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+        let code = code.clone().bit_order(order);
+        let packed = code.encode(&sample).unwrap();
+        assert!(packed.len() < sample.len());
+        assert_eq!(code.decode(&packed, sample.len()).unwrap(), sample);
+    }
+
+    let mut data = vec![0u8; 0x200 + 0x400];
+    let packed = code.encode_string(&table, "abacabad abacaba").unwrap();
+    data[0x210..0x210+packed.len()].copy_from_slice(&packed);
+    let mut rom = Rom::new(data);
+
+    let script = Script::dump_huffman(&rom, &table, &code, &[0x210]);
+    assert!(script.is_ok());
+
+    let mut script = script.unwrap();
+    assert_eq!(script.entries[0].text, "abacabad abacaba");
+    assert_eq!(script.entries[0].length, packed.len());
+
+    script.entries[0].translation = Some("dad cab".to_string());
+    assert_eq!(script.write_huffman_translations(&mut rom, &table, &code, 0).unwrap(), 1);
+    assert_eq!(Script::dump_huffman(&rom, &table, &code, &[0x210]).unwrap().entries[0].text, "dad cab");
+}
+
+#[test]
+fn test_bank_cross_policy() {
+    let mut data = vec![0u8; 0x200 + 0x20000];
+    data[0x200+0xFFFE] = 0x61;
+    data[0x200+0xFFFF] = 0x62;
+    data[0x200] = 0x63;
+    data[0x200+0x10000] = 0x64;
+    data[0x200+0x10001] = 0x00;
+    data[0x201] = 0x00;
+    let mut rom = Rom::new(data);
+    rom.set_memory_map(Some(Mapper::HiRom(HiRom)));
+    let start = Addr24::new(0xC0, 0xFFFE);
+
+    assert_eq!(rom.bank_policy(), BankCrossPolicy::Carry);
+    assert_eq!(rom.read_mapped(start, 3).unwrap(), vec![0x61, 0x62, 0x64]);
+    assert_eq!(rom.read_mapped_with(start, 3, BankCrossPolicy::Wrap).unwrap(), vec![0x61, 0x62, 0x63]);
+    assert!(rom.read_mapped_with(start, 3, BankCrossPolicy::Forbid).is_err());
+
+    let table = TextTable::parse("61=a\n62=b\n63=c\n64=d\n/00=<END>\n").unwrap();
+    assert_eq!(table.read_string(&rom, start).unwrap(), ("abd".to_string(), 4));
+
+    rom.set_bank_policy(BankCrossPolicy::Wrap);
+    assert_eq!(table.read_string(&rom, start).unwrap(), ("abc".to_string(), 4));
+
+    let sheet = TileSheet::<SNESTile2BPPPlanar>::from_rom(&rom, Addr24::new(0xC0, 0xFFF8), 2, 2);
+    assert!(sheet.is_ok());
+    assert_eq!(sheet.unwrap().len(), 2);
+}
+
+#[test]
+fn test_verify_patch() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+
+    let mut data = vec![0u8; 0x200 + 0x8000];
+    data[0x200+0x7FC0..0x200+0x7FD5].copy_from_slice(b"VERIFY TEST          ");
+    data[0x200+0x7FD7] = 0x05;
+    data[0x200+0x7FDC..0x200+0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let mut pristine = Rom::new(data);
+    assert!(pristine.update_header(|_| ()).is_ok());
+
+    let mut hacked = pristine.clone();
+    hacked.write(0x200+0x1000, [0xA9, 0x01, 0x60]).unwrap();
+    assert!(hacked.update_header(|h| h.set_version(1)).is_ok());
+
+    let patch = IpsPatch::from_diff(pristine.as_slice(), hacked.as_slice()).unwrap();
+    let bytes = patch.to_bytes().unwrap();
+    assert_eq!(&bytes[..5], b"PATCH");
+    assert_eq!(IpsPatch::parse(&bytes).unwrap(), patch);
+
+    let assertions = [
+        Assertion::Bytes(Addr24::new(0x00, 0x9000), vec![0xA9, 0x01, 0x60]),
+        Assertion::Crc32(Addr24::new(0x00, 0x9000), 3, crc32(&[0xA9, 0x01, 0x60])),
+        Assertion::RomCrc32(hacked.crc32()),
+        Assertion::Size(0x8000),
+        Assertion::HeaderValid,
+        Assertion::ChecksumFixed,
+    ];
+
+    let report = verify(&pristine, &patch, &assertions);
+    assert!(report.is_ok());
+    assert!(report.unwrap().passed());
+
+    let mut sloppy = patch.clone();
+    sloppy.records.retain(|r| r.offset() < 0x200+0x7FC0);
+    let report = verify(&pristine, &sloppy, &assertions).unwrap();
+    assert!(!report.passed());
+    assert_eq!(report.failures().len(), 2);
+    assert_eq!(pristine.crc32(), Rom::new(&pristine.as_slice()[0x200..]).crc32());
+}
+
+#[test]
+fn test_interrupt_vectors() {
+    let mut data = vec![0u8; 0x200 + 0x8000];
+    data[0x200+0x7FC0..0x200+0x7FD5].copy_from_slice(b"VECTOR TEST          ");
+    data[0x200+0x7FD7] = 0x05;
+    data[0x200+0x7FDC..0x200+0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let mut rom = Rom::new(data);
+
+    assert!(rom.set_reset_vector(0x8000).is_ok());
+    assert!(rom.set_nmi_vector(0x8123).is_ok());
+    assert_eq!(rom.get_vector(InterruptVector::Reset).unwrap(), 0x8000);
+    assert_eq!(rom.read(0x200+0x7FEA, 2).unwrap(), &[0x23, 0x81]);
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_checksum(), rom.checksum());
+
+    assert!(rom.set_reset_vector(0x1234).is_err());
+    assert!(rom.set_irq_vector(0x7FFF).is_err());
+    assert_eq!(rom.get_vector(InterruptVector::Reset).unwrap(), 0x8000);
+}
+
+#[test]
+fn test_offset_per_tile() {
+    let mut bg3 = vec![0u8; 0x800];
+    bg3[2..4].copy_from_slice(&0x2008u16.to_le_bytes());
+    bg3[66..68].copy_from_slice(&0x6010u16.to_le_bytes());
+
+    let opt = ppu::OffsetPerTile::parse(ppu::BgMode(0x02), &bg3);
+    assert!(opt.is_ok());
+
+    let mut opt = opt.unwrap();
+    assert_eq!(opt.column(0, 1), (Some(8), Some(0x10)));
+    assert_eq!(opt.column(1, 1), (None, Some(0x10)));
+    assert!(opt.set_column(1, 1, Some(0x20), None).is_err());
+    assert!(opt.set_column(1, 2, Some(0x20), None).is_ok());
+
+    let mut written = bg3.clone();
+    assert!(opt.write(&mut written).is_ok());
+    assert_eq!(ppu::OffsetPerTile::parse(ppu::BgMode(0x02), &written).unwrap(), opt);
+    assert_eq!(written[4..6], [0x20, 0x40]);
+
+    let mut mode4 = ppu::OffsetPerTile::new(ppu::BgMode(0x04)).unwrap();
+    assert_eq!(mode4.size(), 64);
+    assert!(mode4.set_column(0, 3, None, Some(0x18)).is_ok());
+    assert!(mode4.set_column(1, 3, Some(0x18), None).is_err());
+    assert_eq!(mode4.to_bytes()[6..8], [0x18, 0xA0]);
+    assert!(ppu::OffsetPerTile::new(ppu::BgMode(0x01)).is_err());
+}
+
+#[test]
+fn test_mode7_extbg() {
+    let mut vram = vec![0u8; ppu::MODE7_VRAM_SIZE];
+    vram[0] = 1;
+    vram[(64+9)*2+1] = 0x85;
+    vram[(64+10)*2+1] = 0x05;
+
+    let plane = ppu::Mode7Plane::from_vram(&vram);
+    assert!(plane.is_ok());
+
+    let mut plane = plane.unwrap();
+    assert_eq!(plane.to_vram(), vram);
+    assert_eq!(plane.tiles[1].get_extbg(1, 1).unwrap(), (5, true));
+
+    let mut colors = vec![0u8; 0x200];
+    colors[10..12].copy_from_slice(&0x001Fu16.to_le_bytes());
+    let palette = SNESPalette256::from_data(&colors).unwrap();
+    let transparent = Rgb888(0xFF00FF);
+
+    let (low, high) = plane.render_extbg(&palette, transparent).unwrap();
+    assert_eq!(high.get_pixel(9, 9).unwrap(), transparent);
+    assert_eq!(high.get_pixel(1, 1).unwrap(), Rgb888::from(Bgr555(0x001F)));
+    assert_eq!(low.get_pixel(2, 1).unwrap(), Rgb888::from(Bgr555(0x001F)));
+    assert_eq!(low.get_pixel(1, 1).unwrap(), transparent);
+
+    assert!(plane.tiles[1].set_color_keep_priority(1, 1, 6).is_ok());
+    assert_eq!(plane.tiles[1].get_value(1, 1).unwrap(), 0x86);
+    assert!(plane.tiles[1].set_extbg(0, 0, 0x80, false).is_err());
+    assert_eq!(plane.priority_mask().iter().filter(|&&p| p).count(), 1);
+}
+
+#[test]
+fn test_frame_color_math() {
+    let mut bg1 = ppu::LayerImage::new(ppu::FrameLayer::Bg1, 4, 1);
+    let mut bg2 = ppu::LayerImage::new(ppu::FrameLayer::Bg2, 4, 1);
+    for x in 0..4 {
+        bg1.set(x, 0, Some(ppu::LayerPixel { color: Bgr555(0x0010), rank: 2, palette: 0 })).unwrap();
+        bg2.set(x, 0, Some(ppu::LayerPixel { color: Bgr555(0x0200), rank: 1, palette: 0 })).unwrap();
+    }
+    let layers = [bg1, bg2];
+
+    let mut state = ppu::PpuState::new();
+    state.write(ppu::TM, 0x01);
+    state.write(ppu::TS, 0x02);
+    state.write(ppu::CGWSEL, 0x02);
+    state.write(ppu::CGADSUB, 0x01);
+    let frame = state.compose(&layers, Bgr555(0), 4, 1);
+    assert_eq!(frame.get_pixel(0, 0).unwrap(), Rgb888::from(Bgr555(0x0210)));
+
+    state.write(ppu::CGADSUB, 0x41);
+    state.write(ppu::CGWSEL, 0x00);
+    state.write(ppu::COLDATA, 0x20 | 0x10);
+    assert_eq!(state.fixed_color(), Bgr555(0x0010));
+    assert_eq!(state.compose(&layers, Bgr555(0), 4, 1).get_pixel(0, 0).unwrap(), Rgb888::from(Bgr555(0x0010)));
+
+    state.write(ppu::CGADSUB, 0x00);
+    state.write(ppu::WH0, 1);
+    state.write(ppu::WH1, 2);
+    state.write(ppu::W12SEL, 0x02);
+    state.write(ppu::TMW, 0x01);
+    let frame = state.compose(&layers, Bgr555(0x7C00), 4, 1);
+    assert_eq!(frame.get_pixel(0, 0).unwrap(), Rgb888::from(Bgr555(0x0010)));
+    assert_eq!(frame.get_pixel(1, 0).unwrap(), Rgb888::from(Bgr555(0x7C00)));
+
+    state.write(ppu::CGWSEL, 0xC0);
+    assert_eq!(state.compose(&layers, Bgr555(0), 4, 1).get_pixel(3, 0).unwrap(), Rgb888(0));
+
+    let events = [CpuEvent::RegisterWrite { pc: Addr24::new(0, 0x8000), register: ppu::INIDISP, value: 0x80 }];
+    assert_eq!(ppu::PpuState::from_events(&events).compose(&layers, Bgr555(0x7FFF), 4, 1).get_pixel(0, 0).unwrap(), Rgb888(0));
+}
+
+#[test]
+fn test_frame_obj_priority() {
+    let mut state = ppu::PpuState::new();
+    state.write(ppu::BGMODE, 0x01);
+    state.write(ppu::TM, 0x15);
+
+    let obj_color = Bgr555(0x001F);
+    let bg3_color = Bgr555(0x03E0);
+    let bg1_color = Bgr555(0x7C00);
+    let compose = |state: &ppu::PpuState, obj_priority: u8, bg1_priority: u8, bg3_priority: u8| {
+        let mut obj = ppu::LayerImage::new(ppu::FrameLayer::Obj, 1, 1);
+        let mut bg1 = ppu::LayerImage::new(ppu::FrameLayer::Bg1, 1, 1);
+        let mut bg3 = ppu::LayerImage::new(ppu::FrameLayer::Bg3, 1, 1);
+        obj.set(0, 0, state.layer_pixel(ppu::FrameLayer::Obj, obj_color, obj_priority, 0)).unwrap();
+        bg1.set(0, 0, state.layer_pixel(ppu::FrameLayer::Bg1, bg1_color, bg1_priority, 0)).unwrap();
+        bg3.set(0, 0, state.layer_pixel(ppu::FrameLayer::Bg3, bg3_color, bg3_priority, 0)).unwrap();
+        state.compose(&[obj, bg1, bg3], Bgr555(0), 1, 1).get_pixel(0, 0).unwrap()
+    };
+
+    assert_eq!(compose(&state, 2, 0, 0), Rgb888::from(obj_color));
+    assert_eq!(compose(&state, 2, 1, 0), Rgb888::from(bg1_color));
+    assert_eq!(compose(&state, 3, 1, 1), Rgb888::from(obj_color));
+    assert_eq!(compose(&state, 0, 0, 1), Rgb888::from(bg1_color));
+
+    state.write(ppu::BGMODE, 0x09);
+    assert_eq!(compose(&state, 3, 1, 1), Rgb888::from(bg3_color));
+    assert_eq!(compose(&state, 3, 1, 0), Rgb888::from(obj_color));
+
+    assert_eq!(ppu::BgMode(0x07).layer_rank(ppu::FrameLayer::Bg1, 1), ppu::BgMode(0x07).layer_rank(ppu::FrameLayer::Bg1, 0));
+    assert!(ppu::BgMode(0x02).layer_rank(ppu::FrameLayer::Bg3, 0).is_none());
+    assert!(ppu::BgMode(0x00).layer_rank(ppu::FrameLayer::Obj, 0) > ppu::BgMode(0x00).layer_rank(ppu::FrameLayer::Bg4, 0));
+    assert!(ppu::BgMode(0x00).layer_rank(ppu::FrameLayer::Obj, 0) < ppu::BgMode(0x00).layer_rank(ppu::FrameLayer::Bg4, 1));
+}
+
+#[test]
+fn test_frame_hires_interlace() {
+    let mut bg1 = ppu::LayerImage::new(ppu::FrameLayer::Bg1, 8, 2);
+    let mut bg2 = ppu::LayerImage::new(ppu::FrameLayer::Bg2, 4, 1);
+    for x in 0..8 {
+        for y in 0..2 {
+            bg1.set(x, y, Some(ppu::LayerPixel { color: Bgr555(if y == 0 { 0x001F } else { 0x03E0 }), rank: 2, palette: 0 })).unwrap();
+        }
+    }
+    for x in 0..4 {
+        bg2.set(x, 0, Some(ppu::LayerPixel { color: Bgr555(0x7C00), rank: 1, palette: 0 })).unwrap();
+    }
+    let layers = [bg1, bg2];
+
+    let mut state = ppu::PpuState::new();
+    state.write(ppu::TM, 0x01);
+    state.write(ppu::TS, 0x02);
+    assert!(!state.hires());
+    assert_eq!(state.output_size(4, 1), (4, 1));
+
+    state.write(ppu::BGMODE, 0x05);
+    state.write(ppu::SETINI, 0x01);
+    assert!(state.hires());
+    assert!(state.interlace());
+    assert_eq!(state.output_size(4, 1), (8, 2));
+    assert_eq!(ppu::format_register_write(ppu::SETINI, 0x09), "SETINI = $09 (pseudo-hires, interlace)");
+
+    let frame = state.compose(&layers, Bgr555(0), 4, 1);
+    assert_eq!((frame.width, frame.height), (8, 2));
+    assert_eq!(frame.get_pixel(0, 0).unwrap(), Rgb888::from(Bgr555(0x7C00)));
+    assert_eq!(frame.get_pixel(1, 0).unwrap(), Rgb888::from(Bgr555(0x001F)));
+    assert_eq!(frame.get_pixel(1, 1).unwrap(), Rgb888::from(Bgr555(0x03E0)));
+
+    state.write(ppu::TS, 0x00);
+    state.set_fixed_color(Bgr555(0x0010));
+    let frame = state.compose(&layers, Bgr555(0), 4, 1);
+    assert_eq!(frame.get_pixel(2, 1).unwrap(), Rgb888::from(Bgr555(0x0010)));
+
+    let blended = ppu::blend_hires(&frame);
+    assert_eq!((blended.width, blended.height), (4, 2));
+    let (even, odd) = (frame.get_pixel(0, 0).unwrap().0, frame.get_pixel(1, 0).unwrap().0);
+    assert_eq!(blended.get_pixel(0, 0).unwrap().0 & 0xFF, ((even & 0xFF) + (odd & 0xFF)) / 2);
+}
+
+#[test]
+fn test_extract_assets() {
+    let mut data = vec![0u8; 0x200 + 0x8000];
+    data[0x200+0x7FC0..0x200+0x7FD5].copy_from_slice(b"ASSET TEST           ");
+    data[0x200+0x7FD7] = 0x05;
+    data[0x200+0x7FDC..0x200+0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x200+0x1000..0x200+0x1020].copy_from_slice(b"WELCOME TO THE ASSET TEST ROM!!!");
+    let rom = Rom::new(data);
+
+    let bundle = rom.extract_assets(&AssetConfig::new().passes(&[SurveyPass::Text]));
+    assert!(bundle.is_ok());
+
+    let bundle = bundle.unwrap();
+    let text = bundle.entries.iter().find(|e| e.offset == 0x200+0x1000);
+    assert!(text.is_some());
+    assert_eq!(bundle.file(&text.unwrap().path).unwrap(), &b"WELCOME TO THE ASSET TEST ROM!!!"[..]);
+    assert!(bundle.manifest().contains("offset = 0x001200\n"));
+
+    let png = encode_indexed_png(8, 8, &[1u8; 64], &[Rgb888(0), Rgb888(0xFFFFFF)]);
+    assert_eq!(png[..8], PNG_SIGNATURE);
+    assert_eq!(&png[png.len()-8..png.len()-4], b"IEND");
+
+    let colormaps = decode_tile_colormaps(&[0xFFu8; 64], 4).unwrap();
+    assert_eq!(colormaps.len(), 2);
+    assert_eq!(tiles_to_indexed_image(&colormaps, 16).2[8], 15);
+
+    /* maps and samples have to be pointed at; the map keeps every bit of each entry through Tiled's flip flags */
+    let mut rom = rom;
+    rom.write(0x200+0x2000, [0x01, 0x00, 0x23, 0x5C, 0xFF, 0xFF, 0x00, 0x80]).unwrap();
+    rom.write(0x200+0x3000, [0xB0, 1, 2, 3, 4, 5, 6, 7, 8, 0xB3, 1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+    let bundle = rom.extract_assets(&AssetConfig::new().passes(&[]).map(0x200+0x2000, 2, 2).sample(0x200+0x3000)).unwrap();
+    let map = bundle.file("maps/002200.tmx").unwrap();
+    assert!(String::from_utf8_lossy(map).contains("width=\"2\" height=\"2\""));
+    assert_eq!(decode_tmx(map).unwrap(), vec![0x0001, 0x5C23, 0xFFFF, 0x8000]);
+    assert_eq!(bundle.file("samples/003200.brr").unwrap().len(), 18);
+    assert_eq!(bundle.entries[0].columns, Some(2));
+
+    assert!(matches!(rom.extract_assets(&AssetConfig::new().passes(&[]).map(0x200+0x2000, 0, 4)), Err(Error::InvalidImage(_))));
+    assert!(matches!(rom.extract_assets(&AssetConfig::new().passes(&[]).sample(0x200+0x7FF8)), Err(Error::TruncatedData(_))));
+
+    /* paths are written as escaped strings */
+    let mut bundle = AssetBundle::new();
+    bundle.add(AssetEntry::new(AssetKind::Other, "odd/\"quoted\"\\name.bin", 0, 1), vec![0]);
+    assert!(bundle.manifest().contains("path = \"odd/\\\"quoted\\\"\\\\name.bin\"\n"));
+    assert_eq!(parse_asset_manifest(&bundle.manifest()).unwrap(), bundle.entries);
+}
+
+#[test]
+fn test_pack_assets() {
+    let compressed = hex::decode("78daedccc111c0200800b0d96845e114c44361ff4dfaee0e668000550f7b405d13e65e054f019964fa36608e37881261080ede69195d7959ebd5ac09fb115fd8e02637f9271ff7a9b20a").unwrap();
+    let expected = (0..600usize).map(|i| ((i*i*7 + i/3) % 23 + 65) as u8).collect::<Vec<u8>>();
+    assert_eq!(zlib_decompress(&compressed).unwrap(), expected);
+
+    let mut rom = Rom::new(vec![0u8; 0x200 + 0x10000]);
+    let original = (0..0x40).map(|i| (i * 37) as u8).collect::<Vec<u8>>();
+    rom.write(0x200+0x4000, &original).unwrap();
+
+    let colormaps = decode_tile_colormaps(&original, 4).unwrap();
+    let (width, height, mut pixels) = tiles_to_indexed_image(&colormaps, 2);
+    let mut bundle = AssetBundle::new();
+    let mut tiles = AssetEntry::new(AssetKind::Graphics, "graphics/tiles.png", 0x200+0x4000, 0x40);
+    tiles.bpp = Some(4);
+    tiles.columns = Some(2);
+    bundle.add(tiles, encode_indexed_png(width, height, &pixels, &[Rgb888(0); 16]));
+    let mut text = AssetEntry::new(AssetKind::Text, "text/intro.txt", 0x200+0x5000, 4);
+    text.pointers = vec![0x200+0x6000];
+    bundle.add(text, b"HI!!".to_vec());
+
+    let directory = std::env::temp_dir().join(format!("flyhoney-pack-{}", std::process::id()));
+    assert_eq!(bundle.write_to(&directory).unwrap(), 2);
+
+    let manifest = std::fs::read_to_string(directory.join(ASSET_MANIFEST)).unwrap();
+    let entries = parse_asset_manifest(&manifest);
+    assert!(entries.is_ok());
+
+    let entries = entries.unwrap();
+    assert_eq!(entries, bundle.entries);
+
+    let options = PackOptions::new().free_space(0x200+0x8000..0x200+0x9000);
+    let report = rom.pack_assets(&directory, &entries, &options).unwrap();
+    assert_eq!(report.unchanged, vec!["graphics/tiles.png".to_string()]);
+    assert_eq!(rom.read(0x200+0x5000, 4).unwrap(), b"HI!!");
+
+    pixels[0] = 7;
+    std::fs::write(directory.join("graphics/tiles.png"), encode_indexed_png(width, height, &pixels, &[Rgb888(0); 16])).unwrap();
+    std::fs::write(directory.join("text/intro.txt"), b"HELLO THERE").unwrap();
+
+    let report = rom.pack_assets(&directory, &entries, &options).unwrap();
+    assert_eq!(report.written, vec!["graphics/tiles.png".to_string()]);
+    assert_eq!(report.relocated, vec![("text/intro.txt".to_string(), 0x200+0x5000, 0x200+0x8000)]);
+    assert_eq!(decode_tile_colormaps(rom.read(0x200+0x4000, 0x40).unwrap(), 4).unwrap()[0][0], 7);
+    assert_eq!(rom.read(0x200+0x8000, 11).unwrap(), b"HELLO THERE");
+    assert_eq!(rom.read(0x200+0x6000, 3).unwrap(), &[0x00, 0x80, 0xC0]);
+
+    /* a compressed entry goes back through its codec, a path may hold '#', and maps come back out of TMX */
+    let mut bundle = AssetBundle::new();
+    let mut packed = AssetEntry::new(AssetKind::Text, "text/#1 intro.txt", 0x200+0xA000, 2);
+    packed.codec = Some("rle".to_string());
+    bundle.add(packed, b"AAAAAAAA".to_vec());
+    bundle.add(AssetEntry::new(AssetKind::Map, "maps/bg1.tmx", 0x200+0xB000, 4), encode_tmx(&[0x4123, 0x8001], 2));
+    assert!(bundle.write_to(&directory).is_ok());
+
+    let manifest = std::fs::read_to_string(directory.join(ASSET_MANIFEST)).unwrap().replace("codec = \"rle\"", "codec = \"rle\" # recompressed");
+    let entries = parse_asset_manifest(&manifest).unwrap();
+    assert_eq!(entries, bundle.entries);
+
+    assert!(matches!(rom.pack_assets(&directory, &entries, &PackOptions::new()).unwrap_err().root(), Error::UnknownField(n) if n == "rle"));
+
+    let report = rom.pack_assets(&directory, &entries, &PackOptions::new().codec("rle", RunLengthCodec)).unwrap();
+    assert_eq!(report.written.len(), 2);
+    assert_eq!(rom.read(0x200+0xA000, 2).unwrap(), &[8, b'A']);
+    assert_eq!(rom.read(0x200+0xB000, 4).unwrap(), &[0x23, 0x41, 0x01, 0x80]);
+
+    let (start, end) = (0x200+0x9000, 0x200+0x8000);
+    let reversed = PackOptions::new().codec("rle", RunLengthCodec).free_space(start..end);
+    assert!(matches!(rom.pack_assets(&directory, &entries, &reversed), Err(Error::ReversedRange(0x9200, 0x8200))));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    /* inflating stops at its limit, and a PNG can't ask for more than its header allows */
+    assert!(zlib_decompress_limited(&compressed, 599).is_err());
+    assert!(zlib_decompress_limited(&compressed, 600).is_ok());
+
+    let mut huge = encode_indexed_png(8, 8, &[0u8; 64], &[Rgb888(0)]);
+    huge[16..24].copy_from_slice(&[0x00, 0x10, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00]);
+    assert!(matches!(decode_png(&huge), Err(Error::InvalidImage(_))));
+
+    let tiles = vec![vec![1u8; 64], vec![2u8; 64], vec![1u8; 64], vec![0u8; 64], vec![2u8; 64]];
+    let (unique, indices) = dedup_tile_colormaps(&tiles);
+    assert_eq!(unique, vec![vec![1u8; 64], vec![2u8; 64], vec![0u8; 64]]);
+    assert_eq!(indices, vec![0, 1, 0, 2, 1]);
+
+    /* only the depths PNG defines get as far as unpacking samples */
+    for depth in [0u8, 3, 5, 16] {
+        let mut odd = encode_indexed_png(1, 1, &[0u8], &[Rgb888(0)]);
+        odd[24] = depth;
+        odd[25] = 0;
+        assert!(matches!(decode_png(&odd), Err(Error::InvalidImage(_))));
+    }
+}
+
+struct RunLengthCodec;
+impl Codec for RunLengthCodec {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::<u8>::new();
+
+        let mut index = 0usize;
+
+        while index < data.len() {
+            let run = data[index..].iter().take(255).take_while(|&&b| b == data[index]).count();
+
+            result.extend_from_slice(&[run as u8, data[index]]);
+            index += run;
+        }
+
+        Ok(result)
+    }
+    fn decompress(&self, data: &[u8], _size: usize) -> Result<Vec<u8>, Error> {
+        Ok(data.chunks_exact(2).flat_map(|p| std::iter::repeat_n(p[1], p[0] as usize)).collect())
+    }
+}
+
+#[test]
+fn test_build_fingerprint() {
+    let base = Rom::new(vec![0u8; 0x200 + 0x8000]);
+    assert_eq!(base.build_fingerprint(), None);
+
+    let build = |first: usize, second: usize| {
+        let mut rom = base.clone();
+        rom.start_build_log(true);
+        rom.write(first, [1u8, 2]).unwrap();
+        rom.write_ref(second, &0xBEEFu16).unwrap();
+        rom
+    };
+
+    let a = build(0x300, 0x400);
+    assert!(a.is_deterministic());
+    assert_eq!(a.build_log().unwrap().operations.len(), 2);
+    assert_eq!(a.build_fingerprint(), build(0x300, 0x400).build_fingerprint());
+    assert_ne!(a.build_fingerprint(), build(0x400, 0x300).build_fingerprint());
+
+    let mut other_base = Rom::new(vec![0xFFu8; 0x200 + 0x8000]);
+    other_base.start_build_log(true);
+    other_base.write(0x300, [1u8, 2]).unwrap();
+    other_base.write_ref(0x400, &0xBEEFu16).unwrap();
+    assert_ne!(a.build_fingerprint(), other_base.build_fingerprint());
+    assert_eq!(fnv1a64(FNV_OFFSET, b"a"), 0xAF63DC4C8601EC8C);
+}
+
+#[test]
+fn test_metrics() {
+    let before = metrics();
+
+    let sheet = TileSheet::<SNESTile4BPPIntertwined>::from_data(vec![0u8; 32 * 3], 3).unwrap();
+    let palette = SNESPalette16::from_data(vec![0u8; 32]).unwrap();
+    assert!(sheet.render(&palette).is_ok());
+
+    let rom = Rom::new(vec![0u8; 0x8000]);
+    rom.checksum();
+    assert!(rom.find_bytes([1u8, 2], &CancelToken::new()).unwrap().is_empty());
+
+    /* other tests run alongside this one, so the counters can only be checked as lower bounds */
+    let delta = metrics().since(&before);
+    assert!(delta.bytes_decoded >= 32 * 3);
+    assert!(delta.tiles_decoded >= 3);
+    assert!(delta.tiles_rendered >= 3);
+    assert!(delta.bytes_checksummed >= 0x8000);
+    assert!(delta.bytes_searched >= 0x8000 - 1);
+}
+
+#[test]
+fn test_bps_patch() {
+    let source = (0..0x400u32).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+    let mut target = source.clone();
+    target[0x100..0x104].copy_from_slice(b"BPS!");
+    target.extend_from_slice(&[0xEA; 0x20]);
+
+    let metadata = PatchMetadata::new().with_author("flyhoney").with_description("test patch");
+    let patch = BpsPatch::from_diff(&source, &target).with_metadata(&metadata);
+    let bytes = patch.to_bytes();
+    assert!(bytes.is_ok());
+
+    let bytes = bytes.unwrap();
+    assert_eq!(&bytes[..4], b"BPS1");
+
+    let parsed_result = BpsPatch::parse(&bytes);
+    assert!(parsed_result.is_ok());
+
+    let parsed = parsed_result.unwrap();
+    assert_eq!(parsed, patch);
+    assert_eq!(parsed.metadata().unwrap().author(), Some("flyhoney"));
+    assert_eq!(parsed.metadata().unwrap().description(), Some("test patch"));
+    assert_eq!(Patch::target_size(&parsed), Some(target.len()));
+
+    let mut rom = Rom::new(source.clone());
+    assert!(parsed.apply(&mut rom).is_ok());
+    assert_eq!(rom.as_slice(), &target[..]);
+    assert!(matches!(parsed.apply(&mut rom), Err(Error::DataLengthMismatch(_,_))));
+    assert!(matches!(parsed.apply(&mut Rom::new(vec![0u8; source.len()])), Err(Error::PatchChecksumMismatch(_,_))));
+
+    let mut corrupt = bytes.clone();
+    corrupt[6] ^= 1;
+    assert!(matches!(BpsPatch::parse(&corrupt), Err(Error::PatchChecksumMismatch(_,_))));
+
+    /* a one-byte literal followed by an overlapping target copy expands into a run */
+    let run = BpsPatch { source_size: 0, target_size: 5, metadata: Vec::new(), actions: vec![BpsAction::TargetRead(vec![0x42]), BpsAction::TargetCopy { length: 4, offset: 0 }], source_crc: crc32(&[]), target_crc: crc32(&[0x42; 5]) };
+    assert_eq!(BpsPatch::parse(run.to_bytes().unwrap()).unwrap(), run);
+    assert_eq!(run.apply_to(&[]).unwrap(), vec![0x42; 5]);
+
+    /* sizes and lengths come straight from the patch, so neither may run past what the actions account for */
+    let mut runaway = run.clone();
+    runaway.actions[1] = BpsAction::TargetCopy { length: usize::MAX / 2, offset: 0 };
+    assert!(runaway.apply_to(&[]).is_err());
+    runaway.target_size = usize::MAX;
+    assert!(matches!(runaway.apply_to(&[]), Err(Error::UnaddressableRomSize(_,_))));
+
+    let mut empty = run.clone();
+    empty.actions.push(BpsAction::SourceRead(0));
+    assert!(matches!(empty.to_bytes(), Err(Error::EmptyPatchAction(2))));
+
+    let xml = PatchMetadata::parse("<?xml version=\"1.0\"?>\n<patch>\n  <author>someone</author>\n  <description>fixes</description>\n</patch>");
+    assert_eq!(xml.author(), Some("someone"));
+    assert_eq!(xml.description(), Some("fixes"));
+
+    let ips = IpsPatch::new().truncate_to(Some(0x200));
+    assert_eq!(ips.target_size(), Some(0x200));
+    assert_eq!(ips.metadata(), None);
+}
+
+#[test]
+fn test_normalize() {
+    let mut canonical = (0..0x20000u32).map(|i| (i >> 8) as u8 ^ i as u8).collect::<Vec<u8>>();
+    canonical[0xFFC0..0xFFD5].copy_from_slice(b"NORMALIZE TEST       ");
+    canonical[0xFFD5] = 0x21;
+    canonical[0xFFD7] = 0x07;
+    canonical[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+
+    /* headered, interleaved copy: every bank's upper half first, then the lower halves */
+    let mut dump = vec![0u8; 0x200];
+    for bank in 0..2 { dump.extend_from_slice(&canonical[bank*0x10000+0x8000..(bank+1)*0x10000]); }
+    for bank in 0..2 { dump.extend_from_slice(&canonical[bank*0x10000..bank*0x10000+0x8000]); }
+
+    let mut rom = Rom::new(dump);
+    let report_result = rom.normalize();
+    assert!(report_result.is_ok());
+
+    let report = report_result.unwrap();
+    assert_eq!(report.steps, vec![NormalizeStep::RemovedCopierHeader(0x200), NormalizeStep::Deinterleaved]);
+    assert_eq!(report.normalized_size, 0x20000);
+    assert_eq!(rom.as_slice(), &canonical[..]);
+    assert!(rom.normalize().unwrap().was_canonical());
+
+    let mut overdump = canonical.clone();
+    overdump.extend_from_slice(&canonical);
+    let (trimmed, report) = Rom::new(&overdump).normalized().unwrap();
+    assert_eq!(report.steps, vec![NormalizeStep::TrimmedOverdump { from: 0x40000, to: 0x20000 }]);
+    assert_eq!(trimmed.as_slice(), &canonical[..]);
+
+    let mut padded = canonical.clone();
+    padded.resize(0x30000, 0xFF);
+    assert_eq!(Rom::new(&padded).normalized().unwrap().0.len(), 0x20000);
+
+    padded[0x2FFFF] = 0x00;
+    assert!(Rom::new(&padded).normalized().unwrap().1.was_canonical());
+}
+
+#[test]
+fn test_dump_size() {
+    let mut data = vec![0u8; 0x200 + 0x8000];
+    data[0x200+0x7FC0..0x200+0x7FD5].copy_from_slice(b"DUMP SIZE TEST       ");
+    data[0x200+0x7FD7] = 0x05;
+    data[0x200+0x7FDC..0x200+0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let pristine = Rom::new(&data);
+    assert_eq!(pristine.dump_size(), DumpSize::Matches { declared: 0x8000, actual: 0x8000 });
+
+    let mut mirrored = data.clone();
+    mirrored.extend_from_slice(&data[0x200..]);
+    let mut rom = Rom::new(mirrored);
+    assert_eq!(rom.dump_size(), DumpSize::Overdump { declared: 0x8000, actual: 0x10000, junk: true });
+    assert!(matches!(rom.find_valid_snes_header(), Err(Error::Overdump(0x8000, 0x10000))));
+    assert_eq!(rom.trim_overdump().unwrap(), 0x8000);
+    assert_eq!(rom.as_slice(), pristine.as_slice());
+    assert!(rom.find_valid_snes_header().is_ok());
+
+    let mut expanded = data.clone();
+    expanded.extend((0..0x8000u32).map(|i| i as u8));
+    let mut rom = Rom::new(expanded);
+    assert!(matches!(rom.find_valid_snes_header(), Err(Error::ROMSizeMismatch(0x8000, 0x10000))));
+    assert!(matches!(rom.trim_overdump(), Err(Error::ROMSizeMismatch(_,_))));
+
+    let mut short = data.clone();
+    short[0x200+0x7FD7] = 0x07;
+    let mut rom = Rom::new(short);
+    assert!(rom.dump_size().is_bad_dump());
+    assert!(matches!(rom.trim_overdump(), Err(Error::Underdump(0x20000, 0x8000))));
+}
+
+#[test]
+fn test_supported_peripherals() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"MARIO PAINT          ");
+    data[0x7FD7] = 0x05;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let rom = Rom::new(&data);
+
+    let peripherals_result = rom.supported_peripherals();
+    assert!(peripherals_result.is_ok());
+
+    let peripherals = peripherals_result.unwrap();
+    assert!(peripherals.mouse());
+    assert!(!peripherals.super_scope());
+    assert_eq!(peripherals.to_string(), "Joypad, Mouse");
+    assert_eq!(rom.extended_header().unwrap(), None);
+
+    data[0x7FC0..0x7FD5].copy_from_slice(b"HOMEBREW             ");
+    data[0x7FDA] = 0x33;
+    data[0x7FB0..0x7FB6].copy_from_slice(b"01AHBE");
+    let rom = Rom::new(&data);
+    assert_eq!(rom.extended_header().unwrap().unwrap().game_code, "AHBE");
+    assert_eq!(rom.supported_peripherals().unwrap(), Peripherals(Peripherals::JOYPAD));
+
+    let mut database = PeripheralDatabase::default();
+    database.insert("AHBE", Peripherals(Peripherals::MULTITAP));
+    assert!(rom.supported_peripherals_with(&database).unwrap().multitap());
+}
+
+#[test]
+fn test_rip_sprite() {
+    let mut data = vec![0u8; 0x10000];
+
+    for (n, &index) in [0usize, 1, 16, 17].iter().enumerate() {
+        let mut tile = SNESTile4BPPIntertwined::new();
+        tile.set_value(0, 0, n as u8 + 1).unwrap();
+        data[index*32..(index+1)*32].copy_from_slice(tile.as_data());
+    }
+
+    for i in 0..16u16 { data[0x8000+i as usize*2..0x8002+i as usize*2].copy_from_slice(&Bgr555::new(i as u8, 0, 0).0.to_le_bytes()); }
+
+    let mut rom = Rom::new(data);
+    rom.set_memory_map(Some(Mapper::HiRom(HiRom)));
+    let descriptor = games::SpriteDescriptor::new(Addr24::new(0xC0, 0), 2, 2, Addr24::new(0xC0, 0x8000)).layout(games::SpriteLayout::Oam);
+
+    let sprite_result = games::common::rip_sprite(&rom, &descriptor);
+    assert!(sprite_result.is_ok());
+
+    let sprite = sprite_result.unwrap();
+    assert_eq!((sprite.image.width, sprite.image.height), (16, 16));
+    assert_eq!(sprite.tiles.len(), 4 * 32);
+    assert_eq!(sprite.palette.len(), 16);
+    assert_eq!(sprite.colormaps[3][0], 4);
+    assert_eq!(sprite.image.get_pixel(8, 8).unwrap(), Rgb888::from(Bgr555::new(4, 0, 0)));
+
+    /* the same descriptor read linearly picks up tiles 0-3 instead, and tiles 2 and 3 are blank */
+    let linear = games::common::rip_sprite(&rom, &descriptor.layout(games::SpriteLayout::Linear)).unwrap();
+    assert_eq!(linear.colormaps[1][0], 2);
+    assert_eq!(linear.colormaps[3][0], 0);
+
+    /* only the depths the PPU has are accepted, whatever the shift would make of the rest */
+    for bpp in [0usize, 3, 64, usize::MAX] {
+        assert!(matches!(games::common::rip_sprite(&rom, &descriptor.bpp(bpp)), Err(Error::InvalidBpp(b)) if b == bpp));
+    }
+    assert!(matches!(rom.extract_assets(&AssetConfig::new().bpp(64)), Err(Error::InvalidBpp(64))));
+}
+
+#[test]
+fn test_palette_association() {
+    let mut tile = SNESTile4BPPIntertwined::new();
+    for i in 0..64 { tile.set_value(i % 8, i / 8, (i % 4) as u8).unwrap(); }
+    let colormaps = vec![tile.to_colormap().unwrap()];
+
+    let flat = vec![Bgr555(0x1F); 16];
+    let ramp = (0..16).map(|i| Bgr555::new(i as u8 * 2, 31 - i as u8 * 2, i as u8)).collect::<Vec<Bgr555>>();
+    let grey = (0..16).map(|i| Bgr555::new(i as u8, i as u8, i as u8)).collect::<Vec<Bgr555>>();
+    assert_eq!(palette_fitness(&colormaps, &flat), 0.0);
+    assert!(palette_fitness(&colormaps, &ramp) > palette_fitness(&colormaps, &grey));
+    assert_eq!(best_palette(&colormaps, &[flat.clone(), ramp.clone(), grey]).unwrap().0, 1);
+    assert_eq!(best_palette(&colormaps, &[flat]), None);
+}
+
+#[test]
+fn test_copy_protection() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"PROTECTION TEST      ");
+    data[0x7FD7] = 0x05;
+    data[0x7FD8] = 0x01;
+    data[0x7FD9] = 0x01;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+    let code = hex::decode(concat!(
+        "ad3f21", "2910", "d0fe",      /* LDA $213F : AND #$10 : BNE */
+        "addeff", "cd0000", "d002",    /* LDA $FFDE : CMP $0000 : BNE */
+        "af000870", "cf000070", "f000", /* LDA $700800 : CMP $700000 : BEQ */
+        "60")).unwrap();
+    data[..code.len()].copy_from_slice(&code);
+    let rom = Rom::new(data);
+
+    let checks_result = rom.find_copy_protection(&CancelToken::new());
+    assert!(checks_result.is_ok());
+
+    let checks = checks_result.unwrap();
+    assert_eq!(checks.iter().map(|c| c.kind).collect::<Vec<ProtectionKind>>(), vec![ProtectionKind::RegionLockout, ProtectionKind::ChecksumVerify, ProtectionKind::SramSizeCheck]);
+    assert_eq!(checks[0].address, Addr24::new(0x00, 0x8000));
+    assert_eq!((checks[0].patch_offset, checks[0].original.clone(), checks[0].patch.clone()), (3, vec![0x29, 0x10], vec![0x29, 0x00]));
+    assert_eq!((checks[1].patch_offset, checks[1].patch.clone()), (13, vec![0xEA, 0xEA]));
+    assert_eq!((checks[2].patch_offset, checks[2].patch.clone()), (23, vec![0x80, 0x00]));
+}
+
+#[test]
+fn test_memory_map() {
+    let mut data = vec![0u8; 0x20000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"MAPPER TEST          ");
+    data[0x7FD5] = 0x20;
+    data[0x7FD7] = 0x07;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let mut rom = Rom::new(&data);
+
+    let map_result = rom.memory_map();
+    assert!(map_result.is_ok());
+    assert_eq!(map_result.unwrap(), Mapper::LoRom(LoRom));
+    assert_eq!(rom.address_to_offset(Addr24::new(0x81, 0x8000)).unwrap(), 0x8000);
+    assert_eq!(rom.offset_to_address(0x18000).unwrap(), Addr24::new(0x03, 0x8000));
+    assert!(rom.address_to_offset(Addr24::new(0x00, 0x1000)).is_err());
+
+    rom.set_memory_map(Some(Mapper::HiRom(HiRom)));
+    assert_eq!(rom.address_to_offset(Addr24::new(0xC1, 0x0000)).unwrap(), 0x10000);
+    assert_eq!(rom.memory_map().unwrap().name(), "hirom");
+
+    let mut hirom = vec![0u8; 0x20000];
+    hirom[0xFFC0..0xFFE0].copy_from_slice(&data[0x7FC0..0x7FE0]);
+    hirom[0xFFD5] = 0x31;
+    assert_eq!(Rom::new(&hirom).memory_map().unwrap(), Mapper::HiRom(HiRom));
+
+    /* the detected map is remembered, but not past a write over a header */
+    let mut moved = Rom::new(&hirom);
+    assert_eq!(moved.memory_map().unwrap(), Mapper::HiRom(HiRom));
+    assert!(moved.write(0x10, [0xEA; 0x100]).is_ok());
+    assert_eq!(moved.memory_map().unwrap(), Mapper::HiRom(HiRom));
+    assert!(moved.write(0x7FC0, &data[0x7FC0..0x7FE0]).is_ok());
+    assert_eq!(moved.memory_map().unwrap(), Mapper::LoRom(LoRom));
+    moved.as_mut_slice()[0x7FC0] = 0;
+    assert_eq!(moved.memory_map().unwrap(), Mapper::HiRom(HiRom));
+    assert_eq!(Mapper::from_map_mode(0x35), Some(Mapper::ExHiRom(ExHiRom)));
+
+    assert_eq!(ExHiRom.address_to_pc(Addr24::new(0x80, 0xFFC0)).unwrap(), 0xFFC0);
+    assert_eq!(ExHiRom.address_to_pc(Addr24::new(0x00, 0xFFC0)).unwrap(), 0x40FFC0);
+    assert_eq!(ExHiRom.address_to_pc(Addr24::new(0x40, 0x0000)).unwrap(), 0x400000);
+    assert!(ExHiRom.address_to_pc(Addr24::new(0x7E, 0x8000)).is_err());
+
+    for &pc in &[0usize, 0x123456, 0x3FFFFF, 0x400000, 0x5ABCDE, 0x7DFFFF, 0x7E8000, 0x7FFFFF] {
+        let address = ExHiRom.pc_to_address(pc).unwrap();
+        assert_eq!(ExHiRom.address_to_pc(address).unwrap(), pc);
+    }
+}
+
+#[test]
+fn test_lorom_offsets() {
+    let mut data = vec![0u8; 0x200 + 0x20000];
+    data[0x200+0x7FC0..0x200+0x7FD5].copy_from_slice(b"LOROM OFFSET TEST    ");
+    data[0x200+0x7FD5] = 0x20;
+    data[0x200+0x7FD7] = 0x07;
+    data[0x200+0x7FDC..0x200+0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x200+0x8000] = 0x61;
+    data[0x200+0x18000] = 0x62;
+    let rom = Rom::new(&data);
+
+    assert_eq!(Addr24::new(0x01, 0x8000).to_lorom_offset(&rom).unwrap(), 0x200+0x8000);
+    assert_eq!(Addr24::new(0x83, 0x8000).to_lorom_offset(&rom).unwrap(), 0x200+0x18000);
+    assert!(Addr24::new(0x00, 0x1000).to_lorom_offset(&rom).is_err());
+    assert_eq!(Addr24::from_lorom_offset(&rom, 0x200+0x8010).unwrap(), Addr24::new(0x01, 0x8010));
+    assert_eq!(Addr24::from_mapped_offset(&rom, 0x200+0x18000).unwrap(), Addr24::new(0x03, 0x8000));
+
+    let bank = rom.get_bank(0x01);
+    assert!(bank.is_ok());
+    let bank = bank.unwrap();
+    assert_eq!(bank.len(), 0x8000);
+    assert_eq!(bank[0], 0x61);
+
+    assert_eq!(rom.read_mapped(Addr24::new(0x03, 0x8000), 1).unwrap(), vec![0x62]);
+}
+
+#[test]
+fn test_exhirom_header() {
+    let mut data = vec![0u8; 0x600000];
+    data[0x40FFC0..0x40FFD5].copy_from_slice(b"EXHIROM TEST         ");
+    data[0x40FFD5] = 0x35;
+    data[0x40FFD7] = 0x0D;
+    data[0x40FFDC..0x40FFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x408000] = 0x61;
+    let mut rom = Rom::new(&data);
+
+    let address = rom.find_valid_snes_header_address();
+    assert!(address.is_ok());
+    assert_eq!(address.unwrap(), Addr24::new(0x40, 0xFFC0));
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_title(), "EXHIROM TEST");
+    assert!(rom.get_valid_exhirom_snes_header().is_ok());
+    assert_eq!(rom.memory_map().unwrap(), Mapper::ExHiRom(ExHiRom));
+    assert_eq!(rom.address_to_offset(Addr24::new(0x00, 0x8000)).unwrap(), 0x408000);
+    assert_eq!(rom.read_mapped(Addr24::new(0x80, 0x8000), 1).unwrap(), vec![0x00]);
+    assert_eq!(rom.read_mapped(Addr24::new(0x00, 0x8000), 1).unwrap(), vec![0x61]);
+
+    assert!(rom.update_header(|h| h.set_version(1)).is_ok());
+    assert_eq!(rom.get_exhirom_snes_header().unwrap().get_version(), 1);
+}
+
+#[test]
+fn test_sa1_map() {
+    let sa1 = Sa1::default();
+    assert_eq!(sa1.address_to_pc(Addr24::new(0x00, 0x8000)).unwrap(), 0x000000);
+    assert_eq!(sa1.address_to_pc(Addr24::new(0x21, 0x8000)).unwrap(), 0x108000);
+    assert_eq!(sa1.address_to_pc(Addr24::new(0xD2, 0x1234)).unwrap(), 0x121234);
+    assert!(sa1.address_to_pc(Addr24::new(0x40, 0x0000)).is_err());
+
+    let switched = Sa1::new([0x84, 1, 2, 3]);
+    assert_eq!(switched.address_to_pc(Addr24::new(0x00, 0x8000)).unwrap(), 0x400000);
+    assert_eq!(switched.address_to_pc(Addr24::new(0xC1, 0x0000)).unwrap(), 0x410000);
+    assert_eq!(switched.pc_to_address(0x410000).unwrap(), Addr24::new(0xC1, 0x0000));
+    assert!(switched.pc_to_address(0x010000).is_err());
+
+    let mut data = vec![0u8; 0x200000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"SA1 MAP TEST         ");
+    data[0x7FD5] = 0x23;
+    data[0x7FD7] = 0x0B;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x150000] = 0x61;
+    let mut rom = Rom::new(&data);
+
+    assert_eq!(rom.memory_map().unwrap(), Mapper::Sa1(Sa1::default()));
+    assert_eq!(rom.read_mapped(Addr24::new(0xD5, 0x0000), 1).unwrap(), vec![0x61]);
+
+    rom.set_memory_map(Some(Mapper::Sa1(Sa1::new([1, 0, 2, 3]))));
+    assert_eq!(rom.read_mapped(Addr24::new(0xC5, 0x0000), 1).unwrap(), vec![0x61]);
+    assert_eq!(rom.offset_to_address(0x150000).unwrap(), Addr24::new(0xC5, 0x0000));
+}
+
+#[test]
+fn test_superfx_map() {
+    assert_eq!(SuperFx.address_to_pc(Addr24::new(0x01, 0x8000)).unwrap(), 0x8000);
+    assert_eq!(SuperFx.address_to_pc(Addr24::new(0x81, 0x8000)).unwrap(), 0x8000);
+    assert_eq!(SuperFx.address_to_pc(Addr24::new(0x41, 0x2000)).unwrap(), 0x12000);
+    assert!(SuperFx.address_to_pc(Addr24::new(0x70, 0x0000)).is_err());
+    assert!(SuperFx.address_to_pc(Addr24::new(0x00, 0x2000)).is_err());
+    assert_eq!(SuperFx.pc_to_address(0x12000).unwrap(), Addr24::new(0x02, 0xA000));
+
+    let mut data = vec![0u8; 0x100000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"SUPERFX MAP TEST     ");
+    data[0x7FD5] = 0x20;
+    data[0x7FD6] = 0x13;
+    data[0x7FD7] = 0x0A;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x12000] = 0x61;
+    let rom = Rom::new(&data);
+
+    assert_eq!(rom.memory_map().unwrap(), Mapper::SuperFx(SuperFx));
+    assert_eq!(rom.read_mapped(Addr24::new(0x41, 0x2000), 1).unwrap(), vec![0x61]);
+    assert_eq!(rom.read_mapped(Addr24::new(0x02, 0xA000), 1).unwrap(), vec![0x61]);
+}
+
+#[test]
+fn test_data_rom_maps() {
+    let sdd1 = Sdd1::new([0, 1, 4, 5]);
+    assert_eq!(sdd1.address_to_pc(Addr24::new(0x01, 0x8000)).unwrap(), 0x8000);
+    assert_eq!(sdd1.address_to_pc(Addr24::new(0xE3, 0x1234)).unwrap(), 0x431234);
+    assert_eq!(sdd1.pc_to_address(0x431234).unwrap(), Addr24::new(0xE3, 0x1234));
+    assert!(sdd1.pc_to_address(0x231234).is_err());
+    assert!(sdd1.address_to_pc(Addr24::new(0x40, 0x8000)).is_err());
+
+    let spc = Spc7110::new([0, 3, 2]);
+    assert_eq!(spc.address_to_pc(Addr24::new(0x00, 0x8000)).unwrap(), 0x8000);
+    assert_eq!(spc.address_to_pc(Addr24::new(0xC5, 0x0000)).unwrap(), 0x50000);
+    assert_eq!(spc.address_to_pc(Addr24::new(0xD1, 0x0000)).unwrap(), 0x110000);
+    assert_eq!(spc.address_to_pc(Addr24::new(0xE0, 0x0010)).unwrap(), 0x400010);
+    assert_eq!(spc.pc_to_address(0x400010).unwrap(), Addr24::new(0xE0, 0x0010));
+    assert_eq!(spc.pc_to_address(0x50000).unwrap(), Addr24::new(0xC5, 0x0000));
+    assert!(spc.address_to_pc(Addr24::new(0x50, 0x0000)).is_err());
+
+    let mut data = vec![0u8; 0x200000];
+    data[0xFFC0..0xFFD5].copy_from_slice(b"SPC7110 MAP TEST     ");
+    data[0xFFD5] = 0x3A;
+    data[0xFFD6] = 0xF9;
+    data[0xFFD7] = 0x0B;
+    data[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x100000] = 0x61;
+    let rom = Rom::new(&data);
+
+    assert_eq!(rom.memory_map().unwrap(), Mapper::Spc7110(Spc7110::default()));
+    assert_eq!(rom.read_mapped(Addr24::new(0xD0, 0x0000), 1).unwrap(), vec![0x61]);
+    assert_eq!(Mapper::from_map_mode(0x3A), Some(Mapper::Spc7110(Spc7110::default())));
+}
+
+#[test]
+fn test_mapper_self_test() {
+    let broken = BoardTable { board: "hirom as lorom", mapper: Mapper::LoRom(LoRom), ranges: BOARD_TABLES[1].ranges };
+    let mismatches = broken.verify();
+    assert!(!mismatches.is_empty());
+    assert_eq!(mismatches[0].address, Addr24::new(0x00, 0x8000));
+    assert_eq!(mismatches[0].expected, 0x8000);
+    assert_eq!(mismatches[0].actual, Some(0));
+}
+
+#[test]
+fn test_remap_planner() {
+    let mut data = vec![0x11u8; 0x20000];
+    data[0xFFC0..0xFFD5].copy_from_slice(b"REMAP PLANNER TEST   ");
+    data[0xFFD5] = 0x21;
+    data[0xFFD7] = 0x07;
+    data[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    for byte in &mut data[0x10000..] { *byte = 0xFF; }
+    data[0x10000..0x10006].copy_from_slice(&[0x00, 0x10, 0xC0, 0x00, 0x20, 0xC0]);
+    data[0x10010..0x10012].copy_from_slice(&[0x00, 0x30]);
+    let mut rom = Rom::new(&data);
+
+    let planner = RemapPlanner::new()
+        .table(PointerTable::new("levels", 0x10000, 2, PointerFormat::Long))
+        .table(PointerTable::new("music", 0x10010, 1, PointerFormat::Short(0xC0)));
+    let plan_result = planner.plan(&rom, 0x40000);
+    assert!(plan_result.is_ok());
+    let plan = plan_result.unwrap();
+
+    assert_eq!(plan.occupancy[0].free(), 0);
+    assert_eq!(plan.moves, vec![RelocatedBlock { from: 0x1000, to: 0x20000, length: 0x1000 }]);
+    assert_eq!(plan.pointer_updates.len(), 1);
+    assert_eq!(plan.pointer_updates[0].replacement, vec![0x00, 0x00, 0xC2]);
+    assert!(plan.pinned.iter().any(|(o, _)| *o == 0x3000));
+    assert!(plan.report().contains("repoint levels[0]"));
+
+    assert!(planner.plan(&rom, 0x10000).is_err());
+    assert!(rom.apply_remap_plan(&plan).is_ok());
+    assert_eq!(rom.rom_size(), 0x40000);
+    assert_eq!(rom.read(0x10000, 3).unwrap(), [0x00, 0x00, 0xC2]);
+    assert_eq!(rom.read(0x20000, 1).unwrap(), [0x11]);
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_rom_size(), 0x08);
+    assert_eq!(rom.memory_map().unwrap(), Mapper::HiRom(HiRom));
+    assert!(rom.checksum_warnings().is_empty());
+
+    let mut data = vec![0xFFu8; 0x400000];
+    for byte in &mut data[..0x10000] { *byte = 0x11; }
+    data[0xFFC0..0xFFD5].copy_from_slice(b"REMAP PLANNER TEST   ");
+    data[0xFFD5] = 0x31;
+    data[0xFFD7] = 0x0C;
+    data[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0xFFFC..0x10000].copy_from_slice(&[0x00, 0x80, 0x00, 0x00]);
+    data[0x10000..0x10006].copy_from_slice(&[0x00, 0x10, 0xC0, 0x00, 0x20, 0xC0]);
+    let mut rom = Rom::new(&data);
+
+    let planner = RemapPlanner::new().table(PointerTable::new("levels", 0x10000, 2, PointerFormat::Long));
+    let plan = planner.plan(&rom, 0x500000).unwrap();
+    assert_eq!(plan.mapper, Mapper::ExHiRom(ExHiRom));
+
+    assert!(rom.apply_remap_plan(&plan).is_ok());
+    assert_eq!(rom.find_valid_snes_header_address().unwrap(), Addr24::new(0x40, 0xFFC0));
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_mapping_mode(), 0x35);
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_rom_size(), 0x0D);
+    assert_eq!(rom.read(0x40FFFC, 2).unwrap(), [0x00, 0x80]);
+    assert_eq!(rom.memory_map().unwrap(), Mapper::ExHiRom(ExHiRom));
+    assert!(rom.checksum_warnings().is_empty());
+}
+
+#[test]
+fn test_commands() {
+    let mut rom = Rom::new(vec![0u8; 0x100]);
+    let mut history = CommandHistory::new();
+
+    assert!(history.execute(&mut rom, Box::new(WriteBytes::new(0x10, [1, 2, 3, 4]))).is_ok());
+    assert!(history.execute(&mut rom, Box::new(CopyRegion::new(0x10, 0x12, 4))).is_ok());
+    assert_eq!(rom.read(0x10, 6).unwrap(), [1, 2, 1, 2, 3, 4]);
+
+    let mut tile = SNESTile2BPPPlanar::new();
+    assert!(tile.set_value(0, 0, 3).is_ok());
+    assert!(history.execute(&mut rom, Box::new(InsertTiles::new(0x40, std::slice::from_ref(&tile)))).is_ok());
+    assert!(history.execute(&mut rom, Box::new(FillRegion::new(0x80, 0x10, 0xFF))).is_ok());
+    assert_eq!(rom.read(0x40, 16).unwrap(), tile.as_data());
+    assert_eq!(history.undo_name(), Some("fill region"));
+
+    assert_eq!(history.undo(&mut rom).unwrap(), true);
+    assert_eq!(history.undo(&mut rom).unwrap(), true);
+    assert_eq!(history.undo(&mut rom).unwrap(), true);
+    assert_eq!(rom.read(0x10, 6).unwrap(), [1, 2, 3, 4, 0, 0]);
+    assert_eq!(rom.read(0x80, 1).unwrap(), [0]);
+
+    assert_eq!(history.redo(&mut rom).unwrap(), true);
+    assert_eq!(rom.read(0x10, 6).unwrap(), [1, 2, 1, 2, 3, 4]);
+
+    assert!(history.execute(&mut rom, Box::new(WriteBytes::new(0xFE, [9, 9, 9]))).is_err());
+    assert!(history.execute(&mut rom, Box::new(WriteBytes::new(0, [7]))).is_ok());
+    assert!(!history.can_redo());
+    assert_eq!(history.undo(&mut rom).unwrap(), true);
+    assert_eq!(history.undo(&mut rom).unwrap(), true);
+    assert_eq!(history.undo(&mut rom).unwrap(), true);
+    assert_eq!(history.undo(&mut rom).unwrap(), false);
+    assert!(rom.as_slice().iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_project_validation() {
+    let mut data = vec![0u8; 0x40000];
+    data[0xFFC0..0xFFD5].copy_from_slice(b"RULES TEST           ");
+    data[0xFFD5] = 0x21;
+    data[0xFFD7] = 0x08;
+    data[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+
+    let directory = std::env::temp_dir().join(format!("flyhoney-rules-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("script.bin"), vec![0u8; 0x30]).unwrap();
+    std::fs::write(directory.join(PROJECT_RULES_FILE), "[[rule]]\nkind = \"untouched\"\nbank = $03\n\n[[rule]]\nkind = \"max_size\"\npath = \"script.bin\"\nlength = 0x20\n\n[[rule]]\nkind = \"title\"\ntitle = \"RULES TEST\"\n").unwrap();
+
+    let project_result = Project::new(&directory, Rom::new(&data)).load_rules();
+    assert!(project_result.is_ok());
+    let mut project = project_result.unwrap();
+    assert_eq!(project.rules.len(), 3);
+
+    let violations = project.validate().unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].rule, ValidationRule::MaxFileSize { path: "script.bin".to_string(), length: 0x20 });
+
+    assert!(project.rom.write(0x30010, [1]).is_ok());
+    assert!(project.rom.update_header_unchecked(|h| { let _ = h.set_title("OTHER"); }).is_ok());
+    let violations = project.validate().unwrap();
+    assert_eq!(violations.len(), 3);
+    assert_eq!(violations[0].message, "changed at 0x030010");
+
+    assert!(parse_validation_rules("[[rule]]\nkind = \"untouched\"\n").is_err());
+    assert!(parse_validation_rules("[[rule]]\nkind = \"banana\"\n").is_err());
+
+    /* a '#' inside a title is part of it, and a range that wraps the address space is reported rather than panicking */
+    let rules = parse_validation_rules("[[rule]]\nkind = \"title\" # the header\ntitle = \"RULES #2\"\n\n[[rule]]\nkind = \"untouched\"\noffset = 0x10\nlength = 0xFFFFFFFFFFFFFFFF\n").unwrap();
+    assert_eq!(rules[0], ValidationRule::Title("RULES #2".to_string()));
+
+    let violations = project.rules(rules).validate().unwrap();
+    assert_eq!(violations.len(), 2);
+    assert!(violations[1].message.starts_with("range ends past the rom"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_usage_log() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"USAGE LOG TEST       ");
+    data[0x7FD5] = 0x20;
+    data[0x7FD7] = 0x05;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let rom = Rom::new(&data);
+
+    let mut bus = vec![0u8; BUS_USAGE_SIZE];
+    bus[0x008000] = USAGE_READ | USAGE_EXEC | USAGE_OPCODE | USAGE_FLAG_M;
+    bus[0x808001] = USAGE_READ | USAGE_EXEC;
+    bus[0x008010] = USAGE_READ;
+    bus[0x008020] = USAGE_READ;
+    let log_result = UsageLog::from_bsnes_plus(&rom, &bus);
+    assert!(log_result.is_ok());
+    let log = log_result.unwrap();
+
+    assert_eq!(log.len(), 0x8000);
+    assert_eq!(log.code_bytes(), 2);
+    assert_eq!(log.data_bytes(), 2);
+    assert_eq!(log.opcode_flags(0), Some((true, false)));
+    assert_eq!(log.opcode_flags(1), None);
+
+    let mut map = RegionMap::new(0x8000);
+    assert!(map.mark(0x20, 0x10, RegionKind::Graphics).is_ok());
+    assert!(map.mark(0x10, 1, RegionKind::Code).is_ok());
+    assert_eq!(log.merge_into(&mut map).unwrap(), 3);
+    assert_eq!(map.kind_at(0x01).unwrap(), RegionKind::Code);
+    assert_eq!(map.kind_at(0x10).unwrap(), RegionKind::Data);
+    assert_eq!(map.kind_at(0x20).unwrap(), RegionKind::Graphics);
+
+    let mut cart = vec![0u8; 0x8000];
+    cart[0x100] = USAGE_EXEC;
+    assert!(UsageLog::from_bsnes_plus(&rom, &cart).unwrap().is_code(0x100));
+    assert!(UsageLog::from_bsnes_plus(&rom, [0u8; 16]).is_err());
+}
+
+#[test]
+fn test_mesen_interop() {
+    let labels = "PRG:8000:Reset:entry point\nPRG:9000-90FF:LevelTable\nWORK:0010:PlayerX\nSPCRAM:0200:Driver\nPRG:A000::just a note\n";
+    let table_result = SymbolTable::from_mesen_labels(labels);
+    assert!(table_result.is_ok());
+    let mut table = table_result.unwrap();
+
+    assert_eq!(table.len(), 4);
+    assert_eq!(table.get("Reset").unwrap().comment.as_deref(), Some("entry point"));
+    assert_eq!(table.at(SymbolSpace::Rom, 0x9080).unwrap().name, "LevelTable");
+    assert_eq!(table.at(SymbolSpace::WorkRam, 0x10).unwrap().name, "PlayerX");
+    assert!(table.at(SymbolSpace::Rom, 0x9100).is_none());
+
+    table.insert(Symbol::new(SymbolSpace::Rom, 0x8000, "Boot"));
+    assert!(table.get("Reset").is_none());
+    assert_eq!(table.to_mesen_labels(), "PRG:8000:Boot\nPRG:9000-90FF:LevelTable\nPRG:A000::just a note\nWORK:10:PlayerX\n");
+    assert!(SymbolTable::from_mesen_labels("PRG:zz:Bad\n").is_err());
+
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"MESEN INTEROP TEST   ");
+    let rom = Rom::new(&data);
+
+    let mut cdl = vec![0u8; 0x8000];
+    cdl[0] = CDL_CODE | CDL_MEMORY_8 | CDL_INDEX_8;
+    cdl[1] = CDL_CODE;
+    cdl[0x10] = CDL_DATA;
+    let log = UsageLog::from_mesen_cdl(&rom, &cdl).unwrap();
+    assert_eq!(log.opcode_flags(0), Some((true, true)));
+    assert!(log.is_code(1));
+    assert!(log.is_data(0x10));
+
+    let exported = log.to_mesen_cdl(&rom).unwrap();
+    assert_eq!(&exported[..5], b"CDLv2");
+    assert_eq!(UsageLog::from_mesen_cdl(&rom, &exported).unwrap(), log);
+
+    let mut stale = exported.clone();
+    stale[5] ^= 0xFF;
+    assert!(UsageLog::from_mesen_cdl(&rom, &stale).is_err());
+}
+
+#[test]
+fn test_detect_copier_header() {
+    let mut image = vec![0u8; 0x8000];
+    image[0x7FC0..0x7FD5].copy_from_slice(b"COPIER DETECT TEST   ");
+    image[0x7FD7] = 0x05;
+    image[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    image[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+    let bare = detect_copier_header(&image);
+    assert_eq!(bare.size, 0);
+    assert_eq!(bare.confidence, 1.0);
+
+    let mut headered = vec![0u8; 0x200];
+    headered.extend_from_slice(&image);
+    let detection = Rom::new(&headered).detect_copier_header();
+    assert_eq!(detection.size, 0x200);
+    assert!(detection.confidence > 0.9);
+
+    /* trailing padding makes len % 1024 come out even, hiding the header from header_size() */
+    headered[8..11].copy_from_slice(&COPIER_SIGNATURE);
+    headered.extend_from_slice(&[0xFF; 0x200]);
+    let rom = Rom::new(&headered);
+    assert_eq!(rom.header_size(), 0);
+    let detection = rom.detect_copier_header();
+    assert_eq!(detection.size, 0x200);
+    assert!(detection.evidence.contains(&"copier signature bytes"));
+
+    assert_eq!(detect_copier_header(&[0u8; 0x400]).confidence, 0.0);
+}
+
+#[test]
+fn test_copier_header_round_trip() {
+    let mut image = vec![0u8; 0x8000];
+    image[0x7FC0..0x7FD5].copy_from_slice(b"COPIER STRIP TEST    ");
+    image[0x7FD7] = 0x05;
+    image[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    image[0x1234] = 0x61;
+    let mut rom = Rom::new(&image);
+
+    assert_eq!(rom.add_copier_header().unwrap(), 0x200);
+    assert_eq!(rom.header_size(), 0x200);
+    assert_eq!(rom.header().unwrap()[0..2], [0x04, 0x00]);
+    assert_eq!(rom.read(0x200+0x1234, 1).unwrap(), [0x61]);
+    assert!(rom.find_valid_snes_header().is_ok());
+    assert_eq!(rom.add_copier_header().unwrap(), 0);
+
+    assert_eq!(rom.strip_copier_header().unwrap(), 0x200);
+    assert_eq!(rom.as_slice(), &image[..]);
+    assert_eq!(rom.strip_copier_header().unwrap(), 0);
+
+    let mut padded = build_copier_header(0x8000);
+    padded.extend_from_slice(&image);
+    padded.extend_from_slice(&[0xFF; 0x200]);
+    let mut rom = Rom::new(&padded);
+    assert_eq!(rom.strip_copier_header().unwrap(), 0x200);
+    assert_eq!(rom.as_slice(), &image[..]);
+}
+
+#[test]
+fn test_color_usage() {
+    let mut tiles = vec![SNESTile4BPPPlanar::new(), SNESTile4BPPPlanar::new(), SNESTile4BPPPlanar::new()];
+    assert!(tiles[0].set_value(0, 0, 3).is_ok());
+    assert!(tiles[1].set_value(1, 1, 9).is_ok());
+    assert!(tiles[1].set_value(2, 1, 2).is_ok());
+    let sheet = TileSheet::new(tiles, 3);
+
+    let usage = sheet.color_usage();
+    assert_eq!(usage[0], 64 * 3 - 3);
+    assert_eq!(usage[3], 1);
+    assert_eq!(usage[9], 1);
+    assert_eq!(usage.iter().sum::<u32>(), 64 * 3);
+
+    assert_eq!(sheet.max_indices(), vec![3, 9, 0]);
+    assert_eq!(sheet.tiles_exceeding(2), vec![1]);
+    assert!(!sheet.fits_bpp(2));
+    assert!(sheet.fits_bpp(4));
+}
+
+#[test]
+fn test_patch_region_lock() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"REGION LOCK TEST     ");
+    data[0x7FD7] = 0x05;
+    data[0x7FD9] = 0x02;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+    /* LDA $00213F : AND #$10 : BEQ : RTS in a PAL game, which must see bit 4 set */
+    let code = hex::decode("af3f21002910f0fe60").unwrap();
+    data[..code.len()].copy_from_slice(&code);
+    let mut rom = Rom::new(&data);
+
+    let report_result = rom.patch_region_lock();
+    assert!(report_result.is_ok());
+
+    let report = report_result.unwrap();
+    assert!(report.found());
+    assert_eq!(report.patched.len(), 1);
+    assert_eq!(rom.read(4, 2).unwrap(), &[0xA9, 0x10]);
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_checksum(), rom.checksum());
+
+    let again = rom.patch_region_lock().unwrap();
+    assert!(!again.found());
+
+    let mut clean = Rom::new(vec![0u8; 0x8000]);
+    clean.write(0x7FC0, &data[0x7FC0..0x8000]).unwrap();
+    assert!(!clean.patch_region_lock().unwrap().found());
+}
+
+#[test]
+fn test_convert_to_fastrom() {
+    let mut data = vec![0u8; 0x10000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"FASTROM TEST         ");
+    data[0x7FD5] = 0x20;
+    data[0x7FD7] = 0x06;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+    /* LDA #$01 : PHA : PLB : JSL $018000 : LDA #$01 : STA $420D : RTS */
+    let code = hex::decode("a90148ab22008001a9018d0d4260").unwrap();
+    data[..code.len()].copy_from_slice(&code);
+    data[0x8000] = 0x6B;
+    let mut rom = Rom::new(&data);
+
+    let report_result = rom.convert_to_fastrom(true);
+    assert!(report_result.is_ok());
+
+    let report = report_result.unwrap();
+    assert_eq!(report.map_mode, (0x20, 0x30));
+    assert_eq!(report.memsel_write, Some(10));
+    assert_eq!(report.edits.len(), 2);
+    assert_eq!(rom.read(0, 8).unwrap(), &[0xA9, 0x81, 0x48, 0xAB, 0x22, 0x00, 0x80, 0x81]);
+    assert_eq!(rom.read(8, 2).unwrap(), &[0xA9, 0x01]);
+    assert!(report.review.iter().any(|(_, r)| r.contains("reset handler")));
+
+    let header = rom.find_valid_snes_header().unwrap();
+    assert_eq!(header.get_mapping_mode(), 0x30);
+    assert_eq!(header.get_checksum(), rom.checksum());
+}
+
+#[test]
+fn test_copier_header_formats() {
+    let mut swc = SwcHeader::new(0x100000);
+    swc.set_hirom(true);
+    swc.set_split(true);
+    swc.set_sram_size(Some(0x2000));
+    let detected = CopierHeaderFormat::detect(swc.as_data());
+    assert!(detected.is_some());
+    let detected = detected.unwrap();
+    assert_eq!(detected.name(), "swc");
+    assert_eq!(detected.rom_size(), 0x100000);
+    assert!(detected.hirom() && detected.split() && detected.has_sram());
+    assert_eq!(swc.sram_size(), Some(0x2000));
+    assert_eq!(SwcHeader::new(0x8000).sram_size(), None);
+
+    let mut fig = FigHeader::new(0x80000, true);
+    fig.set_split(true);
+    let detected = CopierHeaderFormat::detect(fig.as_data()).unwrap();
+    assert_eq!(detected.name(), "fig");
+    assert!(detected.hirom() && detected.split() && !detected.has_sram());
+    assert_eq!(FigHeader::new(0x80000, false).board_code(), Some((false, false)));
+
+    let mut smc = SmcHeader::new(0x40000);
+    smc.set_hirom(true);
+    let detected = CopierHeaderFormat::detect(smc.as_data()).unwrap();
+    assert_eq!(detected.name(), "smc");
+    assert_eq!(detected.rom_size(), 0x40000);
+    assert!(detected.hirom() && !detected.split());
+
+    let mut junk = [0u8; 0x200];
+    junk[0x100] = 0x55;
+    junk[3] = 0x12;
+    assert!(CopierHeaderFormat::detect(&junk[..]).is_none());
+    assert!(CopierHeaderFormat::detect(&junk[..0x10]).is_none());
+    assert!(SwcHeader::from_data(&junk[..0x10]).is_err());
+
+    let mut image = build_copier_header(0x8000);
+    image.extend_from_slice(&[0u8; 0x8000]);
+    let rom = Rom::new(&image);
+    assert_eq!(rom.copier_header().map(|h| h.name()), Some("swc"));
+}
+
+#[test]
+fn test_save() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    assert!(rom.path().is_some());
+    assert!(Rom::new(rom.as_slice()).save_in_place().is_err());
+
+    let filename = std::env::temp_dir().join(format!("flyhoney-save-{}.smc", std::process::id()));
+    let offset = rom.header_size() + 0x1234;
+    let original = rom.as_slice()[offset];
+    rom.as_mut_slice()[offset] = original.wrapping_add(1);
+    assert!(rom.save(&filename).is_ok());
+
+    let saved = Rom::from_file(&filename).unwrap();
+    assert_eq!(saved.as_slice(), rom.as_slice());
+    assert_ne!(saved.find_valid_snes_header().unwrap().get_checksum(), rom.checksum());
+
+    let mut saved = saved;
+    let checksum = saved.fix_checksum();
+    assert!(checksum.is_ok());
+    assert_eq!(checksum.unwrap().new, saved.checksum());
+    saved.as_mut_slice()[offset] = original;
+    assert!(saved.save_in_place_with_checksum().is_ok());
+
+    let reloaded = Rom::from_file(&filename).unwrap();
+    assert_eq!(reloaded.find_valid_snes_header().unwrap().get_checksum(), reloaded.checksum());
+    assert_eq!(reloaded.as_slice()[offset], original);
+
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_tile_alignment() {
+    let mut data = vec![0x5Au8, 0xC3, 0x17, 0x99, 0x2E];
+
+    for index in 0..8u8 {
+        let mut tile = SNESTile4BPPIntertwined::new();
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let value = if (x < 1 + index as usize % 6) == (y < 7 - index as usize % 5) { 1 + index } else { 15 - index };
+                assert!(tile.set_value(x, y, value).is_ok());
+            }
+        }
+
+        data.extend_from_slice(tile.as_data());
+    }
+
+    let alignment = detect_tile_alignment::<SNESTile4BPPIntertwined, _>(&data);
+    assert!(alignment.is_ok());
+
+    let alignment = alignment.unwrap();
+    assert_eq!(alignment.offset, 5);
+    assert_eq!(alignment.scores.len(), 32);
+    assert!(alignment.score > 0.4);
+    assert!(alignment.margin() > 0.0);
+
+    assert!(detect_tile_alignment::<SNESTile4BPPIntertwined, _>(&data[..40]).is_err());
+
+    let rom = Rom::new(&data);
+    assert_eq!(rom.detect_tile_alignment::<SNESTile4BPPIntertwined>(0, data.len()).unwrap().offset, 5);
+}
+
+#[test]
+fn test_mirrored_checksum() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let earthbound = rom_result.unwrap();
+    assert_eq!(earthbound.rom_size(), 0x300000);
+    assert_eq!(earthbound.find_valid_snes_header().unwrap().get_checksum(), earthbound.checksum());
+
+    /* every byte 1: the lower power-of-two part counts once, the remainder is mirrored up to the same size */
+    for &(size, expected) in &[(0x280000usize, 0x200000usize + 0x80000 * 4), (0x300000, 0x200000 + 0x100000 * 2), (0x500000, 0x400000 + 0x100000 * 4), (0x600000, 0x400000 + 0x200000 * 2), (0x100000, 0x100000)] {
+        let rom = Rom::new(vec![1u8; size]);
+        assert_eq!(rom.checksum(), expected as u16);
+    }
+
+    let mut headered = vec![0xFFu8; 0x200];
+    headered.extend_from_slice(&[1u8; 0x8000]);
+    assert_eq!(Rom::new(&headered).checksum(), 0x8000);
+
+    /* past 8MB nothing mirrors; those bytes are summed once */
+    assert_eq!(Rom::new(vec![1u8; 0x1000000]).checksum(), 0x1000000usize as u16);
+    assert_eq!(Rom::new(vec![1u8; 0x923400]).checksum(), 0x3400);
+}
+
+#[test]
+fn test_bit_fields() {
+    let data = [0b1011_0010u8, 0b0111_1100, 0xFF];
+
+    let mut msb = BitReader::new(&data);
+    assert_eq!(msb.read(3).unwrap(), 0b101);
+    assert_eq!(msb.read(7).unwrap(), 0b1_0010_01);
+    assert_eq!(msb.position(), 10);
+    assert_eq!(msb.peek(4).unwrap(), 0b1111);
+    assert_eq!(msb.read_signed(4).unwrap(), -1);
+    assert!(msb.read(33).is_err());
+    assert!(msb.read(11).is_err());
+
+    let mut lsb = BitReader::new(&data).order(BitOrder::LsbFirst);
+    assert_eq!(lsb.read(3).unwrap(), 0b010);
+    assert_eq!(lsb.read(7).unwrap(), 0b00_10110);
+    assert!(lsb.read_bit().unwrap());
+
+    for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+        let mut writer = BitWriter::new().order(order);
+        assert!(writer.write(0b101, 3).is_ok());
+        assert!(writer.write(0x1ABC, 13).is_ok());
+        assert!(writer.write(0x7, 5).is_ok());
+        assert_eq!(writer.position(), 21);
+
+        let packed = writer.into_inner();
+        assert_eq!(packed.len(), 3);
+
+        let mut reader = BitReader::new(&packed).order(order);
+        assert_eq!(reader.read(3).unwrap(), 0b101);
+        assert_eq!(reader.read(13).unwrap(), 0x1ABC);
+        assert_eq!(reader.read(5).unwrap(), 0x7);
+    }
+
+    let mut rom = Rom::new(vec![0xFFu8; 0x8000]);
+    assert!(rom.write_bits(0x100, 6, 5, 0b00100, BitOrder::MsbFirst).is_ok());
+    assert_eq!(rom.read(0x100, 2).unwrap(), [0b1111_1100, 0b1001_1111]);
+    assert_eq!(rom.read_bits(0x100, 6, 5, BitOrder::MsbFirst).unwrap(), 0b00100);
+    assert_eq!(rom.bit_reader(0x100, 2, BitOrder::MsbFirst).unwrap().read(8).unwrap(), 0b1111_1100);
+}
+
+#[test]
+fn test_little_endian_fields() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"BYTE ORDER TEST      ");
+    data[0x7FD7] = 0x05;
+    data[0x7FD9..0x7FDB].copy_from_slice(&[0x34, 0x12]);
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    let mut rom = Rom::new(&data);
+
+    let header = rom.find_valid_snes_header().unwrap();
+    assert_eq!(header.get_destination_code(), 0x34);
+    assert_eq!(header.get_region(), Region::Unknown(0x34));
+    assert_eq!(header.get_developer_id(), 0x12);
+    assert_eq!(header.get_vector(InterruptVector::Reset), 0x8000);
+
+    assert!(rom.update_header_unchecked(|h| {
+        h.set_checksum(0xBEEF);
+        h.set_vector(InterruptVector::NativeNmi, 0x8123);
+        h.set_developer_id(0xAB);
+        h.set_region(Region::Germany);
+    }).is_ok());
+    assert_eq!(rom.read(0x7FDC, 4).unwrap(), [0x10, 0x41, 0xEF, 0xBE]);
+    assert_eq!(rom.read(0x7FEA, 2).unwrap(), [0x23, 0x81]);
+    assert_eq!(rom.read(0x7FD9, 2).unwrap(), [0x09, 0xAB]);
+    assert_eq!(rom.find_valid_snes_header().unwrap().video_standard(), VideoStandard::Pal);
+
+    assert_eq!(rom.read_u16(0x7FDE).unwrap(), 0xBEEF);
+    assert_eq!(rom.read_u32(0x7FDC).unwrap(), 0xBEEF4110);
+    assert!(rom.write_addr24(0x100, Addr24::new(0xC1, 0x2345)).is_ok());
+    assert_eq!(rom.read(0x100, 3).unwrap(), [0x45, 0x23, 0xC1]);
+    assert_eq!(rom.read_u24(0x100).unwrap(), 0xC12345);
+    assert_eq!(rom.read_addr24(0x100).unwrap(), Addr24::new(0xC1, 0x2345));
+    assert!(rom.write_u16(0x200, 0xCAFE).is_ok());
+    assert_eq!(rom.read(0x200, 2).unwrap(), [0xFE, 0xCA]);
+    assert!(rom.read_u32(0x7FFE).is_err());
+}
+
+#[test]
+fn test_fix_checksum() {
+    let mut data = vec![1u8; 0x20000];
+    data[0xFFC0..0xFFD5].copy_from_slice(b"FIX CHECKSUM TEST    ");
+    data[0xFFD5] = 0x21;
+    data[0xFFD7] = 0x07;
+    data[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let mut rom = Rom::new(&data);
+    assert_eq!(rom.find_valid_snes_header_address().unwrap(), Addr24::new(0, 0xffc0));
+
+    let fix = rom.fix_checksum();
+    assert!(fix.is_ok());
+
+    let fix = fix.unwrap();
+    assert_eq!(fix.old, 0x0000);
+    assert_eq!(fix.new, rom.checksum());
+    assert!(fix.changed());
+    assert_eq!(rom.read_u16(0xFFDE).unwrap(), fix.new);
+    assert_eq!(rom.read_u16(0xFFDC).unwrap(), fix.new ^ 0xFFFF);
+
+    let again = rom.fix_checksum().unwrap();
+    assert_eq!(again.old, fix.new);
+    assert!(!again.changed());
+}
+
+#[test]
+fn test_checksum_policy() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"BROKEN CHECKSUM HACK ");
+    data[0x7FD7] = 0x05;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0x34, 0x12, 0x34, 0x12]);
+    let mut rom = Rom::new(&data);
+
+    assert_eq!(rom.checksum_policy(), ChecksumPolicy::Strict);
+    assert!(matches!(rom.find_valid_snes_header(), Err(Error::ChecksumComplimentMismatch)));
+    assert!(rom.checksum_warnings().is_empty());
+
+    rom.set_checksum_policy(ChecksumPolicy::WarnOnly);
+    assert!(rom.find_valid_snes_header().is_ok());
+    let warnings = rom.checksum_warnings();
+    assert_eq!(warnings.len(), 2);
+    assert!(matches!(warnings[0], Error::ChecksumComplimentMismatch));
+    assert!(matches!(warnings[1], Error::ChecksumMismatch(0x1234, _)));
+
+    /* the policy only relaxes header lookup; an assertion asked for by name still checks */
+    assert!(Assertion::ChecksumFixed.check(&rom).is_err());
+
+    rom.set_checksum_policy(ChecksumPolicy::Ignore);
+    assert!(rom.find_valid_snes_header().is_ok());
+    assert!(rom.checksum_warnings().is_empty());
+    assert!(Assertion::ChecksumFixed.check(&rom).is_err());
+
+    rom.set_checksum_policy(ChecksumPolicy::Strict);
+    assert!(Assertion::ChecksumFixed.check(&rom).is_err());
+    let header = rom.get_lorom_snes_header().unwrap();
+    assert!(header.validate_fields().is_err());
+    assert!(header.validate_fields_with(ChecksumPolicy::WarnOnly).is_ok());
+}
+
+#[test]
+fn test_expand_rom() {
+    let mut data = (0..0x100000usize).map(|i| (i / 0x8000) as u8).collect::<Vec<u8>>();
+    data[0x7FC0..0x7FD5].copy_from_slice(b"EXPANSION TEST       ");
+    data[0x7FD5] = 0x20;
+    data[0x7FD7] = 0x0A;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let mut rom = Rom::new(&data);
+
+    assert!(matches!(rom.expand_to(0x480000, ExpandFill::Byte(0xFF)), Err(Error::UnaddressableRomSize(0x480000, 0x400000))));
+    assert!(rom.expand_to(0x181000, ExpandFill::Mirror).is_err());
+
+    let added = rom.expand_to(0x180000, ExpandFill::Mirror);
+    assert!(added.is_ok());
+    assert_eq!(added.unwrap(), 0x80000);
+    assert_eq!(rom.rom_size(), 0x180000);
+    assert_eq!(rom.read(0x100000, 0x80000).unwrap(), &data[..0x80000]);
+
+    let header = rom.find_valid_snes_header();
+    assert!(header.is_ok());
+    let header = header.unwrap();
+    assert_eq!(header.get_rom_size(), 0x0B);
+    assert_eq!(header.get_checksum(), rom.checksum());
+
+    assert!(rom.expand_to(0x200000, ExpandFill::Byte(0xFF)).is_ok());
+    assert!(rom.read(0x180000, 0x80000).unwrap().iter().all(|&b| b == 0xFF));
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_rom_size(), 0x0B);
+    assert_eq!(rom.expand_to(0x100000, ExpandFill::Mirror).unwrap(), 0);
+}
+
+#[test]
+fn test_dyn_tiles() {
+    let mut planar = SNESTile4BPPPlanar::new();
+    assert!(planar.set_value(3, 2, 9).is_ok());
+    let mut mode7 = SNESTileMode7::new();
+    assert!(mode7.set_value(0, 0, 0xC4).is_ok());
+
+    let mut tiles: Vec<Box<dyn DynTile>> = vec![Box::new(planar.clone()), Box::new(mode7), TileFormat::Bpp2Intertwined.blank()];
+    assert_eq!(tiles.iter().map(|t| t.format().bpp()).collect::<Vec<usize>>(), vec![4, 8, 2]);
+    assert_eq!(tiles[0].pixel(3, 2).unwrap(), 9);
+    assert_eq!(tiles[1].colormap().unwrap()[0], 0xC4);
+
+    assert!(tiles[2].set_pixel(7, 7, 3).is_ok());
+    assert!(tiles[2].set_pixel(7, 7, 4).is_err());
+    let copy = tiles[2].clone();
+    assert!(tiles[2].set_pixel(7, 7, 0).is_ok());
+    assert_eq!(copy.pixel(7, 7).unwrap(), 3);
+
+    let decoded = TileFormat::Bpp4Planar.decode(planar.as_data());
+    assert!(decoded.is_ok());
+    assert_eq!(decoded.unwrap().data(), planar.as_data());
+
+    let rebuilt = TileFormat::Bpp4Intertwined.from_colormap(&planar.to_colormap().unwrap()).unwrap();
+    assert_eq!(rebuilt.colormap().unwrap(), planar.to_colormap().unwrap());
+    assert!(TileFormat::Bpp4Planar.decode(&[0u8; 8]).is_err());
+    assert_eq!(TileFormat::Bpp2Planar.decode_all(vec![0u8; 48]).unwrap().len(), 3);
+
+    let palette = (0..16).map(|i| Bgr555(i as u16)).collect::<Vec<Bgr555>>();
+    let pixels = tiles[0].render(&palette).unwrap();
+    assert_eq!(pixels[2*8+3], Rgb888::from(Bgr555(9)));
+    assert!(tiles[1].render(&palette).is_err());
+}
+
+#[test]
+fn test_trim_padding() {
+    let mut data = vec![0xFFu8; 0x100000];
+    for b in &mut data[..0x48000] { *b = 0x42; }
+    data[0x7FC0..0x7FD5].copy_from_slice(b"PADDED DUMP          ");
+    data[0x7FD7] = 0x0A;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x48000] = 0x00;
+    let mut rom = Rom::new(&data);
+
+    let removed = rom.trim();
+    assert!(removed.is_ok());
+    assert_eq!(removed.unwrap(), 0x100000 - 0x50000);
+    assert_eq!(rom.rom_size(), 0x50000);
+
+    let header = rom.find_valid_snes_header();
+    assert!(header.is_ok());
+    let header = header.unwrap();
+    assert_eq!(header.get_rom_size(), 0x09);
+    assert_eq!(header.get_checksum(), rom.checksum());
+    assert_eq!(rom.trim().unwrap(), 0);
+
+    let mut overdump = vec![0x11u8; 0x8000];
+    overdump[0x7FC0..0x7FD5].copy_from_slice(b"OVERDUMP             ");
+    overdump[0x7FD7] = 0x05;
+    overdump[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    overdump.extend_from_slice(&[0x00; 0x18000]);
+    let mut rom = Rom::new(&overdump);
+    assert!(rom.find_valid_snes_header().is_err());
+    assert_eq!(rom.trim().unwrap(), 0x18000);
+    assert!(rom.find_valid_snes_header().is_ok());
+
+    /* a 12 megabit game whose last bank happens to be blank stops at its chips, not at its last used bank */
+    let mut overdump = vec![0xFFu8; 0x400000];
+    for b in &mut overdump[..0x170000] { *b = 0x33; }
+    overdump[0xFFC0..0xFFD5].copy_from_slice(b"TWELVE MEGABIT       ");
+    overdump[0xFFD5] = 0x21;
+    overdump[0xFFD7] = 0x0B;
+    overdump[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    let mut rom = Rom::new(&overdump);
+    assert_eq!(rom.trim().unwrap(), 0x400000 - 0x180000);
+    assert_eq!(rom.rom_size(), 0x180000);
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_rom_size(), 0x0B);
+
+    assert!(Rom::new(vec![0xFFu8; 0x8000]).trim().is_err());
+}
+
+#[test]
+fn test_color_math() {
+    let a = Bgr555::new(20, 10, 31);
+    let b = Bgr555::new(15, 12, 1);
+
+    assert_eq!(a.cmath_add(b, false), Bgr555::new(31, 22, 31));
+    assert_eq!(a.cmath_add(b, true), Bgr555::new(17, 11, 16));
+    assert_eq!(a.cmath_sub(b, false), Bgr555::new(5, 0, 30));
+    assert_eq!(a.cmath_sub(b, true), Bgr555::new(2, 0, 15));
+    assert_eq!(Bgr555::new(31, 31, 31).cmath_add(Bgr555::new(31, 31, 31), true), Bgr555::new(31, 31, 31));
+    assert_eq!(Bgr555::new(1, 0, 0).cmath_add(Bgr555::new(0, 0, 0), true), Bgr555::new(0, 0, 0));
+    assert_eq!(Bgr555(0x8000 | 0x1F).cmath_add(Bgr555(0), false), Bgr555(0x1F));
+}
+
+#[test]
+fn test_interleave() {
+    let mut canonical = (0..0x40000u32).map(|i| (i >> 9) as u8 ^ i as u8).collect::<Vec<u8>>();
+    canonical[0xFFC0..0xFFD5].copy_from_slice(b"INTERLEAVE TEST      ");
+    canonical[0xFFD5] = 0x21;
+    canonical[0xFFD7] = 0x08;
+    canonical[0xFFDC..0xFFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+
+    let mut rom = Rom::new(&canonical);
+    assert!(!rom.is_interleaved());
+    assert_eq!(rom.deinterleave().unwrap(), false);
+
+    assert!(rom.interleave().is_ok());
+    assert!(rom.is_interleaved());
+    assert_eq!(rom.read(0, 0x8000).unwrap(), &canonical[0x8000..0x10000]);
+    assert_eq!(rom.read(0x20000, 0x8000).unwrap(), &canonical[..0x8000]);
+
+    assert_eq!(rom.deinterleave().unwrap(), true);
+    assert_eq!(rom.as_slice(), &canonical[..]);
+    assert!(rom.find_valid_snes_header_address().is_ok());
+    assert!(Rom::new(vec![0u8; 0x8000]).interleave().is_err());
+}
+
+#[test]
+fn test_split_files() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    assert!(rom.split(0).is_err());
+
+    let parts_result = rom.split(4);
+    assert!(parts_result.is_ok());
+
+    let parts = parts_result.unwrap();
+    assert_eq!(parts.len(), 4);
+    assert_eq!(parts.iter().map(|p| p.len() - 0x200).sum::<usize>(), rom.rom_size());
+
+    for (index, part) in parts.iter().enumerate() {
+        let header = CopierHeaderFormat::detect(part).unwrap();
+        assert_eq!(header.rom_size(), part.len() - 0x200);
+        assert_eq!(header.split(), index < 3);
+    }
+
+    let mut bare = rom.clone();
+    assert!(bare.strip_copier_header().is_ok());
+    let bare_parts = bare.split(3).unwrap();
+    assert_eq!(bare_parts[0].len(), 0x100200);
+    assert!(CopierHeaderFormat::detect(&bare_parts[0]).unwrap().hirom());
+
+    let filenames = (1..=parts.len()).map(|i| std::env::temp_dir().join(format!("flyhoney-split-{}.{}", std::process::id(), i))).collect::<Vec<_>>();
+    for (filename, part) in filenames.iter().zip(parts.iter()) {
+        std::fs::write(filename, part).unwrap();
+    }
+
+    let merged = Rom::from_split_files(&filenames);
+    assert!(merged.is_ok());
+
+    let merged = merged.unwrap();
+    assert_eq!(&merged.as_slice()[0x200..], &rom.as_slice()[rom.header_size()..]);
+
+    let header = merged.copier_header().unwrap();
+    assert_eq!(header.rom_size(), rom.rom_size());
+    assert!(!header.split());
+
+    for filename in &filenames {
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    let bare = Rom::from_split_data(&[vec![1u8; 0x8000], vec![2u8; 0x8000]]).unwrap();
+    assert_eq!(bare.header_size(), 0);
+    assert_eq!(bare.rom_size(), 0x10000);
+    assert!(matches!(Rom::from_split_data::<Vec<u8>>(&[]), Err(Error::InvalidPartCount(0))));
+}
+
+#[test]
+fn test_pointer_scan() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let table = rom.header_size() + 0x2F8000;
+    let mirror = rom.header_size() + 0x2F9000;
+    let target = Addr24::new(0xC1, 0x2345);
+
+    assert!(rom.write(table, vec![0x40, 0x23, 0xC1, 0x45, 0x23, 0xC1, 0x50, 0x23, 0xC1]).is_ok());
+    assert!(rom.write(mirror, vec![0x45, 0x23, 0x41]).is_ok());
+
+    let references = rom.find_pointers(target);
+    assert!(references.is_ok());
+
+    let references = references.unwrap();
+    assert!(references.iter().any(|r| r.offset == table + 3 && r.format == PointerFormat::Long && !r.same_bank));
+    assert!(references.iter().any(|r| r.offset == mirror && r.format == PointerFormat::Long));
+    assert!(references.iter().any(|r| r.offset == mirror && r.format == PointerFormat::Short(0xC1)));
+    assert!(!references.iter().any(|r| r.offset == table && r.format == PointerFormat::Long));
+
+    let tables = rom.find_pointer_tables(target).unwrap();
+    assert!(tables.iter().any(|t| t.format == PointerFormat::Long && t.offset <= table && t.offset + t.len() >= table + 9));
+
+    assert!(rom.find_pointers(Addr24::new(0x7E, 0x0000)).is_err());
+}
+
+#[test]
+fn test_golden_images() {
+    let mut image = PixelBuffer::new(4, 2);
+    image.fill_rect(0, 0, 2, 2, Rgb888::new(0xF8, 0x00, 0x00));
+    image.fill_rect(2, 0, 2, 2, Rgb888::new(0x00, 0x00, 0xF8));
+
+    let decoded = PixelBuffer::from_png(&image.to_png());
+    assert!(decoded.is_ok());
+    assert_eq!(decoded.unwrap(), image);
+
+    let directory = std::env::temp_dir().join(format!("flyhoney-golden-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let golden = directory.join("frame.png");
+    std::fs::write(&golden, image.to_png()).unwrap();
+
+    assert_pixels_eq(&image, &golden);
+    assert!(compare_golden(&image, &golden).unwrap().is_match());
+
+    let mut changed = image.clone();
+    changed.set_pixel(3, 1, Rgb888(0)).unwrap();
+    let diff = compare_pixels(&changed, &image);
+    assert_eq!(diff.mismatches, 1);
+    assert_eq!(diff.first, Some((3, 1)));
+    assert_eq!((diff.image.width, diff.image.height), (14, 2));
+    assert_eq!(diff.image.get_pixel(10 + 3, 1).unwrap(), TILE_DIFF_SEPARATOR);
+
+    let mut cropped = PixelBuffer::new(2, 2);
+    cropped.fill_rect(0, 0, 2, 2, Rgb888::new(0xF8, 0x00, 0x00));
+    assert_eq!(compare_pixels(&cropped, &image).mismatches, 4);
+
+    let result = std::panic::catch_unwind(|| assert_pixels_eq(&changed, &golden));
+    assert!(result.is_err());
+    assert!(directory.join("frame.diff.png").exists());
+    assert_eq!(PixelBuffer::from_png(&std::fs::read(directory.join("frame.actual.png")).unwrap()).unwrap(), changed);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_metadata_sidecar() {
+    let directory = std::env::temp_dir().join(format!("flyhoney-sidecar-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let filename = directory.join("earthbound.smc");
+    std::fs::copy("test/earthbound.smc", &filename).unwrap();
+
+    let rom_result = Rom::from_file(&filename);
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    assert!(rom.metadata().is_empty());
+
+    rom.metadata_mut().bookmark(0x200 + 0xFFC0, "internal header");
+    rom.metadata_mut().annotate(0x200 + 0x10000, 0x20, "says \"hi\"\nover two lines # not a comment");
+    let copy = rom.clone();
+    rom.metadata_mut().record_patch("fix.ips", &copy);
+    assert!(rom.save_metadata().is_ok());
+    assert!(sidecar_path(&filename).exists());
+    assert_eq!(rom.metadata().title.as_deref(), Some("EARTH BOUND"));
+    assert_eq!(rom.metadata().mapper.as_deref(), Some("hirom"));
+
+    let reloaded = Rom::from_file(&filename).unwrap();
+    assert_eq!(reloaded.metadata(), rom.metadata());
+    assert_eq!(reloaded.metadata().annotations[0].text, "says \"hi\"\nover two lines # not a comment");
+    assert_eq!(reloaded.metadata().patches[0].crc32, rom.crc32());
+
+    let parsed = RomMetadata::from_toml("size = 0x1000 # trailing\n\n[[bookmark]]\noffset = $8000\nname = \"reset\"\n");
+    assert!(parsed.is_ok());
+    let parsed = parsed.unwrap();
+    assert_eq!(parsed.size, Some(0x1000));
+    assert_eq!(parsed.bookmarks[0].offset, 0x8000);
+    assert!(matches!(RomMetadata::from_toml("[[bookmark]]\nname = reset\n"), Err(Error::InvalidMetadataLine(2))));
+    assert!(matches!(RomMetadata::from_toml("colour = 1\n"), Err(Error::UnknownField(_))));
+    assert!(matches!(RomMetadata::from_toml("title = EARTH BOUND\n"), Err(Error::InvalidMetadataLine(1))));
+    assert!(matches!(RomMetadata::from_toml("mapper = hirom\n"), Err(Error::InvalidMetadataLine(1))));
+
+    std::fs::write(sidecar_path(&filename), "offset = 1\n").unwrap();
+    let opened = Rom::from_file(&filename);
+    assert!(opened.is_ok());
+
+    let mut opened = opened.unwrap();
+    assert!(opened.metadata().is_empty());
+    assert!(matches!(opened.load_metadata(), Err(Error::UnknownField(_))));
+    assert!(opened.save_metadata().is_err());
+    assert_eq!(std::fs::read_to_string(sidecar_path(&filename)).unwrap(), "offset = 1\n");
+    assert!(Rom::new(rom.as_slice()).save_metadata().is_err());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_free_space_allocator() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let allocator = FreeSpaceAllocator::new(&rom);
+    assert!(allocator.is_ok());
+
+    let mut allocator = allocator.unwrap();
+    assert!(allocator.add_free(0x2EFF00..0x2F0100).is_ok());
+    assert!(allocator.add_free(0x2F8000..0x2F9000).is_ok());
+    assert!(allocator.add_free(0x2F9000..0x2FA000).is_ok());
+    assert_eq!(allocator.free_ranges().len(), 2);
+    assert!(matches!(allocator.alloc(0x10000, &BankConstraints::new()), Err(Error::NoFreeSpace(0x10000))));
+
+    /* the first range straddles banks $EE/$EF, so an 0x180 block can't start there */
+    let first = allocator.alloc(0x180, &BankConstraints::new());
+    assert!(first.is_ok());
+    assert_eq!(first.unwrap(), Addr24::new(0xEF, 0x8000));
+
+    let crossing = allocator.alloc(0x180, &BankConstraints::new().cross_banks(true)).unwrap();
+    assert_eq!(crossing, Addr24::new(0xEE, 0xFF00));
+
+    let banked = allocator.alloc(0x10, &BankConstraints::new().banks(0xEF..=0xEF)).unwrap();
+    assert_eq!(banked, Addr24::new(0xEF, 0x0080));
+    assert!(matches!(allocator.alloc(0x10, &BankConstraints::new().banks(0xEE..=0xEE)), Err(Error::NoFreeSpace(0x10))));
+
+    assert!(matches!(allocator.reserve(0x2F8100, 0x10), Err(Error::AllocationOverlap(0x2F8100, 0x2F8000))));
+    assert_eq!(allocator.reserve(0x100000, 0x20).unwrap(), Addr24::new(0xD0, 0x0000));
+    assert!(matches!(allocator.reserve(0x10, usize::MAX), Err(Error::OutOfBounds(_, 0x300000))));
+    assert!(matches!(allocator.alloc(usize::MAX, &BankConstraints::new()), Err(Error::NoFreeSpace(usize::MAX))));
+    assert!(allocator.alloc(usize::MAX, &BankConstraints::new().strategy(AllocationStrategy::Rats)).is_err());
+
+    let hostile = format!("size = 0x300000\n\n[[allocation]]\noffset = 0x{:X}\nlength = 0x10\n", usize::MAX - 4);
+    assert!(matches!(FreeSpaceAllocator::from_toml(&hostile, &rom), Err(Error::OutOfBounds(_, 0x300000))));
+
+    let saved = allocator.to_toml();
+    let restored = FreeSpaceAllocator::from_toml(&saved, &rom);
+    assert!(restored.is_ok());
+    assert_eq!(restored.unwrap(), allocator);
+
+    let free_before = allocator.free_bytes();
+    assert_eq!(allocator.free(Addr24::new(0x2F, 0x8000)).unwrap(), 0x180);
+    assert_eq!(allocator.free_bytes(), free_before + 0x180);
+    assert!(matches!(allocator.free(Addr24::new(0xEF, 0x8000)), Err(Error::NotAllocated(_))));
+    assert_eq!(allocator.alloc(0x100, &BankConstraints::new().alignment(0x100)).unwrap(), Addr24::new(0xEF, 0x8000));
+
+    assert!(FreeSpaceAllocator::from_toml("size = 0x1000\n", &rom).is_err());
+    assert!(FreeSpaceAllocator::from_toml("[[allocation]]\noffset = 0x10\nlength = 0x10\n[[allocation]]\noffset = 0x18\nlength = 1\n", &rom).is_err());
+}
+
+#[test]
+fn test_demo_input() {
+    let buttons = Buttons::parse("B+Right");
+    assert!(buttons.is_ok());
+    let buttons = buttons.unwrap();
+    assert_eq!(buttons, Buttons(Buttons::B | Buttons::RIGHT));
+    assert_eq!(buttons.to_string(), "B+Right");
+    assert_eq!(Buttons::default().to_string(), "none");
+    assert!(matches!(Buttons::parse("B+Turbo"), Err(Error::InvalidButton(_))));
+
+    let demo = DemoInput::new().hold(Buttons(0), 10).hold(buttons, 300).hold(buttons, 4).hold(Buttons(Buttons::A), 2);
+    assert_eq!(demo.events.len(), 3);
+    assert_eq!(demo.frames(), 316);
+    assert_eq!(demo.buttons_at(10), Some(buttons));
+    assert_eq!(demo.buttons_at(316), None);
+
+    let runs = DemoFormat::Runs { wide: true, duration_first: false };
+    let encoded = demo.encode(runs);
+    assert_eq!(&encoded[..3], &[0x00, 0x00, 10]);
+    assert_eq!(&encoded[3..9], &[0x00, 0x81, 0xFF, 0x00, 0x81, 49]);
+    assert_eq!(&encoded[encoded.len()-3..], &[0, 0, 0]);
+
+    let decoded = DemoInput::parse(&encoded, runs, 100);
+    assert!(decoded.is_ok());
+    assert_eq!(decoded.unwrap(), demo);
+
+    let narrow = DemoFormat::Runs { wide: false, duration_first: true };
+    let lossy = DemoInput::parse(&demo.encode(narrow), narrow, 100).unwrap();
+    assert_eq!(lossy.buttons_at(315), Some(Buttons(0)));
+    assert_eq!(lossy.buttons_at(20), Some(buttons));
+
+    let frames = DemoFormat::Frames { wide: false };
+    let per_frame = demo.encode(frames);
+    assert_eq!(per_frame.len(), 316);
+    assert_eq!(DemoInput::from_frames(&demo.to_frames()), demo);
+    assert!(DemoInput::parse(&per_frame, frames, 317).is_err());
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let offset = rom.header_size() + 0x2F8000;
+    assert!(rom.write_demo(offset, &demo, runs, 8).is_err());
+    assert_eq!(rom.write_demo(offset, &demo, runs, 0x100).unwrap(), encoded.len());
+    assert_eq!(rom.read_demo(offset, runs, 0x40).unwrap(), demo);
+}
+
+#[test]
+fn test_bank_checked_writes() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let boundary = rom.header_size() + 0x20000;
+    assert_eq!(rom.bank_bounds(boundary - 1).unwrap(), rom.header_size() + 0x10000..boundary);
+    assert!(rom.bank_bounds(0).is_err());
+
+    assert_eq!(rom.bank_write_mode(), BankWriteMode::Allow);
+    assert_eq!(rom.write_checked(boundary - 2, [1u8, 2, 3, 4]).unwrap(), vec![boundary - 2..boundary + 2]);
+
+    rom.set_bank_write_mode(BankWriteMode::Reject);
+    assert!(matches!(rom.write_checked(boundary - 2, [5u8, 6, 7, 8]), Err(Error::BankCrossing(o, b)) if o == boundary - 2 && b == boundary));
+    assert_eq!(rom.read(boundary - 2, 4).unwrap(), &[1, 2, 3, 4]);
+    assert!(rom.write_checked(boundary - 4, [5u8, 6, 7, 8]).is_ok());
+
+    rom.set_bank_write_mode(BankWriteMode::Split);
+    let pieces = rom.write_checked(boundary - 2, [9u8, 10, 11, 12]);
+    assert!(pieces.is_ok());
+    assert_eq!(pieces.unwrap(), vec![boundary - 2..boundary, boundary..boundary + 2]);
+    assert_eq!(rom.read(boundary - 2, 4).unwrap(), &[9, 10, 11, 12]);
+}
+
+#[test]
+fn test_free_space_filters() {
+    let mut data = vec![0u8; 0x600000];
+    data[0x8000] = 0x60;
+    let mut rom = Rom::new(&data);
+    rom.set_memory_map(Some(Mapper::ExHiRom(ExHiRom)));
+
+    let all = rom.find_free_space(&FreeSpaceFilter::new());
+    assert!(all.is_ok());
+
+    let all = all.unwrap();
+    assert_eq!(all.len(), 0x61);
+    assert_eq!(all[0], 0..0x8000);
+    assert_eq!(all[1], 0x8001..0x10000);
+    assert_eq!(rom.find_free_space(&FreeSpaceFilter::new().min_length(0x8000)).unwrap().len(), 0x60);
+
+    let slow = rom.find_free_space(&FreeSpaceFilter::new().slow_banks(true)).unwrap();
+    assert_eq!(slow.len(), 0x20);
+    assert_eq!(slow[0].start, 0x400000);
+    assert_eq!(rom.find_free_space(&FreeSpaceFilter::new().expanded_area()).unwrap(), slow);
+
+    let banked = rom.find_free_space(&FreeSpaceFilter::new().banks(0xC1..=0xC2)).unwrap();
+    assert_eq!(banked, vec![0x10000..0x20000, 0x20000..0x30000]);
+
+    let mut flags = vec![0u8; 0x600000];
+    flags[0x410005] = USAGE_EXEC | USAGE_OPCODE;
+    let without_code = rom.find_free_space(&FreeSpaceFilter::new().expanded_area().exclude_code(UsageLog::from_flags(flags))).unwrap();
+    assert_eq!(without_code.len(), 0x1F);
+    assert!(!without_code.iter().any(|r| r.start == 0x410000));
+}
+
+#[test]
+fn test_png_palette_remap() {
+    let target = [Bgr555(0x0000), Bgr555(0x001F), Bgr555(0x03E0), Bgr555(0x7C00)];
+    let file_palette = [Rgb888::from(Bgr555(0x7C00)), Rgb888::from(Bgr555(0x001F)), Rgb888::from(Bgr555(0x0000)), Rgb888::new(0xFF, 0xFF, 0xFF)];
+    let png = encode_indexed_png(4, 1, &[0, 1, 2, 0], &file_palette);
+    let image = decode_png(&png).unwrap();
+
+    assert_eq!(image.remap_indices(&target, PaletteRemap::Keep).unwrap(), vec![0, 1, 2, 0]);
+
+    let remapped = image.remap_indices(&target, PaletteRemap::Strict);
+    assert!(remapped.is_ok());
+    assert_eq!(remapped.unwrap(), vec![3, 1, 0, 3]);
+
+    let stray = encode_indexed_png(2, 1, &[0, 3], &file_palette);
+    let stray = decode_png(&stray).unwrap();
+    assert!(matches!(stray.remap_indices(&target, PaletteRemap::Strict), Err(Error::UnmatchedColor(_))));
+    assert_eq!(stray.remap_indices(&target, PaletteRemap::Nearest).unwrap(), vec![3, 1]);
+
+    let duplicate = [Bgr555(0x0000), Bgr555(0x001F), Bgr555(0x0000)];
+    let png = encode_indexed_png(2, 1, &[2, 1], &[Rgb888(0), Rgb888::from(Bgr555(0x001F)), Rgb888(0)]);
+    assert_eq!(decode_png(&png).unwrap().remap_indices(&duplicate, PaletteRemap::Strict).unwrap(), vec![2, 1]);
+
+    let mut truecolor = PixelBuffer::new(2, 1);
+    truecolor.set_pixel(0, 0, Rgb888::from(Bgr555(0x03E0))).unwrap();
+    let png = truecolor.to_png();
+    assert_eq!(decode_png(&png).unwrap().remap_indices(&target, PaletteRemap::Strict).unwrap(), vec![2, 0]);
+    assert!(decode_png(&png).unwrap().remap_indices(&target, PaletteRemap::Keep).is_err());
+}
+
+#[test]
+fn test_rom_hashes() {
+    assert_eq!(hex_digest(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(hex_digest(&md5(b"The quick brown fox jumps over the lazy dog")), "9e107d9d372bb6826bd81d3542a419d6");
+    assert_eq!(hex_digest(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(hex_digest(&sha1(b"The quick brown fox jumps over the lazy dog")), "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12");
+
+    /* 56 bytes forces the length into a second block */
+    let long = [0x61u8; 56];
+    assert_eq!(hex_digest(&md5(&long)), "3b0c8ac703f828b04c6c197006d17218");
+    assert_eq!(hex_digest(&sha1(&long)), "c2db330f6083854c99d4b5bfb6e8f29f201be699");
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let bare = Rom::new(&rom.as_slice()[rom.header_size()..]);
+    assert_eq!(rom.md5(), bare.md5());
+    assert_eq!(rom.sha1(), bare.sha1());
+    assert_eq!(rom.md5(), md5(&rom.as_slice()[0x200..]));
+}
+
+game_tables! {
+    HEADER_TITLE: [u8; 21] = (0xC0, 0xFFC0);
+    HEADER_CHECKSUMS: u16 = (0xC0, 0xFFDC) * 2;
+    RESET_VECTOR: u16 = (0xC0, 0xFFFC);
+}
+
+#[test]
+fn test_game_tables() {
+    assert_eq!(HEADER_TITLE.name, "HEADER_TITLE");
+    assert_eq!(HEADER_CHECKSUMS.size(), 4);
+    assert_eq!(HEADER_CHECKSUMS.address(1).unwrap(), Addr24::new(0xC0, 0xFFDE));
+    assert!(HEADER_CHECKSUMS.address(2).is_err());
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let title = HEADER_TITLE.read(&rom);
+    assert!(title.is_ok());
+    assert_eq!(&title.unwrap()[..11], b"EARTH BOUND");
+
+    let checksums = HEADER_CHECKSUMS.all(&rom);
+    assert!(checksums.is_ok());
+
+    let checksums = checksums.unwrap();
+    assert_eq!(checksums[0] ^ checksums[1], 0xFFFF);
+    assert_eq!(HEADER_CHECKSUMS.get(&rom, 1).unwrap(), checksums[1]);
+
+    assert!(RESET_VECTOR.write(&mut rom, &0x8123).is_ok());
+    assert_eq!(RESET_VECTOR.read(&rom).unwrap(), 0x8123);
+    assert_eq!(rom.read(0x200 + 0xFFFC, 2).unwrap(), &[0x23, 0x81]);
+    assert!(HEADER_CHECKSUMS.set(&mut rom, 2, &0).is_err());
+}
+
+#[test]
+fn test_dat_identify() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let dat = format!(r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/dtds/datafile.dtd">
+<datafile>
+    <header>
+        <name>Nintendo - Super Nintendo Entertainment System</name>
+        <version>20260101-000000</version>
+    </header>
+    <!-- <game name="commented out"> -->
+    <game name="Mother 2 - Gyiyg no Gyakushuu (Japan)">
+        <description>Mother 2 - Gyiyg no Gyakushuu (Japan)</description>
+        <rom name="Mother 2 - Gyiyg no Gyakushuu (Japan).sfc" size="3145728" crc="00000000" sha1="0000000000000000000000000000000000000000"/>
+    </game>
+    <game name="EarthBound (USA) (Rev 1) (Tom &amp; Jerry)">
+        <description>EarthBound (USA)</description>
+        <rom name="EarthBound (USA).sfc" size="{}" crc="{:08X}" md5="{}" sha1="{}" status="verified"/>
+    </game>
+</datafile>
+"#, rom.len() - rom.header_size(), rom.crc32(), hex_digest(&rom.md5()), hex_digest(&rom.sha1()).to_uppercase());
+
+    let db_result = RomDatabase::from_dat(&dat);
+    assert!(db_result.is_ok());
+
+    let db = db_result.unwrap();
+    assert_eq!(db.name, "Nintendo - Super Nintendo Entertainment System");
+    assert_eq!(db.entries.len(), 2);
+    assert_eq!(db.entries[0].region.as_deref(), Some("Japan"));
+    assert_eq!(db.entries[0].revision, None);
+
+    let entry = rom.identify(&db);
+    assert!(entry.is_some());
+
+    let entry = entry.unwrap();
+    assert_eq!(entry.name, "EarthBound (USA) (Rev 1) (Tom & Jerry)");
+    assert_eq!(entry.title, "EarthBound");
+    assert_eq!(entry.region.as_deref(), Some("USA"));
+    assert_eq!(entry.revision.as_deref(), Some("Rev 1"));
+    assert_eq!(entry.rom_name, "EarthBound (USA).sfc");
+
+    /* a CRC-only entry still needs the size to agree */
+    let mut crc_only = entry.clone();
+    crc_only.md5 = None;
+    crc_only.sha1 = None;
+    assert!(crc_only.matches(&rom));
+    crc_only.size = Some(0x100000);
+    assert!(!crc_only.matches(&rom));
+
+    let mut digests = RomDigests::new(&rom);
+    assert_eq!(digests.sha1(), hex_digest(&rom.sha1()));
+    assert!(db.entries.iter().filter(|e| e.matches_digests(&mut digests)).count() == 1);
+
+    let mut patched = rom.clone();
+    assert!(patched.write(0x300, [0xEA]).is_ok());
+    assert!(patched.identify(&db).is_none());
+
+    assert!(RomDatabase::from_dat("<datafile><game name=\"x\"><rom name=\"x\"/></datafile>").is_err());
+    assert!(matches!(RomDatabase::from_dat("<datafile>\n<!-- a\nb -->\n<rom name=\"x\"/>\n</datafile>"), Err(Error::InvalidDatLine(4))));
+}
+
+#[test]
+fn test_variant_grouping() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let directory = std::env::temp_dir().join(format!("flyhoney-variants-{}", std::process::id()));
+    std::fs::create_dir_all(directory.join("nested")).unwrap();
+
+    /* a revision with a couple of fixed bytes, and a translation that renames the title */
+    let mut revision = rom.clone();
+    assert!(revision.write(0x18200, [0xEA, 0xEA]).is_ok());
+
+    let mut translation = rom.clone();
+    let header = 0x200 + 0xFFC0;
+    assert!(translation.write(header, b"MOTHER-2             ").is_ok());
+    assert!(translation.write(0x30200, vec![0x20u8; 0x2000]).is_ok());
+
+    let mut unrelated = Rom::new(vec![0x5Au8; 0x100000]);
+    unrelated.write(0x7FC0, b"SOMETHING ELSE       ").unwrap();
+
+    assert!(rom.save(directory.join("EarthBound (USA).sfc")).is_ok());
+    assert!(revision.save(directory.join("EarthBound (USA) (Rev 1).SMC")).is_ok());
+    assert!(translation.save(directory.join("nested").join("Mother 2.sfc")).is_ok());
+    assert!(unrelated.save(directory.join("other.sfc")).is_ok());
+    std::fs::write(directory.join("notes.txt"), "not a rom").unwrap();
+
+    let flat = scan_directory(&directory, &VariantOptions::new());
+    assert!(flat.is_ok());
+    assert_eq!(flat.unwrap().len(), 3);
+
+    let options = VariantOptions::new().recursive(true);
+    let summaries = scan_directory(&directory, &options).unwrap();
+    assert_eq!(summaries.len(), 4);
+
+    let earthbound = summaries.iter().find(|s| s.path.as_ref().unwrap().ends_with("EarthBound (USA).sfc")).unwrap();
+    let mother = summaries.iter().find(|s| s.path.as_ref().unwrap().ends_with("Mother 2.sfc")).unwrap();
+    assert!(earthbound.title_similarity(mother) < 0.8);
+    assert!(earthbound.content_similarity(mother) > 0.9);
+
+    let clusters = scan_variants(&directory, &options);
+    assert!(clusters.is_ok());
+
+    let clusters = clusters.unwrap();
+    assert_eq!(clusters.len(), 2);
+
+    let big = clusters.iter().find(|c| c.members.len() == 3);
+    assert!(big.is_some());
+    assert!(big.unwrap().members.iter().all(|m| m.size == 0x300000));
+
+    /* both edits land on a sample, so demanding identical content splits the translation off; only the shared title keeps the revision */
+    let revision = summaries.iter().find(|s| s.path.as_ref().unwrap().ends_with("EarthBound (USA) (Rev 1).SMC")).unwrap();
+    assert!(earthbound.content_similarity(revision) < 1.0);
+    assert!(earthbound.content_similarity(mother) < 1.0);
+
+    let strict = VariantOptions::new().content_threshold(1.0).title_threshold(1.0);
+    assert!(strict.related(earthbound, revision));
+    assert!(!strict.related(earthbound, mother));
+    assert_eq!(group_variants(&summaries, &strict).len(), 3);
+
+    /* a near-identical title on a different size is a sequel, not a variant */
+    let second = RomSummary { path: None, title: "FINAL FANTASY II".to_string(), size: 0x100000, crc32: 1, samples: vec![1, 2] };
+    let third = RomSummary { path: None, title: "FINAL FANTASY III".to_string(), size: 0x300000, crc32: 2, samples: vec![1, 2] };
+    assert!(second.title_similarity(&third) >= 0.8);
+    assert!(!options.related(&second, &third));
+    assert_eq!(group_variants(&[second, third], &options).len(), 2);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_rom_diff() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let mut hack = rom.clone();
+    assert!(hack.write(0x1000, [0x01, 0x02, 0x03]).is_ok());
+    assert!(hack.write(0x1005, [0xFF]).is_ok());
+    assert!(hack.write(0x20000, [0xEA]).is_ok());
+
+    let ranges = rom.diff(&hack);
+    assert_eq!(ranges.len(), 3);
+    assert_eq!(ranges[0].offset, 0x1000);
+    assert_eq!(ranges[0].old, rom.read(0x1000, 3).unwrap());
+    assert_eq!(ranges[0].new, vec![0x01, 0x02, 0x03]);
+    assert_eq!(ranges[2].end(), 0x20001);
+    assert!(rom.diff(&rom).is_empty());
+
+    let merged = rom.diff_merged(&hack, 2);
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].len(), 6);
+    assert_eq!(merged[0].changed_bytes(), 4);
+
+    /* offsets stay in the original's buffer even when the hack has lost its copier header */
+    let bare = Rom::new(&hack.as_slice()[hack.header_size()..]);
+    assert_eq!(rom.diff(&bare), ranges);
+
+    let mut grown = rom.clone();
+    grown.resize(rom.len() + 0x8000);
+    let growth = rom.diff(&grown);
+    assert_eq!(growth.len(), 1);
+    assert!(growth[0].old.is_empty());
+    assert_eq!(growth[0].new.len(), 0x8000);
+    assert_eq!(growth[0].changed_bytes(), 0x8000);
+
+    let patch = IpsPatch::from_ranges(&ranges, hack.as_slice());
+    assert!(patch.is_ok());
+
+    let mut patched = rom.clone();
+    assert!(patch.unwrap().apply(&mut patched).is_ok());
+    assert_eq!(patched.as_slice(), hack.as_slice());
+
+    /* a change at the offset that spells "EOF" is written from the byte before it, so readers don't stop there */
+    let big = Rom::new(vec![0x11u8; 0x460000]);
+    let mut edited = big.clone();
+    assert!(edited.write(IPS_EOF_OFFSET, [0x22, 0x33]).is_ok());
+
+    let patch = IpsPatch::from_ranges(&big.diff(&edited), edited.as_slice()).unwrap();
+    assert_eq!(patch.records[0].offset(), IPS_EOF_OFFSET - 1);
+
+    let reread = IpsPatch::parse(patch.to_bytes().unwrap()).unwrap();
+    let mut patched = big.clone();
+    assert!(reread.apply(&mut patched).is_ok());
+    assert_eq!(patched.as_slice(), edited.as_slice());
+
+    /* the same goes for a chunk boundary inside one long range */
+    let mut long = big.clone();
+    assert!(long.write(IPS_EOF_OFFSET - 0xFFFF, vec![0x44u8; 0x10010]).is_ok());
+    let patch = IpsPatch::from_ranges(&big.diff(&long), long.as_slice()).unwrap();
+    assert!(patch.records.iter().all(|r| r.offset() != IPS_EOF_OFFSET));
+
+    let mut patched = big.clone();
+    assert!(IpsPatch::parse(patch.to_bytes().unwrap()).unwrap().apply(&mut patched).is_ok());
+    assert_eq!(patched.as_slice(), long.as_slice());
+
+    let mut reserved = IpsPatch::new();
+    reserved.records.push(IpsRecord::Data { offset: IPS_EOF_OFFSET, data: vec![0] });
+    assert!(matches!(reserved.to_bytes(), Err(Error::ReservedPatchOffset(IPS_EOF_OFFSET))));
+}
+
+mod prelude_tests {
+    use crate::prelude::*;
+
+    game_tables! {
+        TITLE: [u8; 21] = (0xC0, 0xFFC0);
+    }
+
+    #[test]
+    fn test_prelude() {
+        let rom_result = Rom::from_file("test/earthbound.smc");
+        assert!(rom_result.is_ok());
+
+        let rom = rom_result.unwrap();
+        let map = rom.memory_map();
+        assert!(map.is_ok());
+        assert_eq!(map.unwrap().address_to_pc(Addr24::new(0xC0, 0xFFC0)).unwrap(), 0xFFC0);
+        assert_eq!(&TITLE.read(&rom).unwrap()[..5], b"EARTH");
+
+        let palette = SNESPalette16::from_data([0u8; 32]);
+        assert!(palette.is_ok());
+        assert_eq!(palette.unwrap().colors().len(), 16);
+
+        let patch = IpsPatch::from_ranges(&rom.diff(&rom), rom.as_slice());
+        assert!(patch.is_ok());
+        assert!(patch.unwrap().records.is_empty());
+    }
+}
+
+#[test]
+fn test_overlay() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let original = rom.read(0x1000, 8).unwrap().to_vec();
+    let mut overlay = rom.overlay();
+    assert!(!overlay.is_modified());
+
+    assert!(overlay.write(0x1002, [0xAA, 0xBB]).is_ok());
+    assert!(overlay.write(0x1003, [0xCC, 0xDD]).is_ok());
+    assert!(overlay.write(rom.len() - 1, [0x00, 0x00]).is_err());
+    assert_eq!(overlay.layers()[0].runs().count(), 1);
+
+    let merged = overlay.read(0x1000, 8);
+    assert!(merged.is_ok());
+    assert_eq!(merged.unwrap(), [&original[..2], &[0xAA, 0xCC, 0xDD], &original[5..]].concat());
+    assert_eq!(rom.read(0x1000, 8).unwrap(), original.as_slice());
+
+    /* a second layer can be thrown away without touching the first */
+    overlay.push_layer();
+    assert!(overlay.write(0x1000, [0x11]).is_ok());
+    assert_eq!(overlay.read_u8(0x1000).unwrap(), 0x11);
+    assert!(overlay.pop_layer().is_some());
+    assert_eq!(overlay.read_u8(0x1000).unwrap(), original[0]);
+
+    /* writing a byte's original value back isn't reported as a change */
+    overlay.push_layer();
+    assert!(overlay.write(0x1007, [original[7]]).is_ok());
+    assert!(overlay.write(0x20000, [0xEA]).is_ok());
+    overlay.squash();
+    assert_eq!(overlay.layers().len(), 1);
+
+    let changes = overlay.changes();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].offset, 0x1002);
+    assert_eq!(changes[0].new, vec![0xAA, 0xCC, 0xDD]);
+
+    let flat = overlay.flatten();
+    assert!(flat.is_ok());
+
+    let flat = flat.unwrap();
+    assert_eq!(flat.read(0x1002, 3).unwrap(), &[0xAA, 0xCC, 0xDD]);
+    assert_eq!(rom.diff(&flat), changes);
+
+    let patch = overlay.to_ips();
+    assert!(patch.is_ok());
+
+    let mut patched = rom.clone();
+    assert!(patch.unwrap().apply(&mut patched).is_ok());
+    assert_eq!(patched.as_slice(), flat.as_slice());
+}
+
+#[test]
+fn test_error_context() {
+    let inner = Error::DataLengthMismatch(31, 32);
+    let context = ErrorContext::new("reading palette").address(Addr24::new(0xC2, 0x1000)).offset(0x21200).length(32);
+    let error = inner.context(context.clone()).context(ErrorContext::new("palette survey"));
+
+    assert!(matches!(error.root(), Error::DataLengthMismatch(31, 32)));
+    assert_eq!(error.contexts(), vec![&ErrorContext::new("palette survey"), &context]);
+    assert_eq!(error.to_string(), "palette survey: reading palette at $C2:1000 (offset 0x021200), 32 bytes: DataLengthMismatch(31, 32)");
+
+    let rom = Rom::new(vec![0u8; 0x8000]);
+    let result = rom.read(0x7FF0, 0x20).with_context(|| ErrorContext::new("reading table").offset(0x7FF0).length(0x20));
+    assert!(result.is_err());
+
+    let error = result.unwrap_err();
+    assert_eq!(error.contexts().len(), 1);
+    assert!(!matches!(error.root(), Error::Context(_, _)));
+    assert!(rom.read(0, 4).context(ErrorContext::new("unused")).is_ok());
+
+    /* the asset packer says which entry didn't fit */
+    let mut rom = Rom::new(vec![0u8; 0x200 + 0x10000]);
+    let directory = std::env::temp_dir().join(format!("flyhoney-context-{}", std::process::id()));
+    let mut bundle = AssetBundle::new();
+    bundle.add(AssetEntry::new(AssetKind::Text, "text/long.txt", 0x200+0x5000, 4), b"TOO LONG".to_vec());
+    assert!(bundle.write_to(&directory).is_ok());
+
+    let packed = rom.pack_assets(&directory, &bundle.entries, &PackOptions::new());
+    assert!(packed.is_err());
+
+    let error = packed.unwrap_err();
+    assert!(matches!(error.root(), Error::DataLengthMismatch(8, 4)));
+    assert_eq!(error.contexts()[0].operation, "packing text/long.txt");
+    assert_eq!(error.contexts()[0].offset, Some(0x200+0x5000));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_edit_journal() {
+    let mut rom = Rom::new(vec![0u8; 0x8000]);
+    assert!(rom.write(0x10, [0x01]).is_ok());
+    assert!(rom.history().is_empty());
+    assert_eq!(rom.undo().unwrap(), false);
+
+    rom.start_journal();
+    assert!(rom.write(0x100, [0xAA, 0xBB]).is_ok());
+    assert!(rom.write_u16(0x101, 0x1234).is_ok());
+    assert!(rom.write(0x7FFF, [0x00, 0x00]).is_err());
+    assert_eq!(rom.history().len(), 2);
+    assert_eq!(rom.history()[1].old, vec![0xBB, 0x00]);
+    assert_eq!(rom.history()[1].new, vec![0x34, 0x12]);
+
+    rom.resize(0x8400);
+    assert_eq!(rom.history().len(), 3);
+    assert_eq!(rom.history()[2].new.len(), 0x400);
+
+    assert_eq!(rom.undo().unwrap(), true);
+    assert_eq!(rom.len(), 0x8000);
+    assert_eq!(rom.undo().unwrap(), true);
+    assert_eq!(rom.read(0x100, 3).unwrap(), &[0xAA, 0xBB, 0x00]);
+    assert_eq!(rom.journal().unwrap().redo_history().len(), 2);
+
+    assert_eq!(rom.redo().unwrap(), true);
+    assert_eq!(rom.read(0x100, 3).unwrap(), &[0xAA, 0x34, 0x12]);
+
+    /* a new edit drops what was left to redo */
+    assert!(rom.write(0x200, [0x55]).is_ok());
+    assert!(!rom.journal().unwrap().can_redo());
+    assert_eq!(rom.redo().unwrap(), false);
+
+    /* shrinking keeps the cut bytes so undo can bring them back */
+    rom.resize(0x101);
+    assert_eq!(rom.undo().unwrap(), true);
+    assert_eq!(rom.len(), 0x8000);
+    assert_eq!(rom.read(0x100, 3).unwrap(), &[0xAA, 0x34, 0x12]);
+    assert_eq!(rom.read(0x200, 1).unwrap(), &[0x55]);
+
+    while rom.undo().unwrap() {}
+    assert_eq!(rom.read(0x100, 3).unwrap(), &[0x00, 0x00, 0x00]);
+    assert_eq!(rom.read(0x10, 1).unwrap(), &[0x01]);
+
+    /* equality is the image alone, so undoing everything gets back to a plain copy of it */
+    let mut plain = Rom::new(vec![0u8; 0x8000]);
+    plain.write(0x10, [0x01]).unwrap();
+    assert_eq!(rom, plain);
+
+    let journal = rom.stop_journal();
+    assert!(journal.is_some());
+    assert_eq!(journal.unwrap().redo_history().len(), 4);
+    assert_eq!(Rom::from_file("test/earthbound.smc").unwrap(), Rom::new(std::fs::read("test/earthbound.smc").unwrap()));
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let original = rom.clone();
+    rom.start_journal_with(EditJournal::new().limit(1));
+    assert!(rom.update_header(|h| h.set_version(2)).is_ok());
+    assert!(rom.fix_checksum().is_ok());
+    assert_eq!(rom.history().len(), 1);
+    assert_eq!(rom.history()[0].operation, "update_header");
+
+    while rom.undo().unwrap() {}
+    assert_ne!(rom.as_slice(), original.as_slice());
+
+    /* normalization edits go through write too, so undo walks all the way back and a failed write logs nothing */
+    let mut rom = original.clone();
+    rom.start_journal();
+    rom.start_build_log(true);
+    assert!(rom.write(rom.len(), [0x00]).is_err());
+    assert!(rom.build_log().unwrap().operations.is_empty());
+
+    assert!(rom.expand_to(0x400000, ExpandFill::Byte(0xFF)).is_ok());
+    while rom.undo().unwrap() {}
+    assert_eq!(rom.as_slice(), original.as_slice());
+}
+
+struct LossyCodec;
+impl Codec for LossyCodec {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(data.iter().map(|b| b & 0xFE).collect())
+    }
+    fn decompress(&self, data: &[u8], _size: usize) -> Result<Vec<u8>, Error> {
+        Ok(data.to_vec())
+    }
+}
+
+#[test]
+fn test_round_trip_check() {
+    let data = b"abracadabra, abracadabra".to_vec();
+    let code = HuffmanCode::from_data(&data).unwrap().round_trip(RoundTripCheck::new());
+
+    let encoded = code.encode(&data);
+    assert!(encoded.is_ok());
+    assert_eq!(code.decode(&encoded.as_ref().unwrap(), data.len()).unwrap(), data);
+
+    let limited = code.clone().round_trip(RoundTripCheck::new().max_size(4));
+    assert!(matches!(limited.encode(&data), Err(Error::DataLengthMismatch(_, 4))));
+    assert!(compress_checked(&code, &data, &RoundTripCheck::new().max_size(64)).is_ok());
+
+    /* bytes the codec can't represent come back different, and the check says where */
+    assert!(LossyCodec.compress(b"BBBB").is_ok());
+    assert!(compress_checked(&LossyCodec, b"BBBB", &RoundTripCheck::default()).is_ok());
+    assert!(matches!(compress_checked(&LossyCodec, b"BBBC", &RoundTripCheck::new()), Err(Error::RoundTripMismatch(3))));
+}
+
+#[test]
+fn test_rom_cursor() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let mut cursor = rom.cursor(Addr24::new(0xC0, 0xFFC0));
+    assert_eq!(cursor.offset().unwrap(), 0x200 + 0xFFC0);
+
+    let title = cursor.read_bytes(21);
+    assert!(title.is_ok());
+    assert_eq!(&title.unwrap()[..11], b"EARTH BOUND");
+    assert_eq!(cursor.position(), Addr24::new(0xC0, 0xFFD5));
+
+    assert!(cursor.skip(0xFFDC - 0xFFD5).is_ok());
+    let complement = cursor.read_u16().unwrap();
+    let checksum = cursor.read_u16().unwrap();
+    assert_eq!(complement ^ checksum, 0xFFFF);
+    assert_eq!(cursor.position(), Addr24::new(0xC0, 0xFFE0));
+
+    /* the bank policy decides what happens at $FFFF */
+    cursor.seek(Addr24::new(0xC0, 0xFFFE));
+    let carried = cursor.clone().read_u32();
+    assert!(carried.is_ok());
+    assert_eq!(carried.unwrap().to_le_bytes()[2..], rom.read(0x200 + 0x10000, 2).unwrap()[..]);
+
+    let mut wrapped = cursor.clone().wrap(BankCrossPolicy::Wrap);
+    assert_eq!(wrapped.read_u24().unwrap().to_le_bytes()[2], rom.read(0x200, 1).unwrap()[0]);
+    assert_eq!(wrapped.position(), Addr24::new(0xC0, 0x0001));
+
+    let mut forbidden = cursor.clone().wrap(BankCrossPolicy::Forbid);
+    assert!(forbidden.read_addr24().is_err());
+    assert_eq!(forbidden.position(), Addr24::new(0xC0, 0xFFFE));
+    assert_eq!(forbidden.read_pointer().unwrap().bank, 0xC0);
+    assert!(forbidden.is_exhausted());
+    assert_eq!(forbidden.position(), Addr24::new(0xC0, 0xFFFF));
+    assert!(forbidden.read_u8().is_err());
+    forbidden.seek(Addr24::new(0xC0, 0xFFC0));
+    assert_eq!(forbidden.read_u8().unwrap(), b'E');
+
+    let from_offset = RomCursor::from_offset(&rom, 0x200 + 0xFFC0);
+    assert!(from_offset.is_ok());
+    assert_eq!(from_offset.unwrap().peek_u8().unwrap(), b'E');
+}
+
+#[test]
+fn test_tile_padding() {
+    let data = (0..80u8).collect::<Vec<u8>>();
+
+    assert!(matches!(TileSheet::<SNESTile4BPPPlanar>::from_data(&data, 16), Err(Error::DataLengthMismatch(80, 64))));
+    assert!(matches!(TileSheet::<SNESTile4BPPPlanar>::from_data_padded(&data, 16, TilePadding::Error), Err(Error::DataLengthMismatch(80, 64))));
+
+    let padded = TileSheet::<SNESTile4BPPPlanar>::from_data_padded(&data, 16, TilePadding::Zero);
+    assert!(padded.is_ok());
+
+    let (sheet, remainder) = padded.unwrap();
+    assert_eq!(remainder, 16);
+    assert_eq!(sheet.len(), 3);
+    assert_eq!(sheet.tiles[2].0[..16], data[64..]);
+    assert!(sheet.tiles[2].0[16..].iter().all(|&b| b == 0));
+
+    let (sheet, remainder) = TileSheet::<SNESTile4BPPPlanar>::from_data_padded(&data, 16, TilePadding::Truncate).unwrap();
+    assert_eq!((sheet.len(), remainder), (2, 16));
+
+    let (sheet, remainder) = TileSheet::<SNESTile2BPPPlanar>::from_data_padded(&data, 16, TilePadding::Truncate).unwrap();
+    assert_eq!((sheet.len(), remainder), (5, 0));
+
+    let (tiles, remainder) = TileFormat::Bpp8Planar.decode_all_padded(&data, TilePadding::Zero).unwrap();
+    assert_eq!((tiles.len(), remainder), (2, 16));
+    assert!(TileFormat::Bpp8Planar.decode_all(&data).is_err());
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let region = TileSheet::<SNESTile4BPPPlanar>::from_rom_region(&rom, Addr24::new(0xC4, 0x0000), 100, 8, TilePadding::Zero);
+    assert!(region.is_ok());
+    assert_eq!(region.as_ref().unwrap().0.len(), 4);
+    assert_eq!(region.unwrap().1, 4);
+}
+
+#[test]
+fn test_endian_reads() {
+    let mut rom = Rom::new(vec![0u8; 0x8000]);
+    assert!(rom.write(0x10, [0x12, 0x34, 0x56, 0x78]).is_ok());
+
+    assert_eq!(rom.read_u8(0x10).unwrap(), 0x12);
+    assert_eq!(rom.read_u16_le(0x10).unwrap(), 0x3412);
+    assert_eq!(rom.read_u16_be(0x10).unwrap(), 0x1234);
+    assert_eq!(rom.read_u24_le(0x10).unwrap(), 0x563412);
+    assert_eq!(rom.read_u24_be(0x10).unwrap(), 0x123456);
+    assert_eq!(rom.read_u32_le(0x10).unwrap(), 0x78563412);
+    assert_eq!(rom.read_u32_be(0x10).unwrap(), 0x12345678);
+    assert_eq!(rom.read_u16(0x10).unwrap(), rom.read_u16_le(0x10).unwrap());
+    assert_eq!(rom.read_addr24(0x10).unwrap(), Addr24::new(0x56, 0x3412));
+    assert!(rom.read_u32_be(0x7FFE).is_err());
+
+    assert!(rom.write_u24_be(0x20, 0xC0FFEE).is_ok());
+    assert_eq!(rom.read(0x20, 3).unwrap(), &[0xC0, 0xFF, 0xEE]);
+    assert!(rom.write_u24_le(0x20, 0xC0FFEE).is_ok());
+    assert_eq!(rom.read(0x20, 3).unwrap(), &[0xEE, 0xFF, 0xC0]);
+    assert!(rom.write_u16_be(0x30, 0xBEEF).is_ok());
+    assert_eq!(rom.read_u16_le(0x30).unwrap(), 0xEFBE);
+    assert!(rom.write_u32_be(0x40, 0x01020304).is_ok());
+    assert_eq!(rom.read_u32_le(0x40).unwrap(), 0x04030201);
+    assert!(rom.write_u8(0x50, 0x99).is_ok());
+    assert!(rom.write_addr24(0x51, Addr24::new(0xC2, 0x8000)).is_ok());
+    assert_eq!(rom.read(0x50, 4).unwrap(), &[0x99, 0x00, 0x80, 0xC2]);
+}
+
+#[test]
+fn test_iter_banks() {
+    let mut data = vec![0u8; 0x200 + 0x20000];
+    data[0x200+0x7FC0..0x200+0x7FD5].copy_from_slice(b"LOROM BANK TEST      ");
+    data[0x200+0x7FD5] = 0x20;
+    data[0x200+0x7FD7] = 0x07;
+    data[0x200+0x7FDC..0x200+0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x200+0x18000] = 0x62;
+    let lorom = Rom::new(&data);
+
+    let banks = lorom.iter_banks().collect::<Vec<_>>();
+    assert_eq!(banks.len(), 4);
+    assert!(banks.iter().all(|(_, b)| b.len() == 0x8000));
+    assert_eq!(banks[3].1[0], 0x62);
+    assert_eq!(lorom.get_bank(banks[3].0).unwrap()[0], 0x62);
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let hirom = rom_result.unwrap();
+    let banks = hirom.iter_banks().map(|(n, b)| (n, b.len())).collect::<Vec<_>>();
+    assert_eq!(banks.len(), 0x30);
+    assert_eq!(banks[0], (0xC0, 0x10000));
+    assert_eq!(banks[0x2F].0, 0xEF);
+    assert_eq!(hirom.get_bank(0xC1).unwrap()[0], hirom.read(0x200 + 0x10000, 1).unwrap()[0]);
+    assert!(hirom.get_bank(0xFF).is_err());
+
+    let mut data = vec![0u8; 0x600000];
+    data[0x40FFC0..0x40FFD5].copy_from_slice(b"EXHIROM TEST         ");
+    data[0x40FFD5] = 0x35;
+    data[0x40FFD7] = 0x0D;
+    data[0x40FFDC..0x40FFE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x400000] = 0x63;
+    let exhirom = Rom::new(&data);
+
+    let bank = exhirom.get_bank(0x40);
+    assert!(bank.is_ok());
+    assert_eq!(bank.unwrap()[0], 0x63);
+    assert_eq!(exhirom.iter_banks().count(), 0x60);
+    assert_eq!(exhirom.iter_banks().nth(0x40).unwrap().0, 0x40);
+}
+
+#[test]
+fn test_rom_similarity() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let bare = Rom::new(&rom.as_slice()[rom.header_size()..]);
+    assert_eq!(rom.similarity(&bare), 1.0);
+    assert!(rom.is_same_game(&bare));
+
+    /* a fixed checksum and a couple of patched bytes barely move the score */
+    let mut fixed = bare.clone();
+    assert!(fixed.write(0x20000, [0xEA, 0xEA]).is_ok());
+    assert!(fixed.update_header(|h| h.set_version(1)).is_ok());
+    assert!(rom.similarity(&fixed) > 0.99);
+    assert!(rom.is_same_game(&fixed));
+
+    /* swapping two banks moves data around without changing it */
+    let mut swapped = bare.clone();
+    let (first, second) = (swapped.read(0x100000, 0x10000).unwrap().to_vec(), swapped.read(0x110000, 0x10000).unwrap().to_vec());
+    assert!(swapped.write(0x100000, &second).is_ok());
+    assert!(swapped.write(0x110000, &first).is_ok());
+    assert_eq!(rom.similarity(&swapped), 1.0);
+
+    let mut expanded = bare.clone();
+    expanded.resize(0x400000);
+    assert!((rom.similarity(&expanded) - 0.75).abs() < 0.01);
+    assert!(!rom.is_same_game(&expanded));
+
+    let other = Rom::new(vec![0x5Au8; 0x300000]);
+    assert!(rom.similarity(&other) < 0.05);
+    assert!(!rom.is_same_game(&other));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mapped_rom() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mapped_result = Rom::from_file_mapped("test/earthbound.smc");
+    assert!(mapped_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let mut mapped = mapped_result.unwrap();
+    assert!(mapped.is_mapped());
+    assert!(!rom.is_mapped());
+    assert_eq!(mapped, rom);
+    assert_eq!(mapped.get_bank(0xC0).unwrap().as_slice(), rom.get_bank(0xC0).unwrap().as_slice());
+    assert!(matches!(mapped.read(rom.len(), 1), Err(Error::PKBufferError(_))));
+    assert!(matches!(rom.read(rom.len() - 1, 2), Err(Error::PKBufferError(_))));
+
+    /* clones share the mapping; the first write copies, and the file on disk stays as it was */
+    let shared = mapped.clone();
+    assert!(shared.is_mapped());
+    assert!(mapped.write(0x200, [0xEA]).is_ok());
+    assert!(!mapped.is_mapped());
+    assert_eq!(mapped.read_u8(0x200).unwrap(), 0xEA);
+    assert_eq!(shared, rom);
+    assert_eq!(std::fs::read("test/earthbound.smc").unwrap(), rom.as_slice());
+
+    /* saving over the file it's mapped from must not empty it */
+    let directory = std::env::temp_dir().join(format!("flyhoney-mapped-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let filename = directory.join("earthbound.smc");
+    std::fs::copy("test/earthbound.smc", &filename).unwrap();
+
+    let mapped = Rom::from_file_mapped(&filename).unwrap();
+    assert!(mapped.save_in_place().is_ok());
+    assert!(mapped.save(&filename).is_ok());
+    assert_eq!(std::fs::read(&filename).unwrap(), rom.as_slice());
+    assert_eq!(mapped.as_slice(), rom.as_slice());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_allocation_strategies() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    let mut allocator = FreeSpaceAllocator::new(&rom).unwrap();
+    assert!(allocator.add_free(0x100000..0x100400).is_ok());
+    assert!(allocator.add_free(0x2F0000..0x2F0100).is_ok());
+    assert!(allocator.add_free(0x2F8000..0x2F8040).is_ok());
+
+    assert_eq!(allocator.alloc(0x30, &BankConstraints::new().strategy(AllocationStrategy::BestFit)).unwrap(), Addr24::new(0xEF, 0x8000));
+    assert_eq!(allocator.alloc(0x10, &BankConstraints::new()).unwrap(), Addr24::new(0xD0, 0x0000));
+    assert_eq!(allocator.alloc(0x10, &BankConstraints::new().strategy(AllocationStrategy::BankAffinity(0xEE))).unwrap(), Addr24::new(0xEF, 0x0000));
+
+    /* the tag sits in front of the block, and freeing by the data's address takes the tag with it */
+    assert_eq!(rats_tag(0x20).unwrap(), *b"STAR\x1F\x00\xE0\xFF");
+    assert!(rats_tag(0x10001).is_err());
+    let tagged = allocator.alloc(0x20, &BankConstraints::new().strategy(AllocationStrategy::Rats)).unwrap();
+    assert_eq!(tagged, Addr24::new(0xD0, 0x0018));
+
+    let mut patched = rom.clone();
+    assert_eq!(patched.write_rats_tags(&allocator).unwrap(), 1);
+    assert_eq!(patched.read(patched.header_size() + 0x100010, 8).unwrap(), b"STAR\x1F\x00\xE0\xFF");
+
+    let restored = FreeSpaceAllocator::from_toml(&allocator.to_toml(), &rom).unwrap();
+    assert_eq!(restored, allocator);
+
+    let free_before = allocator.free_bytes();
+    assert_eq!(allocator.free(tagged).unwrap(), 0x20);
+    assert_eq!(allocator.free_bytes(), free_before + 0x28);
+
+    /* named blocks keep their address across a save and reload as long as they still fit */
+    let directory = std::env::temp_dir().join(format!("flyhoney-allocations-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let mut project = Project::new(&directory, rom.clone());
+    project.allocator = Some(allocator);
+    let hook = project.allocator.as_mut().unwrap().alloc_named("hook", 0x40, &BankConstraints::new()).unwrap();
+    assert!(project.save_allocations().is_ok());
+
+    let project_result = Project::new(&directory, rom.clone()).load_allocations();
+    assert!(project_result.is_ok());
+
+    let mut project = project_result.unwrap();
+    let allocator = project.allocator.as_mut().unwrap();
+    assert_eq!(allocator.named("hook").unwrap().address, hook);
+    assert_eq!(allocator.alloc(0x40, &BankConstraints::new()).unwrap(), Addr24::new(0xD0, 0x0050));
+    assert_eq!(allocator.alloc_named("hook", 0x30, &BankConstraints::new().strategy(AllocationStrategy::BestFit)).unwrap(), hook);
+    assert_ne!(allocator.alloc_named("hook", 0x80, &BankConstraints::new()).unwrap(), hook);
+    assert_eq!(allocator.allocations().iter().filter(|a| a.name.is_some()).count(), 1);
+
+    /* a block that can't grow anywhere stays where it was */
+    let moved = allocator.named("hook").unwrap().clone();
+    let free_before = allocator.free_bytes();
+    assert!(allocator.alloc_named("hook", 0x10000, &BankConstraints::new()).is_err());
+    assert_eq!(allocator.named("hook").unwrap(), &moved);
+    assert_eq!(allocator.free_bytes(), free_before);
+
+    /* names are written as escaped strings, so quotes and '#' survive the round trip */
+    assert!(allocator.alloc_named("say \"hi\" #2", 0x10, &BankConstraints::new()).is_ok());
+    let restored = FreeSpaceAllocator::from_toml(&allocator.to_toml(), &rom).unwrap();
+    assert_eq!(&restored, &*allocator);
+    assert!(restored.named("say \"hi\" #2").is_some());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_header_region() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let header = rom.find_valid_snes_header().unwrap();
+    assert_eq!(header.get_region(), Region::Usa);
+    assert_eq!(header.video_standard(), VideoStandard::Ntsc);
+    assert_eq!(header.get_developer_id(), 0x33);
+
+    assert!(rom.update_header(|h| h.set_region(Region::Australia)).is_ok());
+    assert_eq!(rom.find_valid_snes_header().unwrap().get_destination_code(), 0x11);
+    assert!(rom.find_valid_snes_header().unwrap().get_region().is_pal());
+
+    for code in 0..=0xFFu8 {
+        assert_eq!(Region::from_code(code).code(), code);
+    }
+    assert!(!Region::Brazil.is_pal());
+    assert!(Region::Indonesia.is_pal());
+}
+
+#[test]
+fn test_change_watches() {
+    use std::sync::{Arc, Mutex};
+
+    let mut rom = Rom::new(vec![0u8; 0x8000]);
+    let seen = Arc::new(Mutex::new(Vec::<ChangeEvent>::new()));
+    let sink = seen.clone();
+
+    let tiles = rom.watch(0x1000..0x2000, move |e| sink.lock().unwrap().push(e.clone()));
+    let text = rom.watch_queued(0x4000..0x4100);
+    assert_eq!(rom.watches().len(), 2);
+
+    assert!(rom.write(0x0FF0, [1u8; 0x20]).is_ok());
+    assert!(rom.write(0x3000, [2u8; 4]).is_ok());
+    assert!(rom.write_u16(0x40FF, 0x1234).is_ok());
+
+    /* only the overlap is reported, and a write that misses every range reports nothing */
+    let events = seen.lock().unwrap().clone();
+    assert_eq!(events, vec![ChangeEvent { id: tiles, operation: "write".to_string(), range: 0x1000..0x1010 }]);
+    assert_eq!(rom.pending_events().len(), 1);
+    assert_eq!(rom.pending_events()[0].range, 0x40FF..0x4100);
+
+    let taken = rom.take_events();
+    assert_eq!(taken[0].id, text);
+    assert!(rom.pending_events().is_empty());
+
+    rom.resize(0x4080);
+    assert_eq!(rom.take_events()[0].range, 0x4080..0x4100);
+
+    /* clones don't inherit watches, and watches don't affect equality */
+    let copy = rom.clone();
+    assert!(copy.watches().is_empty());
+    assert_eq!(copy, rom);
+
+    assert!(rom.unwatch(tiles));
+    assert!(!rom.unwatch(tiles));
+    assert!(rom.write(0x1000, [3u8]).is_ok());
+    assert_eq!(seen.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_extended_header() {
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let rom = rom_result.unwrap();
+    assert!(rom.find_valid_snes_header().unwrap().has_extended_header());
+
+    let extended_result = rom.extended_header();
+    assert!(extended_result.is_ok());
+
+    let extended = extended_result.unwrap().unwrap();
+    assert_eq!(extended.maker_code, "01");
+    assert_eq!(extended.game_code, "MB");
+    assert_eq!(extended.expansion_ram_bytes(), 0);
+    assert_eq!(extended.special_version, 0);
+
+    let mut data = b"8PAWJE".to_vec();
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0x00, 0x05, 0x01, 0x02]);
+    let extended = ExtendedHeader::from_data(&data).unwrap();
+    assert_eq!(extended.maker_code, "8P");
+    assert_eq!(extended.game_code, "AWJE");
+    assert_eq!(extended.expansion_flash_bytes(), 0);
+    assert_eq!(extended.expansion_ram_bytes(), 0x8000);
+    assert_eq!(extended.special_version, 1);
+    assert_eq!(extended.chipset_subtype, 2);
+    assert!(ExtendedHeader::from_data(&data[..8]).is_err());
+}
+
+#[test]
+fn test_parallax_tables() {
+    let layout = ParallaxLayout::new(ScrollAxis::Horizontal).band(32, 0.0).band(200, 0.5).band_with_offset(8, 1.0, -4);
+    assert_eq!(layout.total_lines(), 240);
+
+    /* the 200-line band is split across two entries that share one data slot */
+    let table_bytes = layout.indirect_table_bytes(0x0100);
+    assert!(table_bytes.is_ok());
+    assert_eq!(table_bytes.unwrap(), vec![0x20, 0x00, 0x01, 0x7F, 0x02, 0x01, 0x49, 0x02, 0x01, 0x08, 0x04, 0x01, 0x00]);
+    assert_eq!(layout.scroll_data(100, 0), vec![0x00, 0x00, 0x32, 0x00, 0x60, 0x00]);
+
+    let params = layout.channel_params(1, Addr24::new(0xC1, 0x0000), 0x7E).unwrap();
+    assert!(params.hdma_indirect());
+    assert_eq!(params.mode(), TransferMode::Mode2);
+    assert_eq!(params.b_bus_register(), 0x210F);
+    assert!(layout.channel_params(4, Addr24::new(0xC1, 0x0000), 0x7E).is_err());
+    assert!(ParallaxLayout::new(ScrollAxis::Both).band(0, 1.0).indirect_table(0).is_err());
+
+    let rom_result = Rom::from_file("test/earthbound.smc");
+    assert!(rom_result.is_ok());
+
+    let mut rom = rom_result.unwrap();
+    let table_offset = Addr24::new(0xC1, 0x0000).to_mapped_offset(&rom).unwrap();
+    let data_offset = Addr24::new(0xC1, 0x0100).to_mapped_offset(&rom).unwrap();
+    assert!(Addr24::new(0x7E, 0x0000).to_mapped_offset(&rom).is_err());
+    assert!(rom.write(table_offset, layout.indirect_table_bytes(0x0100).unwrap()).is_ok());
+    assert!(rom.write(data_offset, layout.scroll_data(100, 0)).is_ok());
+
+    let table = HdmaTable::from_rom(&rom, Addr24::new(0xC1, 0x0000), TransferMode::Mode2, true).unwrap();
+    let lines = table.scroll_lines(ScrollAxis::Horizontal, &rom, 0xC1);
+    assert!(lines.is_ok());
+
+    let lines = lines.unwrap();
+    assert_eq!(lines.len(), 240);
+    assert_eq!(lines[0], ScrollLine { horizontal: Some(0), vertical: None });
+    assert_eq!(lines[200].horizontal, Some(50));
+    assert_eq!(lines[239].horizontal, Some(96));
+
+    /* a static scene as a direct table reads back the same way */
+    let both = ParallaxLayout::new(ScrollAxis::Both).band(16, 0.25).band(16, 2.0);
+    let direct = both.direct_table(-8, 4).unwrap();
+    let lines = direct.scroll_lines(ScrollAxis::Both, &rom, 0).unwrap();
+    assert_eq!(lines[0], ScrollLine { horizontal: Some(0xFFFE), vertical: Some(1) });
+    assert_eq!(lines[31], ScrollLine { horizontal: Some(0xFFF0), vertical: Some(8) });
+    assert!(direct.scroll_lines(ScrollAxis::Horizontal, &rom, 0).is_err());
+}
+
+#[test]
+fn test_rom_size_byte_bounds() {
+    let mut data = vec![0u8; 0x8000];
+    data[0x7FC0..0x7FD5].copy_from_slice(b"SIZE BYTE TEST       ");
+    data[0x7FD7] = 0x40;
+    data[0x7FDC..0x7FE0].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+
+    let mut rom = Rom::new(&data);
+    let header = *rom.get_ref::<SNESHeader>(0x7FC0).unwrap();
+    assert!(matches!(header.validate_fields(), Err(Error::InvalidRomSizeByte(0x40))));
+    assert_eq!(header.checked_rom_size(), None);
+    assert_eq!(header.declared_rom_size(), 0);
+
+    /* none of the paths that size unvalidated headers may panic on it */
+    rom.set_checksum_policy(ChecksumPolicy::Ignore);
+    assert!(rom.find_valid_snes_header().is_err());
+    assert!(rom.find_multicart_games().is_empty());
+    let _ = detect_copier_header(&data);
+
+    data[0x7FD7] = 0x0D;
+    assert_eq!(Rom::new(&data).get_ref::<SNESHeader>(0x7FC0).unwrap().checked_rom_size(), Some(0x800000));
+}
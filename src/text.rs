@@ -0,0 +1,200 @@
+use crate::{Addr24, Error, Rom};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct TextTable {
+    decode: BTreeMap<Vec<u8>, String>,
+    encode: BTreeMap<String, Vec<u8>>,
+    end_tokens: BTreeSet<Vec<u8>>,
+    max_key: usize,
+    max_value: usize,
+}
+impl TextTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        /* Thingy-style tables: XX=A, XXYY=the, /XX=<END> for terminators and *XX=<LINE> for line breaks */
+        let mut result = Self::new();
+
+        for (number, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim_end_matches(['\r', '\n']);
+            if line.trim().is_empty() || line.starts_with('#') || line.starts_with(';') { continue; }
+
+            let (is_end, is_line, entry) = if let Some(rest) = line.strip_prefix('/') { (true, false, rest) }
+                                           else if let Some(rest) = line.strip_prefix('*') { (false, true, rest) }
+                                           else { (false, false, line) };
+
+            let (key, value) = match entry.split_once('=') {
+                Some((k, v)) => (k.trim(), v.to_string()),
+                None if is_end => (entry.trim(), "<END>".to_string()),
+                None if is_line => (entry.trim(), "\n".to_string()),
+                None => return Err(Error::InvalidTableLine(number + 1)),
+            };
+            let bytes = match parse_hex_bytes(key) {
+                Some(b) => b,
+                None => return Err(Error::InvalidTableLine(number + 1)),
+            };
+            let value = if is_line && value.is_empty() { "\n".to_string() } else { value };
+
+            if is_end { result.end_tokens.insert(bytes.clone()); }
+
+            result.insert(bytes, &value);
+        }
+
+        Ok(result)
+    }
+    pub fn insert(&mut self, bytes: Vec<u8>, value: &str) {
+        self.max_key = self.max_key.max(bytes.len());
+        self.max_value = self.max_value.max(value.chars().count());
+
+        /* the first mapping for a string wins on encode, matching how table files list preferred encodings first */
+        self.encode.entry(value.to_string()).or_insert_with(|| bytes.clone());
+        self.decode.insert(bytes, value.to_string());
+    }
+    pub fn add_end_token(&mut self, bytes: Vec<u8>, value: &str) {
+        self.end_tokens.insert(bytes.clone());
+        self.insert(bytes, value);
+    }
+    pub fn len(&self) -> usize {
+        self.decode.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.decode.is_empty()
+    }
+    pub fn entries(&self) -> impl Iterator<Item = (&Vec<u8>, &String)> {
+        self.decode.iter()
+    }
+    pub fn end_token(&self) -> Option<&Vec<u8>> {
+        self.end_tokens.iter().next()
+    }
+    pub fn end_tokens(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.end_tokens.iter()
+    }
+    pub fn is_end_token(&self, bytes: &[u8]) -> bool {
+        self.end_tokens.contains(bytes)
+    }
+    fn match_bytes(&self, data: &[u8]) -> Option<(usize, &String)> {
+        for size in (1..=self.max_key.min(data.len())).rev() {
+            if let Some(value) = self.decode.get(&data[..size]) { return Some((size, value)); }
+        }
+
+        None
+    }
+    pub fn decode(&self, data: &[u8]) -> String {
+        let mut result = String::new();
+        let mut cursor = 0usize;
+
+        while cursor < data.len() {
+            match self.match_bytes(&data[cursor..]) {
+                Some((size, value)) => { result.push_str(value); cursor += size; },
+                None => { result.push_str(&format!("[{:02X}]", data[cursor])); cursor += 1; },
+            }
+        }
+
+        result
+    }
+    pub fn decode_string(&self, data: &[u8]) -> (String, usize) {
+        /* reads up to and including the first end token; the token itself is left out of the text */
+        let mut result = String::new();
+        let mut cursor = 0usize;
+
+        while cursor < data.len() {
+            match self.match_bytes(&data[cursor..]) {
+                Some((size, _)) if self.is_end_token(&data[cursor..cursor+size]) => return (result, cursor + size),
+                Some((size, value)) => { result.push_str(value); cursor += size; },
+                None => { result.push_str(&format!("[{:02X}]", data[cursor])); cursor += 1; },
+            }
+        }
+
+        (result, cursor)
+    }
+    pub fn read_string(&self, rom: &Rom, address: Addr24) -> Result<(String, usize), Error> {
+        /* walks the ROM's bank-crossing policy byte by byte, since the string length is only known at its end token */
+        let policy = rom.bank_policy();
+        let mut bytes = Vec::<u8>::new();
+
+        while !self.end_tokens.iter().any(|t| bytes.ends_with(t)) {
+            if bytes.len() >= 0x10000 { return Err(Error::TruncatedData(bytes.len())); }
+
+            let offset = match policy.offset(address, bytes.len() as u32) {
+                Ok(a) => rom.mapped_offset(a),
+                Err(e) => return Err(e),
+            };
+
+            match offset {
+                Ok(o) => bytes.push(rom.as_slice()[o]),
+                Err(_) if self.end_tokens.is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(self.decode_string(&bytes))
+    }
+    pub fn tokenize(&self, text: &str) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut result = Vec::<(String, Vec<u8>)>::new();
+        let mut cursor = 0usize;
+
+        while cursor < chars.len() {
+            /* [XX] escapes pass raw bytes through, mirroring what decode emits for unmapped values */
+            if chars[cursor] == '[' && cursor + 3 < chars.len() && chars[cursor+3] == ']' {
+                let hex = chars[cursor+1..cursor+3].iter().collect::<String>();
+
+                if let Ok(b) = u8::from_str_radix(&hex, 16) {
+                    result.push((chars[cursor..cursor+4].iter().collect(), vec![b]));
+                    cursor += 4;
+                    continue;
+                }
+            }
+
+            let mut matched = false;
+
+            for size in (1..=self.max_value.min(chars.len() - cursor)).rev() {
+                let candidate = chars[cursor..cursor+size].iter().collect::<String>();
+
+                if let Some(bytes) = self.encode.get(&candidate) {
+                    result.push((candidate, bytes.clone()));
+                    cursor += size;
+                    matched = true;
+                    break;
+                }
+            }
+
+            if !matched { return Err(Error::UnencodableText(chars[cursor..].iter().collect())); }
+        }
+
+        Ok(result)
+    }
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, Error> {
+        match self.tokenize(text) {
+            Ok(tokens) => Ok(tokens.into_iter().flat_map(|(_, bytes)| bytes).collect()),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn encode_string(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let mut result = match self.encode(text) {
+            Ok(r) => r,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(end) = self.end_token() { result.extend_from_slice(end); }
+
+        Ok(result)
+    }
+}
+
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if text.is_empty() || !text.len().is_multiple_of(2) { return None; }
+
+    let mut result = Vec::<u8>::new();
+
+    for i in (0..text.len()).step_by(2) {
+        match text.get(i..i+2).map(|h| u8::from_str_radix(h, 16)) {
+            Some(Ok(b)) => result.push(b),
+            _ => return None,
+        }
+    }
+
+    Some(result)
+}
@@ -0,0 +1,118 @@
+use crate::{Addr24, Error, MemoryMap, RegionKind, RegionMap, Rom};
+use std::path::Path;
+
+/* bsnes-plus usage flags, one byte per location */
+pub const USAGE_READ: u8 = 0x80;
+pub const USAGE_WRITE: u8 = 0x40;
+pub const USAGE_EXEC: u8 = 0x20;
+pub const USAGE_OPCODE: u8 = 0x10;
+pub const USAGE_FLAG_E: u8 = 0x04;
+pub const USAGE_FLAG_M: u8 = 0x02;
+pub const USAGE_FLAG_X: u8 = 0x01;
+
+/* cpu.usage covers the whole 24-bit bus; the cartridge variant is indexed by rom offset instead */
+pub const BUS_USAGE_SIZE: usize = 0x1000000;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct UsageLog {
+    flags: Vec<u8>,
+}
+impl UsageLog {
+    pub fn from_bsnes_plus<B: AsRef<[u8]>>(rom: &Rom, data: B) -> Result<Self, Error> {
+        let data = data.as_ref();
+        let rom_size = rom.rom_size();
+
+        if data.len() == rom_size { return Ok(Self { flags: data.to_vec() }); }
+        if data.len() != BUS_USAGE_SIZE { return Err(Error::DataLengthMismatch(BUS_USAGE_SIZE,data.len())); }
+
+        let mapper = match rom.memory_map() {
+            Ok(m) => m,
+            Err(e) => return Err(e),
+        };
+        let mut flags = vec![0u8; rom_size];
+
+        /* the same byte shows up through every mirror it was touched in, so fold them together */
+        for (address, &usage) in data.iter().enumerate().filter(|(_, &u)| u != 0) {
+            if let Ok(pc) = mapper.address_to_pc(Addr24::from_u32(address as u32)) {
+                if pc < rom_size { flags[pc] |= usage; }
+            }
+        }
+
+        Ok(Self { flags })
+    }
+    pub fn from_flags(flags: Vec<u8>) -> Self {
+        Self { flags }
+    }
+    pub fn as_slice(&self) -> &[u8] {
+        &self.flags
+    }
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+    pub fn flags_at(&self, offset: usize) -> u8 {
+        self.flags.get(offset).copied().unwrap_or(0)
+    }
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.flags_at(offset) & USAGE_EXEC != 0
+    }
+    pub fn is_data(&self, offset: usize) -> bool {
+        self.flags_at(offset) & (USAGE_READ | USAGE_EXEC) == USAGE_READ
+    }
+    pub fn opcode_flags(&self, offset: usize) -> Option<(bool, bool)> {
+        /* the M and X flags the CPU had when it fetched this opcode, as (8-bit A, 8-bit index) */
+        let flags = self.flags_at(offset);
+
+        if flags & USAGE_OPCODE == 0 { return None; }
+        if flags & USAGE_FLAG_E != 0 { return Some((true, true)); }
+
+        Some((flags & USAGE_FLAG_M != 0, flags & USAGE_FLAG_X != 0))
+    }
+    pub fn code_bytes(&self) -> usize {
+        (0..self.flags.len()).filter(|&o| self.is_code(o)).count()
+    }
+    pub fn data_bytes(&self) -> usize {
+        (0..self.flags.len()).filter(|&o| self.is_data(o)).count()
+    }
+    pub fn merge_into(&self, map: &mut RegionMap) -> Result<usize, Error> {
+        /* executed bytes are code no matter what analysis guessed; read-only bytes only displace guesses
+           that are less specific than data, so a graphics or text marking survives being read */
+        if map.len() != self.flags.len() { return Err(Error::DataLengthMismatch(map.len(),self.flags.len())); }
+
+        let mut changed = 0usize;
+
+        for offset in 0..self.flags.len() {
+            let current = match map.kind_at(offset) {
+                Ok(k) => k,
+                Err(e) => return Err(e),
+            };
+            let kind = if self.is_code(offset) { RegionKind::Code }
+                       else if self.is_data(offset) && matches!(current, RegionKind::Unknown | RegionKind::Code | RegionKind::Free) { RegionKind::Data }
+                       else { continue };
+
+            if kind == current { continue; }
+            if let Err(e) = map.mark(offset, 1, kind) { return Err(e); }
+
+            changed += 1;
+        }
+
+        Ok(changed)
+    }
+    pub fn to_region_map(&self) -> RegionMap {
+        let mut result = RegionMap::new(self.flags.len());
+        let _ = self.merge_into(&mut result);
+
+        result
+    }
+}
+
+impl Rom {
+    pub fn import_usage_log<P: AsRef<Path>>(&self, filename: P) -> Result<UsageLog, Error> {
+        match std::fs::read(filename) {
+            Ok(d) => UsageLog::from_bsnes_plus(self, d),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{crc32, Error, Rom};
+use std::path::{Path, PathBuf};
+
+pub const ROM_EXTENSIONS: [&str; 5] = ["sfc", "smc", "swc", "fig", "bin"];
+pub const SIMILARITY_CHUNK_SIZE: usize = 0x1000;
+pub const SAME_GAME_THRESHOLD: f32 = 0.95;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RomSummary {
+    pub path: Option<PathBuf>,
+    pub title: String,
+    pub size: usize,
+    pub crc32: u32,
+    pub samples: Vec<u32>,
+}
+impl RomSummary {
+    pub fn new(rom: &Rom, sample_count: usize, sample_size: usize) -> Self {
+        /* samples are spread evenly over the headerless image, so two dumps of the same size sample the same places */
+        let data = &rom.as_slice()[rom.header_size()..];
+        let title = match rom.find_valid_snes_header() {
+            Ok(h) => h.get_title(),
+            Err(_) => String::new(),
+        };
+        let mut samples = Vec::<u32>::new();
+
+        if !data.is_empty() && sample_count > 0 {
+            let stride = data.len() / sample_count;
+
+            for index in 0..sample_count {
+                let start = (index * stride).min(data.len() - 1);
+                let end = (start + sample_size).min(data.len());
+
+                samples.push(crc32(&data[start..end]));
+            }
+        }
+
+        Self { path: rom.path().map(|p| p.to_path_buf()), title, size: data.len(), crc32: crc32(data), samples }
+    }
+    pub fn title_key(&self) -> String {
+        /* case, spacing and punctuation all drift between regions' headers */
+        self.title.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_uppercase()).collect()
+    }
+    pub fn title_similarity(&self, other: &Self) -> f32 {
+        let (a, b) = (self.title_key(), other.title_key());
+        let longest = a.chars().count().max(b.chars().count());
+        if longest == 0 { return 0.0; }
+
+        1.0 - edit_distance(&a, &b) as f32 / longest as f32
+    }
+    pub fn content_similarity(&self, other: &Self) -> f32 {
+        /* different sizes put the samples in different places, so they can't be compared */
+        if self.size != other.size || self.samples.is_empty() || self.samples.len() != other.samples.len() { return 0.0; }
+
+        let same = self.samples.iter().zip(&other.samples).filter(|(a, b)| a == b).count();
+        same as f32 / self.samples.len() as f32
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct VariantOptions {
+    pub recursive: bool,
+    pub sample_count: usize,
+    pub sample_size: usize,
+    pub title_threshold: f32,
+    pub content_threshold: f32,
+}
+impl VariantOptions {
+    pub fn new() -> Self {
+        Self { recursive: false, sample_count: 32, sample_size: 0x400, title_threshold: 0.8, content_threshold: 0.5 }
+    }
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+    pub fn samples(mut self, count: usize, size: usize) -> Self {
+        self.sample_count = count;
+        self.sample_size = size;
+        self
+    }
+    pub fn title_threshold(mut self, threshold: f32) -> Self {
+        self.title_threshold = threshold;
+        self
+    }
+    pub fn content_threshold(mut self, threshold: f32) -> Self {
+        self.content_threshold = threshold;
+        self
+    }
+    pub fn related(&self, a: &RomSummary, b: &RomSummary) -> bool {
+        /* revisions share a title and most of their data; translations keep the data but rename the title.
+           a title alone is never enough: sequels differ by a numeral, so it has to come with the same size */
+        if a.size != b.size { return false; }
+        if a.crc32 == b.crc32 { return true; }
+        if a.content_similarity(b) >= self.content_threshold { return true; }
+
+        !a.title_key().is_empty() && a.title_similarity(b) >= self.title_threshold
+    }
+}
+impl Default for VariantOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct VariantCluster {
+    pub title: String,
+    pub members: Vec<RomSummary>,
+}
+
+pub fn scan_directory<P: AsRef<Path>>(directory: P, options: &VariantOptions) -> Result<Vec<RomSummary>, Error> {
+    /* files that won't load as a ROM are skipped; only the directory itself failing is an error */
+    let mut result = Vec::<RomSummary>::new();
+    let mut pending = vec![directory.as_ref().to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let listing = match std::fs::read_dir(&current) {
+            Ok(l) => l,
+            Err(e) => return Err(Error::IoError(e)),
+        };
+        let mut paths = Vec::<PathBuf>::new();
+
+        for entry in listing {
+            match entry {
+                Ok(e) => paths.push(e.path()),
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                if options.recursive { pending.push(path); }
+                continue;
+            }
+
+            let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+            if !matches!(extension, Some(e) if ROM_EXTENSIONS.contains(&e.as_str())) { continue; }
+
+            if let Ok(rom) = Rom::from_file(&path) { result.push(RomSummary::new(&rom, options.sample_count, options.sample_size)); }
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn group_variants(summaries: &[RomSummary], options: &VariantOptions) -> Vec<VariantCluster> {
+    /* single linkage: a revision related to both a translation and the original pulls all three together */
+    let mut parent: Vec<usize> = (0..summaries.len()).collect();
+
+    fn root(parent: &mut [usize], index: usize) -> usize {
+        let mut current = index;
+        while parent[current] != current { parent[current] = parent[parent[current]]; current = parent[current]; }
+        current
+    }
+
+    for a in 0..summaries.len() {
+        for b in a + 1..summaries.len() {
+            if !options.related(&summaries[a], &summaries[b]) { continue; }
+
+            let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+            if ra != rb { parent[rb] = ra; }
+        }
+    }
+
+    let mut result = Vec::<VariantCluster>::new();
+    let mut roots = Vec::<usize>::new();
+
+    for (index, summary) in summaries.iter().enumerate() {
+        let r = root(&mut parent, index);
+
+        match roots.iter().position(|x| *x == r) {
+            Some(p) => result[p].members.push(summary.clone()),
+            None => {
+                roots.push(r);
+                result.push(VariantCluster { title: summary.title.trim().to_string(), members: vec![summary.clone()] });
+            },
+        }
+    }
+
+    result
+}
+
+pub fn scan_variants<P: AsRef<Path>>(directory: P, options: &VariantOptions) -> Result<Vec<VariantCluster>, Error> {
+    match scan_directory(directory, options) {
+        Ok(s) => Ok(group_variants(&s, options)),
+        Err(e) => Err(e),
+    }
+}
+
+impl Rom {
+    fn chunk_hashes(&self) -> Vec<u32> {
+        self.as_slice()[self.header_size()..].chunks(SIMILARITY_CHUNK_SIZE).map(crc32).collect()
+    }
+    pub fn similarity(&self, other: &Rom) -> f32 {
+        /* shared 4KB chunks over the larger image's chunk count; matched wherever they sit, so moved data still counts */
+        let mine = self.chunk_hashes();
+        let mut theirs = other.chunk_hashes();
+        let total = mine.len().max(theirs.len());
+        if total == 0 { return 1.0; }
+
+        theirs.sort_unstable();
+
+        let mut used = vec![false; theirs.len()];
+        let mut shared = 0usize;
+
+        for hash in mine {
+            let mut index = theirs.partition_point(|h| *h < hash);
+
+            while index < theirs.len() && theirs[index] == hash && used[index] { index += 1; }
+
+            if index < theirs.len() && theirs[index] == hash {
+                used[index] = true;
+                shared += 1;
+            }
+        }
+
+        shared as f32 / total as f32
+    }
+    pub fn is_same_game(&self, other: &Rom) -> bool {
+        /* copier headers never count; past that it's the same size and nearly the same data, whatever the internal header says */
+        if self.rom_size() != other.rom_size() { return false; }
+        if self.crc32() == other.crc32() { return true; }
+
+        self.similarity(other) >= SAME_GAME_THRESHOLD
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for j in 0..b.len() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1).min(row[j] + 1).min(diagonal + (ca != b[j]) as usize);
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
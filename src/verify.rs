@@ -0,0 +1,94 @@
+use crate::{crc32, Addr24, Error, Patch, Rom};
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Assertion {
+    Bytes(Addr24, Vec<u8>),
+    Crc32(Addr24, usize, u32),
+    RomCrc32(u32),
+    Size(usize),
+    HeaderValid,
+    ChecksumFixed,
+}
+impl Assertion {
+    pub fn check(&self, rom: &Rom) -> Result<(), String> {
+        match self {
+            Assertion::Bytes(address, expected) => match rom.read_mapped(*address, expected.len()) {
+                Ok(d) if &d == expected => Ok(()),
+                Ok(d) => Err(format!("expected {} found {}", hex_string(expected), hex_string(&d))),
+                Err(e) => Err(format!("{:?}", e)),
+            },
+            Assertion::Crc32(address, size, expected) => match rom.read_mapped(*address, *size) {
+                Ok(d) if crc32(&d) == *expected => Ok(()),
+                Ok(d) => Err(format!("expected crc32 {:08X} found {:08X}", expected, crc32(&d))),
+                Err(e) => Err(format!("{:?}", e)),
+            },
+            Assertion::RomCrc32(expected) => {
+                if rom.crc32() == *expected { Ok(()) }
+                else { Err(format!("expected crc32 {:08X} found {:08X}", expected, rom.crc32())) }
+            },
+            Assertion::Size(expected) => {
+                if rom.rom_size() == *expected { Ok(()) }
+                else { Err(format!("expected 0x{:X} bytes found 0x{:X}", expected, rom.rom_size())) }
+            },
+            Assertion::HeaderValid => match rom.find_valid_snes_header_address() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(format!("{:?}", e)),
+            },
+            Assertion::ChecksumFixed => match rom.find_valid_snes_header() {
+                Ok(h) if h.get_checksum() == rom.checksum() => Ok(()),
+                Ok(h) => Err(format!("header says {:04X} but data sums to {:04X}", h.get_checksum(), rom.checksum())),
+                Err(e) => Err(format!("{:?}", e)),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub failure: Option<String>,
+}
+impl AssertionResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct VerifyReport {
+    pub results: Vec<AssertionResult>,
+}
+impl VerifyReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed())
+    }
+    pub fn failures(&self) -> Vec<&AssertionResult> {
+        self.results.iter().filter(|r| !r.passed()).collect()
+    }
+    pub fn summary(&self) -> String {
+        let mut result = String::new();
+
+        for r in &self.results {
+            match &r.failure {
+                None => result.push_str(&format!("PASS {:?}\n", r.assertion)),
+                Some(f) => result.push_str(&format!("FAIL {:?}: {}\n", r.assertion, f)),
+            }
+        }
+
+        result.push_str(&format!("{}/{} passed\n", self.results.len() - self.failures().len(), self.results.len()));
+        result
+    }
+}
+
+pub fn verify<P: Patch>(pristine: &Rom, patch: &P, assertions: &[Assertion]) -> Result<VerifyReport, Error> {
+    /* the pristine image is never touched, so one base ROM can back any number of regression runs */
+    let mut patched = pristine.clone();
+
+    if let Err(e) = patch.apply(&mut patched) { return Err(e); }
+
+    Ok(VerifyReport { results: assertions.iter().map(|a| AssertionResult { assertion: a.clone(), failure: a.check(&patched).err() }).collect() })
+}
+
+fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ")
+}
@@ -0,0 +1,113 @@
+use crate::Rom;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct WatchId(usize);
+
+/* one write as a watch saw it: only the part that fell inside the watched range */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChangeEvent {
+    pub id: WatchId,
+    pub operation: String,
+    pub range: Range<usize>,
+}
+
+pub type WatchCallback = Box<dyn FnMut(&ChangeEvent) + Send + Sync>;
+
+struct Watch {
+    id: WatchId,
+    range: Range<usize>,
+    callback: Option<WatchCallback>,
+}
+
+/* watches belong to the Rom they were registered on: clones start with none, and they never count toward equality */
+#[derive(Default)]
+pub struct WatchList {
+    watches: Vec<Watch>,
+    events: Vec<ChangeEvent>,
+    next_id: usize,
+}
+impl WatchList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+    pub fn range(&self, id: WatchId) -> Option<Range<usize>> {
+        self.watches.iter().find(|w| w.id == id).map(|w| w.range.clone())
+    }
+    fn add(&mut self, range: Range<usize>, callback: Option<WatchCallback>) -> WatchId {
+        let id = WatchId(self.next_id);
+
+        self.next_id += 1;
+        self.watches.push(Watch { id, range, callback });
+        id
+    }
+    fn remove(&mut self, id: WatchId) -> bool {
+        match self.watches.iter().position(|w| w.id == id) {
+            Some(index) => { self.watches.remove(index); true },
+            None => false,
+        }
+    }
+    fn notify(&mut self, operation: &str, range: Range<usize>) {
+        for watch in &mut self.watches {
+            let hit = range.start.max(watch.range.start)..range.end.min(watch.range.end);
+            if hit.start >= hit.end { continue; }
+
+            let event = ChangeEvent { id: watch.id, operation: operation.to_string(), range: hit };
+
+            match &mut watch.callback {
+                Some(callback) => callback(&event),
+                None => self.events.push(event),
+            }
+        }
+    }
+}
+impl Clone for WatchList {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+impl PartialEq for WatchList {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl Eq for WatchList {}
+impl std::fmt::Debug for WatchList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "WatchList({} watches, {} queued)", self.watches.len(), self.events.len())
+    }
+}
+
+impl Rom {
+    pub fn watch<F: FnMut(&ChangeEvent) + Send + Sync + 'static>(&mut self, range: Range<usize>, callback: F) -> WatchId {
+        /* called from inside the write, after the bytes have landed */
+        self.watches.add(range, Some(Box::new(callback)))
+    }
+    pub fn watch_queued(&mut self, range: Range<usize>) -> WatchId {
+        /* for a UI that redraws on its own schedule: events wait in take_events until then */
+        self.watches.add(range, None)
+    }
+    pub fn unwatch(&mut self, id: WatchId) -> bool {
+        self.watches.remove(id)
+    }
+    pub fn watches(&self) -> &WatchList {
+        &self.watches
+    }
+    pub fn pending_events(&self) -> &[ChangeEvent] {
+        &self.watches.events
+    }
+    pub fn take_events(&mut self) -> Vec<ChangeEvent> {
+        std::mem::take(&mut self.watches.events)
+    }
+    pub(crate) fn notify_change(&mut self, operation: &str, range: Range<usize>) {
+        if self.watches.is_empty() || range.start >= range.end { return; }
+
+        self.watches.notify(operation, range);
+    }
+}